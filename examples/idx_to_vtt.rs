@@ -0,0 +1,44 @@
+//! Convert the subtitles of a `VobSub` `*.idx`/`*.sub` pair to `WebVTT`.
+//!
+//! The crate doesn't provide an `OCR` engine : the text of each subtitle is a placeholder
+//! describing its image, to replace by the text recognized by the `OCR` engine of your choice.
+//!
+//! ```text
+//! cargo run --example idx_to_vtt -- fixtures/example.idx > example.vtt
+//! ```
+
+use std::{env, error::Error, io, path::PathBuf};
+
+use subtile::{
+    image::{ImageArea as _, ToOcrImage as _, ToOcrImageOpt},
+    sink::CueSink as _,
+    time::TimeSpan,
+    vobsub::{palette_rgb_to_luminance, Index, Sub, VobSubIndexedImage, VobSubOcrImage},
+    webvtt::VttWriter,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let idx_path = PathBuf::from(env::args().nth(1).ok_or("usage: idx_to_vtt <file.idx>")?);
+    let idx = Index::open(&idx_path)?;
+    let sub = Sub::open(idx_path.with_extension("sub"))?;
+    let luma_palette = palette_rgb_to_luminance(idx.palette());
+
+    let opt = ToOcrImageOpt::new();
+    let mut writer = VttWriter::new(io::stdout().lock());
+    for subtitle in sub.subtitles::<(TimeSpan, VobSubIndexedImage)>() {
+        let (time, image) = subtitle?;
+        // Image ready for `OCR` : black text on a white background.
+        let ocr = VobSubOcrImage::new(&image, &luma_palette).image(&opt);
+        let area = image.area();
+        let text = format!(
+            "[image {}x{} at {},{}]",
+            ocr.width(),
+            ocr.height(),
+            area.left(),
+            area.top()
+        );
+        writer.write_cue(time, &text)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Convert the subtitles of a `*.sup` file to `srt`.
+//!
+//! The crate doesn't provide an `OCR` engine : the text of each subtitle is a placeholder
+//! describing its image, to replace by the text recognized by the `OCR` engine of your choice.
+//!
+//! ```text
+//! cargo run --example sup_to_srt -- fixtures/only_one.sup > only_one.srt
+//! ```
+
+use std::{env, error::Error, fs::File, io, io::BufReader};
+
+use subtile::{
+    image::ToOcrImageOpt,
+    pgs::{DecodeTimeImage, SupParser},
+    sink::CueSink as _,
+    srt::SrtWriter,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("usage: sup_to_srt <file.sup>")?;
+    let opt = ToOcrImageOpt::new();
+    let mut writer = SrtWriter::new(io::stdout().lock());
+    for subtitle in SupParser::<BufReader<File>, DecodeTimeImage>::from_file(path)? {
+        let (time, image) = subtitle?;
+        // Image ready for `OCR` : black text on a white background.
+        let ocr = image.to_indexed().to_ocr_image(
+            |color| {
+                if color[3] > 127 {
+                    opt.text_color
+                } else {
+                    opt.background_color
+                }
+            },
+            &opt,
+        );
+        let text = format!("[image {}x{}]", ocr.width(), ocr.height());
+        writer.write_cue(time, &text)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,706 @@
+//! `SSA`/`ASS` (`SubStation Alpha`) functionality
+//!
+//! A script is made of sections : `[Script Info]` with the global properties,
+//! `[V4+ Styles]` with the styles, and `[Events]` with the dialogue lines. The lines of
+//! the styles and events sections are lists of fields, named by the `Format:` line.
+use std::{borrow::Cow, fmt, io};
+
+use image::Rgb;
+use thiserror::Error;
+
+use crate::{
+    content::{Orientation, Size},
+    format::SubtitleSink,
+    image::TrackStyle,
+    sink::CueSink,
+    text::TextHints,
+    time::{TimePoint, TimeSpan},
+};
+
+/// Fields of a style line of the `[V4+ Styles]` section, with their default value.
+const STYLE_FIELDS: [(&str, &str); 23] = [
+    ("Name", "Default"),
+    ("Fontname", "Arial"),
+    ("Fontsize", "20"),
+    ("PrimaryColour", "&H00FFFFFF"),
+    ("SecondaryColour", "&H000000FF"),
+    ("OutlineColour", "&H00000000"),
+    ("BackColour", "&H00000000"),
+    ("Bold", "0"),
+    ("Italic", "0"),
+    ("Underline", "0"),
+    ("StrikeOut", "0"),
+    ("ScaleX", "100"),
+    ("ScaleY", "100"),
+    ("Spacing", "0"),
+    ("Angle", "0"),
+    ("BorderStyle", "1"),
+    ("Outline", "2"),
+    ("Shadow", "2"),
+    ("Alignment", "2"),
+    ("MarginL", "10"),
+    ("MarginR", "10"),
+    ("MarginV", "10"),
+    ("Encoding", "1"),
+];
+
+/// Fields of a dialogue line of the `[Events]` section.
+const EVENT_FIELDS: [&str; 10] = [
+    "Layer", "Start", "End", "Style", "Name", "MarginL", "MarginR", "MarginV", "Effect", "Text",
+];
+
+/// Extend `TimePoint` for implement `ASS` specific `Display` : `h:mm:ss.cc`, with
+/// centiseconds.
+#[repr(transparent)]
+pub struct TimePointAss(TimePoint);
+
+impl From<TimePoint> for TimePointAss {
+    fn from(value: TimePoint) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Display for TimePointAss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `ASS` times can't be negative.
+        let centis = self.0.msecs().max(0) / 10;
+        write!(
+            f,
+            "{}:{:02}:{:02}.{:02}",
+            centis / 360_000,
+            centis / 6000 % 60,
+            centis / 100 % 60,
+            centis % 100
+        )
+    }
+}
+
+/// Error of `ass` parsing.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum AssError {
+    /// A style or dialogue line has less fields than its `Format:` line.
+    #[error("line {line} has less fields than its `Format:` line")]
+    MissingField {
+        /// Number of the line, starting at 1.
+        line: usize,
+    },
+
+    /// The start or the end of a dialogue line can't be parsed.
+    #[error("line {line} has an invalid `ass` time")]
+    InvalidTiming {
+        /// Number of the line, starting at 1.
+        line: usize,
+    },
+}
+
+/// A style of the `[V4+ Styles]` section, as a list of named fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssStyle {
+    fields: Vec<(String, String)>,
+}
+
+impl AssStyle {
+    /// Create a style with the default values of the fields, named `name`.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        let style = Self {
+            fields: STYLE_FIELDS
+                .iter()
+                .map(|(field, value)| ((*field).to_owned(), (*value).to_owned()))
+                .collect(),
+        };
+        style.with_field("Name", name)
+    }
+
+    /// Create a style named `name` reproducing the look of a bitmap track inferred in
+    /// a [`TrackStyle`] : size and colors of the characters, outline and bottom margin.
+    ///
+    /// The sizes are in pixels of the screen, so the `PlayRes` of the script should be
+    /// the screen size of the track, see [`AssWriter::with_play_res`].
+    #[must_use]
+    pub fn from_track_style(name: &str, track_style: &TrackStyle) -> Self {
+        let mut style =
+            Self::new(name).with_field("Outline", &track_style.outline_thickness.to_string());
+        if let Some(height) = track_style.text_height {
+            style = style.with_field("Fontsize", &height.to_string());
+        }
+        if let Some(color) = track_style.primary_color {
+            style = style.with_field("PrimaryColour", &ass_color(color));
+        }
+        if let Some(color) = track_style.outline_color {
+            style = style.with_field("OutlineColour", &ass_color(color));
+        }
+        if let Some(margin) = track_style.bottom_margin {
+            style = style.with_field("MarginV", &margin.to_string());
+        }
+        style
+    }
+
+    /// Set the `value` of a `field` (like `Fontname` or `PrimaryColour`).
+    #[must_use]
+    pub fn with_field(mut self, field: &str, value: &str) -> Self {
+        match self.fields.iter_mut().find(|(name, _)| name == field) {
+            Some((_, old)) => value.clone_into(old),
+            None => self.fields.push((field.to_owned(), value.to_owned())),
+        }
+        self
+    }
+
+    /// Name of the style, referenced by the dialogue lines.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.field("Name").unwrap_or_default()
+    }
+
+    /// Value of a `field` of the style.
+    #[must_use]
+    pub fn field(&self, field: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A dialogue line of the `[Events]` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AssEvent {
+    /// Layer of the line, the higher layers are drawn over the lower ones.
+    pub layer: i32,
+    /// Display time of the line.
+    pub time: TimeSpan,
+    /// Name of the style of the line.
+    pub style: String,
+    /// Name of the speaker.
+    pub name: String,
+    /// Text, with its override tags (`{\i1}`) and line breaks (`\N`).
+    pub text: String,
+}
+
+impl AssEvent {
+    /// Text without the override tags, with the line breaks as `\n`.
+    #[must_use]
+    pub fn plain_text(&self) -> String {
+        let mut text = String::with_capacity(self.text.len());
+        let mut chars = self.text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    // Skip the override block.
+                    chars.by_ref().find(|c| *c == '}');
+                }
+                '\\' => match chars.peek() {
+                    Some('N' | 'n') => {
+                        chars.next();
+                        text.push('\n');
+                    }
+                    Some('h') => {
+                        chars.next();
+                        text.push('\u{a0}');
+                    }
+                    Some(&brace @ ('{' | '}')) => {
+                        chars.next();
+                        text.push(brace);
+                    }
+                    _ => text.push(c),
+                },
+                _ => text.push(c),
+            }
+        }
+        text
+    }
+}
+
+/// Content of an `ass` script.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssScript {
+    info: Vec<(String, String)>,
+    styles: Vec<AssStyle>,
+    events: Vec<AssEvent>,
+}
+
+impl AssScript {
+    /// Properties of the `[Script Info]` section, in order.
+    #[must_use]
+    pub fn info(&self) -> &[(String, String)] {
+        &self.info
+    }
+
+    /// Value of the `[Script Info]` property `key` (like `PlayResX`).
+    #[must_use]
+    pub fn info_value(&self, key: &str) -> Option<&str> {
+        self.info
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Styles of the script.
+    #[must_use]
+    pub fn styles(&self) -> &[AssStyle] {
+        &self.styles
+    }
+
+    /// Style named `name`.
+    #[must_use]
+    pub fn style(&self, name: &str) -> Option<&AssStyle> {
+        self.styles.iter().find(|style| style.name() == name)
+    }
+
+    /// Dialogue lines of the script, in order of the file.
+    #[must_use]
+    pub fn events(&self) -> &[AssEvent] {
+        &self.events
+    }
+
+    /// Time and plain text of the dialogue lines, see [`AssEvent::plain_text`].
+    #[must_use]
+    pub fn subtitles(&self) -> Vec<(TimeSpan, String)> {
+        self.events
+            .iter()
+            .map(|event| (event.time, event.plain_text()))
+            .collect()
+    }
+}
+
+/// Section of a script being parsed.
+enum Section {
+    Info,
+    Styles,
+    Events,
+    Other,
+}
+
+/// Parse the content of a `ass` (or `ssa`) script.
+///
+/// The comment lines (`;` and `Comment:`) and the unknown sections are ignored. A
+/// section without `Format:` line uses the fields of the `ASS` specification.
+/// The byte order mark and carriage returns are ignored.
+///
+/// # Errors
+///
+/// Will return the first error of the style and dialogue lines, see [`AssError`].
+pub fn parse_ass(content: &str) -> Result<AssScript, AssError> {
+    let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+    let mut script = AssScript::default();
+    let mut section = Section::Other;
+    let mut style_format = STYLE_FIELDS.map(|(field, _)| field.to_owned()).to_vec();
+    let mut event_format = EVENT_FIELDS.map(str::to_owned).to_vec();
+
+    for (line, text) in (1..).zip(content.lines()) {
+        let text = text.trim();
+        if text.starts_with('[') {
+            section = match text.to_ascii_lowercase().as_str() {
+                "[script info]" => Section::Info,
+                "[v4+ styles]" | "[v4 styles]" => Section::Styles,
+                "[events]" => Section::Events,
+                _ => Section::Other,
+            };
+            continue;
+        }
+        let Some((key, value)) = text.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match (&section, key) {
+            (_, key) if key.starts_with(';') => {}
+            (Section::Info, key) => script.info.push((key.to_owned(), value.to_owned())),
+            (Section::Styles, "Format") => style_format = format_fields(value),
+            (Section::Styles, "Style") => {
+                let values = split_fields(value, style_format.len(), line)?;
+                let fields = style_format.iter().cloned().zip(values).collect();
+                script.styles.push(AssStyle { fields });
+            }
+            (Section::Events, "Format") => event_format = format_fields(value),
+            (Section::Events, "Dialogue") => {
+                let values = split_fields(value, event_format.len(), line)?;
+                script
+                    .events
+                    .push(parse_event(&event_format, &values, line)?);
+            }
+            _ => {}
+        }
+    }
+    Ok(script)
+}
+
+/// Names of the fields of a `Format:` line.
+fn format_fields(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|field| field.trim().to_owned())
+        .collect()
+}
+
+/// Split the `count` fields of a line, the last one (the text) can contain commas.
+fn split_fields(value: &str, count: usize, line: usize) -> Result<Vec<String>, AssError> {
+    let fields = value
+        .splitn(count, ',')
+        .map(|field| field.trim().to_owned())
+        .collect::<Vec<_>>();
+    if fields.len() == count {
+        Ok(fields)
+    } else {
+        Err(AssError::MissingField { line })
+    }
+}
+
+/// Create a dialogue line from the `values` of the fields of `format`.
+fn parse_event(format: &[String], values: &[String], line: usize) -> Result<AssEvent, AssError> {
+    let field = |name: &str| {
+        format
+            .iter()
+            .position(|field| field == name)
+            .map(|position| values[position].as_str())
+    };
+    let time = |name: &str| {
+        field(name)
+            .and_then(parse_time)
+            .ok_or(AssError::InvalidTiming { line })
+    };
+    let time = TimeSpan::new(time("Start")?, time("End")?);
+    Ok(AssEvent {
+        layer: field("Layer")
+            .and_then(|layer| layer.parse().ok())
+            .unwrap_or_default(),
+        time,
+        style: field("Style").unwrap_or("Default").to_owned(),
+        name: field("Name").unwrap_or_default().to_owned(),
+        text: field("Text").unwrap_or_default().to_owned(),
+    })
+}
+
+/// Parse a time `h:mm:ss.cc`, the fraction of second can have any number of digits.
+fn parse_time(time: &str) -> Option<TimePoint> {
+    let (hours, rest) = time.split_once(':')?;
+    let (mins, rest) = rest.split_once(':')?;
+    let (secs, fraction) = rest.split_once('.').unwrap_or((rest, ""));
+    let [hours, mins, secs] = [hours, mins, secs].map(|value| value.trim().parse::<i64>().ok());
+    let msecs = format!("{fraction:0<3}")
+        .get(..3)
+        .and_then(|msecs| msecs.parse::<i64>().ok())?;
+    Some(TimePoint::from_msecs(
+        ((hours? * 60 + mins?) * 60 + secs?) * 1000 + msecs,
+    ))
+}
+
+/// Format a color as an `ASS` color : `&HAABBGGRR`, with an opaque alpha.
+fn ass_color(Rgb([red, green, blue]): Rgb<u8>) -> String {
+    format!("&H00{blue:02X}{green:02X}{red:02X}")
+}
+
+/// The text of a cue, displayed with a `style` of the script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StyledText<'a> {
+    /// Text of the cue.
+    pub text: &'a str,
+    /// Name of the style, the first style of the script if `None`.
+    pub style: Option<&'a str>,
+    /// Orientation of the text, a vertical text is rotated with a `\frz` tag.
+    pub orientation: Orientation,
+}
+
+impl<'a> StyledText<'a> {
+    /// Create the horizontal text of a cue, with an optional `style`.
+    #[must_use]
+    pub const fn new(text: &'a str, style: Option<&'a str>) -> Self {
+        Self {
+            text,
+            style,
+            orientation: Orientation::Horizontal,
+        }
+    }
+
+    /// Set the orientation of the text, like the one guessed from the area of a
+    /// bitmap cue with [`Orientation::guess`].
+    #[must_use]
+    pub const fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+}
+
+impl AsRef<Self> for StyledText<'_> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+/// Streaming writer of subtitles in `ass` format, see [`CueSink`].
+///
+/// The header, with the script info and the styles, is written before the first
+/// subtitle. The lines of the texts are separated by `\N`.
+///
+/// A `str` cue is plain text : its braces are escaped, so they are not read as
+/// override blocks. The override tags of a [`StyledText`] are kept : the output of
+/// [`ass_karaoke_text`](crate::lrc::ass_karaoke_text) can be written as is.
+pub struct AssWriter<W: io::Write> {
+    writer: W,
+    hints: Option<TextHints>,
+    styles: Vec<AssStyle>,
+    play_res: Option<Size>,
+    header_written: bool,
+}
+
+impl<W: io::Write> AssWriter<W> {
+    /// Create a writer of `ass` subtitles in `writer`, with a `Default` style.
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            hints: None,
+            styles: Vec::new(),
+            play_res: None,
+            header_written: false,
+        }
+    }
+
+    /// Create a writer applying the text `hints` on each subtitle. The language of
+    /// the hints is written in the script info.
+    pub const fn with_hints(writer: W, hints: TextHints) -> Self {
+        Self {
+            writer,
+            hints: Some(hints),
+            styles: Vec::new(),
+            play_res: None,
+            header_written: false,
+        }
+    }
+
+    /// Add a style to the script. The first one replaces the `Default` style, and is
+    /// used by the cues without style.
+    #[must_use]
+    pub fn with_style(mut self, style: AssStyle) -> Self {
+        self.styles.push(style);
+        self
+    }
+
+    /// Set the size of the screen the positions and sizes refer to (`PlayResX` and
+    /// `PlayResY`).
+    #[must_use]
+    pub const fn with_play_res(mut self, size: Size) -> Self {
+        self.play_res = Some(size);
+        self
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_dialogue(
+        &mut self,
+        time: TimeSpan,
+        text: &str,
+        style: Option<&str>,
+        orientation: Orientation,
+    ) -> Result<(), io::Error> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            start_ms = time.start.msecs(),
+            end_ms = time.end.msecs(),
+            format = "ass",
+            "write cue"
+        );
+        self.write_header()?;
+        let style = style.unwrap_or_else(|| self.styles[0].name());
+        let start = TimePointAss(time.start);
+        let end = TimePointAss(time.end);
+        let text = text.replace("\r\n", "\n");
+        let text = self
+            .hints
+            .as_ref()
+            .map_or(Cow::Borrowed(text.as_str()), |hints| hints.apply(&text))
+            .replace('\n', "\\N");
+        // Vertical text is rotated a quarter turn clockwise, to read from top to bottom.
+        let rotation = match orientation {
+            Orientation::Horizontal => "",
+            Orientation::Vertical => "{\\frz270}",
+        };
+        writeln!(
+            self.writer,
+            "Dialogue: 0,{start},{end},{style},,0,0,0,,{rotation}{text}"
+        )
+    }
+}
+
+impl<W: io::Write> SubtitleSink for AssWriter<W> {
+    const EXTENSION: &'static str = "ass";
+
+    fn write_header(&mut self) -> Result<(), io::Error> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.header_written = true;
+        if self.styles.is_empty() {
+            self.styles.push(AssStyle::new("Default"));
+        }
+
+        writeln!(self.writer, "[Script Info]\nScriptType: v4.00+")?;
+        if let Some(Size { w, h }) = self.play_res {
+            writeln!(self.writer, "PlayResX: {w}\nPlayResY: {h}")?;
+        }
+        if let Some(lang) = self.hints.as_ref().and_then(|hints| hints.lang.as_deref()) {
+            writeln!(self.writer, "Language: {lang}")?;
+        }
+        let names = STYLE_FIELDS.map(|(field, _)| field);
+        writeln!(self.writer, "\n[V4+ Styles]\nFormat: {}", names.join(", "))?;
+        for style in &self.styles {
+            let values = STYLE_FIELDS.map(|(field, default)| style.field(field).unwrap_or(default));
+            writeln!(self.writer, "Style: {}", values.join(","))?;
+        }
+        writeln!(
+            self.writer,
+            "\n[Events]\nFormat: {}",
+            EVENT_FIELDS.join(", ")
+        )
+    }
+}
+
+impl<W: io::Write> CueSink<str> for AssWriter<W> {
+    type Error = io::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, text: &str) -> Result<(), Self::Error> {
+        let text = text.replace('{', "\\{").replace('}', "\\}");
+        self.write_dialogue(time, &text, None, Orientation::Horizontal)
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.write_header()?;
+        self.writer.flush()
+    }
+}
+
+impl<W: io::Write> CueSink<StyledText<'_>> for AssWriter<W> {
+    type Error = io::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &StyledText<'_>) -> Result<(), Self::Error> {
+        self.write_dialogue(time, cue.text, cue.style, cue.orientation)
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.write_header()?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+    use assert_matches2::assert_matches;
+
+    #[test]
+    fn parse_ass_script() {
+        let content = "\u{FEFF}[Script Info]\r\n; comment\r\nTitle: Example\r\nPlayResX: 1920\r\n\r\n\
+                       [V4+ Styles]\n\
+                       Format: Name, Fontname, Fontsize, PrimaryColour\n\
+                       Style: Default,Arial,48,&H00FFFFFF\n\
+                       Style: Sign,Verdana,32,&H0000FFFF\n\n\
+                       [Events]\n\
+                       Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                       Comment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,ignored\n\
+                       Dialogue: 0,0:00:01.50,0:00:03.00,Default,Bob,0,0,0,,Hello, {\\i1}World{\\i0}\n\
+                       Dialogue: 1,1:02:03.04,1:02:05.5,Sign,,0,0,0,,Two\\Nlines";
+        let script = parse_ass(content).unwrap();
+        assert_eq!(script.info_value("Title"), Some("Example"));
+        assert_eq!(script.info_value("PlayResX"), Some("1920"));
+        assert_eq!(script.styles().len(), 2);
+        let sign = script.style("Sign").unwrap();
+        assert_eq!(sign.field("Fontname"), Some("Verdana"));
+        assert_eq!(sign.field("Fontsize"), Some("32"));
+
+        let events = script.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name, "Bob");
+        assert_eq!(events[0].text, "Hello, {\\i1}World{\\i0}");
+        assert_eq!(events[1].layer, 1);
+        assert_eq!(events[1].style, "Sign");
+        assert_eq!(
+            script.subtitles(),
+            [
+                (span(1500, 3000), "Hello, World".to_owned()),
+                (span(3_723_040, 3_725_500), "Two\nlines".to_owned()),
+            ]
+        );
+
+        assert_matches!(
+            parse_ass("[Events]\nDialogue: 0,0:00:01.00,0:00:02.00"),
+            Err(AssError::MissingField { line: 2 })
+        );
+        assert_matches!(
+            parse_ass("[Events]\nDialogue: 0,0:00:01.00,xx,Default,,0,0,0,,Text"),
+            Err(AssError::InvalidTiming { line: 2 })
+        );
+    }
+
+    #[test]
+    fn stream_ass() {
+        let track_style = TrackStyle {
+            text_height: Some(42),
+            outline_thickness: 3,
+            primary_color: Some(Rgb([255, 255, 0])),
+            outline_color: Some(Rgb([0, 0, 0])),
+            bottom_margin: Some(60),
+        };
+        let mut writer = AssWriter::new(Vec::new())
+            .with_play_res(Size { w: 1920, h: 1080 })
+            .with_style(AssStyle::from_track_style("Main", &track_style))
+            .with_style(AssStyle::new("Sign").with_field("Alignment", "8"));
+        writer
+            .write_all([
+                (span(500, 1499), StyledText::new("Hello\nWorld", None)),
+                (span(2000, 3500), StyledText::new("Sign", Some("Sign"))),
+                (
+                    span(4000, 5000),
+                    StyledText::new("縦書き", None).with_orientation(Orientation::Vertical),
+                ),
+            ])
+            .unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(output.contains("Dialogue: 0,0:00:00.50,0:00:01.49,Main,,0,0,0,,Hello\\NWorld\n"));
+        assert!(
+            output.contains("Dialogue: 0,0:00:04.00,0:00:05.00,Main,,0,0,0,,{\\frz270}縦書き\n")
+        );
+
+        let script = parse_ass(&output).unwrap();
+        assert_eq!(script.info_value("PlayResY"), Some("1080"));
+        let main = script.style("Main").unwrap();
+        assert_eq!(main.field("Fontsize"), Some("42"));
+        assert_eq!(main.field("PrimaryColour"), Some("&H0000FFFF"));
+        assert_eq!(main.field("MarginV"), Some("60"));
+        assert_eq!(script.style("Sign").unwrap().field("Alignment"), Some("8"));
+        assert_eq!(
+            script.subtitles(),
+            [
+                (span(500, 1490), "Hello\nWorld".to_owned()),
+                (span(2000, 3500), "Sign".to_owned()),
+                (span(4000, 5000), "縦書き".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_ass_with_hints() {
+        let mut writer = AssWriter::with_hints(Vec::new(), TextHints::from_lang("ar"));
+        writer.write_cue(span(500, 1499), "{نعم}\n...لا").unwrap();
+        CueSink::<str>::finish(&mut writer).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(output.contains("\nLanguage: ar\n"));
+        assert!(
+            output.contains(",Default,,0,0,0,,\u{200F}\\{نعم\\}\u{200F}\\N\u{200F}...لا\u{200F}\n")
+        );
+
+        // The escaped braces are not read as an override block.
+        let script = parse_ass(&output).unwrap();
+        assert_eq!(
+            script.subtitles(),
+            [(
+                span(500, 1490),
+                "\u{200F}{نعم}\u{200F}\n\u{200F}...لا\u{200F}".to_owned()
+            )]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Runtime description of the capabilities of the crate : version, supported formats and
+//! enabled features.
+//!
+//! Front-ends bundling the crate can adapt their menus from [`Capabilities::current`],
+//! or from its `JSON` export ([`Capabilities::write_json`]) for front-ends written in
+//! other languages, without sniffing the features at compile time.
+use std::io;
+
+/// Kind of content of a format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatKind {
+    /// Subtitles as text.
+    Text,
+    /// Subtitles as images.
+    Bitmap,
+    /// Metadata of the cues, without their content.
+    Metadata,
+    /// Container of subtitle streams.
+    Container,
+}
+
+impl FormatKind {
+    /// Name of the kind, as written in `JSON`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Bitmap => "bitmap",
+            Self::Metadata => "metadata",
+            Self::Container => "container",
+        }
+    }
+}
+
+/// A format handled by the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatInfo {
+    /// Name of the format, also the name of its module.
+    pub name: &'static str,
+    /// Extensions of the files of the format.
+    pub extensions: &'static [&'static str],
+    /// Kind of content of the format.
+    pub kind: FormatKind,
+    /// Can the format be read.
+    pub read: bool,
+    /// Can the format be written.
+    pub write: bool,
+}
+
+impl FormatInfo {
+    const fn new(
+        name: &'static str,
+        extensions: &'static [&'static str],
+        kind: FormatKind,
+        (read, write): (bool, bool),
+    ) -> Self {
+        Self {
+            name,
+            extensions,
+            kind,
+            read,
+            write,
+        }
+    }
+}
+
+/// Formats of the crate.
+const FORMATS: &[FormatInfo] = &[
+    FormatInfo::new("vobsub", &["idx", "sub"], FormatKind::Bitmap, (true, true)),
+    FormatInfo::new("pgs", &["sup"], FormatKind::Bitmap, (true, true)),
+    FormatInfo::new("srt", &["srt"], FormatKind::Text, (true, true)),
+    FormatInfo::new("webvtt", &["vtt"], FormatKind::Text, (false, true)),
+    FormatInfo::new("ass", &["ass", "ssa"], FormatKind::Text, (true, true)),
+    FormatInfo::new("lrc", &["lrc"], FormatKind::Text, (false, true)),
+    FormatInfo::new("sami", &["smi", "sami"], FormatKind::Text, (true, false)),
+    FormatInfo::new("microdvd", &["sub"], FormatKind::Text, (true, false)),
+    FormatInfo::new(
+        "json",
+        &["jsonl", "json"],
+        FormatKind::Metadata,
+        (true, true),
+    ),
+    FormatInfo::new("ts", &["ts", "m2ts"], FormatKind::Container, (true, false)),
+];
+
+/// Optional features of the crate, with their activation.
+const FEATURES: [(&str, bool); 3] = [
+    ("parallel", cfg!(feature = "parallel")),
+    ("rayon", cfg!(feature = "rayon")),
+    ("tracing", cfg!(feature = "tracing")),
+];
+
+/// Capabilities of the crate, as built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Version of the crate.
+    pub version: &'static str,
+    /// Formats handled by the crate.
+    pub formats: &'static [FormatInfo],
+    /// Optional features enabled at build time.
+    pub features: Vec<&'static str>,
+}
+
+impl Capabilities {
+    /// Capabilities of this build of the crate.
+    #[must_use]
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            formats: FORMATS,
+            features: FEATURES
+                .iter()
+                .filter_map(|(name, enabled)| enabled.then_some(*name))
+                .collect(),
+        }
+    }
+
+    /// Formats which can be read.
+    pub fn readers(&self) -> impl Iterator<Item = &FormatInfo> {
+        self.formats.iter().filter(|format| format.read)
+    }
+
+    /// Formats which can be written.
+    pub fn writers(&self) -> impl Iterator<Item = &FormatInfo> {
+        self.formats.iter().filter(|format| format.write)
+    }
+
+    /// Return `true` if the optional feature `name` is enabled.
+    #[must_use]
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.contains(&name)
+    }
+
+    /// Write the capabilities as a `JSON` object on one line.
+    /// # Errors
+    ///
+    /// Will return `Err` if writing in `writer` return an `Err`.
+    pub fn write_json(&self, writer: &mut impl io::Write) -> Result<(), io::Error> {
+        // All the names are ASCII identifiers, without characters to escape.
+        let strings = |values: &[&str]| {
+            values
+                .iter()
+                .map(|value| format!(r#""{value}""#))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let formats = self
+            .formats
+            .iter()
+            .map(|format| {
+                format!(
+                    r#"{{"name":"{}","extensions":[{}],"kind":"{}","read":{},"write":{}}}"#,
+                    format.name,
+                    strings(format.extensions),
+                    format.kind.name(),
+                    format.read,
+                    format.write
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(
+            writer,
+            r#"{{"version":"{}","formats":[{formats}],"features":[{}]}}"#,
+            self.version,
+            strings(&self.features)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_capabilities() {
+        let capabilities = Capabilities::current();
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert!(capabilities.readers().any(|format| format.name == "pgs"));
+        assert!(capabilities.writers().all(|format| format.name != "ts"));
+        assert_eq!(
+            capabilities.has_feature("parallel"),
+            cfg!(feature = "parallel")
+        );
+
+        let mut json = Vec::new();
+        capabilities.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(&format!(
+            r#"{{"version":"{}","formats":[{{"name":"vobsub","extensions":["idx","sub"],"kind":"bitmap","read":true,"write":true}},"#,
+            capabilities.version
+        )));
+        let features = if cfg!(feature = "parallel") {
+            r#""parallel""#
+        } else {
+            ""
+        };
+        assert!(json.contains(&format!(r#""features":[{features}"#)));
+    }
+}
@@ -0,0 +1,474 @@
+//! Character matrix `OCR`, as done by classic `VobSub` tools like `SubRip`.
+//!
+//! Each character of a subtitle image is isolated, and identified by a hash of
+//! its bitmap. The text of known characters is looked up in a [`CharMatrix`],
+//! unknown characters are resolved by a user provided function (prompting the
+//! user for example) and added to the matrix.
+//! The matrix can be saved to and loaded from a file, to be reused for the
+//! next subtitles, allowing fully offline and deterministic conversion.
+//! This module is available with the `char_matrix` feature.
+//!
+//! The matrix file format is a text file, with one character by line :
+//! the hexadecimal hash of the glyph, a tabulation, and the text of the glyph.
+//! In the text, backslash, tabulation, carriage return and line feed are escaped
+//! as `\\`, `\t`, `\r` and `\n`.
+
+use crate::{
+    image::{character_lines, BoundingBox, GrayImage, Luma, ReadingOrder},
+    util::fnv1a,
+};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Error for character matrix handling.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum CharMatrixError {
+    /// Io error on a path.
+    #[error("Io error on '{path}'")]
+    Io {
+        /// Source error
+        source: io::Error,
+        /// Path of the file we tried to read or write
+        path: PathBuf,
+    },
+
+    /// Io error while reading or writing a matrix.
+    #[error("Io error on character matrix")]
+    Stream(#[from] io::Error),
+
+    /// A line of a matrix file is invalid.
+    #[error("invalid character matrix line {line}: '{content}'")]
+    InvalidLine {
+        /// Number of the line (starting at 1)
+        line: usize,
+        /// Content of the line
+        content: String,
+    },
+
+    /// A glyph is unknown, and was not resolved.
+    #[error("unknown glyph {0}")]
+    UnknownGlyph(GlyphHash),
+}
+
+/// Hash identifying the bitmap of a glyph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphHash(u64);
+
+impl fmt::Display for GlyphHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Bitmap of a character, extracted from a subtitle image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    width: u32,
+    height: u32,
+    pixels: Vec<bool>,
+}
+
+impl Glyph {
+    /// Extract the glyph in `bbox` from an `OCR` image.
+    #[must_use]
+    pub fn from_image(image: &GrayImage, bbox: BoundingBox, text_color: Luma<u8>) -> Self {
+        let pixels = (bbox.y..bbox.y + bbox.height)
+            .flat_map(|y| (bbox.x..bbox.x + bbox.width).map(move |x| (x, y)))
+            .map(|(x, y)| *image.get_pixel(x, y) == text_color)
+            .collect();
+        Self {
+            width: bbox.width,
+            height: bbox.height,
+            pixels,
+        }
+    }
+
+    /// Width of the glyph.
+    #[must_use]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the glyph.
+    #[must_use]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Is the pixel at `x`, `y` part of the character.
+    #[must_use]
+    pub fn is_set(&self, x: u32, y: u32) -> bool {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Compute the hash of the glyph.
+    ///
+    /// The hash is a `FNV-1a` of the size and pixels, stable across runs and platforms.
+    #[must_use]
+    pub fn hash(&self) -> GlyphHash {
+        let size_bytes = self
+            .width
+            .to_le_bytes()
+            .into_iter()
+            .chain(self.height.to_le_bytes());
+        let pixel_bytes = self.pixels.chunks(8).map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0_u8, |byte, (bit, &set)| byte | (u8::from(set) << bit))
+        });
+        GlyphHash(fnv1a(size_bytes.chain(pixel_bytes)))
+    }
+}
+
+/// Options for character matrix recognition.
+///
+/// Create them with [`CharMatrixOpt::new`] (or [`Default`]) and the `with_*` methods.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct CharMatrixOpt {
+    /// Color of the text in the `OCR` image.
+    pub text_color: Luma<u8>,
+    /// Reading order of the characters.
+    pub order: ReadingOrder,
+    /// Minimal horizontal gap (in pixels) between two characters to insert a space.
+    pub space_width: u32,
+}
+
+impl Default for CharMatrixOpt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CharMatrixOpt {
+    /// Create the default options : black text, read from left to right.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            text_color: Luma([0]),
+            order: ReadingOrder::LeftToRight,
+            space_width: 6,
+        }
+    }
+
+    /// Set the color of the text.
+    #[must_use]
+    pub const fn with_text_color(mut self, text_color: Luma<u8>) -> Self {
+        self.text_color = text_color;
+        self
+    }
+
+    /// Set the reading order of the characters.
+    #[must_use]
+    pub const fn with_order(mut self, order: ReadingOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Set the minimal gap between two characters to insert a space.
+    #[must_use]
+    pub const fn with_space_width(mut self, space_width: u32) -> Self {
+        self.space_width = space_width;
+        self
+    }
+}
+
+/// Association of glyph hashes to their text.
+#[derive(Debug, Clone, Default)]
+pub struct CharMatrix {
+    chars: HashMap<GlyphHash, String>,
+}
+
+impl CharMatrix {
+    /// Create an empty matrix.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a matrix from a file.
+    ///
+    /// # Errors
+    /// Will return [`CharMatrixError::Io`] if the file can't be read,
+    /// and [`CharMatrixError::InvalidLine`] if a line can't be parsed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CharMatrixError> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|source| CharMatrixError::Io {
+            source,
+            path: path.into(),
+        })?;
+        Self::read(BufReader::new(file))
+    }
+
+    /// Read a matrix from a reader.
+    ///
+    /// # Errors
+    /// Will return [`CharMatrixError::Stream`] if reading failed,
+    /// and [`CharMatrixError::InvalidLine`] if a line can't be parsed.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, CharMatrixError> {
+        let mut chars = HashMap::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let entry = line.split_once('\t').and_then(|(hash, text)| {
+                Some((u64::from_str_radix(hash, 16).ok()?, unescape(text)?))
+            });
+            let Some((hash, text)) = entry else {
+                return Err(CharMatrixError::InvalidLine {
+                    line: idx + 1,
+                    content: line,
+                });
+            };
+            chars.insert(GlyphHash(hash), text);
+        }
+        Ok(Self { chars })
+    }
+
+    /// Save the matrix to a file.
+    ///
+    /// # Errors
+    /// Will return [`CharMatrixError::Io`] if the file can't be written.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CharMatrixError> {
+        let path = path.as_ref();
+        let mkerr = |source| CharMatrixError::Io {
+            source,
+            path: path.into(),
+        };
+        let mut file = io::BufWriter::new(fs::File::create(path).map_err(mkerr)?);
+        self.write(&mut file).map_err(mkerr)?;
+        file.flush().map_err(mkerr)
+    }
+
+    /// Write the matrix, sorted by hash to keep the output stable.
+    ///
+    /// # Errors
+    /// Will return `Err` if write in `writer` return an `Err`.
+    pub fn write(&self, writer: &mut impl Write) -> Result<(), io::Error> {
+        let mut chars = self.chars.iter().collect::<Vec<_>>();
+        chars.sort_by_key(|(hash, _)| hash.0);
+        chars
+            .into_iter()
+            .try_for_each(|(hash, text)| writeln!(writer, "{hash}\t{}", escape(text)))
+    }
+
+    /// Get the text associated with a glyph hash.
+    #[must_use]
+    pub fn get(&self, hash: GlyphHash) -> Option<&str> {
+        self.chars.get(&hash).map(String::as_str)
+    }
+
+    /// Associate a text to a glyph hash.
+    pub fn insert(&mut self, hash: GlyphHash, text: String) {
+        self.chars.insert(hash, text);
+    }
+
+    /// Number of known glyphs.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Is the matrix empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Recognize the text of an `OCR` image.
+    ///
+    /// Unknown glyphs are given to `resolve`, and the returned text is added to
+    /// the matrix. Lines of text are separated by `\n`.
+    ///
+    /// # Errors
+    /// Will return [`CharMatrixError::UnknownGlyph`] if `resolve` return `None`.
+    pub fn recognize<F>(
+        &mut self,
+        image: &GrayImage,
+        opt: &CharMatrixOpt,
+        mut resolve: F,
+    ) -> Result<String, CharMatrixError>
+    where
+        F: FnMut(&Glyph) -> Option<String>,
+    {
+        let mut text = String::new();
+        for (line_idx, line) in character_lines(image, opt.text_color, opt.order)
+            .into_iter()
+            .enumerate()
+        {
+            if line_idx > 0 {
+                text.push('\n');
+            }
+            let mut previous: Option<BoundingBox> = None;
+            for bbox in line {
+                if let Some(prev) = previous {
+                    let gap = match opt.order {
+                        ReadingOrder::LeftToRight => bbox.x.saturating_sub(prev.x + prev.width),
+                        ReadingOrder::RightToLeft => prev.x.saturating_sub(bbox.x + bbox.width),
+                    };
+                    if gap >= opt.space_width {
+                        text.push(' ');
+                    }
+                }
+                previous = Some(bbox);
+
+                let glyph = Glyph::from_image(image, bbox, opt.text_color);
+                let hash = glyph.hash();
+                if let Some(known) = self.get(hash) {
+                    text.push_str(known);
+                } else {
+                    let resolved = resolve(&glyph).ok_or(CharMatrixError::UnknownGlyph(hash))?;
+                    text.push_str(&resolved);
+                    self.insert(hash, resolved);
+                }
+            }
+        }
+        Ok(text)
+    }
+}
+
+// Escape the characters which would break the line format of a matrix file.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Reverse of `escape`, `None` on an unknown or incomplete escape sequence.
+fn unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next()? {
+                '\\' => '\\',
+                't' => '\t',
+                'r' => '\r',
+                'n' => '\n',
+                _ => return None,
+            }
+        } else {
+            c
+        };
+        unescaped.push(c);
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two lines : `| ||` and `-`.
+    const IMAGE: [&str; 5] = [
+        "#.......#.#", //
+        "#.......#.#", //
+        "#.......#.#", //
+        "...........", //
+        "...###.....", //
+    ];
+
+    fn image() -> GrayImage {
+        GrayImage::from_fn(11, 5, |x, y| {
+            if IMAGE[y as usize].as_bytes()[x as usize] == b'#' {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        })
+    }
+
+    #[test]
+    fn recognize_with_resolution() {
+        let mut matrix = CharMatrix::new();
+        let mut resolved = Vec::new();
+        let text = matrix
+            .recognize(&image(), &CharMatrixOpt::default(), |glyph| {
+                resolved.push((glyph.width(), glyph.height()));
+                Some(if glyph.width() == 1 { "l" } else { "-" }.to_owned())
+            })
+            .unwrap();
+        assert_eq!(text, "l ll\n-");
+        // The vertical bar is resolved only once.
+        assert_eq!(resolved, [(1, 3), (3, 1)]);
+        assert_eq!(matrix.len(), 2);
+    }
+
+    #[test]
+    fn recognize_unknown_glyph() {
+        let mut matrix = CharMatrix::new();
+        let res = matrix.recognize(&image(), &CharMatrixOpt::default(), |_| None);
+        assert!(matches!(res, Err(CharMatrixError::UnknownGlyph(_))));
+    }
+
+    #[test]
+    fn matrix_write_and_read() {
+        let mut matrix = CharMatrix::new();
+        matrix.insert(GlyphHash(0x12), "a".to_owned());
+        matrix.insert(GlyphHash(0x3), "é".to_owned());
+        let mut buf = Vec::new();
+        matrix.write(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            "0000000000000003\té\n0000000000000012\ta\n"
+        );
+
+        let read = CharMatrix::read(buf.as_slice()).unwrap();
+        assert_eq!(read.get(GlyphHash(0x12)), Some("a"));
+        assert_eq!(read.get(GlyphHash(0x3)), Some("é"));
+    }
+
+    #[test]
+    fn matrix_write_and_read_escaped() {
+        let mut matrix = CharMatrix::new();
+        matrix.insert(GlyphHash(0x1), "a\tb".to_owned());
+        matrix.insert(GlyphHash(0x2), "c\nd\r".to_owned());
+        matrix.insert(GlyphHash(0x3), "\\n".to_owned());
+        let mut buf = Vec::new();
+        matrix.write(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            "0000000000000001\ta\\tb\n0000000000000002\tc\\nd\\r\n0000000000000003\t\\\\n\n"
+        );
+
+        let read = CharMatrix::read(buf.as_slice()).unwrap();
+        assert_eq!(read.len(), 3);
+        assert_eq!(read.get(GlyphHash(0x1)), Some("a\tb"));
+        assert_eq!(read.get(GlyphHash(0x2)), Some("c\nd\r"));
+        assert_eq!(read.get(GlyphHash(0x3)), Some("\\n"));
+    }
+
+    #[test]
+    fn matrix_read_invalid_escape() {
+        let res = CharMatrix::read(&b"0012\ta\\x\n"[..]);
+        assert!(matches!(
+            res,
+            Err(CharMatrixError::InvalidLine { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn matrix_read_invalid_line() {
+        let res = CharMatrix::read(&b"0012 a\n"[..]);
+        assert!(matches!(
+            res,
+            Err(CharMatrixError::InvalidLine { line: 1, .. })
+        ));
+    }
+}
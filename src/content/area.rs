@@ -50,6 +50,38 @@ impl Area {
             h: usize::from(self.height()),
         }
     }
+
+    /// The smallest area containing both `self` and `other`.
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        Self(AreaValues {
+            x1: self.0.x1.min(other.0.x1),
+            y1: self.0.y1.min(other.0.y1),
+            x2: self.0.x2.max(other.0.x2),
+            y2: self.0.y2.max(other.0.y2),
+        })
+    }
+
+    /// The area shared by `self` and `other`, if they intersect.
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let values = AreaValues {
+            x1: self.0.x1.max(other.0.x1),
+            y1: self.0.y1.max(other.0.y1),
+            x2: self.0.x2.min(other.0.x2),
+            y2: self.0.y2.min(other.0.y2),
+        };
+        (values.x1 <= values.x2 && values.y1 <= values.y2).then_some(Self(values))
+    }
+
+    /// Return `true` if `other` is entirely inside `self`.
+    #[must_use]
+    pub const fn contains(&self, other: &Self) -> bool {
+        self.0.x1 <= other.0.x1
+            && self.0.y1 <= other.0.y1
+            && self.0.x2 >= other.0.x2
+            && self.0.y2 >= other.0.y2
+    }
 }
 
 impl TryFrom<AreaValues> for Area {
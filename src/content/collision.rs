@@ -0,0 +1,105 @@
+use std::fmt;
+
+use super::Area;
+use crate::time::TimeSpan;
+
+/// Two cues displayed at the same time, with intersecting areas.
+///
+/// Most text formats can't represent such cues : their content will collide on screen, or
+/// be merged in a single cue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Collision {
+    /// Index of the first cue, starting first.
+    pub first: usize,
+    /// Index of the second cue.
+    pub second: usize,
+    /// Time during which both cues are displayed.
+    pub time: TimeSpan,
+    /// Area shared by both cues.
+    pub area: Area,
+}
+
+impl fmt::Display for Collision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (start, end) = (self.time.start.msecs(), self.time.end.msecs());
+        write!(
+            f,
+            "{start}ms-{end}ms: cues {} and {} share the {}x{} area at ({}, {})",
+            self.first,
+            self.second,
+            self.area.width(),
+            self.area.height(),
+            self.area.left(),
+            self.area.top()
+        )
+    }
+}
+
+/// Find the cues displayed at the same time on intersecting areas.
+///
+/// Cues are identified by their index in `cues`. Cues just touching in time (one ending when
+/// the other starts) don't collide.
+#[must_use]
+pub fn find_collisions(cues: &[(TimeSpan, Area)]) -> Vec<Collision> {
+    let mut order = (0..cues.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&idx| (cues[idx].0.start, idx));
+
+    let mut collisions = Vec::new();
+    // Cues displayed at the start of the current cue.
+    let mut active: Vec<usize> = Vec::new();
+    for idx in order {
+        let (time, area) = &cues[idx];
+        active.retain(|&other| cues[other].0.end > time.start);
+        for &other in &active {
+            let (other_time, other_area) = &cues[other];
+            let end = other_time.end.min(time.end);
+            let overlap = area.intersection(other_area);
+            if let Some(area) = overlap.filter(|_| end > time.start) {
+                collisions.push(Collision {
+                    first: other,
+                    second: idx,
+                    time: TimeSpan::new(time.start, end),
+                    area,
+                });
+            }
+        }
+        active.push(idx);
+    }
+    collisions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::AreaValues;
+    use crate::time::span;
+
+    fn area(x1: u16, y1: u16, x2: u16, y2: u16) -> Area {
+        Area::try_from(AreaValues { x1, y1, x2, y2 }).unwrap()
+    }
+
+    #[test]
+    fn collisions() {
+        let cues = [
+            (span(0, 2000), area(100, 900, 800, 1000)),
+            (span(1000, 3000), area(700, 950, 1200, 1050)),
+            (span(1500, 2500), area(100, 40, 800, 120)),
+            (span(3000, 4000), area(100, 900, 800, 1000)),
+        ];
+        let collisions = find_collisions(&cues);
+        assert_eq!(
+            collisions,
+            [Collision {
+                first: 0,
+                second: 1,
+                time: span(1000, 2000),
+                area: area(700, 950, 800, 1000),
+            }]
+        );
+        assert_eq!(
+            collisions[0].to_string(),
+            "1000ms-2000ms: cues 0 and 1 share the 101x51 area at (700, 950)"
+        );
+    }
+}
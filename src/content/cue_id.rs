@@ -0,0 +1,77 @@
+use std::fmt;
+
+use crate::{image::DumpId, time::TimePoint};
+
+/// Format of the stream containing a cue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum CueFormat {
+    /// `VobSub` (`*.sub`) stream.
+    VobSub,
+    /// `Presentation Graphic Stream` (`*.sup`) stream.
+    Pgs,
+}
+
+impl fmt::Display for CueFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::VobSub => "vobsub",
+            Self::Pgs => "pgs",
+        })
+    }
+}
+
+/// Stable identifier of a decoded cue, derived from its position in the source stream.
+///
+/// The identifier doesn't depend on the processing of the cues, so it can be used to
+/// cross-reference a cue between dumps, manifests, diagnostics and patches :
+/// [`CueId::sequence`] is the index used by [`DumpId`] and by the `JSON` metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CueId {
+    format: CueFormat,
+    offset: u64,
+    sequence: usize,
+}
+
+impl CueId {
+    /// Create the identifier of the cue number `sequence` of a stream in `format`,
+    /// whose data start at the byte `offset`.
+    #[must_use]
+    pub const fn new(format: CueFormat, offset: u64, sequence: usize) -> Self {
+        Self {
+            format,
+            offset,
+            sequence,
+        }
+    }
+
+    /// Format of the stream.
+    #[must_use]
+    pub const fn format(&self) -> CueFormat {
+        self.format
+    }
+
+    /// Offset in bytes, in the stream, of the data of the cue.
+    #[must_use]
+    pub const fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Index of the cue in the stream, starting at `0`.
+    #[must_use]
+    pub const fn sequence(&self) -> usize {
+        self.sequence
+    }
+
+    /// Identifier of the cue in dumps, for a cue starting at `start`.
+    #[must_use]
+    pub const fn dump_id(&self, start: TimePoint) -> DumpId {
+        DumpId::new(self.sequence, start)
+    }
+}
+
+impl fmt::Display for CueId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}#{}", self.format, self.offset, self.sequence)
+    }
+}
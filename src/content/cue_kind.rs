@@ -0,0 +1,171 @@
+use super::{Area, Size};
+use crate::time::TimeSpan;
+
+/// Kind of the text of a subtitle, deduced from its position.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CueKind {
+    /// Spoken text, displayed at the bottom of the screen.
+    #[default]
+    Dialogue,
+    /// Translation of a text of the video (sign, letter, title, ...), displayed
+    /// near the translated text, usually at the top or the center of the screen.
+    Sign,
+    /// Lyrics of a song, highlighted syllable by syllable with a quick succession of
+    /// short subtitles at the same place.
+    Karaoke,
+}
+
+impl CueKind {
+    /// Guess the kind of a subtitle from its `area` on a `screen`.
+    ///
+    /// A subtitle whose vertical center is in the bottom third of the screen is considered
+    /// as a dialogue, others as signs.
+    #[must_use]
+    pub fn guess(area: &Area, screen: &Size) -> Self {
+        let center = usize::from(area.top()) + usize::from(area.height()) / 2;
+        if center * 3 >= screen.h * 2 {
+            Self::Dialogue
+        } else {
+            Self::Sign
+        }
+    }
+}
+
+/// Classify subtitles as dialogue, signs or karaoke, from their position, duration and size.
+///
+/// It allows, for example, to export the signs in a separate track. Create it with
+/// [`CueClassifier::new`] (or [`Default`]) and the `with_*` methods.
+#[derive(Debug, Clone, Copy)]
+pub struct CueClassifier {
+    karaoke_max_duration: i64,
+    karaoke_max_gap: i64,
+    large_text_percent: usize,
+}
+
+impl Default for CueClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CueClassifier {
+    /// Create a classifier with the default thresholds : karaoke subtitles last at most
+    /// `1000`ms and follow each other by at most `100`ms, and subtitles higher than `20`%
+    /// of the screen are signs.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            karaoke_max_duration: 1000,
+            karaoke_max_gap: 100,
+            large_text_percent: 20,
+        }
+    }
+
+    /// Set the maximum duration of a karaoke subtitle, in milliseconds.
+    #[must_use]
+    pub const fn with_karaoke_max_duration(mut self, msecs: i64) -> Self {
+        self.karaoke_max_duration = msecs;
+        self
+    }
+
+    /// Set the maximum gap between two karaoke subtitles, in milliseconds.
+    #[must_use]
+    pub const fn with_karaoke_max_gap(mut self, msecs: i64) -> Self {
+        self.karaoke_max_gap = msecs;
+        self
+    }
+
+    /// Set the height (in percent of the screen height) from which a subtitle is a sign.
+    #[must_use]
+    pub const fn with_large_text_percent(mut self, percent: usize) -> Self {
+        self.large_text_percent = percent;
+        self
+    }
+
+    /// Return the kind of each of the `cues`, given by their time span and their area
+    /// on a `screen`, in display order.
+    ///
+    /// - A short subtitle, preceded or followed closely by another short subtitle with the
+    ///   same area, is karaoke.
+    /// - Otherwise, a subtitle higher than the large text threshold is a sign, and the
+    ///   others are classified by their position with [`CueKind::guess`].
+    #[must_use]
+    pub fn classify(&self, cues: &[(TimeSpan, Area)], screen: &Size) -> Vec<CueKind> {
+        let short = |(time, _): &(TimeSpan, Area)| {
+            time.end.msecs() - time.start.msecs() <= self.karaoke_max_duration
+        };
+        let chained = |first: &(TimeSpan, Area), second: &(TimeSpan, Area)| {
+            short(first)
+                && short(second)
+                && first.1 == second.1
+                && second.0.start.msecs() - first.0.end.msecs() <= self.karaoke_max_gap
+        };
+
+        cues.iter()
+            .enumerate()
+            .map(|(i, cue)| {
+                let after_previous = i > 0 && chained(&cues[i - 1], cue);
+                let before_next = cues.get(i + 1).is_some_and(|next| chained(cue, next));
+                if after_previous || before_next {
+                    CueKind::Karaoke
+                } else if usize::from(cue.1.height()) * 100 > screen.h * self.large_text_percent {
+                    CueKind::Sign
+                } else {
+                    CueKind::guess(&cue.1, screen)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::AreaValues;
+    use crate::time::span;
+
+    fn area(x1: u16, y1: u16, x2: u16, y2: u16) -> Area {
+        Area::try_from(AreaValues { x1, y1, x2, y2 }).unwrap()
+    }
+
+    #[test]
+    fn guess_kind() {
+        let screen = Size { w: 1920, h: 1080 };
+        let bottom = area(600, 950, 1300, 1030);
+        assert_eq!(CueKind::guess(&bottom, &screen), CueKind::Dialogue);
+        let top = area(600, 40, 1300, 120);
+        assert_eq!(CueKind::guess(&top, &screen), CueKind::Sign);
+        let centered = area(800, 500, 1100, 580);
+        assert_eq!(CueKind::guess(&centered, &screen), CueKind::Sign);
+    }
+
+    #[test]
+    fn classify_cues() {
+        let screen = Size { w: 1920, h: 1080 };
+        let bottom = area(600, 950, 1300, 1030);
+        let lyrics = area(600, 40, 1300, 120);
+        let big = area(200, 700, 1700, 1000);
+        let cues = [
+            (span(0, 2000), bottom),
+            (span(2000, 2400), lyrics),
+            (span(2450, 2900), lyrics),
+            (span(2900, 3500), lyrics),
+            (span(4000, 4500), lyrics),
+            (span(5000, 8000), big),
+            (span(8000, 9000), bottom),
+        ];
+        assert_eq!(
+            CueClassifier::new().classify(&cues, &screen),
+            [
+                CueKind::Dialogue,
+                CueKind::Karaoke,
+                CueKind::Karaoke,
+                CueKind::Karaoke,
+                CueKind::Sign,
+                CueKind::Sign,
+                CueKind::Dialogue,
+            ]
+        );
+    }
+}
@@ -1,8 +1,16 @@
 //! Module for subtitle content utils
 mod area;
+mod collision;
+mod cue_id;
+mod cue_kind;
+mod orientation;
 mod size;
 
 pub use area::{Area, AreaValues};
+pub use collision::{find_collisions, Collision};
+pub use cue_id::{CueFormat, CueId};
+pub use cue_kind::{CueClassifier, CueKind};
+pub use orientation::Orientation;
 pub use size::Size;
 
 use thiserror::Error;
@@ -0,0 +1,68 @@
+use super::{Area, Size};
+
+/// Orientation of the text of a subtitle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Orientation {
+    /// Text is written in horizontal lines.
+    #[default]
+    Horizontal,
+    /// Text is written in vertical columns, as sometimes used for `CJK` subtitles.
+    Vertical,
+}
+
+impl Orientation {
+    /// Guess the orientation of the text of a subtitle from its `area` on a `screen`.
+    ///
+    /// A subtitle much higher than wide is considered as vertical. A subtitle
+    /// only a little higher than wide is considered as vertical only if placed
+    /// on a side of the screen, where vertical subtitles are usually displayed.
+    #[must_use]
+    pub fn guess(area: &Area, screen: &Size) -> Self {
+        let width = usize::from(area.width());
+        let height = usize::from(area.height());
+        let left = usize::from(area.left());
+        let on_side = left + width <= screen.w / 4 || left >= screen.w - screen.w / 4;
+
+        if height >= width * 2 || (height > width && on_side) {
+            Self::Vertical
+        } else {
+            Self::Horizontal
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::AreaValues;
+
+    fn area(x1: u16, y1: u16, x2: u16, y2: u16) -> Area {
+        Area::try_from(AreaValues { x1, y1, x2, y2 }).unwrap()
+    }
+
+    #[test]
+    fn guess_orientation() {
+        let screen = Size { w: 1920, h: 1080 };
+        // Usual bottom subtitle.
+        let bottom = area(600, 950, 1300, 1030);
+        assert_eq!(
+            Orientation::guess(&bottom, &screen),
+            Orientation::Horizontal
+        );
+        // Column of text on the right.
+        let column = area(1800, 100, 1860, 700);
+        assert_eq!(Orientation::guess(&column, &screen), Orientation::Vertical);
+        // Two short columns on the left side.
+        let short_columns = area(40, 100, 200, 300);
+        assert_eq!(
+            Orientation::guess(&short_columns, &screen),
+            Orientation::Vertical
+        );
+        // Same shape at the center of the screen.
+        let centered = area(900, 100, 1060, 300);
+        assert_eq!(
+            Orientation::guess(&centered, &screen),
+            Orientation::Horizontal
+        );
+    }
+}
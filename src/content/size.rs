@@ -1,5 +1,5 @@
 /// The dimensions of an image.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Size {
     /// Width in pixels.
     pub w: usize,
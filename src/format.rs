@@ -0,0 +1,78 @@
+//! Text subtitles output formats.
+//!
+//! The text writers ([`SrtWriter`], [`VttWriter`], [`AssWriter`] and [`LrcWriter`]) stream
+//! the cues as [`CueSink`]s of text. The [`SubtitleSink`] trait completes them with the
+//! writing of the header, so conversion code can be generic over the output format :
+//! ```
+//! # use std::io;
+//! # use subtile::{format::SubtitleSink, time::TimeSpan};
+//! fn convert<S: SubtitleSink>(mut sink: S, cues: &[(TimeSpan, String)]) -> io::Result<S> {
+//!     sink.write_header()?;
+//!     sink.write_all(cues.iter().map(|(time, text)| (*time, text)))?;
+//!     Ok(sink)
+//! }
+//! ```
+//!
+//! [`SrtWriter`]: crate::srt::SrtWriter
+//! [`VttWriter`]: crate::webvtt::VttWriter
+//! [`AssWriter`]: crate::ass::AssWriter
+//! [`LrcWriter`]: crate::lrc::LrcWriter
+use std::io;
+
+use crate::sink::CueSink;
+
+/// Streaming writer of text subtitles in a file format.
+///
+/// The header is written once : by [`SubtitleSink::write_header`], or else before the
+/// first cue, or by [`CueSink::finish`] for an output without cue.
+pub trait SubtitleSink: CueSink<str, Error = io::Error> {
+    /// Extension of the files of the format, like `srt`.
+    const EXTENSION: &'static str;
+
+    /// Write the header of the output, if the format has one and it isn't written yet.
+    ///
+    /// # Errors
+    /// Return the error happened during the write of the header.
+    fn write_header(&mut self) -> Result<(), io::Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ass::AssWriter,
+        lrc::LrcWriter,
+        srt::SrtWriter,
+        time::{TimePoint, TimeSpan},
+        webvtt::VttWriter,
+    };
+
+    /// Write the header and a cue, return the output.
+    fn convert<S: SubtitleSink>(mut sink: S, into_inner: fn(S) -> Vec<u8>) -> String {
+        let time = TimeSpan::new(TimePoint::from_msecs(500), TimePoint::from_msecs(1500));
+        sink.write_header().unwrap();
+        sink.write_header().unwrap();
+        sink.write_all([(time, "Hello")]).unwrap();
+        String::from_utf8(into_inner(sink)).unwrap()
+    }
+
+    #[test]
+    fn generic_conversion() {
+        let srt = convert(SrtWriter::new(Vec::new()), SrtWriter::into_inner);
+        assert_eq!(srt, "1\n00:00:00,500 --> 00:00:01,500\nHello\n\n");
+        let vtt = convert(VttWriter::new(Vec::new()), VttWriter::into_inner);
+        assert_eq!(vtt.matches("WEBVTT").count(), 1);
+        let ass = convert(AssWriter::new(Vec::new()), AssWriter::into_inner);
+        assert_eq!(ass.matches("[Script Info]").count(), 1);
+        assert!(ass.ends_with("Dialogue: 0,0:00:00.50,0:00:01.50,Default,,0,0,0,,Hello\n"));
+        let lrc = convert(LrcWriter::new(Vec::new()), LrcWriter::into_inner);
+        assert_eq!(lrc, "[00:00.50]Hello\n[00:01.50]\n");
+        assert_eq!(
+            [
+                SrtWriter::<Vec<u8>>::EXTENSION,
+                VttWriter::<Vec<u8>>::EXTENSION
+            ],
+            ["srt", "vtt"]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Connected components detection in images.
+
+use image::{GrayImage, Luma};
+use std::cmp::Reverse;
+
+/// Neighborhood used to consider two pixels connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Connectivity {
+    /// Only horizontal and vertical neighbors are connected.
+    Four,
+    /// Diagonal neighbors are also connected.
+    Eight,
+}
+
+/// A group of connected pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Component {
+    /// Coordinates of the pixels of the component.
+    pub pixels: Vec<(u32, u32)>,
+}
+
+impl Component {
+    /// Compute the bounding box of the component.
+    pub(super) fn bounding_box(&self) -> BoundingBox {
+        let (mut x1, mut y1) = (u32::MAX, u32::MAX);
+        let (mut x2, mut y2) = (0, 0);
+        for &(x, y) in &self.pixels {
+            x1 = x1.min(x);
+            y1 = y1.min(y);
+            x2 = x2.max(x);
+            y2 = y2.max(y);
+        }
+        BoundingBox {
+            x: x1,
+            y: y1,
+            width: x2 + 1 - x1,
+            height: y2 + 1 - y1,
+        }
+    }
+}
+
+/// Rectangle containing a group of pixels of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct BoundingBox {
+    /// Coordinate of the leftmost column.
+    pub x: u32,
+    /// Coordinate of the topmost row.
+    pub y: u32,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+impl BoundingBox {
+    /// Smallest box containing all the `boxes`, `None` if there is none.
+    pub(crate) fn enclosing<'a, I>(boxes: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = &'a Self>,
+    {
+        boxes
+            .into_iter()
+            .map(|bbox| (bbox.x, bbox.y, bbox.x + bbox.width, bbox.y + bbox.height))
+            .reduce(|(x1, y1, x2, y2), (bx1, by1, bx2, by2)| {
+                (x1.min(bx1), y1.min(by1), x2.max(bx2), y2.max(by2))
+            })
+            .map(|(x1, y1, x2, y2)| Self {
+                x: x1,
+                y: y1,
+                width: x2 - x1,
+                height: y2 - y1,
+            })
+    }
+}
+
+/// Order in which the characters of a line are read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadingOrder {
+    /// From left to right, like latin scripts.
+    #[default]
+    LeftToRight,
+    /// From right to left, like arabic or hebrew scripts.
+    RightToLeft,
+}
+
+/// Compute the bounding boxes of the characters drawn with `text_color` in an `OCR` image.
+///
+/// Each group of connected pixels (including diagonal neighbors) is considered
+/// as a character, so characters made of several parts (like `i` or `é`)
+/// produce several boxes.
+/// The boxes are grouped by line from top to bottom, and ordered inside a line
+/// following the `order` parameter.
+#[must_use]
+pub fn character_boxes(
+    image: &GrayImage,
+    text_color: Luma<u8>,
+    order: ReadingOrder,
+) -> Vec<BoundingBox> {
+    character_lines(image, text_color, order)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Same as [`character_boxes`], but keep the boxes grouped by line.
+#[must_use]
+pub fn character_lines(
+    image: &GrayImage,
+    text_color: Luma<u8>,
+    order: ReadingOrder,
+) -> Vec<Vec<BoundingBox>> {
+    let (width, height) = image.dimensions();
+    let mut boxes = connected_components(width, height, Connectivity::Eight, |x, y| {
+        *image.get_pixel(x, y) == text_color
+    })
+    .iter()
+    .map(Component::bounding_box)
+    .collect::<Vec<_>>();
+    boxes.sort_by_key(|bbox| (bbox.y, bbox.x));
+
+    // Boxes which overlap vertically are on the same line.
+    let mut lines: Vec<(u32, Vec<BoundingBox>)> = Vec::new();
+    for bbox in boxes {
+        match lines.last_mut() {
+            Some((bottom, line)) if bbox.y < *bottom => {
+                *bottom = (*bottom).max(bbox.y + bbox.height);
+                line.push(bbox);
+            }
+            _ => lines.push((bbox.y + bbox.height, vec![bbox])),
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|(_, mut line)| {
+            match order {
+                ReadingOrder::LeftToRight => line.sort_by_key(|bbox| bbox.x),
+                ReadingOrder::RightToLeft => line.sort_by_key(|bbox| Reverse(bbox.x + bbox.width)),
+            }
+            line
+        })
+        .collect()
+}
+
+/// Find the connected components of the pixels for which `is_set` return `true`.
+///
+/// Components are returned in the order of their first pixel, scanning the
+/// image from top to bottom, and from left to right.
+pub(crate) fn connected_components<F>(
+    width: u32,
+    height: u32,
+    connectivity: Connectivity,
+    is_set: F,
+) -> Vec<Component>
+where
+    F: Fn(u32, u32) -> bool,
+{
+    let idx = |x: u32, y: u32| (y * width + x) as usize;
+    let mut visited = vec![false; (width * height) as usize];
+    let mut components = Vec::new();
+    let mut stack = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            if visited[idx(x, y)] || !is_set(x, y) {
+                continue;
+            }
+            visited[idx(x, y)] = true;
+            stack.push((x, y));
+            let mut pixels = Vec::new();
+            while let Some((px, py)) = stack.pop() {
+                pixels.push((px, py));
+                for (nx, ny) in neighbors(px, py, width, height, connectivity) {
+                    if !visited[idx(nx, ny)] && is_set(nx, ny) {
+                        visited[idx(nx, ny)] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            components.push(Component { pixels });
+        }
+    }
+    components
+}
+
+/// Iterate over the neighbors of a pixel which are inside the image.
+fn neighbors(
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    connectivity: Connectivity,
+) -> impl Iterator<Item = (u32, u32)> {
+    const OFFSETS: [(i8, i8); 8] = [
+        (0, -1),
+        (-1, 0),
+        (1, 0),
+        (0, 1),
+        (-1, -1),
+        (1, -1),
+        (-1, 1),
+        (1, 1),
+    ];
+    let nb_offsets = match connectivity {
+        Connectivity::Four => 4,
+        Connectivity::Eight => 8,
+    };
+    OFFSETS[..nb_offsets].iter().filter_map(move |&(dx, dy)| {
+        let nx = x.checked_add_signed(i32::from(dx))?;
+        let ny = y.checked_add_signed(i32::from(dy))?;
+        (nx < width && ny < height).then_some((nx, ny))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE: [&str; 4] = [
+        "#..#", //
+        ".#..", //
+        "....", //
+        "##.#", //
+    ];
+
+    fn is_set(x: u32, y: u32) -> bool {
+        IMAGE[y as usize].as_bytes()[x as usize] == b'#'
+    }
+
+    #[test]
+    fn components_four_connected() {
+        let components = connected_components(4, 4, Connectivity::Four, is_set);
+        assert_eq!(components.len(), 5);
+        assert_eq!(components[3].pixels.len(), 2);
+    }
+
+    #[test]
+    fn character_boxes_reading_order() {
+        let image = GrayImage::from_fn(
+            4,
+            4,
+            |x, y| {
+                if is_set(x, y) {
+                    Luma([0])
+                } else {
+                    Luma([255])
+                }
+            },
+        );
+        let bbox = |x, y, width, height| BoundingBox {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        let boxes = character_boxes(&image, Luma([0]), ReadingOrder::LeftToRight);
+        assert_eq!(
+            boxes,
+            [
+                bbox(0, 0, 2, 2),
+                bbox(3, 0, 1, 1),
+                bbox(0, 3, 2, 1),
+                bbox(3, 3, 1, 1)
+            ]
+        );
+
+        let boxes = character_boxes(&image, Luma([0]), ReadingOrder::RightToLeft);
+        assert_eq!(
+            boxes,
+            [
+                bbox(3, 0, 1, 1),
+                bbox(0, 0, 2, 2),
+                bbox(3, 3, 1, 1),
+                bbox(0, 3, 2, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn components_eight_connected() {
+        let components = connected_components(4, 4, Connectivity::Eight, is_set);
+        assert_eq!(components.len(), 4);
+        assert_eq!(components[0].pixels.len(), 2);
+    }
+}
@@ -0,0 +1,136 @@
+//! Cropping of the transparent borders of the cue images.
+
+use super::{BoundingBox, ImageSize as _, IndexedImage};
+use crate::content::{Area, AreaValues};
+
+/// Smallest box containing the pixels for which `is_content` returns `true`,
+/// `None` if there is none.
+///
+/// The box is at least 2 pixels wide and high if the image is, as an [`Area`] can't be
+/// smaller : a single column (or row) of content is extended to the next one.
+pub(crate) fn content_box<F>(width: u32, height: u32, is_content: F) -> Option<BoundingBox>
+where
+    F: Fn(u32, u32) -> bool,
+{
+    let (mut x1, mut y1, mut x2, mut y2) = (u32::MAX, u32::MAX, 0, 0);
+    for y in 0..height {
+        for x in (0..width).filter(|&x| is_content(x, y)) {
+            x1 = x1.min(x);
+            x2 = x2.max(x);
+            y1 = y1.min(y);
+            y2 = y2.max(y);
+        }
+    }
+    if x1 > x2 {
+        return None;
+    }
+    let widen = |start: u32, end: u32, size: u32| match (start == end, end + 1 < size) {
+        (false, _) => (start, end),
+        (true, true) => (start, end + 1),
+        (true, false) => (start.saturating_sub(1), end),
+    };
+    let (x1, x2) = widen(x1, x2, width);
+    let (y1, y2) = widen(y1, y2, height);
+    Some(BoundingBox {
+        x: x1,
+        y: y1,
+        width: x2 + 1 - x1,
+        height: y2 + 1 - y1,
+    })
+}
+
+/// Part of an `area` covered by a box of its image.
+pub(crate) fn crop_area(area: Area, bbox: BoundingBox) -> Area {
+    let coord =
+        |start: u16, offset: u32| start.saturating_add(u16::try_from(offset).unwrap_or(u16::MAX));
+    let x1 = coord(area.left(), bbox.x);
+    let y1 = coord(area.top(), bbox.y);
+    Area::try_from(AreaValues {
+        x1,
+        y1,
+        x2: coord(x1, bbox.width - 1),
+        y2: coord(y1, bbox.height - 1),
+    })
+    .unwrap_or(area)
+}
+
+impl<const N: usize> IndexedImage<N> {
+    /// Smallest box containing the non transparent pixels of the image,
+    /// `None` if all its pixels are transparent.
+    #[must_use]
+    pub fn content_box(&self) -> Option<BoundingBox> {
+        let width = self.width();
+        let indexes = self.indexes();
+        let palette = self.palette();
+        content_box(width, self.height(), |x, y| {
+            let index = indexes[y as usize * width as usize + x as usize];
+            palette[usize::from(index)].0[3] > 0
+        })
+    }
+
+    /// Copy of the part of the image in `bbox`, clipped to the image.
+    #[must_use]
+    pub fn crop(&self, bbox: BoundingBox) -> Self {
+        let (width, height) = (self.width(), self.height());
+        let x1 = bbox.x.min(width);
+        let x2 = bbox.x.saturating_add(bbox.width).min(width);
+        let y1 = bbox.y.min(height);
+        let y2 = bbox.y.saturating_add(bbox.height).min(height);
+        let indexes = self
+            .indexes()
+            .chunks_exact(width.max(1) as usize)
+            .take(y2 as usize)
+            .skip(y1 as usize)
+            .flat_map(|row| &row[x1 as usize..x2 as usize])
+            .copied()
+            .collect();
+        Self::new(x2 - x1, y2 - y1, *self.palette(), indexes)
+    }
+
+    /// Crop the transparent borders of the image, displayed on `area`.
+    ///
+    /// Return the cropped image and its area, or `None` if all the pixels are transparent.
+    #[must_use]
+    pub fn crop_to_content(&self, area: &Area) -> Option<(Area, Self)> {
+        let bbox = self.content_box()?;
+        Some((crop_area(*area, bbox), self.crop(bbox)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn crop_indexed_image() {
+        let palette = [Rgba([0, 0, 0, 0]), Rgba([255, 255, 255, 255])];
+        // Content in columns 2 to 4 of the row 3.
+        let indexes = (0..6)
+            .flat_map(|y| (0..8).map(move |x| u8::from(y == 3 && (2..=4).contains(&x))))
+            .collect();
+        let image = IndexedImage::new(8, 6, palette, indexes);
+        let area = Area::try_from(AreaValues {
+            x1: 100,
+            y1: 200,
+            x2: 107,
+            y2: 205,
+        })
+        .unwrap();
+
+        let (cropped_area, cropped) = image.crop_to_content(&area).unwrap();
+        // The single row is extended to the next one.
+        let expected = Area::try_from(AreaValues {
+            x1: 102,
+            y1: 203,
+            x2: 104,
+            y2: 204,
+        })
+        .unwrap();
+        assert_eq!(cropped_area, expected);
+        assert_eq!(cropped.indexes(), [1, 1, 1, 0, 0, 0]);
+
+        let empty = IndexedImage::new(2, 2, palette, vec![0; 4]);
+        assert_eq!(empty.crop_to_content(&area), None);
+    }
+}
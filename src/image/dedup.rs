@@ -0,0 +1,204 @@
+//! Detection of duplicated images, tolerant to palette changes.
+
+use std::collections::HashMap;
+
+use image::Rgba;
+
+use super::{ImageSize as _, IndexedImage};
+use crate::util::fnv1a;
+
+/// How the images are compared to detect duplicates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupMode {
+    /// Images are duplicates if they have the same indexes, and similar colors at the
+    /// same palette entries.
+    #[default]
+    Exact,
+    /// Palettes are normalized before comparison : images are duplicates if the indexes of
+    /// one are the indexes of the other up to a permutation of the palette, and the
+    /// permuted colors are similar.
+    ///
+    /// It catches the duplicates whose palette was reordered or slightly changed by the
+    /// authoring tool, which the [`Exact`](Self::Exact) mode misses.
+    Normalized,
+}
+
+/// Tell if two colors of a palette can be considered the same.
+pub trait ColorMetric {
+    /// Return `true` if `a` and `b` are similar.
+    fn similar(&self, a: Rgba<u8>, b: Rgba<u8>) -> bool;
+}
+
+impl<F> ColorMetric for F
+where
+    F: Fn(Rgba<u8>, Rgba<u8>) -> bool,
+{
+    fn similar(&self, a: Rgba<u8>, b: Rgba<u8>) -> bool {
+        self(a, b)
+    }
+}
+
+/// Colors are similar if each channel differ by at most the tolerance.
+///
+/// Fully transparent colors are always similar, whatever their other channels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColorTolerance(pub u8);
+
+impl ColorMetric for ColorTolerance {
+    fn similar(&self, a: Rgba<u8>, b: Rgba<u8>) -> bool {
+        (a[3] == 0 && b[3] == 0) || a.0.iter().zip(b.0).all(|(a, b)| a.abs_diff(b) <= self.0)
+    }
+}
+
+impl<const N: usize> IndexedImage<N> {
+    /// Indexes of the pixels, with palette entries renumbered in order of first use.
+    fn normalized_indexes(&self) -> impl Iterator<Item = u8> + '_ {
+        let mut numbers = [None; 256];
+        // Up to 256 entries are numbered, counted on a larger type to not overflow.
+        let mut used = 0_u16;
+        self.indexes().iter().map(move |&index| {
+            *numbers[usize::from(index)].get_or_insert_with(|| {
+                let number = u8::try_from(used).unwrap_or(u8::MAX);
+                used += 1;
+                number
+            })
+        })
+    }
+
+    /// Hash of the image for the dedup `mode`, stable across runs and platforms.
+    ///
+    /// Duplicates have the same hash, but images with the same hash need to be checked with
+    /// [`is_duplicate`](Self::is_duplicate). As colors can be similar without being equal,
+    /// only the pixels indexes (and their size) are hashed.
+    #[must_use]
+    pub fn dedup_hash(&self, mode: DedupMode) -> u64 {
+        let size = self.width().to_le_bytes().into_iter();
+        let size = size.chain(self.height().to_le_bytes());
+        match mode {
+            DedupMode::Exact => fnv1a(size.chain(self.indexes().iter().copied())),
+            DedupMode::Normalized => fnv1a(size.chain(self.normalized_indexes())),
+        }
+    }
+
+    /// Check if `other` is a duplicate of this image, with the dedup `mode` and the colors
+    /// compared with `metric`.
+    ///
+    /// Only the palette entries used by the pixels are compared.
+    #[must_use]
+    pub fn is_duplicate(&self, other: &Self, mode: DedupMode, metric: &impl ColorMetric) -> bool {
+        if (self.width(), self.height()) != (other.width(), other.height()) {
+            return false;
+        }
+        let mut mapping = [None; 256];
+        let mut reverse = [None; 256];
+        for (&a, &b) in self.indexes().iter().zip(other.indexes()) {
+            if mode == DedupMode::Exact && a != b {
+                return false;
+            }
+            let (a, b) = (usize::from(a), usize::from(b));
+            if *mapping[a].get_or_insert(b) != b || *reverse[b].get_or_insert(a) != a {
+                return false;
+            }
+        }
+        mapping.iter().enumerate().all(|(a, b)| {
+            b.map_or(true, |b| {
+                metric.similar(self.palette()[a], other.palette()[b])
+            })
+        })
+    }
+}
+
+/// Find the duplicates in a sequence of images.
+///
+/// Images are compared only with the previous ones with the same [`dedup_hash`], so the
+/// cost of the search doesn't grow with the number of distinct images.
+///
+/// [`dedup_hash`]: IndexedImage::dedup_hash
+#[derive(Debug)]
+pub struct Dedup<const N: usize, M> {
+    mode: DedupMode,
+    metric: M,
+    images: HashMap<u64, Vec<(usize, IndexedImage<N>)>>,
+    count: usize,
+}
+
+impl<const N: usize, M: ColorMetric> Dedup<N, M> {
+    /// Create a search of duplicates with the dedup `mode`, and the colors compared
+    /// with `metric`.
+    #[must_use]
+    pub fn new(mode: DedupMode, metric: M) -> Self {
+        Self {
+            mode,
+            metric,
+            images: HashMap::new(),
+            count: 0,
+        }
+    }
+
+    /// Add the next image of the sequence.
+    ///
+    /// Return the position in the sequence of the first image this one duplicates,
+    /// or `None` if it's the first of its kind.
+    pub fn insert(&mut self, image: &IndexedImage<N>) -> Option<usize> {
+        let position = self.count;
+        self.count += 1;
+        let candidates = self.images.entry(image.dedup_hash(self.mode)).or_default();
+        let original = candidates
+            .iter()
+            .find(|(_, known)| known.is_duplicate(image, self.mode, &self.metric))
+            .map(|(position, _)| *position);
+        if original.is_none() {
+            candidates.push((position, image.clone()));
+        }
+        original
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_normalized_palette() {
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let clear = Rgba([0; 4]);
+        let image = IndexedImage::new(2, 2, [clear, red, blue, clear], vec![0, 1, 2, 0]);
+        // Same image with swapped palette entries, a jittered color and another
+        // transparent color.
+        let jittered = Rgba([252, 1, 0, 255]);
+        let permuted = IndexedImage::new(
+            2,
+            2,
+            [blue, Rgba([9, 9, 9, 0]), jittered, clear],
+            vec![3, 2, 0, 3],
+        );
+        let other = IndexedImage::new(2, 2, [clear, red, blue, clear], vec![0, 1, 1, 0]);
+
+        let metric = ColorTolerance(4);
+        assert!(!image.is_duplicate(&permuted, DedupMode::Exact, &metric));
+        assert!(image.is_duplicate(&permuted, DedupMode::Normalized, &metric));
+        assert!(!image.is_duplicate(&permuted, DedupMode::Normalized, &ColorTolerance(0)));
+        assert!(!image.is_duplicate(&other, DedupMode::Normalized, &metric));
+
+        let mut dedup = Dedup::new(DedupMode::Normalized, metric);
+        let found = [&image, &other, &permuted, &other].map(|image| dedup.insert(image));
+        assert_eq!(found, [None, None, Some(0), Some(1)]);
+
+        let mut dedup = Dedup::new(DedupMode::Exact, |a, b| a == b);
+        let found = [&image, &permuted, &image].map(|image| dedup.insert(image));
+        assert_eq!(found, [None, None, Some(0)]);
+    }
+
+    #[test]
+    fn normalize_full_palette() {
+        let palette = [Rgba([0; 4]); 256];
+        let indexes = (0..=255).rev().collect::<Vec<u8>>();
+        let image = IndexedImage::new(16, 16, palette, indexes);
+        assert!(image.normalized_indexes().eq(0..=255));
+        let reversed = IndexedImage::new(16, 16, palette, (0..=255).collect());
+        assert_eq!(
+            image.dedup_hash(DedupMode::Normalized),
+            reversed.dedup_hash(DedupMode::Normalized)
+        );
+    }
+}
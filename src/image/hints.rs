@@ -0,0 +1,93 @@
+//! Hints about the text of a cue, to configure `OCR` engines.
+
+use super::{character_lines, GrayImage, Luma, ReadingOrder};
+use crate::content::{Area, CueKind, Size};
+
+/// Hints about the text of a cue, computed from its `OCR` image and its position.
+///
+/// `OCR` engines work best when configured for the text to recognize (expected size of
+/// characters, segmentation mode for one or several lines, ...); these hints allow to
+/// adapt this configuration for each cue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OcrHints {
+    /// Expected height of the characters in pixels, `None` if the image doesn't contain text.
+    pub char_height: Option<u32>,
+    /// Number of lines of text.
+    pub lines: usize,
+    /// Kind of the cue, guessed from its position.
+    pub kind: CueKind,
+}
+
+impl OcrHints {
+    /// Compute the hints of an `OCR` `image`, with characters drawn with `text_color`,
+    /// displayed at `area` on a `screen`.
+    ///
+    /// The characters are the connected components of the image (see [`character_lines`]).
+    /// The character height is the median of their heights, ignoring the components
+    /// smaller than half of the highest one of their line (dots, accents, punctuation).
+    #[must_use]
+    pub fn new(image: &GrayImage, text_color: Luma<u8>, area: &Area, screen: &Size) -> Self {
+        let lines = character_lines(image, text_color, ReadingOrder::LeftToRight);
+        let mut heights = lines
+            .iter()
+            .flat_map(|line| {
+                let highest = line
+                    .iter()
+                    .map(|bbox| bbox.height)
+                    .max()
+                    .unwrap_or_default();
+                line.iter()
+                    .map(|bbox| bbox.height)
+                    .filter(move |&height| height * 2 >= highest)
+            })
+            .collect::<Vec<_>>();
+        heights.sort_unstable();
+
+        Self {
+            char_height: heights.get(heights.len() / 2).copied(),
+            lines: lines.len(),
+            kind: CueKind::guess(area, screen),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::AreaValues;
+
+    #[test]
+    fn compute_hints() {
+        // Two lines: characters of 8 pixels, with a dot of 2 pixels, and of 10 pixels.
+        let rects = [
+            (0, 0, 3, 8),
+            (5, 0, 3, 8),
+            (10, 6, 2, 2),
+            (0, 12, 3, 10),
+            (5, 12, 3, 10),
+        ];
+        let image = GrayImage::from_fn(20, 24, |x, y| {
+            let inside = rects.iter().any(|&(rx, ry, width, height)| {
+                (rx..rx + width).contains(&x) && (ry..ry + height).contains(&y)
+            });
+            Luma([if inside { 0 } else { 255 }])
+        });
+        let area = Area::try_from(AreaValues {
+            x1: 100,
+            y1: 600,
+            x2: 119,
+            y2: 623,
+        })
+        .unwrap();
+        let screen = Size { w: 720, h: 576 };
+
+        let hints = OcrHints::new(&image, Luma([0]), &area, &screen);
+        assert_eq!(hints.lines, 2);
+        assert_eq!(hints.char_height, Some(10));
+        assert_eq!(hints.kind, CueKind::Dialogue);
+
+        let empty = OcrHints::new(&GrayImage::new(4, 4), Luma([255]), &area, &screen);
+        assert_eq!((empty.char_height, empty.lines), (None, 0));
+    }
+}
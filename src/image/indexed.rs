@@ -0,0 +1,216 @@
+//! Images with indexed colors.
+
+use image::{ImageBuffer, Pixel as _, Rgba};
+
+use super::{ocr, GrayImage, ImageSize, Luma, ToImage, ToImageInto, ToOcrImageOpt};
+
+/// Image whose pixels are indexes in a palette of `N` colors (at most 256).
+///
+/// It's the common representation of the images of the formats using indexed colors :
+/// `IndexedImage<4>` for `VobSub`, `IndexedImage<256>` for `PGS`. Converting between them
+/// keep the palette information, which is lost with a conversion to `RGBA`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedImage<const N: usize> {
+    width: u32,
+    height: u32,
+    palette: [Rgba<u8>; N],
+    indexes: Vec<u8>,
+}
+
+impl<const N: usize> IndexedImage<N> {
+    /// Create an image of `width` x `height` pixels, with the palette indexes of its pixels
+    /// in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `N` is larger than 256, if the number of `indexes` doesn't match the size
+    /// of the image, or if an index is outside of the `palette`.
+    #[must_use]
+    pub fn new(width: u32, height: u32, palette: [Rgba<u8>; N], indexes: Vec<u8>) -> Self {
+        assert!(N <= 256, "palette of {N} colors can't be indexed by bytes");
+        assert_eq!(
+            indexes.len(),
+            width as usize * height as usize,
+            "number of indexes doesn't match image size"
+        );
+        assert!(
+            indexes.iter().all(|&index| usize::from(index) < N),
+            "index outside of the palette"
+        );
+        Self {
+            width,
+            height,
+            palette,
+            indexes,
+        }
+    }
+
+    /// Colors of the palette.
+    #[must_use]
+    pub const fn palette(&self) -> &[Rgba<u8>; N] {
+        &self.palette
+    }
+
+    /// Palette indexes of the pixels, in row-major order.
+    #[must_use]
+    pub fn indexes(&self) -> &[u8] {
+        &self.indexes
+    }
+
+    /// Take the palette indexes of the pixels, to reuse their buffer.
+    #[must_use]
+    pub fn into_indexes(self) -> Vec<u8> {
+        self.indexes
+    }
+
+    /// Convert to an image with a larger palette, filled with transparent colors.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `M` is smaller than `N` or larger than 256.
+    #[must_use]
+    pub fn widen<const M: usize>(self) -> IndexedImage<M> {
+        assert!(
+            N <= M,
+            "palette of {N} colors can't be reduced to {M} colors"
+        );
+        let mut palette = [Rgba([0; 4]); M];
+        palette[..N].copy_from_slice(&self.palette);
+        IndexedImage::new(self.width, self.height, palette, self.indexes)
+    }
+
+    /// Generate the image for `OCR`, with the `OCR` color of each palette entry given
+    /// by `convert`.
+    ///
+    /// The colors are converted once by palette entry, and not for each pixel.
+    #[must_use]
+    pub fn to_ocr_image<F>(&self, convert: F, opt: &ToOcrImageOpt) -> GrayImage
+    where
+        F: Fn(Rgba<u8>) -> Luma<u8>,
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("ocr_image", self.width, self.height).entered();
+        let colors = self.palette.map(convert);
+        let (width, height, border) = (self.width, self.height, opt.border);
+        let pixel_at = |x: u32, y: u32| colors[usize::from(self.indexes[(y * width + x) as usize])];
+        let border_color = opt
+            .border_fill
+            .color(opt.background_color, width, height, pixel_at);
+
+        let image = ImageBuffer::from_fn(width + border * 2, height + border * 2, |x, y| {
+            if x < border || x >= width + border || y < border || y >= height + border {
+                border_color
+            } else {
+                pixel_at(x - border, y - border)
+            }
+        });
+        ocr::post_process(image, *opt)
+    }
+
+    /// Get back the size, the palette and the indexes of the image.
+    #[must_use]
+    pub fn into_parts(self) -> (u32, u32, [Rgba<u8>; N], Vec<u8>) {
+        (self.width, self.height, self.palette, self.indexes)
+    }
+}
+
+impl<const N: usize> ImageSize for IndexedImage<N> {
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+impl<const N: usize> ToImage for IndexedImage<N> {
+    type Pixel = Rgba<u8>;
+
+    fn to_image(&self) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        self.to_image_into(Vec::new())
+    }
+}
+
+impl<const N: usize> ToImageInto for IndexedImage<N> {
+    fn to_image_into(&self, mut buf: Vec<u8>) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        buf.clear();
+        buf.reserve(self.indexes.len() * 4);
+        for &index in &self.indexes {
+            buf.extend_from_slice(self.palette[usize::from(index)].channels());
+        }
+        ImageBuffer::from_vec(self.width, self.height, buf).expect("buffer match the image size")
+    }
+}
+
+/// Image of a cue in two representations, produced from a single decoding : the original
+/// [`IndexedImage`], for archival dumps, and the image prepared for `OCR`.
+///
+/// The `PGS` subtitles are decoded to it by
+/// [`DecodeTimeDualImage`](crate::pgs::DecodeTimeDualImage).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DualImage<const N: usize> {
+    /// Original image, with its palette.
+    pub indexed: IndexedImage<N>,
+    /// Image for `OCR`, see [`IndexedImage::to_ocr_image`].
+    pub ocr: GrayImage,
+}
+
+impl<const N: usize> DualImage<N> {
+    /// Generate the `OCR` image of `indexed`, and keep both.
+    #[must_use]
+    pub fn new<F>(indexed: IndexedImage<N>, convert: F, opt: &ToOcrImageOpt) -> Self
+    where
+        F: Fn(Rgba<u8>) -> Luma<u8>,
+    {
+        let ocr = indexed.to_ocr_image(convert, opt);
+        Self { indexed, ocr }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widen_and_convert() {
+        let palette = [
+            Rgba([0, 0, 0, 0]),
+            Rgba([255, 255, 255, 255]),
+            Rgba([0, 0, 0, 255]),
+            Rgba([128, 128, 128, 255]),
+        ];
+        let image = IndexedImage::new(2, 2, palette, vec![0, 1, 2, 3]);
+        let rgba = image.to_image();
+        assert_eq!(*rgba.get_pixel(1, 0), Rgba([255, 255, 255, 255]));
+
+        let wide = image.clone().widen::<256>();
+        assert_eq!(wide.indexes(), image.indexes());
+        assert_eq!(wide.palette()[3], palette[3]);
+        assert_eq!(wide.palette()[255], Rgba([0; 4]));
+        assert_eq!(wide.to_image(), rgba);
+    }
+
+    #[test]
+    fn dual_image() {
+        let palette = [Rgba([0, 0, 0, 0]), Rgba([255, 255, 255, 255])];
+        let image = IndexedImage::new(3, 1, palette, vec![0, 1, 0]);
+        let opt = ToOcrImageOpt::new().with_border(1);
+        let text_if_opaque = |Rgba([.., alpha])| {
+            if alpha > 0 {
+                opt.text_color
+            } else {
+                opt.background_color
+            }
+        };
+
+        let dual = DualImage::new(image.clone(), text_if_opaque, &opt);
+        assert_eq!(dual.indexed, image);
+        assert_eq!(dual.ocr.dimensions(), (5, 3));
+        let text = dual
+            .ocr
+            .enumerate_pixels()
+            .filter(|(.., p)| **p == opt.text_color);
+        assert_eq!(text.map(|(x, y, _)| (x, y)).collect::<Vec<_>>(), [(2, 1)]);
+    }
+}
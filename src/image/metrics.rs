@@ -0,0 +1,155 @@
+//! Measures of the ink (text pixels) of the cue images.
+//!
+//! These metrics are the base of several heuristics : a sign is often a large box
+//! sparsely filled, a duplicate has the same histogram, and a binarization threshold
+//! is chosen from the luminance histogram.
+
+use super::{
+    components::{connected_components, Component, Connectivity},
+    BoundingBox, GrayImage, ImageSize as _, IndexedImage, Luma,
+};
+
+/// Number of pixels of each luminance of `image`.
+#[must_use]
+pub fn luma_histogram(image: &GrayImage) -> [u64; 256] {
+    let mut histogram = [0; 256];
+    image
+        .pixels()
+        .for_each(|pixel| histogram[usize::from(pixel.0[0])] += 1);
+    histogram
+}
+
+/// Ink metrics of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct InkMetrics {
+    /// Number of pixels of the image.
+    pub pixel_count: usize,
+    /// Number of ink pixels.
+    pub ink_count: usize,
+    /// Smallest box containing the ink pixels, `None` without ink.
+    pub bounding_box: Option<BoundingBox>,
+    /// Number of groups of connected ink pixels (including diagonal neighbors).
+    pub components: usize,
+}
+
+impl InkMetrics {
+    /// Measure the ink of an image of `width` x `height` pixels, the ink pixels being
+    /// the ones for which `is_ink` returns `true`.
+    #[must_use]
+    pub fn from_fn<F>(width: u32, height: u32, is_ink: F) -> Self
+    where
+        F: Fn(u32, u32) -> bool,
+    {
+        let components = connected_components(width, height, Connectivity::Eight, is_ink);
+        let ink_count = components
+            .iter()
+            .map(|component| component.pixels.len())
+            .sum();
+        let bounding_box = BoundingBox::enclosing(
+            &components
+                .iter()
+                .map(Component::bounding_box)
+                .collect::<Vec<_>>(),
+        );
+        Self {
+            pixel_count: width as usize * height as usize,
+            ink_count,
+            bounding_box,
+            components: components.len(),
+        }
+    }
+
+    /// Measure the ink of an `OCR` image, drawn with `text_color`.
+    #[must_use]
+    pub fn from_gray(image: &GrayImage, text_color: Luma<u8>) -> Self {
+        Self::from_fn(image.width(), image.height(), |x, y| {
+            *image.get_pixel(x, y) == text_color
+        })
+    }
+
+    /// Part of the image covered by the ink, from `0` to `1`.
+    #[must_use]
+    pub fn coverage(&self) -> f64 {
+        ratio(self.ink_count, self.pixel_count)
+    }
+
+    /// Part of the bounding box of the ink covered by the ink, from `0` to `1`.
+    #[must_use]
+    pub fn fill_ratio(&self) -> f64 {
+        self.bounding_box.map_or(0., |bbox| {
+            ratio(self.ink_count, bbox.width as usize * bbox.height as usize)
+        })
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.
+    } else {
+        cast::f64(count) / cast::f64(total)
+    }
+}
+
+impl<const N: usize> IndexedImage<N> {
+    /// Number of pixels of each color of the palette.
+    #[must_use]
+    pub fn histogram(&self) -> [usize; N] {
+        let mut histogram = [0; N];
+        self.indexes()
+            .iter()
+            .for_each(|&index| histogram[usize::from(index)] += 1);
+        histogram
+    }
+
+    /// Measure the ink of the image, made of its non transparent pixels.
+    #[must_use]
+    pub fn ink_metrics(&self) -> InkMetrics {
+        let width = self.width();
+        let (indexes, palette) = (self.indexes(), self.palette());
+        InkMetrics::from_fn(width, self.height(), |x, y| {
+            let index = indexes[y as usize * width as usize + x as usize];
+            palette[usize::from(index)].0[3] > 0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn measure_ink() {
+        let palette = [Rgba([0, 0, 0, 0]), Rgba([255, 255, 255, 255])];
+        // A 2x2 square and a diagonal of 5 pixels, in a 10x5 image.
+        let indexes = (0..5)
+            .flat_map(|y| {
+                (0..10).map(move |x| u8::from(matches!((x, y), (1..=2, 1..=2)) || x == y + 5))
+            })
+            .collect();
+        let image = IndexedImage::new(10, 5, palette, indexes);
+        assert_eq!(image.histogram(), [41, 9]);
+
+        let metrics = image.ink_metrics();
+        assert_eq!(metrics.pixel_count, 50);
+        assert_eq!(metrics.ink_count, 9);
+        assert_eq!(metrics.components, 2);
+        assert_eq!(
+            metrics.bounding_box,
+            Some(BoundingBox {
+                x: 1,
+                y: 0,
+                width: 9,
+                height: 5
+            })
+        );
+        assert!((metrics.coverage() - 0.18).abs() < 1e-9);
+        assert!((metrics.fill_ratio() - 0.2).abs() < 1e-9);
+
+        let empty = InkMetrics::from_gray(&GrayImage::new(4, 4), Luma([255]));
+        assert_eq!(empty.bounding_box, None);
+        assert!(empty.fill_ratio().abs() < f64::EPSILON);
+        assert_eq!(luma_histogram(&GrayImage::new(4, 4))[0], 16);
+    }
+}
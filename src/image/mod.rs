@@ -1,11 +1,38 @@
 //! Module for `Image` manipulation.
+mod components;
+pub(crate) mod crop;
+mod dedup;
+mod hints;
+mod indexed;
+mod metrics;
+pub(crate) mod ocr;
 mod pixels;
+mod preprocess;
+mod preview;
+mod raw;
+mod segment;
+mod style;
+mod transform;
 mod utils;
 
 // Re-export some useful image types.
+pub use components::{character_boxes, character_lines, BoundingBox, ReadingOrder};
+pub use dedup::{ColorMetric, ColorTolerance, Dedup, DedupMode};
+pub use hints::OcrHints;
 pub use image::{GrayImage, Luma};
+pub use indexed::{DualImage, IndexedImage};
+pub use metrics::{luma_histogram, InkMetrics};
+pub use ocr::BorderFill;
 pub use pixels::{luma_a_to_luma, luma_a_to_luma_convertor};
-pub use utils::{dump_images, DumpError};
+pub use preprocess::{OcrPipeline, PreprocessStep, ScaleFilter, Threshold};
+pub use preview::{outline_text_regions, PreviewImage, PreviewPalette};
+pub use raw::{PixelFormat, RawImage, RawPixel, ToRawImage};
+pub use segment::{line_boxes, split_lines, SplitMethod, TextLine};
+pub use style::TrackStyle;
+pub use transform::Transform;
+pub use utils::{
+    dump_images, dumped_cue_ids, DumpError, DumpId, DumpPolicy, ImageDumper, DUMP_MANIFEST_FILENAME,
+};
 
 use crate::content::Area;
 use image::{ImageBuffer, Pixel};
@@ -46,8 +73,20 @@ pub trait ToImage {
     fn to_image(&self) -> ImageBuffer<Self::Pixel, Vec<u8>>;
 }
 
+/// Generate an `ImageBuffer` in a caller-supplied buffer, to reuse its allocation.
+///
+/// When processing many images, the buffer of an image no longer used can be
+/// recovered with [`ImageBuffer::into_raw`], and given back to generate the next one.
+pub trait ToImageInto: ToImage {
+    /// Generate the image in `buffer`, whose previous content is discarded.
+    fn to_image_into(&self, buffer: Vec<u8>) -> ImageBuffer<Self::Pixel, Vec<u8>>;
+}
+
 /// Options for image generation.
+///
+/// Create them with [`ToOcrImageOpt::new`] (or [`Default`]) and the `with_*` methods.
 #[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub struct ToOcrImageOpt {
     /// Number of border pixels to add on the input image
     pub border: u32,
@@ -55,18 +94,80 @@ pub struct ToOcrImageOpt {
     pub text_color: Luma<u8>,
     /// Color of the background
     pub background_color: Luma<u8>,
+    /// How the border pixels are filled
+    pub border_fill: BorderFill,
+    /// Swap text and background colors if the image seems inverted
+    /// (when text color pixels are more numerous than background ones), enabled by default.
+    pub auto_invert: bool,
+    /// Maximum size (in pixels) of the isolated specks to remove and of
+    /// the pinholes to fill in the text. `0` disable the despeckle pass.
+    pub despeckle: u32,
 }
 
 // Implement [`Default`] for [`ToOcrImageOpt`] with a border of 5 pixel
-// and colors black for text and white for background.
+// filled with background, colors black for text and white for background,
+// with auto inversion and without despeckle.
 impl Default for ToOcrImageOpt {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToOcrImageOpt {
+    /// Create the default options.
+    #[must_use]
+    pub const fn new() -> Self {
         Self {
             border: 5,
             text_color: Luma([0]),
             background_color: Luma([255]),
+            border_fill: BorderFill::Background,
+            auto_invert: true,
+            despeckle: 0,
         }
     }
+
+    /// Set the number of border pixels.
+    #[must_use]
+    pub const fn with_border(mut self, border: u32) -> Self {
+        self.border = border;
+        self
+    }
+
+    /// Set the color of the text.
+    #[must_use]
+    pub const fn with_text_color(mut self, text_color: Luma<u8>) -> Self {
+        self.text_color = text_color;
+        self
+    }
+
+    /// Set the color of the background.
+    #[must_use]
+    pub const fn with_background_color(mut self, background_color: Luma<u8>) -> Self {
+        self.background_color = background_color;
+        self
+    }
+
+    /// Set how the border pixels are filled.
+    #[must_use]
+    pub const fn with_border_fill(mut self, border_fill: BorderFill) -> Self {
+        self.border_fill = border_fill;
+        self
+    }
+
+    /// Enable or disable the automatic inversion of the colors, enabled by default.
+    #[must_use]
+    pub const fn with_auto_invert(mut self, auto_invert: bool) -> Self {
+        self.auto_invert = auto_invert;
+        self
+    }
+
+    /// Set the maximum size of the specks and pinholes to remove.
+    #[must_use]
+    pub const fn with_despeckle(mut self, despeckle: u32) -> Self {
+        self.despeckle = despeckle;
+        self
+    }
 }
 
 /// Generate a `GrayImage` adapted for `OCR` from self.
@@ -74,3 +175,13 @@ pub trait ToOcrImage {
     /// Generate the image for `OCR` in `GrayImage` format.
     fn image(&self, opt: &ToOcrImageOpt) -> GrayImage;
 }
+
+// Images can be moved to and shared between worker threads.
+const _: () = {
+    use crate::util::assert_send_sync;
+
+    assert_send_sync::<IndexedImage<256>>();
+    assert_send_sync::<RawImage>();
+    assert_send_sync::<OcrHints>();
+    assert_send_sync::<DumpError>();
+};
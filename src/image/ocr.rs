@@ -0,0 +1,278 @@
+//! Helpers to improve images generated for `OCR`.
+
+use super::{
+    components::{connected_components, Connectivity},
+    GrayImage, Luma, ToOcrImageOpt,
+};
+
+/// Define how the border added around an `OCR` image is filled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BorderFill {
+    /// Fill the border with the background color of [`ToOcrImageOpt`].
+    #[default]
+    Background,
+    /// Fill the border with the most common color found on the edges of the image.
+    ///
+    /// This avoid adding a contrasting frame around the text when the
+    /// subtitle touch the edges of the image (dark outline, shadow, ...).
+    EdgeDominant,
+}
+
+impl BorderFill {
+    /// Compute the color used to fill the border of an image of size `width` x `height`.
+    /// `pixel` give access to the already converted color of a pixel of the image.
+    pub(crate) fn color<F>(
+        self,
+        background: Luma<u8>,
+        width: u32,
+        height: u32,
+        pixel: F,
+    ) -> Luma<u8>
+    where
+        F: Fn(u32, u32) -> Luma<u8>,
+    {
+        match self {
+            Self::Background => background,
+            Self::EdgeDominant => dominant_edge_color(width, height, pixel).unwrap_or(background),
+        }
+    }
+}
+
+/// Find the most common color on the edges of an image.
+///
+/// Return `None` if the image is empty.
+/// If several colors are equally common, the lowest value is returned.
+pub(crate) fn dominant_edge_color<F>(width: u32, height: u32, pixel: F) -> Option<Luma<u8>>
+where
+    F: Fn(u32, u32) -> Luma<u8>,
+{
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let mut histogram = [0_u32; 256];
+    let mut count = |x, y| {
+        let Luma([value]) = pixel(x, y);
+        histogram[usize::from(value)] += 1;
+    };
+    for x in 0..width {
+        count(x, 0);
+        if height > 1 {
+            count(x, height - 1);
+        }
+    }
+    for y in 1..height.saturating_sub(1) {
+        count(0, y);
+        if width > 1 {
+            count(width - 1, y);
+        }
+    }
+
+    histogram
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, &count)| count)
+        .map(|(value, _)| Luma([u8::try_from(value).unwrap()]))
+}
+
+/// Apply the post-processing steps enabled in `opt` on a generated `OCR` image.
+pub(crate) fn post_process(mut image: GrayImage, opt: ToOcrImageOpt) -> GrayImage {
+    if opt.auto_invert && looks_inverted(&image, opt) {
+        invert(&mut image, opt);
+    }
+    if opt.despeckle > 0 {
+        despeckle(
+            &mut image,
+            opt.despeckle as usize,
+            opt.text_color,
+            opt.background_color,
+        );
+    }
+    image
+}
+
+/// Check if the image seems to have colors of text and background inverted.
+///
+/// The text of a subtitle cover only a small part of its image, so an image
+/// with more pixels of the text color than of the background color is considered as inverted.
+/// Only the pixels of the source image are counted, not the border added by `opt`.
+fn looks_inverted(image: &GrayImage, opt: ToOcrImageOpt) -> bool {
+    let (width, height) = image.dimensions();
+    let border = opt.border;
+    let inner = image.enumerate_pixels().filter(|&(x, y, _)| {
+        x >= border && y >= border && x + border < width && y + border < height
+    });
+    let (text_pixels, background_pixels) =
+        inner.fold((0_usize, 0_usize), |(text, background), (_, _, &p)| {
+            (
+                text + usize::from(p == opt.text_color),
+                background + usize::from(p == opt.background_color),
+            )
+        });
+    text_pixels > background_pixels
+}
+
+/// Swap text and background colors of the image.
+fn invert(image: &mut GrayImage, opt: ToOcrImageOpt) {
+    image.pixels_mut().for_each(|p| {
+        if *p == opt.text_color {
+            *p = opt.background_color;
+        } else if *p == opt.background_color {
+            *p = opt.text_color;
+        }
+    });
+}
+
+/// Remove small isolated groups of text pixels, and fill small holes in the text.
+///
+/// Text pixels are grouped with their diagonal neighbors, so thin diagonal
+/// strokes are not considered as specks.
+/// The groups of at most `max_size` pixels are changed.
+pub(super) fn despeckle(
+    image: &mut GrayImage,
+    max_size: usize,
+    text_color: Luma<u8>,
+    background_color: Luma<u8>,
+) {
+    let (width, height) = image.dimensions();
+
+    let specks = connected_components(width, height, Connectivity::Eight, |x, y| {
+        *image.get_pixel(x, y) == text_color
+    });
+    specks
+        .iter()
+        .filter(|speck| speck.pixels.len() <= max_size)
+        .flat_map(|speck| speck.pixels.iter())
+        .for_each(|&(x, y)| image.put_pixel(x, y, background_color));
+
+    // Holes touching the edges of the image are part of the background.
+    let holes = connected_components(width, height, Connectivity::Four, |x, y| {
+        *image.get_pixel(x, y) != text_color
+    });
+    holes
+        .iter()
+        .filter(|hole| hole.pixels.len() <= max_size)
+        .filter(|hole| {
+            !hole
+                .pixels
+                .iter()
+                .any(|&(x, y)| x == 0 || y == 0 || x == width - 1 || y == height - 1)
+        })
+        .flat_map(|hole| hole.pixels.iter())
+        .for_each(|&(x, y)| image.put_pixel(x, y, text_color));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edge_color_of_empty_image() {
+        assert_eq!(dominant_edge_color(0, 4, |_, _| Luma([0])), None);
+    }
+
+    #[test]
+    fn edge_color_ignore_inner_pixels() {
+        // 4x3 image with black edges except one pixel, and a white center.
+        let pixel = |x, y| match (x, y) {
+            (1 | 2, 1) | (3, 2) => Luma([255]),
+            _ => Luma([0]),
+        };
+        assert_eq!(dominant_edge_color(4, 3, pixel), Some(Luma([0])));
+    }
+
+    #[test]
+    fn edge_color_with_one_line() {
+        let pixel = |x, _| if x < 3 { Luma([200]) } else { Luma([10]) };
+        assert_eq!(dominant_edge_color(5, 1, pixel), Some(Luma([200])));
+    }
+
+    #[test]
+    fn auto_invert_mostly_text_image() {
+        let opt = ToOcrImageOpt::new().with_border(0).with_auto_invert(true);
+        let image = GrayImage::from_fn(4, 4, |x, _| {
+            if x == 0 {
+                opt.background_color
+            } else {
+                opt.text_color
+            }
+        });
+        let image = post_process(image, opt);
+        assert_eq!(image.pixels().filter(|&&p| p == opt.text_color).count(), 4);
+
+        let no_invert = opt.with_auto_invert(false);
+        let image = post_process(image.clone(), no_invert);
+        assert_eq!(image.pixels().filter(|&&p| p == opt.text_color).count(), 4);
+    }
+
+    #[test]
+    fn auto_invert_keep_regular_image() {
+        let opt = ToOcrImageOpt::new().with_border(0).with_auto_invert(true);
+        let image = GrayImage::from_fn(4, 4, |x, y| {
+            if x == 1 && y > 0 {
+                opt.text_color
+            } else {
+                opt.background_color
+            }
+        });
+        assert_eq!(post_process(image.clone(), opt), image);
+    }
+
+    #[test]
+    fn auto_invert_by_default() {
+        // Dark text covering most of a light background is swapped without opting in.
+        let opt = ToOcrImageOpt::default().with_border(0);
+        assert!(opt.auto_invert);
+        let image = GrayImage::from_fn(4, 4, |x, _| {
+            if x == 0 {
+                opt.background_color
+            } else {
+                opt.text_color
+            }
+        });
+        let image = post_process(image, opt);
+        assert_eq!(image.pixels().filter(|&&p| p == opt.text_color).count(), 4);
+    }
+
+    #[test]
+    fn auto_invert_ignore_border() {
+        // A 2x2 image of text, in a border of background larger than the image.
+        let opt = ToOcrImageOpt::new().with_border(2).with_auto_invert(true);
+        let image = GrayImage::from_fn(6, 6, |x, y| {
+            if (2..4).contains(&x) && (2..4).contains(&y) {
+                opt.text_color
+            } else {
+                opt.background_color
+            }
+        });
+        let image = post_process(image, opt);
+        assert_eq!(image.get_pixel(2, 2), &opt.background_color);
+        assert_eq!(image.get_pixel(0, 0), &opt.text_color);
+    }
+
+    #[test]
+    fn despeckle_remove_specks_and_fill_holes() {
+        let opt = ToOcrImageOpt::new().with_despeckle(1);
+        // A 3x3 square of text with a hole, and an isolated speck.
+        let image = GrayImage::from_fn(8, 6, |x, y| match (x, y) {
+            (2, 2) => opt.background_color,
+            (1..=3, 1..=3) | (6, 1) => opt.text_color,
+            _ => opt.background_color,
+        });
+        let image = post_process(image, opt);
+        let expected = GrayImage::from_fn(8, 6, |x, y| match (x, y) {
+            (1..=3, 1..=3) => opt.text_color,
+            _ => opt.background_color,
+        });
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn border_fill_background() {
+        let color = BorderFill::Background.color(Luma([255]), 2, 2, |_, _| Luma([0]));
+        assert_eq!(color, Luma([255]));
+        let color = BorderFill::EdgeDominant.color(Luma([255]), 2, 2, |_, _| Luma([0]));
+        assert_eq!(color, Luma([0]));
+    }
+}
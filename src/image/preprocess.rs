@@ -0,0 +1,316 @@
+//! Preprocessing of the images before `OCR`.
+//!
+//! The accuracy of `OCR` engines on subtitles images depends a lot on their
+//! preprocessing : the anti-aliased edges must be removed, the specks left by the
+//! compression cleaned and the small `DVD` text upscaled. [`OcrPipeline`] chains
+//! these steps on the [`GrayImage`] generated by [`ToOcrImage`](super::ToOcrImage).
+
+use super::{luma_histogram, ocr, GrayImage, Luma};
+use image::imageops::{self, FilterType};
+use std::num::NonZeroU32;
+
+/// Threshold used to binarize an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    /// Fixed luminance threshold.
+    Fixed(u8),
+    /// Threshold computed from the histogram of each image, with the Otsu method.
+    Otsu,
+}
+
+/// Filter used to upscale an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleFilter {
+    /// Each pixel is repeated, the image stays binary.
+    Nearest,
+    /// Bilinear interpolation, smoother edges.
+    Bilinear,
+}
+
+/// A preprocessing step of an [`OcrPipeline`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreprocessStep {
+    /// Convert each pixel to the text or the background color, from its side of the threshold.
+    Binarize(Threshold),
+    /// Remove the isolated groups of text pixels, and fill the holes in the text,
+    /// of at most this number of pixels.
+    Despeckle(u32),
+    /// Morphological opening (erosion, then dilation) of the text, with a square
+    /// kernel of this radius. Removes the thin noise around the text.
+    Open(u32),
+    /// Morphological closing (dilation, then erosion) of the text, with a square
+    /// kernel of this radius. Joins the broken strokes of the text.
+    Close(u32),
+    /// Multiply the size of the image by a factor.
+    Upscale(NonZeroU32, ScaleFilter),
+}
+
+/// A chain of preprocessing steps applied on images before `OCR`.
+///
+/// The steps are applied in the order they are added. Except [`PreprocessStep::Binarize`]
+/// and [`PreprocessStep::Upscale`], the steps consider as text only the pixels of the
+/// text color, so they are expected after a binarization.
+///
+/// ```
+/// use std::num::NonZeroU32;
+/// use subtile::image::{OcrPipeline, ScaleFilter, Threshold};
+///
+/// let pipeline = OcrPipeline::new()
+///     .binarize(Threshold::Otsu)
+///     .despeckle(2)
+///     .upscale(NonZeroU32::new(3).unwrap(), ScaleFilter::Nearest);
+/// assert_eq!(pipeline.steps().len(), 3);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OcrPipeline {
+    steps: Vec<PreprocessStep>,
+    text_color: Luma<u8>,
+    background_color: Luma<u8>,
+}
+
+impl Default for OcrPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OcrPipeline {
+    /// Create an empty pipeline, for black text on white background
+    /// (the default colors of [`ToOcrImageOpt`](super::ToOcrImageOpt)).
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            text_color: Luma([0]),
+            background_color: Luma([255]),
+        }
+    }
+
+    /// Set the colors of the text and of the background of the images.
+    #[must_use]
+    pub const fn with_colors(mut self, text_color: Luma<u8>, background_color: Luma<u8>) -> Self {
+        self.text_color = text_color;
+        self.background_color = background_color;
+        self
+    }
+
+    /// Add a step at the end of the pipeline.
+    #[must_use]
+    pub fn step(mut self, step: PreprocessStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Add a [`PreprocessStep::Binarize`] step.
+    #[must_use]
+    pub fn binarize(self, threshold: Threshold) -> Self {
+        self.step(PreprocessStep::Binarize(threshold))
+    }
+
+    /// Add a [`PreprocessStep::Despeckle`] step.
+    #[must_use]
+    pub fn despeckle(self, max_size: u32) -> Self {
+        self.step(PreprocessStep::Despeckle(max_size))
+    }
+
+    /// Add a [`PreprocessStep::Open`] step.
+    #[must_use]
+    pub fn open(self, radius: u32) -> Self {
+        self.step(PreprocessStep::Open(radius))
+    }
+
+    /// Add a [`PreprocessStep::Close`] step.
+    #[must_use]
+    pub fn close(self, radius: u32) -> Self {
+        self.step(PreprocessStep::Close(radius))
+    }
+
+    /// Add a [`PreprocessStep::Upscale`] step.
+    #[must_use]
+    pub fn upscale(self, factor: NonZeroU32, filter: ScaleFilter) -> Self {
+        self.step(PreprocessStep::Upscale(factor, filter))
+    }
+
+    /// Steps of the pipeline.
+    #[must_use]
+    pub fn steps(&self) -> &[PreprocessStep] {
+        &self.steps
+    }
+
+    /// Apply all the steps on `image`.
+    #[must_use]
+    pub fn apply(&self, mut image: GrayImage) -> GrayImage {
+        for step in &self.steps {
+            image = match *step {
+                PreprocessStep::Binarize(threshold) => self.binarize_image(image, threshold),
+                PreprocessStep::Despeckle(max_size) => {
+                    ocr::despeckle(
+                        &mut image,
+                        max_size as usize,
+                        self.text_color,
+                        self.background_color,
+                    );
+                    image
+                }
+                PreprocessStep::Open(radius) => {
+                    let eroded = self.morphology(&image, radius, false);
+                    self.morphology(&eroded, radius, true)
+                }
+                PreprocessStep::Close(radius) => {
+                    let dilated = self.morphology(&image, radius, true);
+                    self.morphology(&dilated, radius, false)
+                }
+                PreprocessStep::Upscale(factor, filter) => upscale(&image, factor, filter),
+            };
+        }
+        image
+    }
+
+    fn binarize_image(&self, mut image: GrayImage, threshold: Threshold) -> GrayImage {
+        let threshold = match threshold {
+            Threshold::Fixed(threshold) => threshold,
+            Threshold::Otsu => otsu_threshold(&image),
+        };
+        let dark_text = self.text_color.0[0] < self.background_color.0[0];
+        image.pixels_mut().for_each(|pixel| {
+            let is_text = (pixel.0[0] <= threshold) == dark_text;
+            *pixel = if is_text {
+                self.text_color
+            } else {
+                self.background_color
+            };
+        });
+        image
+    }
+
+    /// Dilate (or erode) the text of `image` with a square kernel of `radius`.
+    ///
+    /// The kernel is clamped to the image : outside of the image is considered as
+    /// the nearest pixel of the edge, so the text touching the edges is not eroded.
+    fn morphology(&self, image: &GrayImage, radius: u32, dilate: bool) -> GrayImage {
+        let (width, height) = image.dimensions();
+        let is_text = |x: u32, y: u32| *image.get_pixel(x, y) == self.text_color;
+        GrayImage::from_fn(width, height, |x, y| {
+            let xs = x.saturating_sub(radius)..=x.saturating_add(radius).min(width - 1);
+            let ys = y.saturating_sub(radius)..=y.saturating_add(radius).min(height - 1);
+            let mut neighbors = ys.flat_map(|ny| xs.clone().map(move |nx| (nx, ny)));
+            let text = if dilate {
+                neighbors.any(|(nx, ny)| is_text(nx, ny))
+            } else {
+                neighbors.all(|(nx, ny)| is_text(nx, ny))
+            };
+            if text {
+                self.text_color
+            } else {
+                self.background_color
+            }
+        })
+    }
+}
+
+fn upscale(image: &GrayImage, factor: NonZeroU32, filter: ScaleFilter) -> GrayImage {
+    let filter = match filter {
+        ScaleFilter::Nearest => FilterType::Nearest,
+        ScaleFilter::Bilinear => FilterType::Triangle,
+    };
+    let (width, height) = image.dimensions();
+    imageops::resize(
+        image,
+        width.saturating_mul(factor.get()),
+        height.saturating_mul(factor.get()),
+        filter,
+    )
+}
+
+/// Compute the threshold separating the luminances of `image` in two classes
+/// with the maximum variance between them.
+fn otsu_threshold(image: &GrayImage) -> u8 {
+    let histogram = luma_histogram(image);
+    let total = histogram.iter().sum::<u64>();
+    let total_sum = histogram
+        .iter()
+        .zip(0_u64..)
+        .map(|(count, value)| count * value)
+        .sum::<u64>();
+
+    let (mut below_count, mut below_sum) = (0_u64, 0_u64);
+    let mut best = (0, 0.);
+    for (threshold, (count, value)) in histogram.iter().zip(0_u64..).enumerate() {
+        below_count += count;
+        below_sum += count * value;
+        let above_count = total - below_count;
+        if below_count == 0 || above_count == 0 {
+            continue;
+        }
+        let below_mean = cast::f64(below_sum) / cast::f64(below_count);
+        let above_mean = cast::f64(total_sum - below_sum) / cast::f64(above_count);
+        let variance =
+            cast::f64(below_count) * cast::f64(above_count) * (below_mean - above_mean).powi(2);
+        if variance > best.1 {
+            best = (threshold, variance);
+        }
+    }
+    u8::try_from(best.0).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preprocess_pipeline() {
+        // Anti-aliased 4x4 square of text, with a speck and a gap in the square.
+        let image = GrayImage::from_fn(10, 8, |x, y| match (x, y) {
+            (3, 3) => Luma([200]),
+            (2..=5, 2..=5) | (8, 1) => Luma([20]),
+            (1 | 6, 2..=5) => Luma([150]),
+            _ => Luma([240]),
+        });
+        let square = |x, y| matches!((x, y), (2..=5, 2..=5));
+
+        let binarized = OcrPipeline::new()
+            .binarize(Threshold::Otsu)
+            .apply(image.clone());
+        assert_eq!(*binarized.get_pixel(1, 2), Luma([255]));
+        assert_eq!(*binarized.get_pixel(3, 3), Luma([255]));
+
+        let closed = OcrPipeline::new()
+            .binarize(Threshold::Fixed(128))
+            .close(1)
+            .apply(image.clone());
+        // The speck is kept, and joined to the closest corner of the image.
+        let expected = GrayImage::from_fn(10, 8, |x, y| {
+            if square(x, y) || matches!((x, y), (8 | 9, 0 | 1)) {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        });
+        assert_eq!(closed, expected);
+
+        let upscaled = OcrPipeline::new()
+            .binarize(Threshold::Fixed(128))
+            .despeckle(1)
+            .open(1)
+            .upscale(NonZeroU32::new(2).unwrap(), ScaleFilter::Nearest)
+            .apply(image);
+        assert_eq!(upscaled.dimensions(), (20, 16));
+        let expected = GrayImage::from_fn(20, 16, |x, y| {
+            if square(x / 2, y / 2) {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        });
+        assert_eq!(upscaled, expected);
+    }
+
+    #[test]
+    fn morphology_keep_text_on_edges() {
+        let text = GrayImage::from_pixel(4, 4, Luma([0]));
+        let pipeline = OcrPipeline::new().close(1);
+        assert_eq!(pipeline.apply(text.clone()), text);
+        let pipeline = OcrPipeline::new().open(1);
+        assert_eq!(pipeline.apply(text.clone()), text);
+    }
+}
@@ -0,0 +1,226 @@
+//! Palettes and marks for the preview images of the cues.
+//!
+//! The colors of subtitles are often close hues (a dark gray outline on a black
+//! shadow, a light green text), hard to tell apart for a color-blind user checking a
+//! dump. The preview palettes replace them with colors ordered by luminance, and the
+//! text regions can be outlined to spot the text at a glance.
+//!
+//! A [`PreviewImage`] applies both to an [`IndexedImage`] when it's converted with
+//! [`ToImage`], to be dumped with [`dump_images`](super::dump_images) or an
+//! [`ImageDumper`](super::ImageDumper).
+
+use image::{Pixel as _, Rgba, RgbaImage};
+
+use super::{
+    character_lines, BoundingBox, GrayImage, ImageSize, IndexedImage, Luma, ReadingOrder, ToImage,
+};
+
+/// Colors of the Okabe-Ito palette, darkest first.
+const OKABE_ITO: [[u8; 3]; 8] = [
+    [0, 0, 0],
+    [0, 114, 178],
+    [213, 94, 0],
+    [0, 158, 115],
+    [204, 121, 167],
+    [230, 159, 0],
+    [86, 180, 233],
+    [240, 228, 66],
+];
+
+/// Alternative palette for the preview images.
+///
+/// The opaque colors are ranked by luminance, and replaced by the colors of the palette
+/// at their rank. Transparent colors stay transparent, and the other ones become fully
+/// opaque.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PreviewPalette {
+    /// Gray levels spread from black to white.
+    #[default]
+    HighContrast,
+    /// Colors of the Okabe-Ito palette, distinguishable with the common color vision
+    /// deficiencies. Colors beyond the eighth rank reuse the palette.
+    OkabeIto,
+}
+
+impl PreviewPalette {
+    /// Replace the colors of `palette`.
+    #[must_use]
+    pub fn map<const N: usize>(self, palette: &[Rgba<u8>; N]) -> [Rgba<u8>; N] {
+        let luma = |color: &Rgba<u8>| color.to_luma().0[0];
+        let mut levels = palette
+            .iter()
+            .filter(|color| color.0[3] > 0)
+            .map(luma)
+            .collect::<Vec<_>>();
+        levels.sort_unstable();
+        levels.dedup();
+        let max_rank = levels.len().saturating_sub(1).max(1);
+
+        palette.map(|color| {
+            if color.0[3] == 0 {
+                return color;
+            }
+            let rank = levels.binary_search(&luma(&color)).unwrap_or_default();
+            let [r, g, b] = match self {
+                Self::HighContrast => {
+                    let level = u8::try_from(rank * 255 / max_rank).unwrap_or(u8::MAX);
+                    [level; 3]
+                }
+                Self::OkabeIto => OKABE_ITO[rank % OKABE_ITO.len()],
+            };
+            Rgba([r, g, b, u8::MAX])
+        })
+    }
+}
+
+impl<const N: usize> IndexedImage<N> {
+    /// Copy of the image with the colors of its palette replaced by the `preview` ones.
+    #[must_use]
+    pub fn with_preview_palette(&self, preview: PreviewPalette) -> Self {
+        Self::new(
+            self.width(),
+            self.height(),
+            preview.map(self.palette()),
+            self.indexes().to_vec(),
+        )
+    }
+}
+
+/// Preview of an [`IndexedImage`], generated by [`ToImage`] with the colors of a
+/// [`PreviewPalette`], and the text regions outlined if requested.
+#[derive(Debug, Clone, Copy)]
+pub struct PreviewImage<'a, const N: usize> {
+    image: &'a IndexedImage<N>,
+    palette: PreviewPalette,
+    outline: Option<Rgba<u8>>,
+}
+
+impl<'a, const N: usize> PreviewImage<'a, N> {
+    /// Preview `image` with the colors of `palette`.
+    #[must_use]
+    pub const fn new(image: &'a IndexedImage<N>, palette: PreviewPalette) -> Self {
+        Self {
+            image,
+            palette,
+            outline: None,
+        }
+    }
+
+    /// Outline the text regions with `color`, see [`outline_text_regions`].
+    #[must_use]
+    pub const fn with_outline(mut self, color: Rgba<u8>) -> Self {
+        self.outline = Some(color);
+        self
+    }
+}
+
+impl<const N: usize> ImageSize for PreviewImage<'_, N> {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+}
+
+impl<const N: usize> ToImage for PreviewImage<'_, N> {
+    type Pixel = Rgba<u8>;
+
+    fn to_image(&self) -> RgbaImage {
+        let mut image = self.image.with_preview_palette(self.palette).to_image();
+        if let Some(color) = self.outline {
+            outline_text_regions(&mut image, color);
+        }
+        image
+    }
+}
+
+/// Draw a `color` rectangle around each line of text of a preview `image`.
+///
+/// The text is made of the non transparent pixels, grouped in lines like with
+/// [`character_lines`]. The rectangles are drawn on the pixels surrounding the lines,
+/// if they are inside the image.
+pub fn outline_text_regions(image: &mut RgbaImage, color: Rgba<u8>) {
+    let (width, height) = image.dimensions();
+    let mask = GrayImage::from_fn(width, height, |x, y| {
+        Luma([if image.get_pixel(x, y).0[3] > 0 {
+            0
+        } else {
+            255
+        }])
+    });
+    let regions = character_lines(&mask, Luma([0]), ReadingOrder::LeftToRight)
+        .into_iter()
+        .filter_map(|line| BoundingBox::enclosing(&line))
+        .collect::<Vec<_>>();
+
+    for region in regions {
+        // Coordinates of the rectangle, one pixel outside of the region.
+        let (left, top) = (i64::from(region.x) - 1, i64::from(region.y) - 1);
+        let right = i64::from(region.x + region.width);
+        let bottom = i64::from(region.y + region.height);
+        let mut put = |x: i64, y: i64| {
+            if let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) {
+                if x < width && y < height {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        };
+        for x in left..=right {
+            put(x, top);
+            put(x, bottom);
+        }
+        for y in top..=bottom {
+            put(left, y);
+            put(right, y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preview_palettes() {
+        let palette = [
+            Rgba([0, 0, 0, 0]),
+            Rgba([30, 90, 30, 255]),
+            Rgba([200, 200, 200, 128]),
+            Rgba([60, 60, 60, 255]),
+        ];
+        let gray = PreviewPalette::HighContrast.map(&palette);
+        assert_eq!(gray[0], palette[0]);
+        assert_eq!(gray[3], Rgba([0, 0, 0, 255]));
+        assert_eq!(gray[1], Rgba([127, 127, 127, 255]));
+        assert_eq!(gray[2], Rgba([255, 255, 255, 255]));
+        let colors = PreviewPalette::OkabeIto.map(&palette);
+        assert_eq!(colors[2], Rgba([213, 94, 0, 255]));
+        let luma = |[r, g, b]: [u8; 3]| Rgba([r, g, b, 255]).to_luma().0[0];
+        assert!(OKABE_ITO.windows(2).all(|c| luma(c[0]) <= luma(c[1])));
+
+        // A line of two characters in a 8x5 image.
+        let indexes = (0..5)
+            .flat_map(|y| (0..8).map(move |x| 3 * u8::from(y == 2 && (x == 2 || x == 4))))
+            .collect();
+        let image = IndexedImage::new(8, 5, palette, indexes);
+        let preview = PreviewImage::new(&image, PreviewPalette::OkabeIto)
+            .with_outline(Rgba([255, 0, 0, 255]))
+            .to_image();
+        let outlined = (0..5)
+            .map(|y| {
+                (0..8)
+                    .map(|x| match preview.get_pixel(x, y).0 {
+                        [255, 0, 0, 255] => '#',
+                        [0, 0, 0, 255] => 'x',
+                        _ => '.',
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outlined,
+            ["........", ".#####..", ".#x.x#..", ".#####..", "........"]
+        );
+    }
+}
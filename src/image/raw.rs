@@ -0,0 +1,163 @@
+//! Images as plain buffers of bytes, for consumers not using the `image` crate types
+//! (`GPU` upload, `FFI`, ...).
+use image::{Luma, LumaA, Pixel, Rgb, Rgba};
+
+/// Layout of the bytes of a pixel in a [`RawImage`], one byte by channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PixelFormat {
+    /// One byte of luminance.
+    Luma,
+    /// One byte of luminance followed by one byte of alpha.
+    LumaAlpha,
+    /// Red, green and blue bytes.
+    Rgb,
+    /// Red, green, blue and alpha bytes.
+    Rgba,
+}
+
+impl PixelFormat {
+    /// Number of bytes of a pixel.
+    #[must_use]
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Luma => 1,
+            Self::LumaAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+}
+
+/// A pixel type of the `image` crate with a known [`PixelFormat`].
+pub trait RawPixel: Pixel<Subpixel = u8> {
+    /// Format of the pixel bytes.
+    const FORMAT: PixelFormat;
+}
+
+impl RawPixel for Luma<u8> {
+    const FORMAT: PixelFormat = PixelFormat::Luma;
+}
+impl RawPixel for LumaA<u8> {
+    const FORMAT: PixelFormat = PixelFormat::LumaAlpha;
+}
+impl RawPixel for Rgb<u8> {
+    const FORMAT: PixelFormat = PixelFormat::Rgb;
+}
+impl RawPixel for Rgba<u8> {
+    const FORMAT: PixelFormat = PixelFormat::Rgba;
+}
+
+/// An image stored as a plain buffer of bytes, line after line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawImage {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+}
+
+impl RawImage {
+    /// Create a raw image from tightly packed lines of pixels.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the size of `data` doesn't match the size and format of the image.
+    #[must_use]
+    pub fn new(data: Vec<u8>, width: u32, height: u32, format: PixelFormat) -> Self {
+        let stride = width as usize * format.bytes_per_pixel();
+        assert_eq!(
+            data.len(),
+            stride * height as usize,
+            "raw image data doesn't match the image size"
+        );
+        Self {
+            data,
+            width,
+            height,
+            stride,
+            format,
+        }
+    }
+
+    /// Bytes of the pixels.
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Width of the image, in pixels.
+    #[must_use]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image, in pixels.
+    #[must_use]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Number of bytes between the start of two consecutive lines.
+    #[must_use]
+    pub const fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Format of the pixels.
+    #[must_use]
+    pub const fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Split the image in `(data, width, height, stride, format)`.
+    #[must_use]
+    pub fn into_parts(self) -> (Vec<u8>, u32, u32, usize, PixelFormat) {
+        (self.data, self.width, self.height, self.stride, self.format)
+    }
+}
+
+/// Generate a [`RawImage`] from `self`.
+pub trait ToRawImage {
+    /// Generate the image as a plain buffer of bytes.
+    fn to_raw_image(&self) -> RawImage;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        content::{Area, AreaValues},
+        image::ToImage as _,
+        vobsub::{conv_to_rgba, VobSubIndexedImage, VobSubToImage},
+    };
+
+    #[test]
+    fn raw_image_of_vobsub_image() {
+        let area = Area::try_from(AreaValues {
+            x1: 10,
+            y1: 20,
+            x2: 12,
+            y2: 21,
+        })
+        .unwrap();
+        let image =
+            VobSubIndexedImage::new(area, [0, 1, 2, 3], [0, 15, 15, 8], vec![0, 1, 1, 0, 2, 3]);
+        let mut palette = [Rgb([0, 0, 0]); 16];
+        palette[1] = Rgb([255, 0, 0]);
+        palette[2] = Rgb([0, 255, 0]);
+        palette[3] = Rgb([0, 0, 255]);
+        let converter = VobSubToImage::new(&image, &palette, conv_to_rgba);
+
+        let raw = converter.to_raw_image();
+        assert_eq!((raw.width(), raw.height()), (3, 2));
+        assert_eq!((raw.stride(), raw.format()), (12, PixelFormat::Rgba));
+        assert_eq!(raw.data(), converter.to_image().as_raw().as_slice());
+        assert_eq!(&raw.data()[4..8], [255, 0, 0, 15]);
+
+        let (data, width, height, stride, format) = raw.clone().into_parts();
+        assert_eq!(RawImage::new(data, width, height, format), raw);
+        assert_eq!(stride, width as usize * format.bytes_per_pixel());
+    }
+}
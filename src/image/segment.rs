@@ -0,0 +1,178 @@
+//! Segmentation of subtitle images in lines of text.
+//!
+//! `OCR` engines recognize better a single line of text than a block of several
+//! lines, so the images of multi-line cues can be split in one image per line, from an
+//! `OCR` image ([`ToOcrImage`](super::ToOcrImage)) of a `VobSub` or `PGS` cue.
+
+use image::imageops;
+
+use super::{character_lines, BoundingBox, GrayImage, Luma, ReadingOrder};
+
+/// Method used to find the lines of text of an image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitMethod {
+    /// The lines are separated by the rows without text pixels.
+    #[default]
+    Projection,
+    /// The lines are groups of characters (connected components) overlapping vertically,
+    /// see [`character_lines`].
+    ///
+    /// Slower, but separate the lines touching each other if their characters don't.
+    Components,
+}
+
+/// A line of text of an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TextLine {
+    /// Box of the line in the source image.
+    pub bbox: BoundingBox,
+    /// Image of the line, cropped from the source image.
+    pub image: GrayImage,
+}
+
+/// Find the bounding boxes of the lines of text drawn with `text_color` in `image`,
+/// from top to bottom.
+///
+/// The lines less than half as high as the highest one, like the diacritics above
+/// capitals, are merged with the nearest line.
+#[must_use]
+pub fn line_boxes(
+    image: &GrayImage,
+    text_color: Luma<u8>,
+    method: SplitMethod,
+) -> Vec<BoundingBox> {
+    let lines = match method {
+        SplitMethod::Projection => projection_lines(image, text_color),
+        SplitMethod::Components => character_lines(image, text_color, ReadingOrder::LeftToRight)
+            .iter()
+            .filter_map(BoundingBox::enclosing)
+            .collect(),
+    };
+    merge_small_lines(lines)
+}
+
+/// Split `image` in one image for each line of text drawn with `text_color`,
+/// from top to bottom.
+///
+/// The line images are surrounded by a `margin` of pixels of the source image,
+/// if they are available.
+#[must_use]
+pub fn split_lines(
+    image: &GrayImage,
+    text_color: Luma<u8>,
+    method: SplitMethod,
+    margin: u32,
+) -> Vec<TextLine> {
+    let (width, height) = image.dimensions();
+    line_boxes(image, text_color, method)
+        .into_iter()
+        .map(|bbox| {
+            let x = bbox.x.saturating_sub(margin);
+            let y = bbox.y.saturating_sub(margin);
+            let right = (bbox.x + bbox.width).saturating_add(margin).min(width);
+            let bottom = (bbox.y + bbox.height).saturating_add(margin).min(height);
+            let image = imageops::crop_imm(image, x, y, right - x, bottom - y).to_image();
+            TextLine { bbox, image }
+        })
+        .collect()
+}
+
+/// Lines of text separated by empty rows.
+fn projection_lines(image: &GrayImage, text_color: Luma<u8>) -> Vec<BoundingBox> {
+    let (width, height) = image.dimensions();
+    let text_columns = |y: u32| (0..width).filter(move |&x| *image.get_pixel(x, y) == text_color);
+
+    let mut lines = Vec::new();
+    let mut line: Option<(u32, u32, u32)> = None;
+    for y in 0..=height {
+        let columns = if y < height {
+            let mut columns = text_columns(y);
+            columns
+                .next()
+                .map(|first| (first, columns.next_back().unwrap_or(first)))
+        } else {
+            None
+        };
+        line = match (line, columns) {
+            (None, None) => None,
+            (None, Some((x1, x2))) => Some((y, x1, x2)),
+            (Some((top, x1, x2)), Some((cx1, cx2))) => Some((top, x1.min(cx1), x2.max(cx2))),
+            (Some((top, x1, x2)), None) => {
+                lines.push(BoundingBox {
+                    x: x1,
+                    y: top,
+                    width: x2 + 1 - x1,
+                    height: y - top,
+                });
+                None
+            }
+        };
+    }
+    lines
+}
+
+/// Merge the small lines with their nearest line, the next one if they are as near.
+fn merge_small_lines(mut lines: Vec<BoundingBox>) -> Vec<BoundingBox> {
+    let max_height = lines
+        .iter()
+        .map(|line| line.height)
+        .max()
+        .unwrap_or_default();
+    let mut idx = 0;
+    while idx < lines.len() {
+        if lines.len() == 1 || lines[idx].height * 2 >= max_height {
+            idx += 1;
+            continue;
+        }
+        let small = lines.remove(idx);
+        let gap_previous = idx.checked_sub(1).map(|previous| {
+            small
+                .y
+                .saturating_sub(lines[previous].y + lines[previous].height)
+        });
+        let gap_next = lines
+            .get(idx)
+            .map(|next| next.y.saturating_sub(small.y + small.height));
+        let target = match (gap_previous, gap_next) {
+            (Some(previous), Some(next)) if previous < next => idx - 1,
+            (Some(_), None) => idx - 1,
+            _ => idx,
+        };
+        lines[target] = BoundingBox::enclosing(&[lines[target], small]).unwrap();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_text_lines() {
+        let text = Luma([0]);
+        // Two lines, an accent above the first one, separated by an empty row.
+        let image = GrayImage::from_fn(12, 10, |x, y| match (x, y) {
+            (3, 1) | (2..=5, 3..=4) | (1..=9, 6..=8) => text,
+            _ => Luma([255]),
+        });
+        let bbox = |x, y, width, height| BoundingBox {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        assert_eq!(
+            line_boxes(&image, text, SplitMethod::Projection),
+            [bbox(2, 1, 4, 4), bbox(1, 6, 9, 3)]
+        );
+
+        let lines = split_lines(&image, text, SplitMethod::Components, 1);
+        let boxes = lines.iter().map(|line| line.bbox).collect::<Vec<_>>();
+        assert_eq!(boxes, [bbox(2, 1, 4, 4), bbox(1, 6, 9, 3)]);
+        assert_eq!(lines[0].image.dimensions(), (6, 6));
+        assert_eq!(*lines[0].image.get_pixel(2, 1), text);
+        assert_eq!(lines[1].image.dimensions(), (11, 5));
+    }
+}
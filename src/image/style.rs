@@ -0,0 +1,225 @@
+//! Inference of the global style of a bitmap subtitles track.
+
+use std::{collections::HashMap, hash::Hash};
+
+use image::{Rgb, Rgba, RgbaImage};
+
+use super::{GrayImage, Luma, OcrHints};
+use crate::content::{Area, Size};
+
+/// Minimal alpha of a pixel considered as part of the text.
+const OPAQUE_ALPHA: u8 = 128;
+
+/// Global style of a bitmap subtitles track, inferred from its images by [`TrackStyle::infer`].
+///
+/// Each parameter is the median (or the most frequent value for colors) of the values
+/// found in the images, so that occasional signs or effects don't change the style of
+/// the dialogues. It allows text outputs (like `ASS`) to reproduce the look of the track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TrackStyle {
+    /// Height of the characters in pixels, without their outline.
+    pub text_height: Option<u32>,
+    /// Thickness of the outline of the characters in pixels, `0` if they aren't outlined.
+    pub outline_thickness: u32,
+    /// Color of the characters.
+    pub primary_color: Option<Rgb<u8>>,
+    /// Color of the outline of the characters.
+    pub outline_color: Option<Rgb<u8>>,
+    /// Distance in pixels between the bottom of the subtitles and the bottom of the screen.
+    pub bottom_margin: Option<u32>,
+}
+
+impl TrackStyle {
+    /// Infer the style of a track from its images, with their `area` on the `screen`.
+    #[must_use]
+    pub fn infer<'a, I>(images: I, screen: &Size) -> Self
+    where
+        I: IntoIterator<Item = (Area, &'a RgbaImage)>,
+    {
+        let mut text_heights = Vec::new();
+        let mut outline_thicknesses = Vec::new();
+        let mut primary_colors = HashMap::new();
+        let mut outline_colors = HashMap::new();
+        let mut bottom_margins = Vec::new();
+
+        for (area, image) in images {
+            let style = ImageStyle::analyze(image);
+            let Some(primary) = style.primary else {
+                continue;
+            };
+            *primary_colors.entry(primary).or_insert(0) += 1;
+            if let Some(outline) = style.outline {
+                *outline_colors.entry(outline).or_insert(0) += 1;
+            }
+            outline_thicknesses.push(style.outline_thickness);
+
+            let mask = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+                Luma([if image.get_pixel(x, y)[3] >= OPAQUE_ALPHA {
+                    0
+                } else {
+                    255
+                }])
+            });
+            let hints = OcrHints::new(&mask, Luma([0]), &area, screen);
+            if let Some(height) = hints.char_height {
+                text_heights.push(height.saturating_sub(2 * style.outline_thickness));
+            }
+            let bottom = usize::from(area.top()) + usize::from(area.height());
+            if let Ok(margin) = u32::try_from(screen.h.saturating_sub(bottom)) {
+                bottom_margins.push(margin);
+            }
+        }
+
+        Self {
+            text_height: median(text_heights),
+            outline_thickness: median(outline_thicknesses).unwrap_or_default(),
+            primary_color: most_frequent(primary_colors).map(Rgb),
+            outline_color: most_frequent(outline_colors).map(Rgb),
+            bottom_margin: median(bottom_margins),
+        }
+    }
+}
+
+/// Style of a single image.
+struct ImageStyle {
+    primary: Option<[u8; 3]>,
+    outline: Option<[u8; 3]>,
+    outline_thickness: u32,
+}
+
+impl ImageStyle {
+    /// Find the colors of an image : the outline is the most frequent color of the opaque
+    /// pixels touching the transparent background, the primary color the most frequent
+    /// color of the other opaque pixels.
+    fn analyze(image: &RgbaImage) -> Self {
+        let (width, height) = image.dimensions();
+        let opaque = |x: u32, y: u32| image.get_pixel(x, y)[3] >= OPAQUE_ALPHA;
+        let rgb = |pixel: &Rgba<u8>| [pixel[0], pixel[1], pixel[2]];
+
+        let mut edge_colors = HashMap::new();
+        let mut inner_colors = HashMap::new();
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if !opaque(x, y) {
+                continue;
+            }
+            let on_edge = [(0, -1), (-1, 0), (1, 0), (0, 1)].iter().any(|&(dx, dy)| {
+                match (x.checked_add_signed(dx), y.checked_add_signed(dy)) {
+                    (Some(nx), Some(ny)) if nx < width && ny < height => !opaque(nx, ny),
+                    _ => true,
+                }
+            });
+            let colors = if on_edge {
+                &mut edge_colors
+            } else {
+                &mut inner_colors
+            };
+            *colors.entry(rgb(pixel)).or_insert(0) += 1;
+        }
+
+        let outline = most_frequent(edge_colors.clone());
+        let primary = most_frequent(
+            inner_colors
+                .into_iter()
+                .filter(|(color, _)| Some(*color) != outline),
+        )
+        .or_else(|| most_frequent(edge_colors));
+        let outline = outline.filter(|outline| Some(*outline) != primary);
+
+        // Thickness of the outline : length of the outline runs between the background and
+        // the primary color, in the rows of the image.
+        let mut thicknesses = Vec::new();
+        if let (Some(primary), Some(outline)) = (primary, outline) {
+            for y in 0..height {
+                let mut run: Option<u32> = None;
+                for x in 0..width {
+                    let pixel = image.get_pixel(x, y);
+                    run = match (opaque(x, y), rgb(pixel)) {
+                        (false, _) => Some(0),
+                        (true, color) if color == outline => run.map(|run| run + 1),
+                        (true, color) => {
+                            if let Some(run) = run.filter(|&run| run > 0 && color == primary) {
+                                thicknesses.push(run);
+                            }
+                            None
+                        }
+                    };
+                }
+            }
+        }
+
+        Self {
+            primary,
+            outline,
+            outline_thickness: median(thicknesses).unwrap_or_default(),
+        }
+    }
+}
+
+/// Median of `values`, the upper one for an even number of values.
+fn median<T: Ord + Copy>(mut values: Vec<T>) -> Option<T> {
+    values.sort_unstable();
+    values.get(values.len() / 2).copied()
+}
+
+/// Most frequent key of `counts`, the smallest one between equally frequent keys.
+fn most_frequent<K: Ord + Hash>(counts: impl IntoIterator<Item = (K, usize)>) -> Option<K> {
+    counts
+        .into_iter()
+        .max_by(|(left, left_count), (right, right_count)| {
+            left_count.cmp(right_count).then_with(|| right.cmp(left))
+        })
+        .map(|(key, _)| key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::AreaValues;
+
+    /// A line of `count` white characters of 4x10 pixels, with a black outline of 1 pixel.
+    fn outlined_text(count: u32) -> RgbaImage {
+        let glyph = |x: u32| x % 8 < 6;
+        RgbaImage::from_fn(count * 8, 12, |x, y| {
+            let inside_x = glyph(x) && x % 8 > 0 && x % 8 < 5;
+            if !glyph(x) {
+                Rgba([0, 0, 0, 0])
+            } else if inside_x && (1..11).contains(&y) {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn infer_track_style() {
+        let area = |y1| {
+            Area::try_from(AreaValues {
+                x1: 100,
+                y1,
+                x2: 139,
+                y2: y1 + 11,
+            })
+            .unwrap()
+        };
+        let images = [outlined_text(5), outlined_text(5), outlined_text(5)];
+        let screen = Size { w: 1920, h: 1080 };
+        let cues = [
+            (area(1000), &images[0]),
+            (area(1010), &images[1]),
+            (area(50), &images[2]),
+        ];
+
+        let style = TrackStyle::infer(cues, &screen);
+        assert_eq!(style.primary_color, Some(Rgb([255, 255, 255])));
+        assert_eq!(style.outline_color, Some(Rgb([0, 0, 0])));
+        assert_eq!(style.outline_thickness, 1);
+        assert_eq!(style.text_height, Some(10));
+        assert_eq!(style.bottom_margin, Some(68));
+
+        let empty = TrackStyle::infer([], &screen);
+        assert_eq!(empty.primary_color, None);
+        assert_eq!(empty.outline_thickness, 0);
+    }
+}
@@ -0,0 +1,169 @@
+//! Geometric transforms of the cue images, to correct images authored mirrored or rotated.
+
+use image::{imageops, ImageBuffer, Pixel};
+
+use super::{ImageSize as _, IndexedImage};
+use crate::content::{Area, AreaValues, ContentError, Size};
+
+/// A flip or a rotation of an image.
+///
+/// The transforms are applied to the image in place : the area of a transformed cue
+/// keeps its center, see [`Transform::apply_area`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Mirror the image left to right.
+    FlipHorizontal,
+    /// Mirror the image top to bottom.
+    FlipVertical,
+    /// Rotate the image by 90 degrees clockwise.
+    Rotate90,
+    /// Rotate the image by 270 degrees clockwise (90 degrees counterclockwise).
+    Rotate270,
+}
+
+impl Transform {
+    /// Size of a transformed image of `width`x`height` pixels.
+    #[must_use]
+    pub const fn transformed_size(self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Self::FlipHorizontal | Self::FlipVertical => (width, height),
+            Self::Rotate90 | Self::Rotate270 => (height, width),
+        }
+    }
+
+    /// Transform an image.
+    #[must_use]
+    pub fn apply<P>(
+        self,
+        image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> ImageBuffer<P, Vec<P::Subpixel>>
+    where
+        P: Pixel + 'static,
+    {
+        match self {
+            Self::FlipHorizontal => imageops::flip_horizontal(image),
+            Self::FlipVertical => imageops::flip_vertical(image),
+            Self::Rotate90 => imageops::rotate90(image),
+            Self::Rotate270 => imageops::rotate270(image),
+        }
+    }
+
+    /// Area of a cue displayed in `area` after the transform of its image, on a `screen`.
+    ///
+    /// The flips keep the area. The rotations swap the width and the height around the
+    /// center of the area, moved if needed to stay on the screen.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`ContentError::InvalidAreaBounding`] if the rotated area is larger
+    /// than the screen.
+    pub fn apply_area(self, area: &Area, screen: &Size) -> Result<Area, ContentError> {
+        let (width, height) = (area.width(), area.height());
+        let (new_width, new_height) = match self {
+            Self::FlipHorizontal | Self::FlipVertical => return Ok(*area),
+            Self::Rotate90 | Self::Rotate270 => (height, width),
+        };
+        let position = |start: u16, size: u16, new_size: u16, screen_size: usize| {
+            let centered =
+                (i64::from(start) + i64::from(size) / 2 - i64::from(new_size) / 2).max(0);
+            let max = i64::try_from(screen_size).ok()? - i64::from(new_size);
+            let start = u16::try_from(centered.min(max)).ok()?;
+            Some((start, start.checked_add(new_size - 1)?))
+        };
+        let (x1, x2) = position(area.left(), width, new_width, screen.w)
+            .ok_or(ContentError::InvalidAreaBounding)?;
+        let (y1, y2) = position(area.top(), height, new_height, screen.h)
+            .ok_or(ContentError::InvalidAreaBounding)?;
+        Area::try_from(AreaValues { x1, y1, x2, y2 })
+    }
+
+    /// Transform the `indexes` of the pixels of an image of `width`x`height` pixels,
+    /// in row-major order.
+    pub(crate) fn apply_indexes(self, indexes: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let (new_width, new_height) = self.transformed_size(width, height);
+        (0..new_height)
+            .flat_map(|y| (0..new_width).map(move |x| (x, y)))
+            .map(|position| {
+                let (x, y) = self.source_position(position, width, height);
+                indexes[y as usize * width as usize + x as usize]
+            })
+            .collect()
+    }
+
+    /// Position in the source image of `width`x`height` pixels of the pixel at `x`,`y`
+    /// in the transformed image.
+    const fn source_position(self, (x, y): (u32, u32), width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Self::FlipHorizontal => (width - 1 - x, y),
+            Self::FlipVertical => (x, height - 1 - y),
+            Self::Rotate90 => (y, height - 1 - x),
+            Self::Rotate270 => (width - 1 - y, x),
+        }
+    }
+}
+
+impl<const N: usize> IndexedImage<N> {
+    /// Transform the image, keeping its palette.
+    #[must_use]
+    pub fn transformed(&self, transform: Transform) -> Self {
+        let (width, height) = (self.width(), self.height());
+        let (new_width, new_height) = transform.transformed_size(width, height);
+        let indexes = transform.apply_indexes(self.indexes(), width, height);
+        Self::new(new_width, new_height, *self.palette(), indexes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::ToImage as _;
+    use image::Rgba;
+
+    #[test]
+    fn transform_images_and_areas() {
+        let palette = [0, 64, 128, 255].map(|value| Rgba([value, value, value, 255]));
+        // 3x2 image :
+        // 0 1 2
+        // 3 0 1
+        let image = IndexedImage::new(3, 2, palette, vec![0, 1, 2, 3, 0, 1]);
+        let transforms = [
+            (Transform::FlipHorizontal, (3, 2), vec![2, 1, 0, 1, 0, 3]),
+            (Transform::FlipVertical, (3, 2), vec![3, 0, 1, 0, 1, 2]),
+            (Transform::Rotate90, (2, 3), vec![3, 0, 0, 1, 1, 2]),
+            (Transform::Rotate270, (2, 3), vec![2, 1, 1, 0, 0, 3]),
+        ];
+        for (transform, (width, height), indexes) in transforms {
+            let transformed = image.transformed(transform);
+            assert_eq!(transformed.indexes(), indexes, "{transform:?}");
+            assert_eq!((transformed.width(), transformed.height()), (width, height));
+            // Same result than the transform of the `RGBA` image.
+            assert_eq!(transformed.to_image(), transform.apply(&image.to_image()));
+        }
+
+        let screen = Size { w: 1920, h: 1080 };
+        let area = Area::try_from(AreaValues {
+            x1: 100,
+            y1: 900,
+            x2: 499,
+            y2: 999,
+        })
+        .unwrap();
+        assert_eq!(
+            Transform::FlipVertical.apply_area(&area, &screen).unwrap(),
+            area
+        );
+        // Centered on the same point, moved up to stay on the screen.
+        let rotated = Transform::Rotate90.apply_area(&area, &screen).unwrap();
+        assert_eq!(
+            (rotated.left(), rotated.width(), rotated.height()),
+            (250, 100, 400)
+        );
+        assert_eq!(
+            (rotated.top(), rotated.top() + rotated.height()),
+            (680, 1080)
+        );
+        assert!(Transform::Rotate270
+            .apply_area(&area, &Size { w: 1920, h: 200 })
+            .is_err());
+    }
+}
@@ -1,11 +1,19 @@
-use crate::SubtileError;
+use super::{ImageSize as _, IndexedImage};
+use crate::{
+    time::{TimePoint, TimeSpan},
+    SubtileError,
+};
 use image::{EncodableLayout, Pixel, PixelWithColorType};
 use std::{
     borrow::Borrow,
-    fs::create_dir_all,
-    io,
+    collections::BTreeSet,
+    fmt,
+    fs::{self, create_dir_all, File, OpenOptions},
+    io::{self, BufWriter, Write as _},
+    num::ParseIntError,
     ops::Deref,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use thiserror::Error;
 
@@ -23,17 +31,131 @@ pub enum DumpError {
     },
 
     /// Error during file dump
-    #[error("could not write image dump file '{}'", filename.display())]
+    #[error("could not write image dump file '{}'{}", filename.display(), cue_suffix(*cue))]
     DumpImage {
         /// Path of the file write failed
         filename: PathBuf,
+        /// Identifier of the cue of the image, if dumped with one
+        cue: Option<DumpId>,
         /// Error source
         source: image::ImageError,
     },
+
+    /// Error during paletted file dump
+    #[error("could not write paletted image dump file '{}'{}", filename.display(), cue_suffix(*cue))]
+    DumpIndexedImage {
+        /// Path of the file write failed
+        filename: PathBuf,
+        /// Identifier of the cue of the image, if dumped with one
+        cue: Option<DumpId>,
+        /// Error source
+        source: png::EncodingError,
+    },
+
+    /// Error during manifest write
+    #[error("could not write dump manifest '{}'", path.display())]
+    Manifest {
+        /// Path of the manifest file
+        path: PathBuf,
+        /// Error source
+        source: io::Error,
+    },
+
+    /// Error during manifest read
+    #[error("could not read dump manifest '{}'", path.display())]
+    ManifestRead {
+        /// Path of the manifest file
+        path: PathBuf,
+        /// Error source
+        source: io::Error,
+    },
+
+    /// Invalid line in an existing manifest
+    #[error("invalid line {line} in dump manifest '{}'", path.display())]
+    ManifestParse {
+        /// Path of the manifest file
+        path: PathBuf,
+        /// Number of the line, starting at 1
+        line: usize,
+    },
+}
+
+/// Identification of the cue in the error messages.
+fn cue_suffix(cue: Option<DumpId>) -> String {
+    cue.map(|id| format!(" of cue {id} (start: {:.3}s)", id.start().to_secs()))
+        .unwrap_or_default()
+}
+
+/// Policy applied when the dump of an image fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DumpPolicy {
+    /// Stop the dump at the first failure, and return its error.
+    #[default]
+    Abort,
+    /// Skip the failed images, and return their errors once all the images are processed.
+    ///
+    /// The failed cues are not listed in the manifest, so an append of the same cues
+    /// (see [`ImageDumper::with_append`]) retries only them.
+    Continue,
+}
+
+/// Name of the manifest file written by [`ImageDumper::cue_images`].
+pub const DUMP_MANIFEST_FILENAME: &str = "manifest.tsv";
+
+/// Stable identifier of a dumped cue, derived from the index of the cue in the
+/// source stream and its start time.
+///
+/// As the index is the one of the source stream, the identifier of a cue doesn't change
+/// if other cues are skipped or processed in another order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DumpId {
+    index: usize,
+    start: TimePoint,
+}
+
+impl DumpId {
+    /// Create the identifier of the cue at `index` in the stream, starting at `start`.
+    #[must_use]
+    pub const fn new(index: usize, start: TimePoint) -> Self {
+        Self { index, start }
+    }
+
+    /// Index of the cue in the source stream.
+    #[must_use]
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Start time of the cue.
+    #[must_use]
+    pub const fn start(&self) -> TimePoint {
+        self.start
+    }
+}
+
+impl fmt::Display for DumpId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:06}-{:09}", self.index, self.start.msecs())
+    }
+}
+
+impl FromStr for DumpId {
+    type Err = ParseIntError;
+
+    /// Parse an identifier in the format of its [`Display`](fmt::Display) implementation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, start) = s.split_once('-').unwrap_or((s, ""));
+        Ok(Self::new(
+            index.parse()?,
+            TimePoint::from_msecs(start.parse()?),
+        ))
+    }
 }
 
 /// Dump some images in a folder specified by the path.
 ///
+/// Use an [`ImageDumper`] to choose the [`DumpPolicy`], or to dump cues with a manifest.
+///
 /// # Errors
 /// Will return `DumpError::Folder` if the output folder creation failed.
 /// Will return `DumpError::DumpImage` if the dump of one image failed.
@@ -49,41 +171,563 @@ where
     Img: Borrow<image::ImageBuffer<P, Container>>,
     Iter: IntoIterator<Item = Img>,
 {
-    let folder_path = PathBuf::from(path);
+    ImageDumper::new(path).images(images)?;
+    Ok(())
+}
 
-    // create path if not exist
-    if !folder_path.is_dir() {
-        create_dir_all(folder_path.as_path()).map_err(|source| DumpError::Folder {
-            path: folder_path.clone(),
+/// Dump of images in a folder, as `PNG` files.
+///
+/// The images are dumped either as a sequence, named from their position, or as cues
+/// with a manifest, named from their [`DumpId`]. The manifest file
+/// ([`DUMP_MANIFEST_FILENAME`]) list one cue per line, sorted by identifier, with tab
+/// separated fields : identifier, start and end times in milliseconds, image filename.
+/// Output is the same whatever the order in which the cues are provided.
+///
+/// The indexed images are dumped as paletted `PNG` files, which are much smaller and
+/// faster to write than `RGBA` ones, and keep the palette of the images.
+///
+/// The dump methods return the errors of the images skipped with [`DumpPolicy::Continue`].
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDumper<'a> {
+    path: &'a str,
+    policy: DumpPolicy,
+    append: bool,
+}
+
+impl<'a> ImageDumper<'a> {
+    /// Create a dump in the folder of `path`, created if needed, aborting at the first
+    /// failure.
+    #[must_use]
+    pub const fn new(path: &'a str) -> Self {
+        Self {
+            path,
+            policy: DumpPolicy::Abort,
+            append: false,
+        }
+    }
+
+    /// Set the `policy` applied on failures, [`DumpPolicy::Abort`] by default.
+    #[must_use]
+    pub const fn with_policy(mut self, policy: DumpPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Add the cues to the dump folder, skipping the cues already listed in its manifest.
+    ///
+    /// It allows to resume an interrupted dump : each image is recorded in the manifest as
+    /// soon as it's written, so the cues dumped before an interruption are not dumped again.
+    /// The identifiers of these cues are available with [`dumped_cue_ids`], to skip them
+    /// before the decoding of their images. Once all the cues are dumped, the manifest is
+    /// sorted as for a dump from scratch.
+    #[must_use]
+    pub const fn with_append(mut self) -> Self {
+        self.append = true;
+        self
+    }
+
+    /// Dump some images, named from their position in `images`.
+    ///
+    /// # Errors
+    /// Will return `DumpError::Folder` if the output folder creation failed.
+    /// Will return `DumpError::DumpImage` if the dump of one image failed, with
+    /// [`DumpPolicy::Abort`].
+    #[profiling::function]
+    pub fn images<'b, Iter, Img, P, Container>(
+        &self,
+        images: Iter,
+    ) -> Result<Vec<DumpError>, SubtileError>
+    where
+        P: Pixel + PixelWithColorType + 'b,
+        [P::Subpixel]: EncodableLayout,
+        Container: Deref<Target = [P::Subpixel]> + 'b,
+        Img: Borrow<image::ImageBuffer<P, Container>>,
+        Iter: IntoIterator<Item = Img>,
+    {
+        self.dump_sequence(images, |filepath, img| {
+            dump_image(filepath, img.borrow(), None)
+        })
+    }
+
+    /// Dump images of cues, with a manifest.
+    ///
+    /// # Errors
+    /// Will return `DumpError::Folder` if the output folder creation failed.
+    /// Will return `DumpError::ManifestRead` or `DumpError::ManifestParse` if the existing
+    /// manifest can't be read, when appending.
+    /// Will return `DumpError::DumpImage` if the dump of one image failed, with
+    /// [`DumpPolicy::Abort`].
+    /// Will return `DumpError::Manifest` if the manifest write failed.
+    #[profiling::function]
+    pub fn cue_images<'b, Iter, Img, P, Container>(
+        &self,
+        cues: Iter,
+    ) -> Result<Vec<DumpError>, SubtileError>
+    where
+        P: Pixel + PixelWithColorType + 'b,
+        [P::Subpixel]: EncodableLayout,
+        Container: Deref<Target = [P::Subpixel]> + 'b,
+        Img: Borrow<image::ImageBuffer<P, Container>>,
+        Iter: IntoIterator<Item = (DumpId, TimeSpan, Img)>,
+    {
+        self.dump_cues(cues, |filepath, id, img| {
+            dump_image(filepath, img.borrow(), Some(id))
+        })
+    }
+
+    /// Dump indexed images, named from their position in `images`.
+    ///
+    /// # Errors
+    /// Will return `DumpError::Folder` if the output folder creation failed.
+    /// Will return `DumpError::DumpIndexedImage` if the dump of one image failed, with
+    /// [`DumpPolicy::Abort`].
+    #[profiling::function]
+    pub fn indexed_images<const N: usize, Iter, Img>(
+        &self,
+        images: Iter,
+    ) -> Result<Vec<DumpError>, SubtileError>
+    where
+        Img: Borrow<IndexedImage<N>>,
+        Iter: IntoIterator<Item = Img>,
+    {
+        self.dump_sequence(images, |filepath, img| {
+            dump_indexed_image(filepath, img.borrow(), None)
+        })
+    }
+
+    /// Dump indexed images of cues, with a manifest.
+    ///
+    /// # Errors
+    /// Will return `DumpError::Folder` if the output folder creation failed.
+    /// Will return `DumpError::ManifestRead` or `DumpError::ManifestParse` if the existing
+    /// manifest can't be read, when appending.
+    /// Will return `DumpError::DumpIndexedImage` if the dump of one image failed, with
+    /// [`DumpPolicy::Abort`].
+    /// Will return `DumpError::Manifest` if the manifest write failed.
+    #[profiling::function]
+    pub fn indexed_cue_images<const N: usize, Iter, Img>(
+        &self,
+        cues: Iter,
+    ) -> Result<Vec<DumpError>, SubtileError>
+    where
+        Img: Borrow<IndexedImage<N>>,
+        Iter: IntoIterator<Item = (DumpId, TimeSpan, Img)>,
+    {
+        self.dump_cues(cues, |filepath, id, img| {
+            dump_indexed_image(filepath, img.borrow(), Some(id))
+        })
+    }
+
+    /// Dump the `images` with `dump`, named from their position.
+    fn dump_sequence<Iter, F>(
+        &self,
+        images: Iter,
+        mut dump: F,
+    ) -> Result<Vec<DumpError>, SubtileError>
+    where
+        Iter: IntoIterator,
+        F: FnMut(&Path, Iter::Item) -> Result<(), DumpError>,
+    {
+        let folder_path = create_dump_folder(self.path)?;
+
+        let mut failures = Vec::new();
+        for (i, img) in images.into_iter().enumerate() {
+            let filepath = folder_path.join(format!("{i:06}.png"));
+            if let Err(err) = dump(&filepath, img) {
+                skip_failure(&mut failures, err, self.policy)?;
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Dump the images of cues with `dump`, and write the manifest.
+    ///
+    /// In `append` mode, the cues of the existing manifest are skipped, and each new
+    /// entry is appended to the manifest as soon as its image is written.
+    fn dump_cues<Iter, Img, F>(
+        &self,
+        cues: Iter,
+        mut dump: F,
+    ) -> Result<Vec<DumpError>, SubtileError>
+    where
+        Iter: IntoIterator<Item = (DumpId, TimeSpan, Img)>,
+        F: FnMut(&Path, DumpId, Img) -> Result<(), DumpError>,
+    {
+        let folder_path = create_dump_folder(self.path)?;
+        let manifest_path = folder_path.join(DUMP_MANIFEST_FILENAME);
+        let manifest_error = |source| DumpError::Manifest {
+            path: manifest_path.clone(),
             source,
-        })?;
+        };
+
+        let (mut entries, mut journal) = if self.append {
+            let entries = read_manifest(&manifest_path)?;
+            // Rewrite the valid entries, to drop a line truncated by an interruption.
+            write_manifest(&manifest_path, &entries).map_err(manifest_error)?;
+            let file = OpenOptions::new()
+                .append(true)
+                .open(&manifest_path)
+                .map_err(manifest_error)?;
+            (entries, Some(file))
+        } else {
+            (Vec::new(), None)
+        };
+        let dumped = entries
+            .iter()
+            .map(|(id, _, _)| *id)
+            .collect::<BTreeSet<_>>();
+
+        let mut failures = Vec::new();
+        for (id, times, img) in cues {
+            if dumped.contains(&id) {
+                continue;
+            }
+            let filename = format!("{id}.png");
+            if let Err(err) = dump(&folder_path.join(&filename), id, img) {
+                skip_failure(&mut failures, err, self.policy)?;
+                continue;
+            }
+            let entry = (id, times, filename);
+            if let Some(journal) = journal.as_mut() {
+                write_manifest_entry(journal, &entry).map_err(manifest_error)?;
+            }
+            entries.push(entry);
+        }
+        entries.sort_unstable_by_key(|(id, _, _)| *id);
+        write_manifest(&manifest_path, &entries).map_err(manifest_error)?;
+
+        Ok(failures)
     }
+}
 
-    images
-        .into_iter()
-        .enumerate()
-        .try_for_each(move |(i, img)| {
-            let filepath = folder_path.clone().join(format!("{i:06}.png"));
-            dump_image(&filepath, img.borrow()).map_err(|source| DumpError::DumpImage {
-                filename: filepath,
+/// Identifiers of the cues listed in the manifest of a dump folder, empty if the
+/// folder or its manifest doesn't exist.
+///
+/// # Errors
+/// Will return `DumpError::ManifestRead` or `DumpError::ManifestParse` if the manifest
+/// can't be read.
+pub fn dumped_cue_ids(path: &str) -> Result<BTreeSet<DumpId>, SubtileError> {
+    let entries = read_manifest(&Path::new(path).join(DUMP_MANIFEST_FILENAME))?;
+    Ok(entries.into_iter().map(|(id, _, _)| id).collect())
+}
+
+/// An entry of the manifest : identifier, times and filename of a cue.
+type ManifestEntry = (DumpId, TimeSpan, String);
+
+/// Add the error of a failed dump to the `failures`, or return it with [`DumpPolicy::Abort`].
+fn skip_failure(
+    failures: &mut Vec<DumpError>,
+    err: DumpError,
+    policy: DumpPolicy,
+) -> Result<(), DumpError> {
+    match policy {
+        DumpPolicy::Abort => Err(err),
+        DumpPolicy::Continue => {
+            log::warn!("{err}, skipped");
+            failures.push(err);
+            Ok(())
+        }
+    }
+}
+
+/// Read the entries of a manifest, without the last line if it's truncated.
+fn read_manifest(path: &Path) -> Result<Vec<ManifestEntry>, DumpError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(DumpError::ManifestRead {
+                path: path.to_path_buf(),
                 source,
             })
-        })?;
+        }
+    };
+    let complete = content.rfind('\n').map_or("", |end| &content[..end]);
+    complete
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| {
+            parse_manifest_entry(line).ok_or_else(|| DumpError::ManifestParse {
+                path: path.to_path_buf(),
+                line: idx + 1,
+            })
+        })
+        .collect()
+}
 
-    Ok(())
+/// Parse a line of the manifest.
+fn parse_manifest_entry(line: &str) -> Option<ManifestEntry> {
+    let mut fields = line.split('\t');
+    let id = fields.next()?.parse().ok()?;
+    let start = TimePoint::from_msecs(fields.next()?.parse().ok()?);
+    let end = TimePoint::from_msecs(fields.next()?.parse().ok()?);
+    let filename = fields.next()?.to_owned();
+    fields
+        .next()
+        .is_none()
+        .then(|| (id, TimeSpan::new(start, end), filename))
+}
+
+/// Write the manifest entries in the file.
+fn write_manifest(path: &Path, entries: &[ManifestEntry]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for entry in entries {
+        write_manifest_entry(&mut writer, entry)?;
+    }
+    writer.flush()
+}
+
+/// Write a manifest entry as a line.
+fn write_manifest_entry(mut writer: impl io::Write, entry: &ManifestEntry) -> io::Result<()> {
+    let (id, times, filename) = entry;
+    writeln!(
+        writer,
+        "{id}\t{}\t{}\t{filename}",
+        times.start.msecs(),
+        times.end.msecs()
+    )
 }
 
-/// Dump one image
+/// Create the dump folder if not exist.
+fn create_dump_folder(path: &str) -> Result<PathBuf, DumpError> {
+    let folder_path = PathBuf::from(path);
+    if !folder_path.is_dir() {
+        create_dir_all(folder_path.as_path()).map_err(|source| DumpError::Folder {
+            path: folder_path.clone(),
+            source,
+        })?;
+    }
+    Ok(folder_path)
+}
+
+/// Dump one image, of the `cue` if any.
 #[profiling::function]
-fn dump_image<P, Pix, Container>(
-    filename: P,
+fn dump_image<Pix, Container>(
+    filename: &Path,
     image: &image::ImageBuffer<Pix, Container>, // image::Luma<u8>, Vec<u8>
-) -> Result<(), image::ImageError>
+    cue: Option<DumpId>,
+) -> Result<(), DumpError>
 where
-    P: AsRef<Path>,
     Pix: Pixel + PixelWithColorType,
     [Pix::Subpixel]: EncodableLayout,
     Container: Deref<Target = [Pix::Subpixel]>,
 {
-    image.save(filename)
+    image.save(filename).map_err(|source| DumpError::DumpImage {
+        filename: filename.to_path_buf(),
+        cue,
+        source,
+    })
+}
+
+/// Dump one indexed image, of the `cue` if any, as a paletted `PNG`, with the smallest
+/// bit depth able to index its palette.
+#[profiling::function]
+fn dump_indexed_image<const N: usize>(
+    filename: &Path,
+    image: &IndexedImage<N>,
+    cue: Option<DumpId>,
+) -> Result<(), DumpError> {
+    let write = || {
+        let file = File::create(filename).map_err(png::EncodingError::from)?;
+        let (depth, bits) = match N {
+            0..=2 => (png::BitDepth::One, 1),
+            3..=4 => (png::BitDepth::Two, 2),
+            5..=16 => (png::BitDepth::Four, 4),
+            _ => (png::BitDepth::Eight, 8),
+        };
+        let palette = image.palette();
+        let mut encoder = png::Encoder::new(BufWriter::new(file), image.width(), image.height());
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(depth);
+        encoder.set_compression(png::Compression::Fast);
+        encoder.set_palette(
+            palette
+                .iter()
+                .flat_map(|color| [color[0], color[1], color[2]])
+                .collect::<Vec<_>>(),
+        );
+        encoder.set_trns(palette.iter().map(|color| color[3]).collect::<Vec<_>>());
+
+        // Pack the indexes of each row, starting with the most significant bits.
+        let per_byte = 8 / bits;
+        let data = image
+            .indexes()
+            .chunks(image.width().max(1) as usize)
+            .flat_map(|row| {
+                row.chunks(per_byte).map(|pixels| {
+                    pixels.iter().enumerate().fold(0, |byte, (idx, &index)| {
+                        byte | index << (8 - bits * (idx + 1))
+                    })
+                })
+            })
+            .collect::<Vec<u8>>();
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()
+    };
+    write().map_err(|source| DumpError::DumpIndexedImage {
+        filename: filename.to_path_buf(),
+        cue,
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+    use std::{env, fs};
+
+    #[test]
+    fn dump_id_format_is_stable() {
+        let id = DumpId::new(42, TimePoint::from_msecs(61_500));
+        assert_eq!(id.to_string(), "000042-000061500");
+        assert!(id < DumpId::new(43, TimePoint::from_msecs(0)));
+    }
+
+    #[test]
+    fn dump_cue_images_with_sorted_manifest() {
+        let folder = env::temp_dir().join(format!("subtile-dump-{}", std::process::id()));
+        let image = GrayImage::from_pixel(2, 2, Luma([255]));
+        let cue = |index, start| {
+            let times = TimeSpan::new(
+                TimePoint::from_msecs(start),
+                TimePoint::from_msecs(start + 10),
+            );
+            (DumpId::new(index, times.start), times, &image)
+        };
+
+        ImageDumper::new(folder.to_str().unwrap())
+            .cue_images([cue(3, 300), cue(1, 100)])
+            .unwrap();
+
+        let manifest = fs::read_to_string(folder.join(DUMP_MANIFEST_FILENAME)).unwrap();
+        assert_eq!(
+            manifest,
+            "000001-000000100\t100\t110\t000001-000000100.png\n\
+             000003-000000300\t300\t310\t000003-000000300.png\n"
+        );
+        assert!(folder.join("000003-000000300.png").is_file());
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn dump_indexed_images_as_paletted_png() {
+        use crate::image::ToImage as _;
+        use image::Rgba;
+
+        let folder = env::temp_dir().join(format!("subtile-dump-indexed-{}", std::process::id()));
+        let palette = [
+            Rgba([0, 0, 0, 0]),
+            Rgba([255, 255, 255, 255]),
+            Rgba([0, 0, 0, 255]),
+            Rgba([255, 0, 0, 128]),
+        ];
+        let image = IndexedImage::new(5, 2, palette, vec![0, 1, 2, 3, 1, 3, 2, 1, 0, 0]);
+
+        ImageDumper::new(folder.to_str().unwrap())
+            .indexed_images([&image])
+            .unwrap();
+
+        let filename = folder.join("000000.png");
+        let bytes = fs::read(&filename).unwrap();
+        // Bit depth of the `IHDR` chunk, and color type `Indexed`.
+        assert_eq!(bytes[24..26], [2, 3]);
+        let decoded = image::open(&filename).unwrap().into_rgba8();
+        assert_eq!(decoded, image.to_image());
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn append_cue_images_resume_dump() {
+        let folder = env::temp_dir().join(format!("subtile-dump-append-{}", std::process::id()));
+        let path = folder.to_str().unwrap();
+        let image = GrayImage::from_pixel(2, 2, Luma([255]));
+        let cue = |index, start| {
+            let times = TimeSpan::new(
+                TimePoint::from_msecs(start),
+                TimePoint::from_msecs(start + 10),
+            );
+            (DumpId::new(index, times.start), times, &image)
+        };
+
+        let append = ImageDumper::new(path).with_append();
+        append.cue_images([cue(2, 200)]).unwrap();
+        // Simulate an interruption during the write of an entry.
+        let manifest_path = folder.join(DUMP_MANIFEST_FILENAME);
+        let mut manifest = OpenOptions::new()
+            .append(true)
+            .open(&manifest_path)
+            .unwrap();
+        write!(manifest, "000009-0000").unwrap();
+        drop(manifest);
+        assert_eq!(
+            dumped_cue_ids(path)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [DumpId::new(2, TimePoint::from_msecs(200))]
+        );
+
+        fs::remove_file(folder.join("000002-000000200.png")).unwrap();
+        append
+            .cue_images([cue(3, 300), cue(2, 200), cue(1, 100)])
+            .unwrap();
+
+        // The already dumped cue is skipped.
+        assert!(!folder.join("000002-000000200.png").exists());
+        let manifest = fs::read_to_string(manifest_path).unwrap();
+        assert_eq!(
+            manifest,
+            "000001-000000100\t100\t110\t000001-000000100.png\n\
+             000002-000000200\t200\t210\t000002-000000200.png\n\
+             000003-000000300\t300\t310\t000003-000000300.png\n"
+        );
+        assert_eq!("000003-000000300".parse(), Ok(cue(3, 300).0));
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn continue_dump_on_error() {
+        let folder = env::temp_dir().join(format!("subtile-dump-policy-{}", std::process::id()));
+        let path = folder.to_str().unwrap();
+        let image = GrayImage::from_pixel(2, 2, Luma([255]));
+        let cue = |index, start| {
+            let times = TimeSpan::new(
+                TimePoint::from_msecs(start),
+                TimePoint::from_msecs(start + 10),
+            );
+            (DumpId::new(index, times.start), times, &image)
+        };
+        // A folder in place of the image of the second cue makes its write fail.
+        let blocker = folder.join("000002-000000200.png");
+        fs::create_dir_all(&blocker).unwrap();
+
+        let cues = [cue(1, 100), cue(2, 200), cue(3, 300)];
+        let dumper = ImageDumper::new(path);
+        let SubtileError::ImageDump(err) = dumper.cue_images(cues).unwrap_err() else {
+            panic!("unexpected error");
+        };
+        assert!(err
+            .to_string()
+            .contains("of cue 000002-000000200 (start: 0.200s)"));
+
+        let dumper = dumper.with_policy(DumpPolicy::Continue);
+        let failures = dumper.cue_images(cues).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(
+            failures[0],
+            DumpError::DumpImage { cue: Some(id), .. } if id == cue(2, 200).0
+        ));
+        assert!(folder.join("000003-000000300.png").is_file());
+        assert_eq!(dumped_cue_ids(path).unwrap().len(), 2);
+
+        // The failed cue is retried by an append.
+        fs::remove_dir(&blocker).unwrap();
+        let failures = dumper.with_append().cue_images(cues).unwrap();
+        assert!(failures.is_empty());
+        assert!(blocker.is_file());
+        assert_eq!(dumped_cue_ids(path).unwrap().len(), 3);
+        fs::remove_dir_all(folder).unwrap();
+    }
 }
@@ -0,0 +1,532 @@
+//! `JSON` functionality, to exchange cues metadata with other tools.
+//!
+//! Metadata are exported as `JSON Lines` : one `JSON` object by line and by cue,
+//! easy to consume in shell pipelines or from other languages.
+//!
+//! Cue timing adjustments can be imported from a [`TimingPatch`] file, a sequence
+//! of `JSON` objects (one by line, or in an array) keyed by the cue index :
+//! `{"index":3,"start":1200,"end":2500}` to override the times (in milliseconds)
+//! of a cue, or `{"index":4,"delete":true}` to remove it.
+//! Other fields are ignored, so lines exported with [`write_jsonl`] can be edited and imported back.
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take, take_while},
+    character::complete::{char, multispace0},
+    combinator::{all_consuming, cut, map, map_res, value},
+    error::ErrorKind,
+    multi::{many0, separated_list0},
+    number::complete::recognize_float,
+    sequence::{delimited, preceded, separated_pair, terminated},
+    IResult, Parser as _,
+};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::{
+    content::Area,
+    time::{TimePoint, TimeSpan},
+    util::fnv1a,
+};
+
+/// Error for `JSON` handling.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum JsonError {
+    /// Io error on a path.
+    #[error("io error on '{path}'")]
+    Io {
+        /// Source error
+        source: io::Error,
+        /// Path of the file we tried to read
+        path: PathBuf,
+    },
+
+    /// The content is not valid `JSON`.
+    #[error("invalid JSON syntax at byte {offset}")]
+    Syntax {
+        /// Offset in the content where the parsing failed.
+        offset: usize,
+    },
+
+    /// The arrays and objects are nested too deeply.
+    #[error("JSON values nested more than {MAX_DEPTH} levels at byte {offset}")]
+    TooDeep {
+        /// Offset in the content of the value exceeding the depth.
+        offset: usize,
+    },
+
+    /// A field of a cue patch is missing or has an invalid value.
+    #[error("invalid or missing field `{field}` in cue patch")]
+    InvalidField {
+        /// Name of the field.
+        field: &'static str,
+    },
+}
+
+/// Metadata of a cue, exported without the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CueMetadata {
+    /// Index of the cue in the stream.
+    pub index: usize,
+    /// Display times of the cue.
+    pub time: TimeSpan,
+    /// Location of the cue on the screen, if known.
+    pub area: Option<Area>,
+    /// Is the cue forced.
+    pub forced: bool,
+    /// Hash of the cue content, if computed (see [`content_hash`]).
+    pub hash: Option<u64>,
+}
+
+impl CueMetadata {
+    /// Create metadata of a cue with only index and times.
+    #[must_use]
+    pub const fn new(index: usize, time: TimeSpan) -> Self {
+        Self {
+            index,
+            time,
+            area: None,
+            forced: false,
+            hash: None,
+        }
+    }
+}
+
+/// Compute the hash of a cue content (like raw image data), stable across runs and platforms.
+#[must_use]
+pub fn content_hash(data: &[u8]) -> u64 {
+    fnv1a(data.iter().copied())
+}
+
+/// Write cues metadata in `JSON Lines` format.
+/// # Errors
+///
+/// Will return `Err` if write in `writer` return an `Err`.
+pub fn write_jsonl(writer: &mut impl io::Write, cues: &[CueMetadata]) -> Result<(), io::Error> {
+    cues.iter().try_for_each(|cue| write_line(writer, cue))
+}
+
+/// Write metadata of a cue as a `JSON` object on one line.
+///
+/// Times are in milliseconds, and the hash is an hexadecimal string
+/// as a 64 bits value can't be represented by all `JSON` parsers.
+/// # Errors
+///
+/// Will return `Err` if writing in `writer` return an `Err`.
+pub fn write_line(writer: &mut impl io::Write, cue: &CueMetadata) -> Result<(), io::Error> {
+    let CueMetadata {
+        index,
+        time,
+        area,
+        forced,
+        hash,
+    } = cue;
+    write!(
+        writer,
+        r#"{{"index":{index},"start":{},"end":{},"area":"#,
+        time.start.msecs(),
+        time.end.msecs()
+    )?;
+    match area {
+        Some(area) => write!(
+            writer,
+            r#"{{"x":{},"y":{},"width":{},"height":{}}}"#,
+            area.left(),
+            area.top(),
+            area.width(),
+            area.height()
+        )?,
+        None => write!(writer, "null")?,
+    }
+    write!(writer, r#","forced":{forced},"hash":"#)?;
+    match hash {
+        Some(hash) => writeln!(writer, r#""{hash:016x}"}}"#),
+        None => writeln!(writer, "null}}"),
+    }
+}
+
+/// Adjustment of a cue from a [`TimingPatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CuePatch {
+    /// Override the start and/or the end time of the cue.
+    Retime {
+        /// New start time, if changed.
+        start: Option<TimePoint>,
+        /// New end time, if changed.
+        end: Option<TimePoint>,
+    },
+    /// Remove the cue.
+    Delete,
+}
+
+/// Per-cue timing adjustments, keyed by cue index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimingPatch {
+    patches: BTreeMap<usize, CuePatch>,
+}
+
+impl TimingPatch {
+    /// Read a patch from a file.
+    ///
+    /// # Errors
+    /// Will return [`JsonError::Io`] if the file can't be read, or
+    /// the error of [`TimingPatch::parse`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, JsonError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|source| JsonError::Io {
+            source,
+            path: path.into(),
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Parse a patch from its `JSON` content.
+    /// If several patches are provided for the same cue, the last one is kept.
+    ///
+    /// # Errors
+    /// Will return [`JsonError::Syntax`] if the content is not valid `JSON`,
+    /// [`JsonError::TooDeep`] if its values are nested too deeply, or
+    /// [`JsonError::InvalidField`] if a patch doesn't have a valid index or times.
+    pub fn parse(content: &str) -> Result<Self, JsonError> {
+        let (_, values) = document(content).map_err(|err| {
+            let (rest, code) = match err {
+                nom::Err::Error(err) | nom::Err::Failure(err) => (err.input, err.code),
+                nom::Err::Incomplete(_) => ("", ErrorKind::Complete),
+            };
+            let offset = content.len() - rest.len();
+            if code == ErrorKind::TooLarge {
+                JsonError::TooDeep { offset }
+            } else {
+                JsonError::Syntax { offset }
+            }
+        })?;
+        let patches = values.iter().map(cue_patch).collect::<Result<_, _>>()?;
+        Ok(Self { patches })
+    }
+
+    /// Get the patch of the cue at `index`.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&CuePatch> {
+        self.patches.get(&index)
+    }
+
+    /// Number of patched cues.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.patches.len()
+    }
+
+    /// Return `true` if the patch doesn't change any cue.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    /// Apply the patch on a stream of cues, the index of a cue being its position in the stream.
+    pub fn apply<'a, I, T>(&'a self, cues: I) -> impl Iterator<Item = (TimeSpan, T)> + 'a
+    where
+        I: IntoIterator<Item = (TimeSpan, T)>,
+        I::IntoIter: 'a,
+    {
+        cues.into_iter()
+            .enumerate()
+            .filter_map(|(index, (mut time, cue))| match self.get(index) {
+                None => Some((time, cue)),
+                Some(CuePatch::Delete) => None,
+                Some(&CuePatch::Retime { start, end }) => {
+                    time.start = start.unwrap_or(time.start);
+                    time.end = end.unwrap_or(time.end);
+                    Some((time, cue))
+                }
+            })
+    }
+}
+
+/// A parsed `JSON` value.
+#[derive(Debug, Clone, PartialEq)]
+enum Value<'a> {
+    Null,
+    Bool(bool),
+    Number(&'a str),
+    String(String),
+    Array(Vec<Self>),
+    Object(Vec<(String, Self)>),
+}
+
+impl Value<'_> {
+    fn field(&self, name: &str) -> Option<&Self> {
+        let Self::Object(fields) = self else {
+            return None;
+        };
+        fields
+            .iter()
+            .rev()
+            .find_map(|(key, value)| (key == name).then_some(value))
+    }
+}
+
+/// Build a cue patch from a `JSON` object.
+fn cue_patch(value: &Value) -> Result<(usize, CuePatch), JsonError> {
+    let index = match value.field("index") {
+        Some(Value::Number(number)) => number.parse().ok(),
+        _ => None,
+    }
+    .ok_or(JsonError::InvalidField { field: "index" })?;
+
+    let patch = match value.field("delete") {
+        Some(Value::Bool(true)) => CuePatch::Delete,
+        Some(Value::Bool(false) | Value::Null) | None => CuePatch::Retime {
+            start: time_field(value, "start")?,
+            end: time_field(value, "end")?,
+        },
+        Some(_) => return Err(JsonError::InvalidField { field: "delete" }),
+    };
+    Ok((index, patch))
+}
+
+/// Read an optional time field, in milliseconds.
+fn time_field(value: &Value, field: &'static str) -> Result<Option<TimePoint>, JsonError> {
+    match value.field(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(number)) => number
+            .parse()
+            .map(|msecs| Some(TimePoint::from_msecs(msecs)))
+            .map_err(|_err| JsonError::InvalidField { field }),
+        Some(_) => Err(JsonError::InvalidField { field }),
+    }
+}
+
+/// Parse a sequence of `JSON` values, in an array or separated by whitespaces (`JSON Lines`).
+fn document(input: &str) -> IResult<&str, Vec<Value<'_>>> {
+    alt((
+        all_consuming(ws(json_array)),
+        all_consuming(preceded(
+            multispace0,
+            many0(terminated(
+                |input| json_value(input, MAX_DEPTH),
+                multispace0,
+            )),
+        )),
+    ))
+    .parse(input)
+}
+
+/// Wrap a parser to ignore the surrounding whitespaces.
+fn ws<'a, O>(
+    parser: impl nom::Parser<&'a str, Output = O, Error = nom::error::Error<&'a str>>,
+) -> impl nom::Parser<&'a str, Output = O, Error = nom::error::Error<&'a str>> {
+    delimited(multispace0, parser, multispace0)
+}
+
+/// Maximum number of nested arrays and objects, to bound the recursion of the parser.
+const MAX_DEPTH: usize = 64;
+
+/// Parse a `JSON` value, with at most `depth` levels of nested arrays and objects.
+///
+/// The content of an array or an object is cut : its errors are reported where
+/// they happen, instead of at the start of the value.
+fn json_value(input: &str, depth: usize) -> IResult<&str, Value<'_>> {
+    if depth == 0 && input.starts_with(['[', '{']) {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            ErrorKind::TooLarge,
+        )));
+    }
+    let nested = move |input| json_value(input, depth - 1);
+    alt((
+        value(Value::Null, tag("null")),
+        value(Value::Bool(true), tag("true")),
+        value(Value::Bool(false), tag("false")),
+        map(recognize_float, Value::Number),
+        map(json_string, Value::String),
+        map(
+            preceded(
+                char('['),
+                cut(terminated(
+                    separated_list0(char(','), ws(nested)),
+                    ws(char(']')),
+                )),
+            ),
+            Value::Array,
+        ),
+        map(
+            preceded(
+                char('{'),
+                cut(terminated(
+                    separated_list0(
+                        char(','),
+                        separated_pair(ws(json_string), cut(char(':')), cut(ws(nested))),
+                    ),
+                    ws(char('}')),
+                )),
+            ),
+            Value::Object,
+        ),
+    ))
+    .parse(input)
+}
+
+fn json_array(input: &str) -> IResult<&str, Vec<Value<'_>>> {
+    delimited(
+        char('['),
+        separated_list0(char(','), ws(|input| json_value(input, MAX_DEPTH - 1))),
+        ws(char(']')),
+    )
+    .parse(input)
+}
+
+/// Parse a `JSON` string, with its escape sequences.
+fn json_string(input: &str) -> IResult<&str, String> {
+    let (mut input, _) = char('"').parse(input)?;
+    let mut string = String::new();
+    loop {
+        let (rest, chunk) = take_while(|c| c != '"' && c != '\\').parse(input)?;
+        string.push_str(chunk);
+        let (rest, end) = alt((char('"'), char('\\'))).parse(rest)?;
+        if end == '"' {
+            return Ok((rest, string));
+        }
+        let (rest, escaped) = alt((
+            value('"', char('"')),
+            value('\\', char('\\')),
+            value('/', char('/')),
+            value('\u{8}', char('b')),
+            value('\u{c}', char('f')),
+            value('\n', char('n')),
+            value('\r', char('r')),
+            value('\t', char('t')),
+            preceded(
+                char('u'),
+                map_res(take(4_usize), |hex| {
+                    u32::from_str_radix(hex, 16)
+                        .ok()
+                        .map(|code| char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .ok_or(())
+                }),
+            ),
+        ))
+        .parse(rest)?;
+        string.push(escaped);
+        input = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::AreaValues;
+    use crate::time::span;
+    use assert_matches2::assert_matches;
+
+    #[test]
+    fn write_cues_metadata() {
+        let time = TimeSpan::new(TimePoint::from_msecs(500), TimePoint::from_msecs(1499));
+        let mut cue = CueMetadata::new(1, time);
+        cue.area = Some(
+            Area::try_from(AreaValues {
+                x1: 10,
+                y1: 20,
+                x2: 109,
+                y2: 49,
+            })
+            .unwrap(),
+        );
+        cue.forced = true;
+        cue.hash = Some(content_hash(b"image"));
+
+        let mut output = Vec::new();
+        write_jsonl(&mut output, &[CueMetadata::new(0, time), cue]).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                r#"{"index":0,"start":500,"end":1499,"area":null,"forced":false,"hash":null}"#,
+                "\n",
+                r#"{"index":1,"start":500,"end":1499,"area":{"x":10,"y":20,"width":100,"height":30},"forced":true,"hash":"#,
+                r#""2ab612888528489a"}"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn parse_timing_patch() {
+        let content = r#"
+            {"index":0,"start":500,"end":1499,"area":null,"forced":false,"hash":"2ab6\"12"}
+            {"index": 2, "delete": true}
+            {"index": 3, "end": 4200}
+        "#;
+        let patch = TimingPatch::parse(content).unwrap();
+        assert_eq!(patch.len(), 3);
+        assert_eq!(patch.get(2), Some(&CuePatch::Delete));
+        assert_eq!(
+            patch.get(3),
+            Some(&CuePatch::Retime {
+                start: None,
+                end: Some(TimePoint::from_msecs(4200)),
+            })
+        );
+
+        let array = TimingPatch::parse(r#"[{"index":2,"delete":true}, {"index":3,"end":4200}]"#);
+        assert_eq!(array.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn parse_invalid_timing_patch() {
+        assert_matches!(
+            TimingPatch::parse(r#"{"index":1,"start":}"#),
+            Err(JsonError::Syntax { offset: 19 })
+        );
+        assert_matches!(
+            TimingPatch::parse("{\"index\":1}\n{\"index\" 2}"),
+            Err(JsonError::Syntax { offset: 21 })
+        );
+        assert_matches!(
+            TimingPatch::parse(r#"{"start":100}"#),
+            Err(JsonError::InvalidField { field: "index" })
+        );
+        assert_matches!(
+            TimingPatch::parse(r#"{"index":1,"end":"100"}"#),
+            Err(JsonError::InvalidField { field: "end" })
+        );
+    }
+
+    #[test]
+    fn parse_too_deep_timing_patch() {
+        let nested = |depth| {
+            format!(
+                r#"{{"index":1,"notes":{}{}}}"#,
+                "[".repeat(depth),
+                "]".repeat(depth)
+            )
+        };
+        assert_eq!(TimingPatch::parse(&nested(MAX_DEPTH - 1)).unwrap().len(), 1);
+        assert_matches!(
+            TimingPatch::parse(&nested(MAX_DEPTH)),
+            Err(JsonError::TooDeep { offset: 82 })
+        );
+        // Without stack overflow.
+        assert_matches!(
+            TimingPatch::parse(&nested(1_000_000)),
+            Err(JsonError::TooDeep { offset: 82 })
+        );
+    }
+
+    #[test]
+    fn apply_timing_patch() {
+        let patch =
+            TimingPatch::parse(r#"{"index":1,"delete":true} {"index":2,"start":2500}"#).unwrap();
+
+        let cues = [
+            (span(0, 1000), 'a'),
+            (span(1000, 2000), 'b'),
+            (span(2000, 3000), 'c'),
+        ];
+        let patched = patch.apply(cues).collect::<Vec<_>>();
+        assert_eq!(patched, [(span(0, 1000), 'a'), (span(2500, 3000), 'c')]);
+    }
+}
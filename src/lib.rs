@@ -20,12 +20,30 @@
 // For error-chain.
 #![recursion_limit = "1024"]
 
+pub mod ass;
+pub mod capabilities;
+#[cfg(feature = "char_matrix")]
+pub mod char_matrix;
 pub mod content;
 mod errors;
+pub mod format;
 pub mod image;
+pub mod json;
+pub mod limits;
+pub mod lrc;
+pub mod microdvd;
+#[cfg(feature = "parallel")]
+pub mod parallel;
 pub mod pgs;
+pub mod postprocess;
+pub mod raster;
+pub mod sami;
+pub mod sink;
 pub mod srt;
+pub mod synthetic;
+pub mod text;
 pub mod time;
+pub mod ts;
 mod util;
 pub mod vobsub;
 pub mod webvtt;
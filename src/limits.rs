@@ -0,0 +1,169 @@
+//! Limits of the memory allocated by the decoders.
+//!
+//! Sizes read from subtitle data drive the allocations of the decoders : a `PGS` object can
+//! announce up to 16 MiB of data, and images of both formats can be up to 65535x65535 pixels.
+//! [`MemoryLimits`] caps these allocations, to process untrusted data with bounded resources.
+//! The checks are done before allocating, from the sizes announced by the data.
+use thiserror::Error;
+
+/// Default maximum size of a decoded image : 64 MiB.
+pub const DEFAULT_MAX_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+/// Default maximum size of the encoded data of a cue : 16 MiB, the maximum size of `PGS` object data.
+pub const DEFAULT_MAX_CUE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Error returned when data exceed a [`MemoryLimits`].
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LimitError {
+    /// The decoded image would be larger than the limit.
+    #[error("image of {width}x{height} pixels ({bytes} bytes) exceed the limit of {max} bytes")]
+    ImageTooLarge {
+        /// Width of the image.
+        width: u32,
+        /// Height of the image.
+        height: u32,
+        /// Size of the decoded image.
+        bytes: usize,
+        /// Maximum size allowed.
+        max: usize,
+    },
+
+    /// The encoded data of the cue are larger than the limit.
+    #[error("cue data of {bytes} bytes exceed the limit of {max} bytes")]
+    CueTooLarge {
+        /// Size of the cue data.
+        bytes: usize,
+        /// Maximum size allowed.
+        max: usize,
+    },
+}
+
+/// Maximum sizes of the buffers allocated while decoding a cue.
+///
+/// The size of an image is counted in the native format of the decoder : one byte
+/// per pixel for `VobSub` indexed images, two bytes per pixel for `PGS` decoded images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimits {
+    max_image_bytes: usize,
+    max_cue_bytes: usize,
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryLimits {
+    /// Limits that accept any size.
+    pub const UNLIMITED: Self = Self {
+        max_image_bytes: usize::MAX,
+        max_cue_bytes: usize::MAX,
+    };
+
+    /// Create the default limits ([`DEFAULT_MAX_IMAGE_BYTES`] and [`DEFAULT_MAX_CUE_BYTES`]).
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_image_bytes: DEFAULT_MAX_IMAGE_BYTES,
+            max_cue_bytes: DEFAULT_MAX_CUE_BYTES,
+        }
+    }
+
+    /// Set the maximum size of a decoded image.
+    #[must_use]
+    pub const fn with_max_image_bytes(mut self, max_image_bytes: usize) -> Self {
+        self.max_image_bytes = max_image_bytes;
+        self
+    }
+
+    /// Set the maximum size of the encoded data of a cue.
+    #[must_use]
+    pub const fn with_max_cue_bytes(mut self, max_cue_bytes: usize) -> Self {
+        self.max_cue_bytes = max_cue_bytes;
+        self
+    }
+
+    /// Maximum size of a decoded image.
+    #[must_use]
+    pub const fn max_image_bytes(&self) -> usize {
+        self.max_image_bytes
+    }
+
+    /// Maximum size of the encoded data of a cue.
+    #[must_use]
+    pub const fn max_cue_bytes(&self) -> usize {
+        self.max_cue_bytes
+    }
+
+    /// Check the size of an image of `width` x `height` pixels of `bytes_per_pixel` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`LimitError::ImageTooLarge`] if the image is larger than the limit.
+    pub const fn check_image(
+        &self,
+        width: u32,
+        height: u32,
+        bytes_per_pixel: usize,
+    ) -> Result<usize, LimitError> {
+        let bytes = (width as usize)
+            .saturating_mul(height as usize)
+            .saturating_mul(bytes_per_pixel);
+        if bytes > self.max_image_bytes {
+            return Err(LimitError::ImageTooLarge {
+                width,
+                height,
+                bytes,
+                max: self.max_image_bytes,
+            });
+        }
+        Ok(bytes)
+    }
+
+    /// Check the size of the encoded data of a cue.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`LimitError::CueTooLarge`] if the data are larger than the limit.
+    pub const fn check_cue(&self, bytes: usize) -> Result<(), LimitError> {
+        if bytes > self.max_cue_bytes {
+            return Err(LimitError::CueTooLarge {
+                bytes,
+                max: self.max_cue_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_limits() {
+        let limits = MemoryLimits::new()
+            .with_max_image_bytes(100)
+            .with_max_cue_bytes(10);
+        assert_eq!(limits.check_image(10, 5, 2), Ok(100));
+        assert_eq!(
+            limits.check_image(10, 6, 2),
+            Err(LimitError::ImageTooLarge {
+                width: 10,
+                height: 6,
+                bytes: 120,
+                max: 100
+            })
+        );
+        assert_eq!(limits.check_cue(10), Ok(()));
+        assert_eq!(
+            limits.check_cue(11),
+            Err(LimitError::CueTooLarge { bytes: 11, max: 10 })
+        );
+        assert_eq!(
+            MemoryLimits::UNLIMITED.check_image(u32::MAX, u32::MAX, 4),
+            Ok(usize::MAX)
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! `LRC` (lyrics) functionality
+//!
+//! Each line of a `LRC` file is a text preceded by the time it is displayed
+//! (`[mm:ss.xx]`), and is displayed until the next line. The enhanced format add
+//! the time of each word (`<mm:ss.xx>`), used for karaoke.
+use std::{fmt, io};
+
+use crate::{
+    format::SubtitleSink,
+    sink::CueSink,
+    time::{TimePoint, TimeSpan},
+};
+
+/// Extend `TimePoint` for implement `LRC` specific `Display` : minutes, seconds and hundredths.
+#[repr(transparent)]
+pub struct TimePointLrc(TimePoint);
+
+impl From<TimePoint> for TimePointLrc {
+    fn from(value: TimePoint) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Display for TimePointLrc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `LRC` doesn't support negative times.
+        let centis = self.0.msecs().max(0) / 10;
+        let (minutes, centis) = (centis / 6000, centis % 6000);
+        write!(f, "{minutes:02}:{:02}.{:02}", centis / 100, centis % 100)
+    }
+}
+
+/// A word of a text, with the time it is sung.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LrcWord {
+    /// Start time of the word.
+    pub time: TimePoint,
+    /// Text of the word.
+    pub text: String,
+}
+
+impl LrcWord {
+    /// Create a word sung at `time`.
+    #[must_use]
+    pub fn new(time: TimePoint, text: impl Into<String>) -> Self {
+        Self {
+            time,
+            text: text.into(),
+        }
+    }
+}
+
+/// Estimate the time of each word of a text from the duration of its subtitle.
+///
+/// The text is not analyzed : after a lead-in delay, the duration of the subtitle is
+/// distributed to the words proportionally to their number of characters. It's approximate,
+/// but sufficient for a lot of karaoke uses, when only the text (from `OCR`) and the
+/// duration of the subtitles are known.
+///
+/// Create it with [`WordTimingEstimator::new`] (or [`Default`]) and the `with_*` methods.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WordTimingEstimator {
+    lead_in: i64,
+}
+
+impl WordTimingEstimator {
+    /// Create an estimator without lead-in.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { lead_in: 0 }
+    }
+
+    /// Set the delay (in milliseconds) between the start of the subtitle and the first word.
+    #[must_use]
+    pub const fn with_lead_in(mut self, msecs: i64) -> Self {
+        self.lead_in = msecs;
+        self
+    }
+
+    /// Estimate the start time of the words of `text`, displayed during `time`.
+    ///
+    /// The words are separated by whitespaces, including line breaks.
+    #[must_use]
+    pub fn estimate(&self, time: TimeSpan, text: &str) -> Vec<LrcWord> {
+        let words = text.split_whitespace().collect::<Vec<_>>();
+        let total = words.iter().map(|word| char_count(word)).sum::<i64>();
+        let start = time.start.msecs() + self.lead_in.max(0);
+        let duration = (time.end.msecs() - start).max(0);
+
+        let mut chars = 0;
+        words
+            .into_iter()
+            .map(|word| {
+                let time = TimePoint::from_msecs(start + duration * chars / total);
+                chars += char_count(word);
+                LrcWord {
+                    time,
+                    text: word.to_owned(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Number of characters of a word, as `i64` to compute times.
+fn char_count(word: &str) -> i64 {
+    i64::try_from(word.chars().count()).unwrap_or(i64::MAX)
+}
+
+/// Format `words` sung during `time` as a text with `ASS` karaoke tags (`{\kNN}`, with
+/// durations in centiseconds).
+///
+/// The delay before the first word is marked by a tag without text, and the last word lasts
+/// until the end of `time`.
+#[must_use]
+pub fn ass_karaoke_text(time: TimeSpan, words: &[LrcWord]) -> String {
+    let centis = |time: TimePoint| time.msecs() / 10;
+    let starts = words.iter().map(|word| centis(word.time));
+    let ends = starts.clone().skip(1).chain([centis(time.end)]);
+    let lead_in = words
+        .first()
+        .map_or(0, |word| centis(word.time) - centis(time.start));
+
+    let mut text = if lead_in > 0 {
+        format!("{{\\k{lead_in}}}")
+    } else {
+        String::new()
+    };
+    let tags = words
+        .iter()
+        .zip(starts.zip(ends))
+        .map(|(word, (start, end))| format!("{{\\k{}}}{}", (end - start).max(0), word.text));
+    text.push_str(&tags.collect::<Vec<_>>().join(" "));
+    text
+}
+
+/// Write subtitles in `lrc` format.
+/// # Errors
+///
+/// Will return `Err` if write in `writer` return an `Err`.
+pub fn write_lrc(
+    writer: &mut impl io::Write,
+    subtitles: &[(TimeSpan, String)],
+) -> Result<(), io::Error> {
+    LrcWriter::new(writer).write_all(subtitles.iter().map(|(time, text)| (*time, text)))
+}
+
+/// Write subtitles in enhanced `lrc` format, with the time of each word.
+/// # Errors
+///
+/// Will return `Err` if write in `writer` return an `Err`.
+pub fn write_enhanced_lrc(
+    writer: &mut impl io::Write,
+    subtitles: &[(TimeSpan, Vec<LrcWord>)],
+) -> Result<(), io::Error> {
+    let mut lrc_writer = LrcWriter::new(writer);
+    for (time, words) in subtitles {
+        lrc_writer.write_words(*time, words)?;
+    }
+    lrc_writer.finish()
+}
+
+/// Streaming writer of subtitles in `lrc` format, see [`CueSink`].
+///
+/// As a `LRC` line is displayed until the next one, an empty line is written at the end
+/// of a subtitle if it isn't followed immediately by another. The lines of a multi-lines
+/// subtitle are joined with spaces.
+pub struct LrcWriter<W: io::Write> {
+    writer: W,
+    /// End of the last written subtitle, not yet marked by an empty line.
+    pending_end: Option<TimePoint>,
+}
+
+impl<W: io::Write> LrcWriter<W> {
+    /// Create a writer of `lrc` subtitles in `writer`.
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            pending_end: None,
+        }
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write a subtitle in enhanced `lrc` format, with the time of each word.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if write in the writer return an `Err`.
+    pub fn write_words(&mut self, time: TimeSpan, words: &[LrcWord]) -> Result<(), io::Error> {
+        self.start_line(time)?;
+        for (idx, word) in words.iter().enumerate() {
+            let separator = if idx > 0 { " " } else { "" };
+            let word_time = TimePointLrc(word.time);
+            write!(self.writer, "{separator}<{word_time}>{}", word.text)?;
+        }
+        writeln!(self.writer)
+    }
+
+    /// Write the end of the previous subtitle if needed, and the time of the new line.
+    fn start_line(&mut self, time: TimeSpan) -> Result<(), io::Error> {
+        if let Some(end) = self.pending_end.replace(time.end) {
+            if end < time.start {
+                writeln!(self.writer, "[{}]", TimePointLrc(end))?;
+            }
+        }
+        write!(self.writer, "[{}]", TimePointLrc(time.start))
+    }
+}
+
+impl<W: io::Write> CueSink<str> for LrcWriter<W> {
+    type Error = io::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, text: &str) -> Result<(), Self::Error> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            start_ms = time.start.msecs(),
+            end_ms = time.end.msecs(),
+            format = "lrc",
+            "write cue"
+        );
+        self.start_line(time)?;
+        let text = text.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+        writeln!(self.writer, "{text}")
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        if let Some(end) = self.pending_end.take() {
+            writeln!(self.writer, "[{}]", TimePointLrc(end))?;
+        }
+        self.writer.flush()
+    }
+}
+
+/// The `lrc` files written have no header : the `ID` tags (`[ar:Artist]`) are optional.
+impl<W: io::Write> SubtitleSink for LrcWriter<W> {
+    const EXTENSION: &'static str = "lrc";
+
+    fn write_header(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+
+    #[test]
+    fn write_lrc_lines() {
+        let subtitles = [
+            (span(1000, 2500), "Hello\nWorld".to_owned()),
+            (span(2500, 3000), "Next".to_owned()),
+            (span(61_230, 62_000), "Later".to_owned()),
+        ];
+        let mut output = Vec::new();
+        write_lrc(&mut output, &subtitles).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[00:01.00]Hello World\n[00:02.50]Next\n[00:03.00]\n[01:01.23]Later\n[01:02.00]\n"
+        );
+    }
+
+    #[test]
+    fn write_enhanced_lrc_words() {
+        let word = |time, text: &str| LrcWord::new(TimePoint::from_msecs(time), text);
+        let subtitles = [(
+            span(1000, 2000),
+            vec![word(1000, "Hello"), word(1500, "World")],
+        )];
+        let mut output = Vec::new();
+        write_enhanced_lrc(&mut output, &subtitles).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[00:01.00]<00:01.00>Hello <00:01.50>World\n[00:02.00]\n"
+        );
+    }
+
+    #[test]
+    fn estimate_word_timings() {
+        let time = span(1000, 3000);
+        let words = WordTimingEstimator::new()
+            .with_lead_in(200)
+            .estimate(time, "Hey\nhello world");
+        let starts = words
+            .iter()
+            .map(|word| word.time.msecs())
+            .collect::<Vec<_>>();
+        assert_eq!(starts, [1200, 1615, 2307]);
+        assert_eq!(words[0].text, "Hey");
+
+        assert_eq!(
+            ass_karaoke_text(time, &words),
+            r"{\k20}{\k41}Hey {\k69}hello {\k70}world"
+        );
+        assert!(WordTimingEstimator::new().estimate(time, " ").is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+//! `MicroDVD` (`.sub` text) functionality
+//!
+//! Each line of a `MicroDVD` file is a subtitle, with start and end times expressed
+//! in frames : `{start}{end}Text|Second line`.
+use std::sync::LazyLock;
+
+use regex::Regex;
+use thiserror::Error;
+
+use crate::time::{FrameRate, TimePoint, TimeSpan};
+
+/// Error of `MicroDVD` parsing.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MicroDvdError {
+    /// The frame rate given by the file is not a positive number.
+    #[error("invalid frame rate {0}")]
+    InvalidFrameRate(f64),
+
+    /// A line is not a valid subtitle.
+    #[error("line {line} is not a valid `MicroDVD` subtitle")]
+    InvalidLine {
+        /// Number of the line, starting at 1.
+        line: usize,
+    },
+}
+
+/// Parse the content of a `MicroDVD` file, with a frame `rate`.
+///
+/// If the first subtitle is displayed at frame 0 or 1 and contains only a number, it is
+/// interpreted as the frame rate of the file, used instead of `rate`.
+/// Line separators `|` are converted to line breaks and the formatting codes
+/// (`{y:i}`, `{c:$0000FF}`, ...) are removed. Empty lines are ignored.
+///
+/// # Errors
+///
+/// Will return [`MicroDvdError::InvalidFrameRate`] if the frame rate of the file isn't
+/// positive, or [`MicroDvdError::InvalidLine`] if a line can't be parsed.
+pub fn parse_microdvd(
+    content: &str,
+    mut rate: FrameRate,
+) -> Result<Vec<(TimeSpan, String)>, MicroDvdError> {
+    static LINE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^\{(\d+)\}\{(\d+)\}(.*)$").unwrap());
+    static FORMATTING: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\{[a-zA-Z]:[^}]*\}").unwrap());
+
+    let mut subtitles = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid_line = || MicroDvdError::InvalidLine { line: idx + 1 };
+        let captures = LINE.captures(line).ok_or_else(invalid_line)?;
+        let frame = |group: usize| captures[group].parse::<u32>().ok().ok_or_else(invalid_line);
+        let (start, end) = (frame(1)?, frame(2)?);
+        let text = &captures[3];
+
+        if subtitles.is_empty() && start <= 1 && end <= 1 {
+            if let Ok(file_fps) = text.trim().parse() {
+                rate = FrameRate::from_fps(file_fps)
+                    .ok_or(MicroDvdError::InvalidFrameRate(file_fps))?;
+                continue;
+            }
+        }
+        let frame_time = |frame| TimePoint::from_frames(i64::from(frame), rate);
+        let time = TimeSpan::new(frame_time(start), frame_time(end));
+        let text = FORMATTING.replace_all(text, "").replace('|', "\n");
+        subtitles.push((time, text));
+    }
+    Ok(subtitles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+    use assert_matches2::assert_matches;
+
+    #[test]
+    #[expect(clippy::literal_string_with_formatting_args)]
+    fn parse_lines() {
+        let content = "{25}{50}Hello|World\n\n{75}{100}{y:i}Italic {c:$0000FF}text\n";
+        let subtitles = parse_microdvd(content, FrameRate::PAL).unwrap();
+        assert_eq!(
+            subtitles,
+            [
+                (span(1000, 2000), "Hello\nWorld".to_owned()),
+                (span(3000, 4000), "Italic text".to_owned()),
+            ]
+        );
+
+        let with_fps = format!("{{1}}{{1}}50\n{content}");
+        let subtitles = parse_microdvd(&with_fps, FrameRate::PAL).unwrap();
+        assert_eq!(subtitles[0].0, span(500, 1000));
+        let with_fps = format!("{{1}}{{1}}23.976\n{content}");
+        let subtitles = parse_microdvd(&with_fps, FrameRate::PAL).unwrap();
+        assert_eq!(subtitles[0].0, span(1043, 2085));
+
+        assert_matches!(
+            parse_microdvd("{25}{50}Hello\n{75}Oops\n", FrameRate::PAL),
+            Err(MicroDvdError::InvalidLine { line: 2 })
+        );
+        assert_matches!(
+            parse_microdvd(&format!("{{0}}{{0}}0\n{content}"), FrameRate::PAL),
+            Err(MicroDvdError::InvalidFrameRate(_))
+        );
+    }
+}
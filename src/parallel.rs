@@ -0,0 +1,90 @@
+//! Parallel processing based on scoped threads of the standard library.
+//!
+//! This module is available with the `parallel` feature, and doesn't require any
+//! additional dependency. It is intended to decode the images of the cues in parallel,
+//! while keeping the output in the order of the input :
+//! ```
+//! # use subtile::{parallel, pgs::{DecodeMode, RleEncodedImage}};
+//! # fn decode(images: Vec<RleEncodedImage>) {
+//! let decoded = parallel::map_ordered(images, parallel::available_workers(), |image| {
+//!     image.decode(DecodeMode::Lenient)
+//! });
+//! # }
+//! ```
+use std::{
+    num::NonZeroUsize,
+    sync::{mpsc, Mutex, PoisonError},
+    thread,
+};
+
+/// Number of workers to use by default : the available parallelism of the system, or 1 if unknown.
+#[must_use]
+pub fn available_workers() -> NonZeroUsize {
+    thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+}
+
+/// Apply `f` to each of the `items` on `workers` threads, and return the results in the order
+/// of the items.
+///
+/// The items are distributed one by one to the workers, so the work is balanced even
+/// if the processing time of the items differs. The output doesn't depend on the number of workers.
+///
+/// # Panics
+///
+/// Will panic if `f` panics on one of the items.
+pub fn map_ordered<I, U, F>(items: I, workers: NonZeroUsize, f: F) -> Vec<U>
+where
+    I: IntoIterator,
+    I::IntoIter: Send,
+    I::Item: Send,
+    U: Send,
+    F: Fn(I::Item) -> U + Sync,
+{
+    let items = Mutex::new(items.into_iter().enumerate());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..workers.get() {
+            let sender = sender.clone();
+            let (items, f) = (&items, &f);
+            scope.spawn(move || loop {
+                let next = items.lock().unwrap_or_else(PoisonError::into_inner).next();
+                let Some((idx, item)) = next else {
+                    break;
+                };
+                if sender.send((idx, f(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut results = Vec::new();
+        for (idx, result) in receiver {
+            if idx >= results.len() {
+                results.resize_with(idx + 1, || None);
+            }
+            results[idx] = Some(result);
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("all the items are processed"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_keep_items_order() {
+        let items = (0..100_u64).collect::<Vec<_>>();
+        let expected = items.iter().map(|n| n * n).collect::<Vec<_>>();
+        for workers in [1, 3, 200] {
+            let workers = NonZeroUsize::new(workers).unwrap();
+            assert_eq!(map_ordered(items.clone(), workers, |n| n * n), expected);
+        }
+        assert!(map_ordered(Vec::<u64>::new(), available_workers(), |n| n).is_empty());
+    }
+}
@@ -1,14 +1,45 @@
-use crate::time::{TimePoint, TimeSpan};
-use std::io::{BufRead, Seek};
+use crate::{
+    image::{DualImage, ToOcrImageOpt},
+    limits::MemoryLimits,
+    time::{TimePoint, TimeSpan},
+};
+use image::{Luma, Rgba};
+use std::{
+    io::{BufRead, Seek},
+    mem,
+};
 
 use super::{
-    ods::{self, ObjectDefinitionSegment},
-    pds,
+    display_set::{read_display_set, EpochPalettes},
+    ods::StreamEnd,
+    pds::Palette,
     pgs_image::RleEncodedImage,
-    segment::{read_header, skip_segment, SegmentTypeCode},
-    PgsError,
+    CompositionState, Diagnostic, PgsError,
 };
 
+/// Policy applied on malformed data during decoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Malformed data are reported as errors.
+    #[default]
+    Strict,
+    /// Malformed data are fixed when possible, to decode as much as possible.
+    Lenient,
+}
+
+/// Handling of the display sets updating only the palette of the displayed subtitle,
+/// used by some streams to implement fades.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FadeMode {
+    /// A fade sequence is a single subtitle, with the most opaque palette of the
+    /// sequence. This is what a subtitle conversion wants.
+    #[default]
+    Collapse,
+    /// Each palette update ends the subtitle and starts a new one with the updated
+    /// palette. This is what a player wants.
+    Faithful,
+}
+
 /// Trait of `Presentation Graphic Stream` decoding.
 pub trait PgsDecoder {
     /// Type of the Output data for the image.
@@ -17,123 +48,321 @@ pub trait PgsDecoder {
     /// Parse next subtitle `PGS` and return an `Output` value.
     /// The `Output` depending of the data we want to decode.
     ///
+    /// The decoder keep the state of the composition between calls, so it should
+    /// be used on successive display sets of the same stream.
+    ///
     /// # Errors
     /// Return the error happened during parsing or decoding.
-    fn parse_next<R>(reader: &mut R) -> Result<Option<Self::Output>, PgsError>
+    fn parse_next<R>(&mut self, reader: &mut R) -> Result<Option<Self::Output>, PgsError>
     where
         R: BufRead + Seek;
 }
 
 /// Decoder for `PGS` who provide only the times of subtitles.
-pub struct DecodeTimeOnly;
+#[derive(Default)]
+pub struct DecodeTimeOnly {
+    tracker: CompositionTracker,
+}
+impl DecodeTimeOnly {
+    /// Create a decoder applying the memory `limits` on the decoded objects.
+    #[must_use]
+    pub fn with_limits(limits: MemoryLimits) -> Self {
+        Self {
+            tracker: CompositionTracker {
+                limits,
+                ..CompositionTracker::default()
+            },
+        }
+    }
+
+    /// Set the policy applied on malformed segments, [`DecodeMode::Strict`] by default.
+    ///
+    /// In [`DecodeMode::Lenient`], the segments of unknown type are skipped, and
+    /// reported in [`Self::diagnostics`].
+    #[must_use]
+    pub const fn with_decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.tracker.mode = mode;
+        self
+    }
+
+    /// Issues found and fixed in the segments read so far.
+    ///
+    /// They are kept until taken with [`Self::take_diagnostics`].
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.tracker.diagnostics
+    }
+
+    /// Take the issues found so far, to process them while decoding a long stream
+    /// without keeping them all.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.tracker.diagnostics)
+    }
+}
 impl PgsDecoder for DecodeTimeOnly {
     type Output = TimeSpan;
 
-    fn parse_next<R>(reader: &mut R) -> Result<Option<Self::Output>, PgsError>
+    fn parse_next<R>(&mut self, reader: &mut R) -> Result<Option<Self::Output>, PgsError>
     where
         R: BufRead + Seek,
     {
-        let mut start_time = None;
-        let mut subtitle = None;
+        let subtitle = self.tracker.next_subtitle(reader, false)?;
+        Ok(subtitle.map(|(times, _)| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                start_ms = times.start.msecs(),
+                end_ms = times.end.msecs(),
+                "decoded cue"
+            );
+            times
+        }))
+    }
+}
 
-        while let Some(seg_header) = {
-            if subtitle.is_some() {
-                None
-            } else {
-                read_header(reader)?
-            }
-        } {
-            match seg_header.type_code() {
-                SegmentTypeCode::End => {
-                    let time = TimePoint::from_msecs(i64::from(seg_header.presentation_time()));
-
-                    if let Some(start_time) = start_time {
-                        subtitle = Some(TimeSpan::new(start_time, time));
-                    } else {
-                        start_time = Some(time);
-                    }
-                }
-                SegmentTypeCode::Pds
-                | SegmentTypeCode::Ods
-                | SegmentTypeCode::Pcs
-                | SegmentTypeCode::Wds => {
-                    // Segment content are not taken into account, skipped
-                    skip_segment(reader, &seg_header)?;
-                }
-            }
+/// Decoder for `PGS` who provide the times and images of the subtitles.
+#[derive(Default)]
+pub struct DecodeTimeImage {
+    tracker: CompositionTracker,
+}
+impl DecodeTimeImage {
+    /// Create a decoder applying the memory `limits` on the decoded objects.
+    #[must_use]
+    pub fn with_limits(limits: MemoryLimits) -> Self {
+        Self {
+            tracker: CompositionTracker {
+                limits,
+                ..CompositionTracker::default()
+            },
         }
+    }
 
-        Ok(subtitle)
+    /// Set how the palette updates of fades are handled, [`FadeMode::Collapse`] by default.
+    #[must_use]
+    pub const fn with_fade_mode(mut self, fade_mode: FadeMode) -> Self {
+        self.tracker.fade_mode = fade_mode;
+        self
     }
-}
 
-/// Decoder for `PGS` who provide the times and images of the subtitles.
-pub struct DecodeTimeImage {}
+    /// Set the policy applied on malformed segments, [`DecodeMode::Strict`] by default.
+    ///
+    /// In [`DecodeMode::Lenient`], the segments of unknown type are skipped, and
+    /// reported in [`Self::diagnostics`].
+    #[must_use]
+    pub const fn with_decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.tracker.mode = mode;
+        self
+    }
+
+    /// Issues found and fixed in the segments read so far.
+    ///
+    /// They are kept until taken with [`Self::take_diagnostics`].
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.tracker.diagnostics
+    }
+
+    /// Take the issues found so far, to process them while decoding a long stream
+    /// without keeping them all.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.tracker.diagnostics)
+    }
+}
 impl PgsDecoder for DecodeTimeImage {
     type Output = (TimeSpan, RleEncodedImage);
 
-    fn parse_next<R>(reader: &mut R) -> Result<Option<Self::Output>, PgsError>
+    fn parse_next<R>(&mut self, reader: &mut R) -> Result<Option<Self::Output>, PgsError>
     where
         R: BufRead + Seek,
     {
-        let mut start_time = None;
-        let mut subtitle = None;
-        let mut palette = None;
-        let mut image = None;
-        let mut prev_ods = None;
-
-        while let Some(seg_header) = {
-            if subtitle.is_some() {
-                None
-            } else {
-                read_header(reader)?
+        let Some((times, image)) = self.tracker.next_subtitle(reader, true)? else {
+            return Ok(None);
+        };
+        let image = image.ok_or(PgsError::MissingImage)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            start_ms = times.start.msecs(),
+            end_ms = times.end.msecs(),
+            width = crate::image::ImageSize::width(&image),
+            height = crate::image::ImageSize::height(&image),
+            "decoded cue"
+        );
+        Ok(Some((times, image)))
+    }
+}
+
+/// Decoder for `PGS` who provide the times of the subtitles, with their images both
+/// indexed and prepared for `OCR`, from a single decoding of each image.
+///
+/// For the pipelines doing `OCR` while keeping the original images, like for archival
+/// dumps. The `OCR` color of each palette entry is given by the `convert` function.
+pub struct DecodeTimeDualImage<F> {
+    decoder: DecodeTimeImage,
+    convert: F,
+    opt: ToOcrImageOpt,
+}
+impl<F> DecodeTimeDualImage<F>
+where
+    F: Fn(Rgba<u8>) -> Luma<u8>,
+{
+    /// Create a decoder reading the subtitles with `decoder`, and generating their
+    /// `OCR` image with `convert` and `opt`.
+    #[must_use]
+    pub const fn new(decoder: DecodeTimeImage, convert: F, opt: ToOcrImageOpt) -> Self {
+        Self {
+            decoder,
+            convert,
+            opt,
+        }
+    }
+
+    /// The decoder of the subtitles, to get its state (the diagnostics for example).
+    #[must_use]
+    pub const fn decoder(&self) -> &DecodeTimeImage {
+        &self.decoder
+    }
+
+    /// The decoder of the subtitles, to update its state (take the diagnostics for example).
+    pub fn decoder_mut(&mut self) -> &mut DecodeTimeImage {
+        &mut self.decoder
+    }
+}
+impl<F> PgsDecoder for DecodeTimeDualImage<F>
+where
+    F: Fn(Rgba<u8>) -> Luma<u8>,
+{
+    type Output = (TimeSpan, DualImage<256>);
+
+    fn parse_next<R>(&mut self, reader: &mut R) -> Result<Option<Self::Output>, PgsError>
+    where
+        R: BufRead + Seek,
+    {
+        let subtitle = self.decoder.parse_next(reader)?;
+        Ok(subtitle.map(|(times, image)| {
+            let dual = DualImage::new(image.to_indexed(), &self.convert, &self.opt);
+            (times, dual)
+        }))
+    }
+}
+
+/// Track the state of the composition through display sets to find subtitles boundaries.
+///
+/// A subtitle start with a display set showing objects, and end with the next display set
+/// clearing the screen or showing other objects. Display sets that only refresh
+/// (acquisition point) are ignored, like clears when nothing is displayed.
+/// Display sets that update the palette of the displayed subtitle are handled
+/// according to the [`FadeMode`].
+#[derive(Default)]
+struct CompositionTracker {
+    /// Start time and image of the currently displayed subtitle.
+    displayed: Option<(TimePoint, Option<RleEncodedImage>)>,
+    /// Last image defined in the current epoch.
+    epoch_image: Option<RleEncodedImage>,
+    /// Palettes defined in the current epoch.
+    palettes: EpochPalettes,
+    /// End of the stream, to check the length of the objects.
+    stream_end: StreamEnd,
+    /// Memory limits applied on the objects.
+    limits: MemoryLimits,
+    fade_mode: FadeMode,
+    /// Policy applied on malformed segments.
+    mode: DecodeMode,
+    /// Issues found and fixed in lenient mode.
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl CompositionTracker {
+    fn next_subtitle<R>(
+        &mut self,
+        reader: &mut R,
+        with_image: bool,
+    ) -> Result<Option<(TimeSpan, Option<RleEncodedImage>)>, PgsError>
+    where
+        R: BufRead + Seek,
+    {
+        while let Some(display_set) = read_display_set(
+            reader,
+            with_image,
+            &self.limits,
+            self.mode,
+            &mut self.palettes,
+            &mut self.stream_end,
+        )? {
+            self.diagnostics
+                .extend_from_slice(display_set.diagnostics());
+            let state = display_set.composition_state();
+            if state == Some(CompositionState::EpochStart) {
+                self.epoch_image = None;
             }
-        } {
-            match seg_header.type_code() {
-                SegmentTypeCode::Pds => {
-                    let seg_size = seg_header.size() as usize;
-                    let pds = pds::read(reader, seg_size)?;
-                    palette = Some(pds.palette);
-                }
-                SegmentTypeCode::Ods => {
-                    let seg_size = seg_header.size() as usize;
-                    let ods = ods::read(reader, seg_size, prev_ods.take())?;
-
-                    // If data are complete, construct `image` from palette and image data
-                    // otherwise, keep read data to complete it with data from following segment.
-                    if let ObjectDefinitionSegment::Complete(ods) = ods {
-                        let palette = palette.take().ok_or(PgsError::MissingPalette)?;
-                        image = Some(RleEncodedImage::new(
-                            ods.width,
-                            ods.height,
-                            palette,
-                            ods.object_data,
-                        ));
-                    } else {
-                        prev_ods = Some(ods);
-                    }
+            if display_set.is_palette_update() {
+                let time = display_set.time();
+                if let Some(cue) = self.update_palette(time, display_set.palette()) {
+                    return Ok(Some(cue));
                 }
-                SegmentTypeCode::End => {
-                    let time = TimePoint::from_msecs(i64::from(seg_header.presentation_time()));
+                continue;
+            }
 
-                    if let Some(start_time) = start_time {
-                        let times = TimeSpan::new(start_time, time);
+            let time = display_set.time();
+            if display_set.is_clear() {
+                if let Some((start, image)) = self.displayed.take() {
+                    return Ok(Some((TimeSpan::new(start, time), image)));
+                }
+                // Nothing is displayed, the clear is redundant.
+                continue;
+            }
+            if state == Some(CompositionState::AcquisitionPoint) && self.displayed.is_some() {
+                continue;
+            }
 
-                        let image = image.take().ok_or(PgsError::MissingImage)?;
-                        subtitle = Some((times, image));
-                    } else {
-                        start_time = Some(time);
-                    }
+            // A composition can show the object of the epoch with another palette,
+            // at another position.
+            let palette = display_set.palette().cloned();
+            let position = display_set
+                .objects()
+                .first()
+                .map(|object| (object.x, object.y));
+            if let Some(image) = display_set.into_image() {
+                self.epoch_image = Some(image);
+            } else if let Some(image) = &mut self.epoch_image {
+                if let Some(palette) = palette {
+                    image.set_palette(palette);
                 }
-                SegmentTypeCode::Pcs | SegmentTypeCode::Wds => {
-                    // Segment not taken into account are skipped
-                    skip_segment(reader, &seg_header)?;
+                if let Some(position) = position {
+                    image.set_position(position);
                 }
             }
+            let previous = self.displayed.replace((time, self.epoch_image.clone()));
+            if let Some((start, image)) = previous {
+                return Ok(Some((TimeSpan::new(start, time), image)));
+            }
         }
+        Ok(None)
+    }
 
-        assert!(palette.is_none()); // palette should be transferred into image before get out of the function.
-        assert!(prev_ods.is_none()); // Ods data should be converted into image before get out of the function.
-        Ok(subtitle)
+    /// Apply a palette update of the displayed subtitle at `time`, and return the
+    /// subtitle ended by the update, if any.
+    fn update_palette(
+        &mut self,
+        time: TimePoint,
+        palette: Option<&Palette>,
+    ) -> Option<(TimeSpan, Option<RleEncodedImage>)> {
+        let (start, image) = self.displayed.as_mut()?;
+        if let (Some(epoch_image), Some(palette)) = (&mut self.epoch_image, palette) {
+            epoch_image.set_palette(palette.clone());
+        }
+        match self.fade_mode {
+            FadeMode::Collapse => {
+                if let (Some(image), Some(palette)) = (image, palette) {
+                    if palette.opacity() > image.palette().opacity() {
+                        image.set_palette(palette.clone());
+                    }
+                }
+                None
+            }
+            FadeMode::Faithful => {
+                let start = *start;
+                let previous = self.displayed.replace((time, self.epoch_image.clone()));
+                previous.map(|(_, image)| (TimeSpan::new(start, time), image))
+            }
+        }
     }
 }
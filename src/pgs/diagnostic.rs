@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// Issue found in the data and fixed during a lenient decoding (see [`DecodeMode::Lenient`]).
+///
+/// [`DecodeMode::Lenient`]: super::DecodeMode::Lenient
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Diagnostic {
+    /// A run of 0 pixels was ignored.
+    ZeroLengthRun {
+        /// Line of the run.
+        line: u32,
+    },
+
+    /// A line of the image doesn't have the width of the image :
+    /// the line is truncated or filled with transparent pixels.
+    LineWidthMismatch {
+        /// Index of the line.
+        line: u32,
+        /// Number of pixels encoded in the line.
+        decoded: usize,
+        /// Width of the image.
+        width: u16,
+    },
+
+    /// The image data doesn't have the number of pixels of the image :
+    /// the pixels are truncated or filled with transparent pixels.
+    PixelCountMismatch {
+        /// Number of pixels decoded.
+        decoded: usize,
+        /// Number of pixels of the image.
+        expected: usize,
+    },
+
+    /// A color id used by the image is not defined by the palette :
+    /// the pixels of this color are transparent.
+    UndefinedColor {
+        /// The color id.
+        color_id: u8,
+    },
+
+    /// A segment of unknown type was skipped.
+    UnknownSegment {
+        /// Type code of the segment.
+        type_code: u8,
+        /// Size of the segment data.
+        size: u16,
+    },
+
+    /// A `Window Definition Segment` too short for the windows it defines was
+    /// ignored : the display set has no window.
+    InvalidWindowDefinition {
+        /// Size of the segment data.
+        size: u16,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroLengthRun { line } => write!(f, "ignored run of 0 pixels on line {line}"),
+            Self::LineWidthMismatch {
+                line,
+                decoded,
+                width,
+            } => write!(
+                f,
+                "line {line} contain {decoded} pixels instead of image width {width}"
+            ),
+            Self::PixelCountMismatch { decoded, expected } => write!(
+                f,
+                "image data contain {decoded} pixels instead of {expected}"
+            ),
+            Self::UndefinedColor { color_id } => {
+                write!(f, "color {color_id} is not defined by the palette")
+            }
+            Self::UnknownSegment { type_code, size } => write!(
+                f,
+                "skipped segment of unknown type {type_code:#04x} ({size} bytes)"
+            ),
+            Self::InvalidWindowDefinition { size } => write!(
+                f,
+                "ignored window definition segment too short for its windows ({size} bytes)"
+            ),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use super::{
+    ods::{self, ObjectDefinitionSegment, StreamEnd},
+    pcs::{self, CompositionObject, CompositionState, PresentationCompositionSegment},
+    pds::{self, Palette},
+    pgs_image::RleEncodedImage,
+    segment::{read_header_with_mode, skip_segment, SegmentTypeCode},
+    wds::{self, Window},
+    DecodeMode, Diagnostic, PgsError,
+};
+use crate::{content::Size, limits::MemoryLimits, time::TimePoint};
+use std::io::{BufRead, Cursor, Seek};
+
+/// A `Display Set` of a `Presentation Graphic Stream` : the group of segments
+/// terminated by an `END` segment.
+///
+/// A display set either shows an image or, without object, clears the screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplaySet {
+    time: TimePoint,
+    composition: Option<PresentationCompositionSegment>,
+    windows: Vec<Window>,
+    palette: Option<Palette>,
+    image: Option<RleEncodedImage>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DisplaySet {
+    /// Presentation time of the display set.
+    #[must_use]
+    pub const fn time(&self) -> TimePoint {
+        self.time
+    }
+
+    /// State of the composition, if the display set contain a `Presentation Composition Segment`.
+    #[must_use]
+    pub fn composition_state(&self) -> Option<CompositionState> {
+        self.composition.as_ref().map(|pcs| pcs.composition_state)
+    }
+
+    /// Size of the screen of the composition, if the display set contain a
+    /// `Presentation Composition Segment`.
+    #[must_use]
+    pub fn screen_size(&self) -> Option<Size> {
+        self.composition.as_ref().map(|pcs| Size {
+            w: usize::from(pcs.width),
+            h: usize::from(pcs.height),
+        })
+    }
+
+    /// Objects displayed by the composition, with their position on the screen.
+    ///
+    /// Empty without `Presentation Composition Segment`.
+    #[must_use]
+    pub fn objects(&self) -> &[CompositionObject] {
+        self.composition
+            .as_ref()
+            .map_or(&[], |pcs| pcs.objects.as_slice())
+    }
+
+    /// Windows defined by the `Window Definition Segment` of the display set, if any.
+    #[must_use]
+    pub fn windows(&self) -> &[Window] {
+        &self.windows
+    }
+
+    /// Return `true` if the display set clears the screen : it doesn't display any object.
+    ///
+    /// Without `Presentation Composition Segment`, a display set is considered as
+    /// a clear if it doesn't contain an image.
+    #[must_use]
+    pub fn is_clear(&self) -> bool {
+        self.composition
+            .as_ref()
+            .map_or_else(|| self.image.is_none(), |pcs| pcs.number_of_objects == 0)
+    }
+
+    /// Return `true` if the display set only update the palette of the current display.
+    #[must_use]
+    pub fn is_palette_update(&self) -> bool {
+        self.composition
+            .as_ref()
+            .is_some_and(|pcs| pcs.palette_update)
+    }
+
+    /// Palette selected by the composition, among the palettes defined in the epoch.
+    ///
+    /// Without `Presentation Composition Segment` or if its palette is not defined,
+    /// the last defined palette is used. It is the palette of [`Self::image`].
+    #[must_use]
+    pub const fn palette(&self) -> Option<&Palette> {
+        self.palette.as_ref()
+    }
+
+    /// Image carried by the display set, if any.
+    ///
+    /// The image is placed at the position of the object of the composition with its id.
+    #[must_use]
+    pub const fn image(&self) -> Option<&RleEncodedImage> {
+        self.image.as_ref()
+    }
+
+    /// Issues found and fixed while reading the segments, in [`DecodeMode::Lenient`].
+    ///
+    /// [`DecodeMode::Lenient`]: super::DecodeMode::Lenient
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Extract the image carried by the display set, if any.
+    #[must_use]
+    pub fn into_image(self) -> Option<RleEncodedImage> {
+        self.image
+    }
+}
+
+/// Decode a single `Display Set` from its segments bytes.
+///
+/// This is useful for callers who already extracted the segments of a display set
+/// from their container (one `Matroska` block for example). Data after the `END`
+/// segment are ignored. The default [`MemoryLimits`] are applied.
+///
+/// # Errors
+///
+/// Will return [`PgsError::IncompleteDisplaySet`] if the data is not terminated
+/// by an `END` segment, or the error happened during segments parsing.
+pub fn decode_display_set(data: &[u8]) -> Result<DisplaySet, PgsError> {
+    decode_display_set_with_limits(data, &MemoryLimits::default())
+}
+
+/// Decode a single `Display Set` from its segments bytes, like [`decode_display_set`],
+/// with custom memory `limits`.
+///
+/// # Errors
+///
+/// Will return [`PgsError::IncompleteDisplaySet`] if the data is not terminated
+/// by an `END` segment, or the error happened during segments parsing,
+/// including the data exceeding the `limits`.
+pub fn decode_display_set_with_limits(
+    data: &[u8],
+    limits: &MemoryLimits,
+) -> Result<DisplaySet, PgsError> {
+    let mut palettes = EpochPalettes::default();
+    read_display_set(
+        &mut Cursor::new(data),
+        true,
+        limits,
+        DecodeMode::Strict,
+        &mut palettes,
+        &mut StreamEnd::default(),
+    )?
+    .ok_or(PgsError::IncompleteDisplaySet)
+}
+
+/// Palettes defined in an epoch, by id.
+///
+/// A `PGS` stream can define up to 8 palettes, and each composition select
+/// the one used by its objects.
+#[derive(Debug, Default)]
+pub(crate) struct EpochPalettes {
+    palettes: Vec<(u8, Palette)>,
+    /// Id of the last defined palette.
+    last: Option<u8>,
+}
+
+impl EpochPalettes {
+    /// Define a palette, or update its entries if its id is already defined.
+    fn define(&mut self, pds: pds::PaletteDefinitionSegment) {
+        match self
+            .palettes
+            .iter_mut()
+            .find(|(id, _)| *id == pds.palette_id)
+        {
+            Some((_, palette)) => palette.update(pds.palette.entries().iter().cloned()),
+            None => self.palettes.push((pds.palette_id, pds.palette)),
+        }
+        self.last = Some(pds.palette_id);
+    }
+
+    fn get(&self, id: u8) -> Option<&Palette> {
+        self.palettes
+            .iter()
+            .find_map(|(palette_id, palette)| (*palette_id == id).then_some(palette))
+    }
+
+    /// Select the palette of the `composition`, or the last defined one.
+    fn select(&self, composition: Option<&PresentationCompositionSegment>) -> Option<&Palette> {
+        composition
+            .and_then(|pcs| self.get(pcs.palette_id))
+            .or_else(|| self.last.and_then(|id| self.get(id)))
+    }
+}
+
+/// Read segments up to the next `END` segment and build the corresponding [`DisplaySet`].
+/// If `with_image` is `false`, the palette and object segments are skipped,
+/// otherwise the objects are checked against the memory `limits`.
+///
+/// The palettes defined in the display set are added to the `palettes` of the epoch,
+/// cleared at the start of a new epoch. The segments of unknown type are skipped
+/// in [`DecodeMode::Lenient`].
+///
+/// Return `None` if the reader is consumed before an `END` segment.
+pub(crate) fn read_display_set<R>(
+    reader: &mut R,
+    with_image: bool,
+    limits: &MemoryLimits,
+    mode: DecodeMode,
+    palettes: &mut EpochPalettes,
+    stream_end: &mut StreamEnd,
+) -> Result<Option<DisplaySet>, PgsError>
+where
+    R: BufRead + Seek,
+{
+    let mut composition: Option<pcs::PresentationCompositionSegment> = None;
+    let mut windows = Vec::new();
+    let mut object = None;
+    let mut prev_ods = None;
+    let mut diagnostics = Vec::new();
+
+    while let Some(seg_header) = read_header_with_mode(reader, mode, &mut diagnostics)? {
+        match seg_header.type_code() {
+            SegmentTypeCode::Pcs => {
+                let seg_size = seg_header.size() as usize;
+                let pcs = pcs::read(reader, seg_size)?;
+                if pcs.composition_state == CompositionState::EpochStart {
+                    *palettes = EpochPalettes::default();
+                }
+                composition = Some(pcs);
+            }
+            SegmentTypeCode::Pds | SegmentTypeCode::Ods if !with_image => {
+                skip_segment(reader, &seg_header)?;
+            }
+            SegmentTypeCode::Pds => {
+                let seg_size = seg_header.size() as usize;
+                palettes.define(pds::read(reader, seg_size)?);
+            }
+            SegmentTypeCode::Ods => {
+                let seg_size = seg_header.size() as usize;
+                let ods = ods::read(reader, seg_size, prev_ods.take(), limits, stream_end)?;
+
+                // If data are complete, keep the object to construct the image at the end
+                // of the display set, otherwise, keep read data to complete it with data
+                // from following segment.
+                if let ObjectDefinitionSegment::Complete(ods) = ods {
+                    object = Some(ods);
+                } else {
+                    prev_ods = Some(ods);
+                }
+            }
+            SegmentTypeCode::End => {
+                let time = TimePoint::from_msecs(i64::from(seg_header.presentation_time()));
+                // The image use the palette selected by the composition.
+                let palette = palettes.select(composition.as_ref()).cloned();
+                let image = object
+                    .map(|ods| {
+                        let palette = palette.clone().ok_or(PgsError::MissingPalette)?;
+                        let image =
+                            RleEncodedImage::new(ods.width, ods.height, palette, ods.object_data);
+                        let position = position(composition.as_ref(), ods.object_id);
+                        Ok::<_, PgsError>(match position {
+                            Some((x, y)) => image.with_position(x, y),
+                            None => image,
+                        })
+                    })
+                    .transpose()?;
+                return Ok(Some(DisplaySet {
+                    time,
+                    composition,
+                    windows,
+                    palette,
+                    image,
+                    diagnostics,
+                }));
+            }
+            SegmentTypeCode::Wds => {
+                let seg_size = seg_header.size() as usize;
+                // The windows are only informative, a lenient decoding can go without.
+                windows = match wds::read(reader, seg_size) {
+                    Err(wds::Error::TooShort { .. }) if mode == DecodeMode::Lenient => {
+                        diagnostics.push(Diagnostic::InvalidWindowDefinition {
+                            size: seg_header.size(),
+                        });
+                        Vec::new()
+                    }
+                    windows => windows?,
+                };
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Position of the object of the `composition` with the id `object_id`, if any.
+fn position(
+    composition: Option<&PresentationCompositionSegment>,
+    object_id: u16,
+) -> Option<(u16, u16)> {
+    composition
+        .and_then(|pcs| {
+            pcs.objects
+                .iter()
+                .find(|object| object.object_id == object_id)
+        })
+        .map(|object| (object.x, object.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_display_set, decode_display_set_with_limits, read_display_set, CompositionState,
+        EpochPalettes, StreamEnd,
+    };
+    use crate::{
+        content::Size,
+        image::ImageSize as _,
+        limits::{LimitError, MemoryLimits},
+        pgs::{DecodeMode, PgsError},
+        time::TimePoint,
+    };
+    use assert_matches2::assert_matches;
+    use std::{fs, io::Cursor};
+
+    #[test]
+    fn decode_display_sets_from_bytes() {
+        let data = fs::read("./fixtures/only_one.sup").unwrap();
+
+        let show = decode_display_set(&data).unwrap();
+        assert_eq!(show.time(), TimePoint::from_msecs(500));
+        assert_eq!(show.composition_state(), Some(CompositionState::EpochStart));
+        assert!(!show.is_clear());
+        let image = show.image().unwrap();
+        assert_eq!((image.width(), image.height()), (78, 36));
+        assert_eq!(image.raw().len(), 1573);
+        assert_eq!(image.palette().len(), 165);
+        assert_eq!(
+            format!("{image:?}"),
+            "RleEncodedImage { width: 78, height: 36, position: (985, 779), palette_len: 165, \
+             raw: 00 c0 4e a4 00 00 00 c0 4e a4 00 00 00 c0 4e a4 (1573 bytes) }"
+        );
+        assert_eq!(show.screen_size(), Some(Size { w: 2048, h: 858 }));
+        assert_eq!(show.objects().len(), 1);
+        let window = show.windows()[0];
+        assert_eq!(Some(window.area().unwrap()), image.area());
+        assert_eq!((window.x, window.y), image.position());
+        assert_eq!(show, decode_display_set(&data).unwrap());
+        let decoded = image.decode(DecodeMode::Strict).unwrap();
+        assert_eq!(decoded.lines().count(), 36);
+        assert!(decoded.diagnostics().is_empty());
+
+        let mut reader = Cursor::new(data.as_slice());
+        read_display_set(
+            &mut reader,
+            true,
+            &MemoryLimits::default(),
+            DecodeMode::Strict,
+            &mut EpochPalettes::default(),
+            &mut StreamEnd::default(),
+        )
+        .unwrap()
+        .unwrap();
+        let offset = usize::try_from(reader.position()).unwrap();
+        let clear = decode_display_set(&data[offset..]).unwrap();
+        assert_eq!(clear.time(), TimePoint::from_msecs(1499));
+        assert!(clear.is_clear());
+        assert!(clear.into_image().is_none());
+
+        assert_matches!(
+            decode_display_set(&data[..offset - 13]),
+            Err(PgsError::IncompleteDisplaySet)
+        );
+    }
+
+    #[test]
+    fn decode_display_set_over_limits() {
+        let data = fs::read("./fixtures/only_one.sup").unwrap();
+        let limits = MemoryLimits::new().with_max_image_bytes(78 * 36 * 2 - 1);
+        assert_matches!(
+            decode_display_set_with_limits(&data, &limits),
+            Err(PgsError::Limit(LimitError::ImageTooLarge {
+                bytes: 5616,
+                ..
+            }))
+        );
+        let limits = MemoryLimits::new().with_max_cue_bytes(1000);
+        assert_matches!(
+            decode_display_set_with_limits(&data, &limits),
+            Err(PgsError::Limit(LimitError::CueTooLarge {
+                bytes: 1577,
+                max: 1000
+            }))
+        );
+    }
+}
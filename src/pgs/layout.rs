@@ -0,0 +1,307 @@
+//! Allocation of the windows and epochs of an encoded `Presentation Graphic Stream`.
+//!
+//! An encoder has to place each cue in a window, and to group the cues in epochs :
+//! the windows can't change inside an epoch, and each epoch start by a display set
+//! with all the data needed to decode it. Players differ in their support of the possible
+//! layouts, so the strategies are configurable with a [`LayoutConfig`].
+use thiserror::Error;
+
+use super::CompositionState;
+use crate::{
+    content::{Area, AreaValues},
+    time::TimeSpan,
+};
+
+/// Size of the decoded object buffer of `BluRay` players.
+pub const DECODED_OBJECT_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+/// Placement of the window of the cues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowStrategy {
+    /// One window of `height` lines at the bottom of the screen, for all the cues.
+    ///
+    /// This is the most compatible layout, as the window never changes, but cues
+    /// displayed elsewhere (top positioned signs for example) can't be encoded, and the
+    /// whole window is cleared and redrawn on each cue.
+    SingleBottom {
+        /// Height of the window.
+        height: u16,
+    },
+    /// A window fitting tightly the cues of each epoch.
+    ///
+    /// Smaller windows are faster to draw for the players, and allow any cue position,
+    /// but changing windows require a new epoch.
+    Tight,
+}
+
+/// Grouping of the cues in epochs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpochStrategy {
+    /// Each cue start a new epoch.
+    ///
+    /// Each display set is self-contained, which is robust to seeking and to players with
+    /// partial support of compositions updates, at the cost of a larger stream.
+    PerCue,
+    /// Cues separated by at most `max_gap_msecs` milliseconds share an epoch (a scene).
+    ///
+    /// The stream is smaller, but the window of a scene must contain all its cues, and
+    /// seeking in the middle of a scene rely on the support of the player.
+    PerScene {
+        /// Maximum gap between two cues of the same epoch, in milliseconds.
+        max_gap_msecs: i64,
+    },
+}
+
+/// Configuration of the layout of the encoded cues.
+///
+/// Create it with [`LayoutConfig::new`] (or [`Default`]) and the `with_*` methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LayoutConfig {
+    /// Width of the video.
+    pub screen_width: u16,
+    /// Height of the video.
+    pub screen_height: u16,
+    /// Placement of the windows.
+    pub window: WindowStrategy,
+    /// Grouping of the cues in epochs.
+    pub epoch: EpochStrategy,
+}
+
+impl Default for LayoutConfig {
+    /// A `1920x1080` screen, with a tight window by cue.
+    fn default() -> Self {
+        Self::new(1920, 1080)
+    }
+}
+
+impl LayoutConfig {
+    /// Create the configuration for a screen of `screen_width`x`screen_height`,
+    /// with a tight window by cue.
+    #[must_use]
+    pub const fn new(screen_width: u16, screen_height: u16) -> Self {
+        Self {
+            screen_width,
+            screen_height,
+            window: WindowStrategy::Tight,
+            epoch: EpochStrategy::PerCue,
+        }
+    }
+
+    /// Set the placement of the windows.
+    #[must_use]
+    pub const fn with_window(mut self, window: WindowStrategy) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set the grouping of the cues in epochs.
+    #[must_use]
+    pub const fn with_epoch(mut self, epoch: EpochStrategy) -> Self {
+        self.epoch = epoch;
+        self
+    }
+}
+
+/// Error of the layout of cues.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LayoutError {
+    /// The screen or the bottom window have an empty size.
+    #[error("screen and windows should not be empty")]
+    EmptyScreen,
+
+    /// A cue is not entirely inside the screen.
+    #[error("cue {index} is outside of the screen")]
+    OutsideScreen {
+        /// Index of the cue.
+        index: usize,
+    },
+
+    /// A cue is not entirely inside the bottom window.
+    #[error("cue {index} is outside of the bottom window")]
+    OutsideWindow {
+        /// Index of the cue.
+        index: usize,
+    },
+
+    /// The object of a cue doesn't fit in the decoded object buffer of the players.
+    #[error("cue {index} object of {bytes} bytes exceed the decoded object buffer")]
+    ObjectTooLarge {
+        /// Index of the cue.
+        index: usize,
+        /// Size of the decoded object.
+        bytes: usize,
+    },
+}
+
+/// Layout of an encoded cue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CueLayout {
+    /// Display time of the cue.
+    pub time: TimeSpan,
+    /// Area of the object of the cue.
+    pub object: Area,
+    /// Window containing the object.
+    pub window: Area,
+    /// Index of the epoch of the cue.
+    pub epoch: usize,
+    /// Composition state of the display set showing the cue :
+    /// [`CompositionState::EpochStart`] for the first cue of an epoch.
+    pub composition_state: CompositionState,
+}
+
+/// Compute the layout of the `cues`, given as their times and areas, sorted by time.
+///
+/// # Errors
+///
+/// Will return a [`LayoutError`] if a cue can't be placed on the screen or in the window,
+/// or is too large for the players.
+pub fn plan_layout(
+    config: &LayoutConfig,
+    cues: &[(TimeSpan, Area)],
+) -> Result<Vec<CueLayout>, LayoutError> {
+    let screen = screen_area(
+        config.screen_width,
+        config.screen_height,
+        config.screen_height,
+    )?;
+    let bottom_window = match config.window {
+        WindowStrategy::SingleBottom { height } => Some(screen_area(
+            config.screen_width,
+            config.screen_height,
+            height,
+        )?),
+        WindowStrategy::Tight => None,
+    };
+
+    // Check the cues and group them in epochs, with the union of their areas.
+    let mut epochs: Vec<(Area, Vec<usize>)> = Vec::new();
+    let mut previous: Option<TimeSpan> = None;
+    for (index, (time, area)) in cues.iter().enumerate() {
+        if !screen.contains(area) {
+            return Err(LayoutError::OutsideScreen { index });
+        }
+        if bottom_window.is_some_and(|window| !window.contains(area)) {
+            return Err(LayoutError::OutsideWindow { index });
+        }
+        let bytes = area.size().w * area.size().h;
+        if bytes > DECODED_OBJECT_BUFFER_SIZE {
+            return Err(LayoutError::ObjectTooLarge { index, bytes });
+        }
+
+        let same_epoch = match config.epoch {
+            EpochStrategy::PerCue => false,
+            EpochStrategy::PerScene { max_gap_msecs } => previous
+                .is_some_and(|previous| time.start.msecs() - previous.end.msecs() <= max_gap_msecs),
+        };
+        match epochs.last_mut() {
+            Some((areas, indexes)) if same_epoch => {
+                *areas = areas.union(area);
+                indexes.push(index);
+            }
+            _ => epochs.push((*area, vec![index])),
+        }
+        previous = Some(*time);
+    }
+
+    let mut layouts = Vec::with_capacity(cues.len());
+    for (epoch, (areas, indexes)) in epochs.into_iter().enumerate() {
+        let window = bottom_window.unwrap_or(areas);
+        layouts.extend(indexes.into_iter().enumerate().map(|(position, index)| {
+            let (time, object) = cues[index];
+            CueLayout {
+                time,
+                object,
+                window,
+                epoch,
+                composition_state: if position == 0 {
+                    CompositionState::EpochStart
+                } else {
+                    CompositionState::Normal
+                },
+            }
+        }));
+    }
+    Ok(layouts)
+}
+
+/// Area of the `height` bottom lines of the screen.
+fn screen_area(width: u16, screen_height: u16, height: u16) -> Result<Area, LayoutError> {
+    if width == 0 || height == 0 || height > screen_height {
+        return Err(LayoutError::EmptyScreen);
+    }
+    Area::try_from(AreaValues {
+        x1: 0,
+        y1: screen_height - height,
+        x2: width - 1,
+        y2: screen_height - 1,
+    })
+    .map_err(|_err| LayoutError::EmptyScreen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+
+    fn cue(start: i64, end: i64, x1: u16, y1: u16) -> (TimeSpan, Area) {
+        let time = span(start, end);
+        let area = Area::try_from(AreaValues {
+            x1,
+            y1,
+            x2: x1 + 99,
+            y2: y1 + 49,
+        })
+        .unwrap();
+        (time, area)
+    }
+
+    #[test]
+    fn tight_windows_per_scene() {
+        let cues = [
+            cue(0, 1000, 100, 900),
+            cue(1100, 2000, 300, 950),
+            cue(5000, 6000, 100, 50),
+        ];
+        let config =
+            LayoutConfig::default().with_epoch(EpochStrategy::PerScene { max_gap_msecs: 500 });
+        let layouts = plan_layout(&config, &cues).unwrap();
+        let epochs = layouts
+            .iter()
+            .map(|layout| layout.epoch)
+            .collect::<Vec<_>>();
+        assert_eq!(epochs, [0, 0, 1]);
+        assert_eq!(layouts[1].composition_state, CompositionState::Normal);
+        assert_eq!(layouts[2].composition_state, CompositionState::EpochStart);
+        let window = layouts[0].window;
+        assert_eq!((window.left(), window.top()), (100, 900));
+        assert_eq!((window.width(), window.height()), (300, 100));
+        assert_eq!(layouts[2].window, cues[2].1);
+
+        let per_cue = plan_layout(&LayoutConfig::default(), &cues).unwrap();
+        assert!(per_cue
+            .iter()
+            .all(|layout| layout.composition_state == CompositionState::EpochStart));
+    }
+
+    #[test]
+    fn single_bottom_window() {
+        let config =
+            LayoutConfig::default().with_window(WindowStrategy::SingleBottom { height: 200 });
+        let cues = [cue(0, 1000, 100, 900), cue(2000, 3000, 100, 50)];
+        assert_eq!(
+            plan_layout(&config, &cues),
+            Err(LayoutError::OutsideWindow { index: 1 })
+        );
+        let layouts = plan_layout(&config, &cues[..1]).unwrap();
+        assert_eq!(layouts[0].window.top(), 880);
+        assert_eq!(layouts[0].window.width(), 1920);
+
+        assert_eq!(
+            plan_layout(&LayoutConfig::default(), &[cue(0, 1000, 1900, 0)]),
+            Err(LayoutError::OutsideScreen { index: 0 })
+        );
+    }
+}
@@ -1,21 +1,40 @@
-//! Read functionalities for Presentation Graphic Stream (.sup)
+//! Read and write functionalities for Presentation Graphic Stream (.sup)
 //!
 //! Presentation Graphic Stream (SUP files) `BluRay` Subtitle Format doc :
 //! <https://blog.thescorpius.com/index.php/2017/07/15/presentation-graphic-stream-sup-files-bluray-subtitle-format/>
 //!
 mod decoder;
+mod diagnostic;
+mod display_set;
+pub mod layout;
 mod ods;
+mod pcs;
 mod pds;
 mod pgs_image;
+mod rle;
 mod segment;
 mod sup;
 mod u24;
+mod validate;
+mod wds;
+mod writer;
 
-pub use decoder::{DecodeTimeImage, DecodeTimeOnly, PgsDecoder};
-pub use pgs_image::{RleEncodedImage, RleToImage};
-pub use sup::SupParser;
+pub use decoder::{
+    DecodeMode, DecodeTimeDualImage, DecodeTimeImage, DecodeTimeOnly, FadeMode, PgsDecoder,
+};
+pub use diagnostic::Diagnostic;
+pub use display_set::{decode_display_set, decode_display_set_with_limits, DisplaySet};
+pub use pcs::{CompositionObject, CompositionState};
+pub use pds::{Palette, PaletteEntry};
+pub use pgs_image::{DecodedImage, RleEncodedImage, RleToImage};
+pub use rle::RleError;
+pub use sup::{IdentifiedCues, SupParser};
+pub use validate::{validate_sup, BdLimits, ValidationReport, Violation};
+pub use wds::Window;
+pub use writer::{EncodeError, SupWriter};
 
 use self::segment::SegmentTypeCode;
+use crate::limits::LimitError;
 use std::{
     io::{self, BufRead, Seek},
     num::TryFromIntError,
@@ -40,10 +59,26 @@ pub enum PgsError {
     #[error("object Definition Segment parsing")]
     ODSParse(#[from] ods::Error),
 
+    /// Encapsulates errors from `Presentation Composition Segment` parsing.
+    #[error("presentation Composition Segment parsing")]
+    PCSParse(#[from] pcs::Error),
+
+    /// Encapsulates errors from `Window Definition Segment` parsing.
+    #[error("window Definition Segment parsing")]
+    WDSParse(#[from] wds::Error),
+
     /// Encapsulates errors from `Palette Definition Segment` parsing.
     #[error("palette Definition Segment parsing")]
     PDSParse(#[from] pds::Error),
 
+    /// An object exceed the memory limits of the decoder.
+    #[error("object exceed the memory limits")]
+    Limit(#[from] LimitError),
+
+    /// Encapsulates errors from `Rle` image data decoding.
+    #[error("rle image data decoding")]
+    Rle(#[from] RleError),
+
     /// Invalid segment type code value.
     #[error("invalid value '{value:#02x}' for Segment Type Code")]
     SegmentInvalidTypeCode {
@@ -69,13 +104,41 @@ pub enum PgsError {
         type_code: SegmentTypeCode,
     },
 
+    /// `ReadError` occurred during skipping a segment of unknown type.
+    #[error("skipping Segment of unknown type '{type_code:#02x}'")]
+    UnknownSegmentSkip {
+        /// Parent `ReadError`
+        #[source]
+        source: ReadError,
+        /// type code of the segment we skip
+        type_code: u8,
+    },
+
+    /// `ReadError` occurred during reading the data of a segment.
+    #[error("reading Segment {type_code}")]
+    SegmentRead {
+        /// Parent `ReadError`
+        #[source]
+        source: ReadError,
+        /// type code of the segment we read
+        type_code: SegmentTypeCode,
+    },
+
     /// Error if image is missing to complete the parsing of a subtitle.
     #[error("missing image during `Presentation Graphic Stream (PGS)` parsing")]
     MissingImage,
 
+    /// Data of a display set end before its `END` segment.
+    #[error("display set is not terminated by an `END` segment")]
+    IncompleteDisplaySet,
+
     /// Palette is missing after image parsing.
     #[error("missing palette after image parsing")]
     MissingPalette,
+
+    /// The position in the stream, for the identifier of a cue, can't be read.
+    #[error("failed to get the position in the stream")]
+    StreamPosition(#[source] io::Error),
 }
 
 /// Error from data read for parsing.
@@ -152,3 +215,18 @@ where
     }
 }
 impl<U> ReadExt for U where U: BufRead + Seek {}
+
+// Parsers, cues and images can be moved to and shared between worker threads.
+const _: () = {
+    use crate::{time::TimeSpan, util::assert_send_sync};
+    use image::LumaA;
+    use std::{fs::File, io::BufReader};
+
+    assert_send_sync::<SupParser<BufReader<File>, DecodeTimeImage>>();
+    assert_send_sync::<SupParser<BufReader<File>, DecodeTimeOnly>>();
+    assert_send_sync::<(TimeSpan, RleEncodedImage)>();
+    assert_send_sync::<DecodedImage>();
+    assert_send_sync::<DisplaySet>();
+    assert_send_sync::<RleToImage<'_, LumaA<u8>, fn(LumaA<u8>) -> LumaA<u8>>>();
+    assert_send_sync::<PgsError>();
+};
@@ -1,12 +1,14 @@
-use super::{u24::u24, ReadError, ReadExt as _};
+use super::{u24::u24, PgsError, ReadError, ReadExt as _};
+use crate::{limits::MemoryLimits, util::BytesFormatter};
 use std::{
     fmt::{Debug, Display},
-    io::{self, BufRead, Seek},
+    io::{self, BufRead, Seek, SeekFrom},
 };
 use thiserror::Error;
 
 /// Error `ODS` (Object Definition Segment) handling.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Error while tried reading `LastInSequence` flag.
     #[error("reading `LastInSequenceFlag` failed")]
@@ -16,14 +18,30 @@ pub enum Error {
     #[error("`LastInSequenceFlag` : '{value:02x}' is not a valid value")]
     LastInSequenceFlagInvalidValue { value: u8 },
 
-    /// Value of flag `LastInSequence` is not managed by the current code.
-    #[error("`LastInSequenceFlag`::'{0}' flag is not managed")]
-    LastInSequenceFlagNotManaged(LastInSequenceFlag),
+    /// The `LastInSequence` flag doesn't match the segments of the object read before.
+    #[error("`LastInSequenceFlag`::'{0}' doesn't match the segments read before")]
+    UnexpectedSequenceFlag(LastInSequenceFlag),
+
+    /// The segment is too short to contain the fields of an object.
+    #[error("segment of {size} bytes too short for the object fields ({min} bytes)")]
+    SegmentTooShort { size: usize, min: usize },
+
+    /// The data of the segments of an object exceed its `Object Data Length`.
+    #[error("segments of an object contain {assembled} bytes of data, more than the {declared} bytes declared")]
+    ObjectDataOverflow { declared: usize, assembled: usize },
+
+    /// The last segment of an object doesn't complete its data.
+    #[error("segments of an object contain {assembled} bytes of data, less than the {declared} bytes declared")]
+    ObjectDataIncomplete { declared: usize, assembled: usize },
 
     /// Failed during `Object ID` and `Object Version Number` skipping.
     #[error("skipping `Object ID` and `Object Version Number`")]
     SkipObjectIdAndVerNum(#[source] ReadError),
 
+    /// Failed during `Object ID` reading.
+    #[error("read `Object ID` field")]
+    ReadObjectId(#[source] io::Error),
+
     /// Failed during `Object Data Length` reading.
     #[error("read `Object Data Length` field")]
     ReadObjectDataLength(#[source] io::Error),
@@ -36,6 +54,14 @@ pub enum Error {
     #[error("read Height of the image incarried by the `Object Definition Segment`(s)")]
     ReadHeight(#[source] io::Error),
 
+    /// Failed to find the remaining size of the stream.
+    #[error("find the size of the stream after the `Object Definition Segment`")]
+    StreamLength(#[source] io::Error),
+
+    /// The `Object Data Length` doesn't match the size of the segments of the object.
+    #[error("`Object Data Length` of {declared} bytes, but only {available} bytes are available")]
+    InvalidObjectDataLength { declared: usize, available: u64 },
+
     /// The read of object data failed.
     #[error("try reading object data (buffer slice size: {buff_size})")]
     ObjectData {
@@ -45,9 +71,13 @@ pub enum Error {
     },
 }
 
+/// Number of bytes by pixel of the decoded images (`LumaA<u8>`), for the memory limits.
+const DECODED_BYTES_PER_PIXEL: usize = 2;
+
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum LastInSequenceFlag {
+    Middle = 0x00,
     Last = 0x40,
     First = 0x80,
     FirstAndLast = 0xC0,
@@ -57,6 +87,7 @@ impl TryFrom<u8> for LastInSequenceFlag {
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
+            0x00 => Ok(Self::Middle),
             0x40 => Ok(Self::Last),
             0x80 => Ok(Self::First),
             0xC0 => Ok(Self::FirstAndLast),
@@ -72,6 +103,7 @@ impl From<LastInSequenceFlag> for u8 {
 impl From<LastInSequenceFlag> for &'static str {
     fn from(val: LastInSequenceFlag) -> Self {
         match val {
+            LastInSequenceFlag::Middle => "Middle",
             LastInSequenceFlag::Last => "Last",
             LastInSequenceFlag::First => "First",
             LastInSequenceFlag::FirstAndLast => "First and last",
@@ -113,38 +145,89 @@ pub enum ObjectDefinitionSegment {
 
 /// This segment defines the graphics object : it contain the image.
 /// The `object_data` contain theimage data compressed using Run-length Encoding (RLE)
-#[derive(Debug)] //TODO: define a custom Debug
 pub struct ObjectDefinitionSegmentData {
+    /// Id of the object, referenced by the objects of the composition.
+    pub object_id: u16,
     pub width: u16,
     pub height: u16,
     pub object_data: Vec<u8>,
 }
 
+impl Debug for ObjectDefinitionSegmentData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectDefinitionSegmentData")
+            .field("object_id", &self.object_id)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("object_data", &BytesFormatter(&self.object_data))
+            .finish()
+    }
+}
+
+/// Size of the fields of the first segment of an object, before the object data.
+const FIRST_FIELDS_LEN: usize = 11;
+/// Size of the fields of the next segments of an object, before the object data.
+const NEXT_FIELDS_LEN: usize = 4;
+
+/// End of the stream, found once for all the objects read from it.
+#[derive(Debug, Default)]
+pub(crate) struct StreamEnd(Option<u64>);
+
+impl StreamEnd {
+    /// Number of bytes between the current position of `reader` and the end of the stream.
+    fn remaining<Reader: Seek>(&mut self, reader: &mut Reader) -> io::Result<u64> {
+        let position = reader.stream_position()?;
+        let end = if let Some(end) = self.0 {
+            end
+        } else {
+            let end = reader.seek(SeekFrom::End(0))?;
+            reader.seek(SeekFrom::Start(position))?;
+            *self.0.insert(end)
+        };
+        Ok(end.saturating_sub(position))
+    }
+}
+
 pub fn read<Reader: BufRead + Seek>(
     reader: &mut Reader,
     segments_size: usize,
     current_ods: Option<ObjectDefinitionSegment>,
-) -> Result<ObjectDefinitionSegment, Error> {
-    handle_object_fields(reader)?;
+    limits: &MemoryLimits,
+    stream_end: &mut StreamEnd,
+) -> Result<ObjectDefinitionSegment, PgsError> {
+    let object_id = handle_object_fields(reader)?;
     let last_in_sequence_flag = LastInSequenceFlag::read(reader)?;
 
     match current_ods {
         None => {
-            assert!(
-                last_in_sequence_flag == LastInSequenceFlag::First
-                    || last_in_sequence_flag == LastInSequenceFlag::FirstAndLast
-            );
+            if !matches!(
+                last_in_sequence_flag,
+                LastInSequenceFlag::First | LastInSequenceFlag::FirstAndLast
+            ) {
+                return Err(Error::UnexpectedSequenceFlag(last_in_sequence_flag).into());
+            }
 
             let data_size = read_obj_data_length(reader)?;
             let (width, height) = read_img_size(reader)?;
+            limits.check_cue(data_size)?;
+            limits.check_image(width.into(), height.into(), DECODED_BYTES_PER_PIXEL)?;
+            // Only read data from this segment, additional data are in the next segment, if there are any.
+            let read_data_size = fragment_size(segments_size, FIRST_FIELDS_LEN)?;
+            check_obj_data_length(
+                reader,
+                stream_end,
+                data_size,
+                read_data_size,
+                last_in_sequence_flag,
+            )?;
             let data_size = data_size - 4; // don't know why for now !!! Object Data Length include Width + Height ?
             let mut object_data = vec![0; data_size]; // Create a `Vec` for contain data of object (image)
 
-            let read_data_size = segments_size - 11; // Only read data from this segment, additional data are in the next segment, if there are any.
             let data_buff = &mut object_data.as_mut_slice()[0..read_data_size];
             read_object_data(reader, data_buff)?;
 
             let data = ObjectDefinitionSegmentData {
+                object_id,
                 width,
                 height,
                 object_data,
@@ -152,29 +235,53 @@ pub fn read<Reader: BufRead + Seek>(
 
             if last_in_sequence_flag == LastInSequenceFlag::FirstAndLast {
                 assert!(read_data_size == data_size);
-                assert!(segments_size == 11 + data_size);
-
                 Ok(ObjectDefinitionSegment::Complete(data))
-            } else if last_in_sequence_flag == LastInSequenceFlag::First {
+            } else {
                 Ok(ObjectDefinitionSegment::Partial {
                     data,
                     amount_of_data_read: read_data_size,
                 })
-            } else {
-                Err(Error::LastInSequenceFlagNotManaged(last_in_sequence_flag))
             }
         }
         Some(ObjectDefinitionSegment::Partial {
             mut data,
             amount_of_data_read,
         }) => {
-            assert!(last_in_sequence_flag == LastInSequenceFlag::Last); //TODO: not first and not last ?
+            let is_last = match last_in_sequence_flag {
+                LastInSequenceFlag::Middle => false,
+                LastInSequenceFlag::Last => true,
+                LastInSequenceFlag::First | LastInSequenceFlag::FirstAndLast => {
+                    return Err(Error::UnexpectedSequenceFlag(last_in_sequence_flag).into());
+                }
+            };
 
             let start_idx = amount_of_data_read;
-            let end_idx = start_idx + (segments_size - 4);
+            let declared = data.object_data.len();
+            let fragment_size = fragment_size(segments_size, NEXT_FIELDS_LEN)?;
+            let end_idx = start_idx
+                .checked_add(fragment_size)
+                .filter(|&end_idx| end_idx <= declared)
+                .ok_or_else(|| Error::ObjectDataOverflow {
+                    declared,
+                    assembled: start_idx.saturating_add(fragment_size),
+                })?;
+            if is_last && end_idx < declared {
+                return Err(Error::ObjectDataIncomplete {
+                    declared,
+                    assembled: end_idx,
+                }
+                .into());
+            }
             let read_slice = &mut data.object_data.as_mut_slice()[start_idx..end_idx];
             read_object_data(reader, read_slice)?;
-            Ok(ObjectDefinitionSegment::Complete(data))
+            if is_last {
+                Ok(ObjectDefinitionSegment::Complete(data))
+            } else {
+                Ok(ObjectDefinitionSegment::Partial {
+                    data,
+                    amount_of_data_read: end_idx,
+                })
+            }
         }
         Some(ObjectDefinitionSegment::Complete(_)) => {
             panic!("read shouln'd be called with a `Complete` `ObjectDefinitionSegment`");
@@ -182,13 +289,27 @@ pub fn read<Reader: BufRead + Seek>(
     }
 }
 
-// Handle `Object ID` and `Object Version Number` fields by skip it.
-// They are not useful for current subtitle management.
-fn handle_object_fields<Reader: BufRead + Seek>(reader: &mut Reader) -> Result<(), Error> {
+// Size of the object data in a segment of `segments_size` bytes, after `fields_len` bytes
+// of fields.
+const fn fragment_size(segments_size: usize, fields_len: usize) -> Result<usize, Error> {
+    match segments_size.checked_sub(fields_len) {
+        Some(size) => Ok(size),
+        None => Err(Error::SegmentTooShort {
+            size: segments_size,
+            min: fields_len,
+        }),
+    }
+}
+
+// Handle `Object ID` and `Object Version Number` fields : read the id, used to place
+// the object, and skip the version, not useful for current subtitle management.
+fn handle_object_fields<Reader: BufRead + Seek>(reader: &mut Reader) -> Result<u16, Error> {
+    let mut object_id = [0; 2];
     reader
-        .skip_data(2 + 1)
-        .map_err(Error::SkipObjectIdAndVerNum)?;
-    Ok(())
+        .read_exact(&mut object_id)
+        .map_err(Error::ReadObjectId)?;
+    reader.skip_data(1).map_err(Error::SkipObjectIdAndVerNum)?;
+    Ok(u16::from_be_bytes(object_id))
 }
 
 // Read the `Object Data Length` field and return value in `usize`.
@@ -201,6 +322,39 @@ fn read_obj_data_length<Reader: BufRead + Seek>(reader: &mut Reader) -> Result<u
     Ok(object_data_length.to_u32().try_into().unwrap())
 }
 
+// Check the `Object Data Length` before allocating the object data, a bogus length
+// could allocate up to 16 MiB.
+// The object data of a complete segment must fill it, and the data of a partial one
+// can't exceed the data of this segment and the remaining of the stream.
+fn check_obj_data_length<Reader: BufRead + Seek>(
+    reader: &mut Reader,
+    stream_end: &mut StreamEnd,
+    declared: usize,
+    read_data_size: usize,
+    last_in_sequence_flag: LastInSequenceFlag,
+) -> Result<(), Error> {
+    // The length include the width and height fields.
+    let segment_data = (read_data_size + 4) as u64;
+    let available = if last_in_sequence_flag == LastInSequenceFlag::FirstAndLast {
+        segment_data
+    } else {
+        segment_data + stream_end.remaining(reader).map_err(Error::StreamLength)?
+    };
+    let valid = if last_in_sequence_flag == LastInSequenceFlag::FirstAndLast {
+        declared as u64 == available
+    } else {
+        declared >= 4 + read_data_size && declared as u64 <= available
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidObjectDataLength {
+            declared,
+            available,
+        })
+    }
+}
+
 // Read the image size (width and height) fields.
 fn read_img_size<Reader: BufRead + Seek>(reader: &mut Reader) -> Result<(u16, u16), Error> {
     let mut buffer = [0; 2];
@@ -223,3 +377,203 @@ fn read_object_data<Reader: BufRead + Seek>(
             buff_size: data_buff.len(),
         })
 }
+
+/// Flag of an `Object Definition Segment` containing the first part of an object.
+const FIRST_IN_SEQUENCE_FLAG: u8 = 0x80;
+
+/// View of the fields of an `Object Definition Segment`, read from the content of
+/// the segment without copying the object data.
+///
+/// Only used to inspect the segments : [`read`] doesn't copy the segments either, it
+/// reads the fields from the stream, and the object data directly in the buffer of the
+/// assembled object, owned by the decoded image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ObjectDefinitionView {
+    pub object_id: u16,
+    /// Size of the object, only defined by the first segment of an object.
+    pub size: Option<(u16, u16)>,
+}
+
+impl ObjectDefinitionView {
+    /// Parse the content of a segment, or return `None` if it is too short.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (&[id_high, id_low, _version, flag], rest) = data.split_first_chunk()?;
+        let size = if flag & FIRST_IN_SEQUENCE_FLAG == 0 {
+            None
+        } else {
+            // The object data length is not needed, only the size of the image.
+            let (&[_, _, _, width_high, width_low, height_high, height_low], _) =
+                rest.split_first_chunk()?;
+            let width = u16::from_be_bytes([width_high, width_low]);
+            let height = u16::from_be_bytes([height_high, height_low]);
+            Some((width, height))
+        };
+        Some(Self {
+            object_id: u16::from_be_bytes([id_high, id_low]),
+            size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Reader counting the seeks to the end of the stream.
+    struct CountEndSeeks {
+        inner: Cursor<Vec<u8>>,
+        end_seeks: usize,
+    }
+    impl Seek for CountEndSeeks {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            if matches!(pos, SeekFrom::End(_)) {
+                self.end_seeks += 1;
+            }
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn stream_end_found_once() {
+        let mut reader = CountEndSeeks {
+            inner: Cursor::new(vec![0; 10]),
+            end_seeks: 0,
+        };
+        let mut stream_end = StreamEnd::default();
+        assert_eq!(stream_end.remaining(&mut reader).unwrap(), 10);
+        reader.inner.set_position(4);
+        assert_eq!(stream_end.remaining(&mut reader).unwrap(), 6);
+        // The position is kept, and the end is searched only the first time.
+        assert_eq!(reader.inner.position(), 4);
+        assert_eq!(reader.end_seeks, 1);
+    }
+
+    #[test]
+    fn object_views() {
+        let first = [
+            0x00, 0x01, 0x00, 0x80, // id, version, first in sequence
+            0x00, 0x00, 0x07, 0x00, 0x02, 0x00, 0x01, // length, width, height
+            0x01, 0x02, 0x00,
+        ];
+        assert_eq!(
+            ObjectDefinitionView::parse(&first),
+            Some(ObjectDefinitionView {
+                object_id: 1,
+                size: Some((2, 1))
+            })
+        );
+        let last = [0x00, 0x02, 0x00, 0x40, 0x00];
+        assert_eq!(
+            ObjectDefinitionView::parse(&last),
+            Some(ObjectDefinitionView {
+                object_id: 2,
+                size: None
+            })
+        );
+        assert!(ObjectDefinitionView::parse(&first[..8]).is_none());
+    }
+
+    #[test]
+    fn reject_bogus_object_data_length() {
+        let read = |data: &[u8]| {
+            let mut reader = std::io::Cursor::new(data);
+            read(
+                &mut reader,
+                14,
+                None,
+                &MemoryLimits::default(),
+                &mut StreamEnd::default(),
+            )
+        };
+        // First of a sequence declaring 16 MiB, followed by a few bytes.
+        let mut first = vec![
+            0x00, 0x01, 0x00, 0x80, // id, version, first in sequence
+            0xFF, 0xFF, 0xFF, 0x00, 0x02, 0x00, 0x01, // length, width, height
+            0x01, 0x02, 0x00,
+        ];
+        first.extend([0; 16]);
+        assert!(matches!(
+            read(&first),
+            Err(PgsError::ODSParse(Error::InvalidObjectDataLength {
+                declared: 0xFF_FFFF,
+                available: 26,
+            }))
+        ));
+        // Complete object with a length not matching the segment.
+        first[3] = 0xC0;
+        assert!(matches!(
+            read(&first),
+            Err(PgsError::ODSParse(Error::InvalidObjectDataLength {
+                available: 7,
+                ..
+            }))
+        ));
+        first[4..7].copy_from_slice(&[0x00, 0x00, 0x07]);
+        assert!(matches!(
+            read(&first),
+            Ok(ObjectDefinitionSegment::Complete(_))
+        ));
+    }
+
+    #[test]
+    fn assemble_object_fragments() {
+        let limits = MemoryLimits::default();
+        let first = [
+            0x00, 0x01, 0x00, 0x80, // id, version, first in sequence
+            0x00, 0x00, 0x0A, 0x00, 0x03, 0x00, 0x02, // length, width, height
+            0x01, 0x02,
+        ];
+        let middle = [0x00, 0x01, 0x00, 0x00, 0x03, 0x04];
+        let last = [0x00, 0x01, 0x00, 0x40, 0x05, 0x06];
+        let read_all = |segments: &[&[u8]]| {
+            let mut reader = std::io::Cursor::new(segments.concat());
+            let mut stream_end = StreamEnd::default();
+            segments.iter().try_fold(None, |ods, segment| {
+                read(&mut reader, segment.len(), ods, &limits, &mut stream_end).map(Some)
+            })
+        };
+
+        let Ok(Some(ObjectDefinitionSegment::Complete(data))) = read_all(&[&first, &middle, &last])
+        else {
+            panic!("the object should be complete");
+        };
+        assert_eq!(data.object_data, [1, 2, 3, 4, 5, 6]);
+
+        assert!(matches!(
+            read_all(&[&first, &last]),
+            Err(PgsError::ODSParse(Error::ObjectDataIncomplete {
+                declared: 6,
+                assembled: 4
+            }))
+        ));
+        assert!(matches!(
+            read_all(&[&first, &middle, &middle, &last]),
+            Err(PgsError::ODSParse(Error::ObjectDataOverflow {
+                declared: 6,
+                assembled: 8
+            }))
+        ));
+        assert!(matches!(
+            read_all(&[&middle]),
+            Err(PgsError::ODSParse(Error::UnexpectedSequenceFlag(
+                LastInSequenceFlag::Middle
+            )))
+        ));
+    }
+
+    #[test]
+    fn object_data_debug() {
+        let data = ObjectDefinitionSegmentData {
+            object_id: 1,
+            width: 20,
+            height: 1,
+            object_data: (0..20).collect(),
+        };
+        assert_eq!(
+            format!("{data:?}"),
+            "ObjectDefinitionSegmentData { object_id: 1, width: 20, height: 1, object_data: \
+             00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f (20 bytes) }"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::image::BoundingBox;
+use std::io::{self, Read};
+use thiserror::Error;
+
+/// Error `PCS` (Presentation Composition Segment) handling.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Read `PresentationCompositionSegment` in a buffer failed.
+    #[error("failed to read buffer with `PresentationCompositionSegment`")]
+    BufferParse(#[source] io::Error),
+
+    /// The segment is too short to contain the composition fields.
+    #[error("`PresentationCompositionSegment` of size {0} is too short")]
+    TooShort(usize),
+
+    /// Value read for `Composition State` is invalid.
+    #[error("`CompositionState` : '{0:#02x}' is not a valid value")]
+    InvalidCompositionState(u8),
+}
+
+/// Length of the fixed part of the segment, before the composition objects.
+const FIXED_FIELDS_LEN: usize = 2 + 2 + 1 + 2 + 1 + 1 + 1 + 1;
+
+/// Flag of the `Palette Update` field when the display set only update the palette.
+const PALETTE_UPDATE_FLAG: u8 = 0x80;
+
+/// Length of a composition object, without the cropping fields.
+const OBJECT_LEN: usize = 2 + 1 + 1 + 2 + 2;
+
+/// Length of the cropping fields of a cropped composition object.
+const CROPPING_LEN: usize = 2 + 2 + 2 + 2;
+
+/// Flag of the `Object Cropped Flag` field when the object is cropped.
+const OBJECT_CROPPED_FLAG: u8 = 0x40;
+
+/// State of the composition, define the type of the `Display Set`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositionState {
+    /// The display set contain only functional segments that are different from the preceding one.
+    Normal,
+    /// The display set refresh the current composition, with the same content.
+    AcquisitionPoint,
+    /// Start of a new display, the display set contain all the data needed.
+    EpochStart,
+}
+
+impl TryFrom<u8> for CompositionState {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Self::Normal),
+            0x40 => Ok(Self::AcquisitionPoint),
+            0x80 => Ok(Self::EpochStart),
+            value => Err(Error::InvalidCompositionState(value)),
+        }
+    }
+}
+
+impl From<CompositionState> for u8 {
+    fn from(value: CompositionState) -> Self {
+        match value {
+            CompositionState::Normal => 0x00,
+            CompositionState::AcquisitionPoint => 0x40,
+            CompositionState::EpochStart => 0x80,
+        }
+    }
+}
+
+/// An object displayed by a composition, and its position on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CompositionObject {
+    /// Id of the object, as defined by its `Object Definition Segment`.
+    pub object_id: u16,
+    /// Id of the window in which the object is displayed.
+    pub window_id: u8,
+    /// Horizontal position of the top left pixel of the object on the screen.
+    pub x: u16,
+    /// Vertical position of the top left pixel of the object on the screen.
+    pub y: u16,
+    /// Part of the object displayed, relative to the object, if it is cropped.
+    pub cropping: Option<BoundingBox>,
+}
+
+/// This segment define the composition of the display : how many objects are displayed,
+/// where, and if the display set only update the palette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PresentationCompositionSegment {
+    pub width: u16,
+    pub height: u16,
+    pub composition_state: CompositionState,
+    pub palette_update: bool,
+    pub palette_id: u8,
+    pub number_of_objects: u8,
+    /// Objects defined in the segment, the truncated ones are ignored.
+    pub objects: Vec<CompositionObject>,
+}
+
+pub(crate) fn read<R: Read>(
+    reader: &mut R,
+    segments_size: usize,
+) -> Result<PresentationCompositionSegment, Error> {
+    let mut pcs_buf = vec![0; segments_size];
+    reader
+        .read_exact(&mut pcs_buf)
+        .map_err(Error::BufferParse)?;
+    parse(&pcs_buf)
+}
+
+/// Parse the content of a segment, read in `pcs_buf`.
+pub(crate) fn parse(pcs_buf: &[u8]) -> Result<PresentationCompositionSegment, Error> {
+    if pcs_buf.len() < FIXED_FIELDS_LEN {
+        return Err(Error::TooShort(pcs_buf.len()));
+    }
+
+    // Frame Rate and Composition Number are not used.
+    let width = u16::from_be_bytes([pcs_buf[0], pcs_buf[1]]);
+    let height = u16::from_be_bytes([pcs_buf[2], pcs_buf[3]]);
+    let composition_state = CompositionState::try_from(pcs_buf[7])?;
+    let palette_update = pcs_buf[8] == PALETTE_UPDATE_FLAG;
+    let palette_id = pcs_buf[9];
+    let number_of_objects = pcs_buf[10];
+
+    // Some streams don't define the objects they count, they are ignored.
+    let mut objects = Vec::with_capacity(usize::from(number_of_objects));
+    let mut data = &pcs_buf[FIXED_FIELDS_LEN..];
+    for _ in 0..number_of_objects {
+        let Some((object, rest)) = parse_object(data) else {
+            break;
+        };
+        objects.push(object);
+        data = rest;
+    }
+
+    Ok(PresentationCompositionSegment {
+        width,
+        height,
+        composition_state,
+        palette_update,
+        palette_id,
+        number_of_objects,
+        objects,
+    })
+}
+
+/// Parse a composition object at the start of `data`, and return it with the rest of
+/// the data, or `None` if `data` is too short.
+fn parse_object(data: &[u8]) -> Option<(CompositionObject, &[u8])> {
+    let field = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+    if data.len() < OBJECT_LEN {
+        return None;
+    }
+    let cropped = data[3] & OBJECT_CROPPED_FLAG != 0;
+    let len = if cropped {
+        OBJECT_LEN + CROPPING_LEN
+    } else {
+        OBJECT_LEN
+    };
+    if data.len() < len {
+        return None;
+    }
+    let cropping = cropped.then(|| BoundingBox {
+        x: field(8).into(),
+        y: field(10).into(),
+        width: field(12).into(),
+        height: field(14).into(),
+    });
+    let object = CompositionObject {
+        object_id: field(0),
+        window_id: data[2],
+        x: field(4),
+        y: field(6),
+        cropping,
+    };
+    Some((object, &data[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches2::assert_matches;
+
+    #[test]
+    fn read_composition() {
+        let data = [
+            0x07, 0x80, 0x04, 0x38, 0x10, 0x00, 0xb2, 0x80, 0x00, 0x00, 0x01, //
+            0x00, 0x00, 0x00, 0x00, 0x01, 0xf1, 0x03, 0x93,
+        ];
+        let pcs = read(&mut data.as_slice(), data.len()).unwrap();
+        assert_eq!((pcs.width, pcs.height), (1920, 1080));
+        assert_eq!(pcs.composition_state, CompositionState::EpochStart);
+        assert!(!pcs.palette_update);
+        assert_eq!(pcs.number_of_objects, 1);
+        assert_eq!(
+            pcs.objects,
+            [CompositionObject {
+                object_id: 0,
+                window_id: 0,
+                x: 497,
+                y: 915,
+                cropping: None,
+            }]
+        );
+
+        assert_matches!(read(&mut &data[..8], 8), Err(Error::TooShort(8)));
+        assert!(parse(&data[..15]).unwrap().objects.is_empty());
+
+        // A cropped object, with the cropping fields.
+        let mut cropped = data.to_vec();
+        cropped[14] = OBJECT_CROPPED_FLAG;
+        assert!(parse(&cropped).unwrap().objects.is_empty());
+        cropped.extend([0, 2, 0, 4, 0, 10, 0, 8]);
+        let pcs = parse(&cropped).unwrap();
+        assert_eq!(
+            pcs.objects[0].cropping,
+            Some(BoundingBox {
+                x: 2,
+                y: 4,
+                width: 10,
+                height: 8
+            })
+        );
+    }
+}
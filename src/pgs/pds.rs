@@ -1,86 +1,371 @@
-use std::io::{self, Read};
+use image::Rgba;
+use std::{
+    fmt,
+    io::{self, BufRead},
+};
 use thiserror::Error;
 
 /// Error `PDS` (Palette Definition Segment) handling.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Read `PaletteDefinitionSegment` in a buffer failed.
     #[error("failed to read buffer with `PaletteDefinitionSegment`")]
     BufferParse(#[source] io::Error),
+
+    /// The segment is too short to contain the palette id and version.
+    #[error("`PaletteDefinitionSegment` of size {0} is too short")]
+    TooShort(usize),
 }
 
-#[derive(Debug, Clone)]
+/// Palette of a `PGS` image, defined by a `Palette Definition Segment`.
+///
+/// Entries are looked up by their id, so a palette can define any subset of the 256 ids.
+/// The ids not defined are reported by [`RleEncodedImage::decode`].
+///
+/// [`RleEncodedImage::decode`]: super::RleEncodedImage::decode
+#[derive(Clone, PartialEq, Eq)]
 pub struct Palette {
     entries: Vec<PaletteEntry>,
-    offset: i16,
+    /// Position in `entries` of the entry of each id.
+    positions: [Option<u8>; 256],
 }
 impl Palette {
     fn new(entries: Vec<PaletteEntry>) -> Self {
-        let offset = compute_offset(&entries);
-        Self { entries, offset }
+        let mut palette = Self {
+            entries: Vec::with_capacity(entries.len()),
+            positions: [None; 256],
+        };
+        palette.update(entries);
+        palette
+    }
+
+    /// Define or replace the `entries` with their ids, keeping the other entries.
+    ///
+    /// It handles the palettes reusing an id with another set of entries, and the entries
+    /// defined several times in a segment (the last definition is used).
+    pub(crate) fn update(&mut self, entries: impl IntoIterator<Item = PaletteEntry>) {
+        for entry in entries {
+            let position = &mut self.positions[usize::from(entry.entry_id)];
+            if let Some(position) = *position {
+                self.entries[usize::from(position)] = entry;
+            } else {
+                // At most 256 entries, as many as the ids.
+                *position = u8::try_from(self.entries.len()).ok();
+                self.entries.push(entry);
+            }
+        }
     }
 
-    #[expect(clippy::cast_sign_loss)]
+    /// Get the entry of the color `id`, or `None` if the palette doesn't define it.
+    #[must_use]
     pub fn get(&self, id: u8) -> Option<&PaletteEntry> {
-        let idx = i16::from(id) + self.offset;
-        self.entries.get(idx as usize)
+        self.positions[usize::from(id)].map(|position| &self.entries[usize::from(position)])
+    }
+
+    /// Return `true` if the palette defines the color `id`.
+    #[must_use]
+    pub fn contains(&self, id: u8) -> bool {
+        self.positions[usize::from(id)].is_some()
+    }
+
+    /// Entries of the palette, in definition order.
+    #[must_use]
+    pub fn entries(&self) -> &[PaletteEntry] {
+        &self.entries
+    }
+
+    /// Number of entries of the palette.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Sum of the opacity (`transparency` value) of the entries, to compare the
+    /// palettes of a fade.
+    #[must_use]
+    pub fn opacity(&self) -> u32 {
+        self.entries
+            .iter()
+            .map(|entry| u32::from(entry.transparency))
+            .sum()
+    }
+
+    /// Return `true` if the palette doesn't have any entry.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
 }
 
-fn compute_offset(palette: &[PaletteEntry]) -> i16 {
-    //HACK offset is computed only on the first element, should be checked for all entries
-    if palette.is_empty() {
-        0
-    } else {
-        0 - i16::from(palette[0].entry_id)
+impl fmt::Debug for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Palette")
+            .field("entries", &self.entries)
+            .finish_non_exhaustive()
     }
 }
 
-#[derive(Debug, Clone)]
+/// Color entry of a [`Palette`].
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PaletteEntry {
-    entry_id: u8,               // Entry number of the palette
-    pub luminance: u8,          // Luminance (Y value)
-    _color_difference_red: u8,  // Color Difference Red (Cr value)
-    _color_difference_blue: u8, // Color Difference Blue (Cb value)
-    pub transparency: u8,       // Transparency (Alpha value)
+    entry_id: u8, // Entry number of the palette
+    /// Luminance (Y value)
+    pub luminance: u8,
+    color_difference_red: u8,  // Color Difference Red (Cr value)
+    color_difference_blue: u8, // Color Difference Blue (Cb value)
+    /// Transparency (Alpha value)
+    pub transparency: u8,
+}
+
+impl PaletteEntry {
+    /// Entry number in the palette, the color id used in image data.
+    #[must_use]
+    pub const fn id(&self) -> u8 {
+        self.entry_id
+    }
+
+    /// Color difference red (Cr value).
+    #[must_use]
+    pub const fn color_difference_red(&self) -> u8 {
+        self.color_difference_red
+    }
+
+    /// Color difference blue (Cb value).
+    #[must_use]
+    pub const fn color_difference_blue(&self) -> u8 {
+        self.color_difference_blue
+    }
+
+    /// Convert the color to `RGBA`, from the `BT.709` limited range `YCbCr` of `BluRay` video.
+    #[must_use]
+    pub fn to_rgba(&self) -> Rgba<u8> {
+        let luma = (f64::from(self.luminance) - 16.) * 255. / 219.;
+        let red_diff = (f64::from(self.color_difference_red) - 128.) * 255. / 224.;
+        let blue_diff = (f64::from(self.color_difference_blue) - 128.) * 255. / 224.;
+        let channel = |value: f64| cast::u8(value.round().clamp(0., 255.)).unwrap_or_default();
+        Rgba([
+            channel(1.5748f64.mul_add(red_diff, luma)),
+            channel(0.4681f64.mul_add(-red_diff, 0.1873f64.mul_add(-blue_diff, luma))),
+            channel(1.8556f64.mul_add(blue_diff, luma)),
+            self.transparency,
+        ])
+    }
+
+    /// Create the entry `entry_id` of a `RGBA` color, in the `BT.709` limited range `YCbCr`
+    /// of `BluRay` video.
+    pub(crate) fn from_rgba(entry_id: u8, Rgba([red, green, blue, alpha]): Rgba<u8>) -> Self {
+        let (red, green, blue) = (f64::from(red), f64::from(green), f64::from(blue));
+        let luma = 0.0722f64.mul_add(blue, 0.2126f64.mul_add(red, 0.7152 * green));
+        let channel = |value: f64| cast::u8(value.round().clamp(0., 255.)).unwrap_or_default();
+        Self {
+            entry_id,
+            luminance: channel(luma.mul_add(219. / 255., 16.)),
+            color_difference_red: channel(((red - luma) / 1.5748).mul_add(224. / 255., 128.)),
+            color_difference_blue: channel(((blue - luma) / 1.8556).mul_add(224. / 255., 128.)),
+            transparency: alpha,
+        }
+    }
+
+    /// Bytes of the entry in a `Palette Definition Segment`.
+    pub(crate) const fn to_bytes(&self) -> [u8; ENTRY_LEN] {
+        [
+            self.entry_id,
+            self.luminance,
+            self.color_difference_red,
+            self.color_difference_blue,
+            self.transparency,
+        ]
+    }
 }
 #[derive(Debug)]
 pub(crate) struct PaletteDefinitionSegment {
-    _palette_id: u8,             // ID of the palette
+    pub palette_id: u8,          // ID of the palette
     _palette_version_number: u8, //	Version of this palette within the Epoch
     pub palette: Palette,
 }
 
-pub(crate) fn read<R: Read>(
+/// Length of a palette entry in the segment.
+const ENTRY_LEN: usize = 5;
+
+pub(crate) fn read<R: BufRead>(
     reader: &mut R,
     segments_size: usize,
 ) -> Result<PaletteDefinitionSegment, Error> {
+    // The segment is parsed in the buffer of the reader when it holds the whole segment,
+    // and copied only otherwise.
+    let buffered = reader.fill_buf().map_err(Error::BufferParse)?;
+    if let Some(pds_buf) = buffered.get(..segments_size) {
+        let pds = PaletteDefinitionView::parse(pds_buf).map(PaletteDefinitionSegment::from);
+        reader.consume(segments_size);
+        return pds;
+    }
+
     let mut pds_buf = vec![0; segments_size];
     reader
         .read_exact(&mut pds_buf)
         .map_err(Error::BufferParse)?;
+    PaletteDefinitionView::parse(&pds_buf).map(PaletteDefinitionSegment::from)
+}
 
-    let palette_id = pds_buf[0];
-    let palette_version_number = pds_buf[1];
-
-    let nb_palette_entry: usize = (segments_size - 2) / 5;
-    assert_eq!((nb_palette_entry * 5) + 2, segments_size);
-    let range = 0..nb_palette_entry;
-    let palette_entries = range
-        .map(|idx| {
-            let offset = 2 + (idx * 5);
-            PaletteEntry {
-                entry_id: pds_buf[offset],
-                luminance: pds_buf[offset + 1],
-                _color_difference_red: pds_buf[offset + 2],
-                _color_difference_blue: pds_buf[offset + 3],
-                transparency: pds_buf[offset + 4],
-            }
+impl From<PaletteDefinitionView<'_>> for PaletteDefinitionSegment {
+    fn from(view: PaletteDefinitionView<'_>) -> Self {
+        Self {
+            palette_id: view.palette_id,
+            _palette_version_number: view.palette_version_number,
+            palette: Palette::new(view.entries().collect()),
+        }
+    }
+}
+
+/// View of the content of a `Palette Definition Segment`, borrowing the entries
+/// instead of copying them.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PaletteDefinitionView<'a> {
+    pub palette_id: u8,
+    pub palette_version_number: u8,
+    entries: &'a [u8],
+}
+
+impl<'a> PaletteDefinitionView<'a> {
+    /// Parse the content of a segment.
+    pub const fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let Some((&[palette_id, palette_version_number], entries)) = data.split_first_chunk()
+        else {
+            return Err(Error::TooShort(data.len()));
+        };
+        Ok(Self {
+            palette_id,
+            palette_version_number,
+            entries,
         })
-        .collect();
-    Ok(PaletteDefinitionSegment {
-        _palette_id: palette_id,
-        _palette_version_number: palette_version_number,
-        palette: Palette::new(palette_entries),
-    })
+    }
+
+    /// Number of entries defined by the segment.
+    pub const fn entry_count(&self) -> usize {
+        self.entries.len() / ENTRY_LEN
+    }
+
+    /// Entries defined by the segment.
+    pub fn entries(&self) -> impl Iterator<Item = PaletteEntry> + 'a {
+        // A palette can be empty, and trailing bytes not forming an entry are padding.
+        self.entries
+            .chunks_exact(ENTRY_LEN)
+            .map(|entry| PaletteEntry {
+                entry_id: entry[0],
+                luminance: entry[1],
+                color_difference_red: entry[2],
+                color_difference_blue: entry[3],
+                transparency: entry[4],
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgs::{pgs_image::RleEncodedImage, DecodeMode, Diagnostic, RleError};
+    use assert_matches2::assert_matches;
+    use image::LumaA;
+    use std::io::BufReader;
+
+    #[test]
+    fn read_defensively() {
+        // Empty palette, with a trailing padding byte.
+        let pds = read(&mut [0x01, 0x00, 0xff].as_slice(), 3).unwrap();
+        assert_eq!(pds.palette_id, 1);
+        assert!(pds.palette.is_empty());
+
+        // Sparse entries, an entry redefined, and trailing padding.
+        let data = [
+            0x00, 0x00, 0x05, 16, 128, 128, 255, 0x07, 235, 128, 128, 255, 0x05, 20, 128, 128, 0,
+            0x00, 0x00,
+        ];
+        let mut palette = read(&mut data.as_slice(), data.len()).unwrap().palette;
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette.get(5).unwrap().luminance, 20);
+        assert_eq!(palette.get(7).unwrap().luminance, 235);
+        assert!(palette.get(0).is_none());
+
+        // Reuse of the palette id with other entries.
+        let update = read(&mut [0x00, 0x01, 0x00, 16, 128, 128, 255].as_slice(), 7).unwrap();
+        palette.update(update.palette.entries().iter().cloned());
+        assert_eq!(palette.len(), 3);
+        assert_eq!(palette.get(0).unwrap().luminance, 16);
+
+        assert_matches!(read(&mut [0x00].as_slice(), 1), Err(Error::TooShort(1)));
+    }
+
+    #[test]
+    fn read_segment_larger_than_buffer() {
+        let data = [
+            0x02, 0x00, 0x05, 16, 128, 128, 255, 0x07, 235, 128, 128, 255, 0x42,
+        ];
+        let mut reader = BufReader::with_capacity(4, data.as_slice());
+        let pds = read(&mut reader, data.len() - 1).unwrap();
+        assert_eq!(pds.palette_id, 2);
+        assert_eq!(pds.palette.len(), 2);
+        assert_eq!(pds.palette.get(7).unwrap().luminance, 235);
+        // The next segment is not consumed.
+        assert_eq!(reader.fill_buf().unwrap(), [0x42]);
+    }
+
+    #[test]
+    fn image_raw_data_and_palette() {
+        let data = [0x00, 0x00, 0x05, 16, 90, 240, 255, 0x07, 235, 128, 128, 0];
+        let palette = read(&mut data.as_slice(), data.len()).unwrap().palette;
+        let rle = vec![5, 7, 0, 0];
+        let image = RleEncodedImage::new(2, 1, palette, rle.clone());
+        assert_eq!(image.raw(), rle);
+
+        let entries = image.palette().entries();
+        assert_eq!(
+            entries.iter().map(PaletteEntry::id).collect::<Vec<_>>(),
+            [5, 7]
+        );
+        assert_eq!(entries[0].color_difference_red(), 90);
+        assert_eq!(entries[0].color_difference_blue(), 240);
+        assert_eq!((entries[1].luminance, entries[1].transparency), (235, 0));
+    }
+
+    const fn entry(entry_id: u8) -> PaletteEntry {
+        PaletteEntry {
+            entry_id,
+            luminance: 200,
+            color_difference_red: 128,
+            color_difference_blue: 128,
+            transparency: 255,
+        }
+    }
+
+    // One pixel of color 5, one of the undefined color 7, and the end of line.
+    fn image_with_undefined_color() -> RleEncodedImage {
+        let palette = Palette::new(vec![entry(5), entry(200)]);
+        assert!(palette.contains(200) && !palette.contains(7));
+        assert_eq!(palette.get(200), Some(&entry(200)));
+        RleEncodedImage::new(2, 1, palette, vec![5, 7, 0, 0])
+    }
+
+    #[test]
+    fn undefined_color_strict() {
+        assert_matches!(
+            image_with_undefined_color().decode(DecodeMode::Strict),
+            Err(RleError::UndefinedColor { color_id: 7 })
+        );
+    }
+
+    #[test]
+    fn undefined_color_lenient() {
+        let decoded = image_with_undefined_color()
+            .decode(DecodeMode::Lenient)
+            .unwrap();
+        assert_eq!(
+            decoded.pixels(),
+            [LumaA([200, 255]), LumaA([u8::MAX, u8::MIN])]
+        );
+        assert_eq!(
+            decoded.diagnostics(),
+            [Diagnostic::UndefinedColor { color_id: 7 }]
+        );
+    }
 }
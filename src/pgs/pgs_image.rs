@@ -1,16 +1,30 @@
-use super::pds::{Palette, PaletteEntry};
-use crate::image::{ImageSize, ToImage, ToOcrImage, ToOcrImageOpt};
-use image::{ImageBuffer, Luma, LumaA, Pixel, Primitive};
-use std::io::{ErrorKind, Read as _};
+use super::wds::position_area;
+use super::{
+    pds::{Palette, PaletteEntry},
+    rle::{self, RleError, Run, RunReader},
+    DecodeMode, Diagnostic,
+};
+use crate::{
+    content::Area,
+    image::{
+        ocr, ImageSize, IndexedImage, RawImage, RawPixel, ToImage, ToImageInto, ToOcrImage,
+        ToOcrImageOpt, ToRawImage,
+    },
+    util::BytesFormatter,
+};
+use image::{ImageBuffer, Luma, LumaA, Pixel, Primitive, Rgba};
+use std::{fmt, iter};
 
 /// Define a type of `fn` who covert pixel from `PaletteEntry` to a target color type.
 type PixelConversion<TargetColor> = fn(&PaletteEntry) -> TargetColor;
 
 /// Store Image data directly from `PGS`.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct RleEncodedImage {
     width: u16,
     height: u16,
+    /// Position of the top left pixel of the image on the screen.
+    position: (u16, u16),
     palette: Palette,
     raw: Vec<u8>,
 }
@@ -24,11 +38,65 @@ impl RleEncodedImage {
         Self {
             width,
             height,
+            position: (0, 0),
             palette,
             raw,
         }
     }
 
+    /// Set the position of the top left pixel of the image on the screen, `(0, 0)` by default.
+    #[must_use]
+    pub const fn with_position(mut self, x: u16, y: u16) -> Self {
+        self.position = (x, y);
+        self
+    }
+
+    /// Position (`x`, `y`) of the top left pixel of the image on the screen, as placed by
+    /// the object of its composition with its id.
+    #[must_use]
+    pub const fn position(&self) -> (u16, u16) {
+        self.position
+    }
+
+    /// Area of the image on the screen, like the [`ImageArea`] of the `VobSub` images.
+    ///
+    /// This is an inherent method, `RleEncodedImage` doesn't implement [`ImageArea`] :
+    /// its area is optional, and the trait would conflict with the [`ImageSize`]
+    /// implementation of the image.
+    ///
+    /// Return `None` if the image is less than 2 pixels wide or high, or if it goes
+    /// beyond the largest coordinates.
+    ///
+    /// [`ImageArea`]: crate::image::ImageArea
+    /// [`ImageSize`]: crate::image::ImageSize
+    #[must_use]
+    pub fn area(&self) -> Option<Area> {
+        let (x, y) = self.position;
+        position_area(x, y, self.width, self.height)
+    }
+
+    /// Move the image to `position` on the screen.
+    pub(crate) fn set_position(&mut self, position: (u16, u16)) {
+        self.position = position;
+    }
+
+    /// Raw `Rle` encoded data of the image, as read in the `Object Definition Segment`.
+    #[must_use]
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Palette used by the image.
+    #[must_use]
+    pub const fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Replace the palette used by the image.
+    pub(crate) fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
     /// Iterate on image pixels converted with a specified function.
     pub fn pixels<D: Primitive>(
         &self,
@@ -36,13 +104,202 @@ impl RleEncodedImage {
     ) -> RlePixelIterator<'_, LumaA<D>> {
         RlePixelIterator {
             rle_image: self,
-            raw_data: &self.raw,
+            runs: RunReader::new(&self.raw, self.width, DecodeMode::Lenient),
             current_color: LumaA([D::DEFAULT_MIN_VALUE, D::DEFAULT_MAX_VALUE]),
             default_color: LumaA([D::DEFAULT_MAX_VALUE, D::DEFAULT_MIN_VALUE]), // Default: white + transparent
             nb_remaining_pixels: 0,
+            nb_remaining_total: self.nb_pixels(),
             convert,
         }
     }
+
+    /// Decode the pixels of the image line by line, applying the `mode` policy on malformed data.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the data is truncated, and in [`DecodeMode::Strict`]
+    /// if a run is malformed, if a color is not defined by the palette, or if the lines
+    /// or the image doesn't have the expected size.
+    /// In [`DecodeMode::Lenient`], missing pixels and undefined colors are transparent white,
+    /// and the fixes are reported in [`DecodedImage::diagnostics`].
+    pub fn decode(&self, mode: DecodeMode) -> Result<DecodedImage, RleError> {
+        let expected = self.nb_pixels();
+        let mut pixels = Vec::with_capacity(expected);
+        let mut runs = RunReader::new(&self.raw, self.width, mode);
+        let mut undefined_colors = Vec::new();
+        while let Some(Run { color, count }) = runs.next_run()? {
+            let color = match color.map(|color_id| (color_id, self.palette.get(color_id))) {
+                None => DEFAULT_COLOR,
+                Some((_, Some(entry))) => pe_to_luma_a(entry),
+                Some((color_id, None)) => {
+                    if mode == DecodeMode::Strict {
+                        return Err(RleError::UndefinedColor { color_id });
+                    }
+                    if !undefined_colors.contains(&color_id) {
+                        undefined_colors.push(color_id);
+                    }
+                    DEFAULT_COLOR
+                }
+            };
+            pixels.extend(iter::repeat(color).take(usize::from(count)));
+        }
+
+        let mut diagnostics = runs.diagnostics().to_vec();
+        diagnostics.extend(
+            undefined_colors
+                .into_iter()
+                .map(|color_id| Diagnostic::UndefinedColor { color_id }),
+        );
+        if pixels.len() != expected {
+            let decoded = pixels.len();
+            if mode == DecodeMode::Strict {
+                return Err(RleError::PixelCountMismatch { decoded, expected });
+            }
+            diagnostics.push(Diagnostic::PixelCountMismatch { decoded, expected });
+            pixels.resize(expected, DEFAULT_COLOR);
+        }
+        Ok(DecodedImage {
+            width: self.width,
+            height: self.height,
+            pixels,
+            diagnostics,
+        })
+    }
+
+    /// Decode the image to an [`IndexedImage`], keeping the color ids of the pixels.
+    ///
+    /// The decoding is lenient (see [`DecodeMode::Lenient`]). Colors not defined by
+    /// the palette are transparent white, and the pixels missing in the data use the
+    /// color `255`, conventionally transparent.
+    #[must_use]
+    pub fn to_indexed(&self) -> IndexedImage<256> {
+        self.to_indexed_into(Vec::new())
+    }
+
+    /// Decode the image like [`Self::to_indexed`], with the pixels in `buffer` to reuse
+    /// its allocation, for example taken from a previous image with
+    /// [`IndexedImage::into_indexes`].
+    #[must_use]
+    pub fn to_indexed_into(&self, buffer: Vec<u8>) -> IndexedImage<256> {
+        let mut palette = [DEFAULT_RGBA; 256];
+        for (id, color) in (0..=u8::MAX).zip(&mut palette) {
+            if let Some(entry) = self.palette.get(id) {
+                *color = entry.to_rgba();
+            }
+        }
+
+        let mut indexes = buffer;
+        self.decompress_into(&mut indexes);
+        IndexedImage::new(self.width(), self.height(), palette, indexes)
+    }
+
+    /// Decode the color ids of the pixels in `indexes`, replacing its content, like
+    /// the indexes of [`Self::to_indexed`]. Reusing the same buffer for several images
+    /// avoids an allocation by image.
+    pub fn decompress_into(&self, indexes: &mut Vec<u8>) {
+        indexes.clear();
+        let size = (self.width, self.height);
+        if rle::decode_into(&self.raw, size, DecodeMode::Lenient, u8::MAX, indexes).is_err() {
+            // Truncated data : the pixels decoded before are kept.
+            indexes.resize(self.nb_pixels(), u8::MAX);
+        }
+    }
+
+    /// Crop the transparent borders of the image, displayed on `area`
+    /// (the position of the object in the composition).
+    ///
+    /// Return the cropped image, decoded like with [`Self::to_indexed`], and its area,
+    /// or `None` if all the pixels are transparent.
+    #[must_use]
+    pub fn crop_to_content(&self, area: &Area) -> Option<(Area, IndexedImage<256>)> {
+        self.to_indexed().crop_to_content(area)
+    }
+
+    fn nb_pixels(&self) -> usize {
+        usize::from(self.width) * usize::from(self.height)
+    }
+
+    /// Get the color of `color_id` from the palette, or `default_color` if not present in it.
+    fn color<C>(&self, color_id: u8, convert: PixelConversion<C>, default_color: C) -> C {
+        self.palette.get(color_id).map_or(default_color, convert)
+    }
+}
+
+impl fmt::Debug for RleEncodedImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RleEncodedImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("position", &self.position)
+            .field("palette_len", &self.palette.len())
+            .field("raw", &BytesFormatter(&self.raw))
+            .finish()
+    }
+}
+
+/// Default color of pixels not defined by the image data : white + transparent.
+const DEFAULT_COLOR: LumaA<u8> = LumaA([u8::MAX, u8::MIN]);
+/// [`DEFAULT_COLOR`] in `RGBA`.
+const DEFAULT_RGBA: Rgba<u8> = Rgba([u8::MAX, u8::MAX, u8::MAX, u8::MIN]);
+
+/// Pixels of a [`RleEncodedImage`] decoded with [`RleEncodedImage::decode`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct DecodedImage {
+    width: u16,
+    height: u16,
+    pixels: Vec<LumaA<u8>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DecodedImage {
+    /// Pixels of the image, in row-major order.
+    #[must_use]
+    pub fn pixels(&self) -> &[LumaA<u8>] {
+        &self.pixels
+    }
+
+    /// Pixels of the line `y`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `y` is not lower than the height of the image.
+    #[must_use]
+    pub fn line(&self, y: u16) -> &[LumaA<u8>] {
+        assert!(y < self.height, "line {y} is out of the image");
+        let width = usize::from(self.width);
+        let start = usize::from(y) * width;
+        &self.pixels[start..start + width]
+    }
+
+    /// Iterate on the lines of the image.
+    pub fn lines(&self) -> impl Iterator<Item = &[LumaA<u8>]> {
+        (0..self.height).map(|y| self.line(y))
+    }
+
+    /// Issues found and fixed during a lenient decoding.
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
+
+impl fmt::Debug for DecodedImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DecodedImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("diagnostics", &self.diagnostics)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ImageSize for DecodedImage {
+    fn width(&self) -> u32 {
+        u32::from(self.width)
+    }
+    fn height(&self) -> u32 {
+        u32::from(self.height)
+    }
 }
 
 impl ImageSize for RleEncodedImage {
@@ -70,7 +327,7 @@ impl<'a> IntoIterator for &'a RleEncodedImage {
     fn into_iter(self) -> Self::IntoIter {
         RlePixelIterator {
             rle_image: self,
-            raw_data: &self.raw,
+            runs: RunReader::new(&self.raw, self.width, DecodeMode::Lenient),
             current_color: LumaA([
                 <u8 as Primitive>::DEFAULT_MIN_VALUE,
                 <u8 as Primitive>::DEFAULT_MAX_VALUE,
@@ -80,6 +337,7 @@ impl<'a> IntoIterator for &'a RleEncodedImage {
                 <u8 as Primitive>::DEFAULT_MIN_VALUE,
             ]), // Default: white + transparent
             nb_remaining_pixels: 0,
+            nb_remaining_total: self.nb_pixels(),
             convert: pe_to_luma_a,
         }
     }
@@ -112,6 +370,23 @@ where
     pub const fn new(rle_image: &'a RleEncodedImage, conv_fn: C) -> Self {
         Self { rle_image, conv_fn }
     }
+
+    /// Bytes of the converted pixels, line after line.
+    fn raw_pixels(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.write_raw_pixels(&mut buf);
+        buf
+    }
+
+    /// Write the bytes of the converted pixels in `buf`, replacing its content.
+    fn write_raw_pixels(&self, buf: &mut Vec<u8>) {
+        buf.clear();
+        buf.reserve(self.rle_image.nb_pixels() * P::CHANNEL_COUNT as usize);
+        self.rle_image
+            .into_iter()
+            .map(|p| (self.conv_fn)(p))
+            .for_each(|p| buf.extend_from_slice(p.channels()));
+    }
 }
 
 impl<P, C> ToImage for RleToImage<'_, P, C>
@@ -128,16 +403,35 @@ where
     {
         let width = self.rle_image.width();
         let height = self.rle_image.height();
-        let pixel_iter = self.rle_image.into_iter();
+        ImageBuffer::<P, Vec<u8>>::from_vec(width, height, self.raw_pixels())
+            .expect("Failed to create image buffer")
+    }
+}
 
-        let buf_size = (width * height) as usize * P::CHANNEL_COUNT as usize;
-        let mut buf = Vec::with_capacity(buf_size);
-        pixel_iter
-            .map(|p| (self.conv_fn)(p))
-            .for_each(|p| buf.extend_from_slice(p.channels()));
+impl<P, C> ToImageInto for RleToImage<'_, P, C>
+where
+    P: Pixel<Subpixel = u8>,
+    C: Fn(LumaA<u8>) -> P,
+{
+    #[profiling::function]
+    fn to_image_into(&self, mut buffer: Vec<u8>) -> ImageBuffer<P, Vec<u8>> {
+        let width = self.rle_image.width();
+        let height = self.rle_image.height();
+        self.write_raw_pixels(&mut buffer);
+        ImageBuffer::from_vec(width, height, buffer).expect("Failed to create image buffer")
+    }
+}
 
-        ImageBuffer::<P, Vec<u8>>::from_vec(width, height, buf)
-            .expect("Failed to create image buffer")
+impl<P, C> ToRawImage for RleToImage<'_, P, C>
+where
+    P: RawPixel,
+    C: Fn(LumaA<u8>) -> P,
+{
+    #[profiling::function]
+    fn to_raw_image(&self) -> RawImage {
+        let width = self.rle_image.width();
+        let height = self.rle_image.height();
+        RawImage::new(self.raw_pixels(), width, height, P::FORMAT)
     }
 }
 
@@ -150,29 +444,42 @@ where
     fn image(&self, opt: &ToOcrImageOpt) -> image::GrayImage {
         let width = self.rle_image.width();
         let height = self.rle_image.height();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("ocr_image", width, height).entered();
         let border = opt.border;
 
-        let raw_pixels = self.rle_image.into_iter().collect::<Vec<_>>();
+        let pixels = self
+            .rle_image
+            .into_iter()
+            .map(&self.conv_fn)
+            .collect::<Vec<_>>();
+        let pixel_at = |x: u32, y: u32| pixels[(y * width + x) as usize];
+        let border_color = opt
+            .border_fill
+            .color(opt.background_color, width, height, pixel_at);
 
-        ImageBuffer::from_fn(width + border * 2, height + border * 2, |x, y| {
+        let image = ImageBuffer::from_fn(width + border * 2, height + border * 2, |x, y| {
             if x < border || x >= width + border || y < border || y >= height + border {
-                opt.background_color
+                border_color
             } else {
-                let offset = (y - border) * width + (x - border);
-                let pixel = raw_pixels[offset as usize];
-                (self.conv_fn)(pixel)
+                pixel_at(x - border, y - border)
             }
-        })
+        });
+        ocr::post_process(image, *opt)
     }
 }
 
 /// struct to iterate on pixel of an `Rle` image.
+///
+/// The iteration is lenient (see [`DecodeMode::Lenient`]), and if the data doesn't
+/// contain all the pixels of the image, the missing ones are filled with the default color.
 pub struct RlePixelIterator<'a, C> {
     rle_image: &'a RleEncodedImage,
-    raw_data: &'a [u8],
+    runs: RunReader<'a>,
     current_color: C,
     default_color: C,
     nb_remaining_pixels: u16,
+    nb_remaining_total: usize,
     convert: PixelConversion<C>,
 }
 
@@ -185,28 +492,28 @@ where
     type Item = Pix;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.nb_remaining_total == 0 {
+            return None; // End of pixels
+        }
+        self.nb_remaining_total -= 1;
+
         if self.nb_remaining_pixels > 0 {
             self.nb_remaining_pixels -= 1;
             Some(self.current_color)
-        } else if let Some((color_id, nb_pixel)) = self.read_next_pixel() {
-            let color = if let Some(color) = self.rle_image.palette.get(color_id) {
-                (self.convert)(color)
-            } else {
-                // If color_id is not present in palette, return default value
-                self.default_color
-            };
-
-            self.current_color = color;
-            self.nb_remaining_pixels = nb_pixel - 1;
+        } else if let Ok(Some(Run { color, count })) = self.runs.next_run() {
+            self.current_color = color.map_or(self.default_color, |color_id| {
+                self.rle_image
+                    .color(color_id, self.convert, self.default_color)
+            });
+            self.nb_remaining_pixels = count - 1;
             Some(self.current_color)
         } else {
-            None // End of pixels
+            Some(self.default_color)
         }
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let nb_pixels = (self.rle_image.width() * self.rle_image.height()) as usize;
-        (nb_pixels, Some(nb_pixels))
+        (self.nb_remaining_total, Some(self.nb_remaining_total))
     }
 }
 
@@ -216,92 +523,3 @@ where
     Pix: Copy + Pixel<Subpixel = Sub>,
 {
 }
-
-impl<C> RlePixelIterator<'_, C> {
-    /// Read next pixel info(color and number of instance).
-    fn read_next_pixel(&mut self) -> Option<(u8 /*color */, u16 /*nb_pixels*/)> {
-        const MARKER: u8 = 0;
-        const COLOR_0: u8 = 0;
-        loop {
-            let mut color: [u8; 1] = [0; 1];
-            let res = self.raw_data.read_exact(&mut color);
-            if let Err(err) = res {
-                if err.kind() == ErrorKind::UnexpectedEof {
-                    return None;
-                }
-            }
-
-            let next = color[0];
-            if next == MARKER {
-                let mut l2 = [0; 1];
-                self.raw_data.read_exact(&mut l2).unwrap();
-
-                if l2[0] == MARKER {
-                    //break; // End of line
-                } else {
-                    let byte = l2[0];
-                    let nb_pixels = match CountMarker::from(byte) {
-                        CountMarker::Long => {
-                            let mut l3 = [0; 1];
-                            self.raw_data.read_exact(&mut l3).unwrap();
-                            let count_bytes = [byte & 0b0011_1111, l3[0]];
-                            u16::from_be_bytes(count_bytes)
-                        }
-                        CountMarker::Short => {
-                            let count_bits = byte & 0b0011_1111;
-                            u16::from(u8::from_be(count_bits))
-                        }
-                    };
-
-                    let color_marker = ColorMarker::from(byte);
-                    let color = match color_marker {
-                        ColorMarker::Color0 => COLOR_0,
-                        ColorMarker::ColorN => {
-                            let mut color = [0; 1];
-                            self.raw_data.read_exact(&mut color).unwrap();
-                            color[0]
-                        }
-                    };
-
-                    return Some((color, nb_pixels));
-                }
-            } else {
-                return Some((next, 1));
-            }
-        }
-    }
-}
-
-/// Decode the color marker.
-enum ColorMarker {
-    /// color 0 : black
-    Color0,
-    /// color N : color define in code
-    ColorN,
-}
-impl From<u8> for ColorMarker {
-    fn from(value: u8) -> Self {
-        if (value & 0b1000_0000) > 0 {
-            Self::ColorN
-        } else {
-            Self::Color0
-        }
-    }
-}
-
-/// Decode the pixels count marcker.
-enum CountMarker {
-    /// the number of pixels is between 1 and 63
-    Short,
-    /// the number of pixels is between 64 and 16383
-    Long,
-}
-impl From<u8> for CountMarker {
-    fn from(value: u8) -> Self {
-        if (value & 0b0100_0000) > 0 {
-            Self::Long
-        } else {
-            Self::Short
-        }
-    }
-}
@@ -0,0 +1,543 @@
+//! `Run-length encoding` codec of the `PGS` object data.
+//!
+//! Each line of the image is encoded as a sequence of runs of a color, ended by
+//! an end of line marker. The image data can be decoded run by run with a [`RunReader`],
+//! or to a buffer of color ids with [`decode_into`], and encoded from a buffer of color
+//! ids with [`encode`].
+use super::{DecodeMode, Diagnostic};
+use std::{iter, mem};
+use thiserror::Error;
+
+/// Error of `PGS` `Rle` data decoding.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RleError {
+    /// The data end in the middle of a run.
+    #[error("object data is truncated in the middle of a run")]
+    Truncated,
+
+    /// A run with a count of 0 pixels.
+    #[error("run of 0 pixels on line {line}")]
+    ZeroLengthRun {
+        /// Line of the run.
+        line: u32,
+    },
+
+    /// A run overshoot the end of the line.
+    #[error("run of {count} pixels at x={x} overshoot the line {line} of width {width}")]
+    RunOverflow {
+        /// Line of the run.
+        line: u32,
+        /// Position of the run start in the line.
+        x: u16,
+        /// Number of pixels of the run.
+        count: u16,
+        /// Width of the image.
+        width: u16,
+    },
+
+    /// A line doesn't have the width of the image.
+    #[error("line {line} contain {decoded} pixels instead of image width {width}")]
+    LineWidthMismatch {
+        /// Index of the line.
+        line: u32,
+        /// Number of pixels encoded in the line.
+        decoded: usize,
+        /// Width of the image.
+        width: u16,
+    },
+
+    /// The data doesn't contain the number of pixels of the image.
+    #[error("object data contain {decoded} pixels instead of {expected}")]
+    PixelCountMismatch {
+        /// Number of pixels decoded.
+        decoded: usize,
+        /// Number of pixels of the image.
+        expected: usize,
+    },
+
+    /// A color id used by the image is not defined by the palette.
+    #[error("color {color_id} is not defined by the palette")]
+    UndefinedColor {
+        /// The color id.
+        color_id: u8,
+    },
+}
+
+/// Marker starting an encoded run or an end of line.
+const MARKER: u8 = 0;
+/// Color of a run encoded without color.
+const COLOR_0: u8 = 0;
+
+/// An encoded element of the object data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RleItem {
+    /// `count` pixels of the `color`.
+    Run { color: u8, count: u16 },
+    /// End of the current line.
+    EndOfLine,
+}
+
+/// Read the next encoded element from `data`, or `None` at the end of the data.
+pub(crate) fn read_item(data: &mut &[u8]) -> Result<Option<RleItem>, RleError> {
+    let Some((&first, rest)) = data.split_first() else {
+        return Ok(None);
+    };
+    *data = rest;
+    if first != MARKER {
+        return Ok(Some(RleItem::Run {
+            color: first,
+            count: 1,
+        }));
+    }
+
+    let byte = next_byte(data)?;
+    if byte == MARKER {
+        return Ok(Some(RleItem::EndOfLine));
+    }
+    let count = match CountMarker::from(byte) {
+        CountMarker::Long => u16::from_be_bytes([byte & 0b0011_1111, next_byte(data)?]),
+        CountMarker::Short => u16::from(byte & 0b0011_1111),
+    };
+    let color = match ColorMarker::from(byte) {
+        ColorMarker::Color0 => COLOR_0,
+        ColorMarker::ColorN => next_byte(data)?,
+    };
+    Ok(Some(RleItem::Run { color, count }))
+}
+
+fn next_byte(data: &mut &[u8]) -> Result<u8, RleError> {
+    let (&byte, rest) = data.split_first().ok_or(RleError::Truncated)?;
+    *data = rest;
+    Ok(byte)
+}
+
+/// A run of pixels, as returned by [`RunReader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Run {
+    /// Color of the pixels, or `None` for the pixels added to fill a short line.
+    pub color: Option<u8>,
+    /// Number of pixels.
+    pub count: u16,
+}
+
+/// Read the runs of an image line by line, applying the [`DecodeMode`] policy on malformed data.
+///
+/// Lines are delimited by the end of line markers, and their length is checked against the width.
+/// In [`DecodeMode::Strict`] runs of 0 pixels, runs overshooting the line and lines with
+/// a length different of the width are errors.
+/// In [`DecodeMode::Lenient`] runs of 0 pixels are ignored, long lines are truncated
+/// and short lines are filled, with a [`Diagnostic`] for each fix.
+pub(crate) struct RunReader<'a> {
+    data: &'a [u8],
+    width: u16,
+    mode: DecodeMode,
+    line: u32,
+    x: u16,
+    line_len: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> RunReader<'a> {
+    pub const fn new(data: &'a [u8], width: u16, mode: DecodeMode) -> Self {
+        Self {
+            data,
+            width,
+            mode,
+            line: 0,
+            x: 0,
+            line_len: 0,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Return the next run, or `None` at the end of the data.
+    pub fn next_run(&mut self) -> Result<Option<Run>, RleError> {
+        loop {
+            let Some(item) = read_item(&mut self.data)? else {
+                // The last line may not have an end of line marker.
+                return if self.line_len > 0 {
+                    self.end_line()
+                } else {
+                    Ok(None)
+                };
+            };
+            let (color, count) = match item {
+                RleItem::EndOfLine => match self.end_line()? {
+                    Some(run) => return Ok(Some(run)),
+                    None => continue,
+                },
+                RleItem::Run { color, count } => (color, count),
+            };
+
+            if count == 0 {
+                if self.mode == DecodeMode::Strict {
+                    return Err(RleError::ZeroLengthRun { line: self.line });
+                }
+                self.diagnostics
+                    .push(Diagnostic::ZeroLengthRun { line: self.line });
+                continue;
+            }
+            let remaining = self.width - self.x;
+            if count > remaining && self.mode == DecodeMode::Strict {
+                return Err(RleError::RunOverflow {
+                    line: self.line,
+                    x: self.x,
+                    count,
+                    width: self.width,
+                });
+            }
+            self.line_len += usize::from(count);
+            let count = count.min(remaining);
+            if count > 0 {
+                self.x += count;
+                return Ok(Some(Run {
+                    color: Some(color),
+                    count,
+                }));
+            }
+        }
+    }
+
+    /// Diagnostics of the fixes applied since the start of the reading.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Handle the end of the current line, returning the run filling the line if it is short.
+    fn end_line(&mut self) -> Result<Option<Run>, RleError> {
+        let line = self.line;
+        let line_len = mem::take(&mut self.line_len);
+        let x = mem::take(&mut self.x);
+        self.line += 1;
+
+        if line_len != usize::from(self.width) {
+            let (width, decoded) = (self.width, line_len);
+            if self.mode == DecodeMode::Strict {
+                return Err(RleError::LineWidthMismatch {
+                    line,
+                    decoded,
+                    width,
+                });
+            }
+            self.diagnostics.push(Diagnostic::LineWidthMismatch {
+                line,
+                decoded,
+                width,
+            });
+        }
+        let missing = self.width - x;
+        Ok((missing > 0).then_some(Run {
+            color: None,
+            count: missing,
+        }))
+    }
+}
+
+/// Decode the color ids of the pixels of an image of `width`x`height` pixels from `data`,
+/// appending them to `pixels` in row-major order, and return the fixes applied in
+/// [`DecodeMode::Lenient`].
+///
+/// The `mode` policy is applied on malformed data like by [`RunReader`], and on data
+/// not containing the number of pixels of the image. The pixels missing in the data
+/// have the color `fill`. On error, the pixels decoded before it are kept in `pixels`.
+pub(crate) fn decode_into(
+    data: &[u8],
+    (width, height): (u16, u16),
+    mode: DecodeMode,
+    fill: u8,
+    pixels: &mut Vec<u8>,
+) -> Result<Vec<Diagnostic>, RleError> {
+    let expected = usize::from(width) * usize::from(height);
+    let start = pixels.len();
+    pixels.reserve(expected);
+    let mut runs = RunReader::new(data, width, mode);
+    while let Some(Run { color, count }) = runs.next_run()? {
+        pixels.extend(iter::repeat(color.unwrap_or(fill)).take(usize::from(count)));
+    }
+
+    let mut diagnostics = runs.diagnostics().to_vec();
+    let decoded = pixels.len() - start;
+    if decoded != expected {
+        if mode == DecodeMode::Strict {
+            return Err(RleError::PixelCountMismatch { decoded, expected });
+        }
+        diagnostics.push(Diagnostic::PixelCountMismatch { decoded, expected });
+        pixels.resize(start + expected, fill);
+    }
+    Ok(diagnostics)
+}
+
+/// Maximum count of a run with a short count marker.
+const MAX_SHORT_COUNT: u16 = 0b0011_1111;
+/// Maximum count of a run, above it a run is encoded in several parts.
+const MAX_LONG_COUNT: u16 = 0x3fff;
+
+/// Encode the color ids of the pixels of an image of `width` pixels, in row-major order.
+pub(crate) fn encode(pixels: &[u8], width: u16) -> Vec<u8> {
+    let mut data = Vec::new();
+    for line in pixels.chunks(usize::from(width).max(1)) {
+        for run in line.chunk_by(|left, right| left == right) {
+            // A run is at most a line of `width` pixels.
+            let mut count = u16::try_from(run.len()).unwrap_or(u16::MAX);
+            while count > 0 {
+                let part = count.min(MAX_LONG_COUNT);
+                write_run(&mut data, run[0], part);
+                count -= part;
+            }
+        }
+        data.extend([MARKER, MARKER]);
+    }
+    data
+}
+
+/// Write a run of `count` pixels of `color`, using the shortest encoding.
+fn write_run(data: &mut Vec<u8>, color: u8, count: u16) {
+    let [high, low] = count.to_be_bytes();
+    match (color, count) {
+        (COLOR_0, ..=MAX_SHORT_COUNT) => data.extend([MARKER, low]),
+        (COLOR_0, _) => data.extend([MARKER, 0b0100_0000 | high, low]),
+        (_, 1..=2) => data.extend(iter::repeat(color).take(usize::from(count))),
+        (_, ..=MAX_SHORT_COUNT) => data.extend([MARKER, 0b1000_0000 | low, color]),
+        (_, _) => data.extend([MARKER, 0b1100_0000 | high, low, color]),
+    }
+}
+
+/// Decode the color marker.
+enum ColorMarker {
+    /// color 0 : black
+    Color0,
+    /// color N : color define in code
+    ColorN,
+}
+impl From<u8> for ColorMarker {
+    fn from(value: u8) -> Self {
+        if (value & 0b1000_0000) > 0 {
+            Self::ColorN
+        } else {
+            Self::Color0
+        }
+    }
+}
+
+/// Decode the pixels count marcker.
+enum CountMarker {
+    /// the number of pixels is between 1 and 63
+    Short,
+    /// the number of pixels is between 64 and 16383
+    Long,
+}
+impl From<u8> for CountMarker {
+    fn from(value: u8) -> Self {
+        if (value & 0b0100_0000) > 0 {
+            Self::Long
+        } else {
+            Self::Short
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches2::assert_matches;
+
+    fn runs(data: &[u8], width: u16, mode: DecodeMode) -> Result<Vec<(u8, u16)>, RleError> {
+        let mut reader = RunReader::new(data, width, mode);
+        let mut runs = Vec::new();
+        while let Some(Run { color, count }) = reader.next_run()? {
+            runs.push((color.unwrap_or(u8::MAX), count));
+        }
+        Ok(runs)
+    }
+
+    #[test]
+    fn read_items() {
+        let mut data = [
+            0x05, // one pixel of color 5
+            0x00, 0x03, // 3 pixels of color 0
+            0x00, 0x83, 0x07, // 3 pixels of color 7
+            0x00, 0x40, 0x50, // 80 pixels of color 0
+            0x00, 0xc0, 0x50, 0x09, // 80 pixels of color 9
+            0x00, 0x00, // end of line
+        ]
+        .as_slice();
+        let mut items = Vec::new();
+        while let Some(item) = read_item(&mut data).unwrap() {
+            items.push(item);
+        }
+        assert_eq!(
+            items,
+            [
+                RleItem::Run { color: 5, count: 1 },
+                RleItem::Run { color: 0, count: 3 },
+                RleItem::Run { color: 7, count: 3 },
+                RleItem::Run {
+                    color: 0,
+                    count: 80
+                },
+                RleItem::Run {
+                    color: 9,
+                    count: 80
+                },
+                RleItem::EndOfLine,
+            ]
+        );
+        assert_matches!(
+            read_item(&mut [0x00, 0xc0, 0x50].as_slice()),
+            Err(RleError::Truncated)
+        );
+    }
+
+    #[test]
+    fn encode_runs() {
+        let mut pixels = vec![0; 100];
+        pixels.extend([5, 7, 7]);
+        pixels.extend([7; 3]);
+        pixels.extend([9; 94]);
+        pixels.extend([0; 200]);
+        let data = encode(&pixels, 200);
+        assert_eq!(
+            data,
+            [
+                0x00, 0x40, 0x64, // 100 pixels of color 0
+                0x05, // one pixel of color 5
+                0x00, 0x85, 0x07, // 5 pixels of color 7
+                0x00, 0xc0, 0x5e, 0x09, // 94 pixels of color 9
+                0x00, 0x00, // end of line
+                0x00, 0x40, 0xc8, // 200 pixels of color 0
+                0x00, 0x00, // end of line
+            ]
+        );
+        let decoded = runs(&data, 200, DecodeMode::Strict).unwrap();
+        assert_eq!(decoded, [(0, 100), (5, 1), (7, 5), (9, 94), (0, 200)]);
+    }
+
+    #[test]
+    fn round_trip_all_markers() {
+        // Counts at the limits of the single pixels, short and long count markers,
+        // and split runs, for the color 0 and another color.
+        let counts = [1, 2, 3, 63, 64, 255, 16_383, 16_384, 40_000];
+        for color in [0, 1, 255] {
+            for count in counts {
+                let pixels = vec![color; usize::from(count)];
+                let data = encode(&pixels, count);
+
+                let mut items = Vec::new();
+                let mut reader = data.as_slice();
+                while let Some(item) = read_item(&mut reader).unwrap() {
+                    items.push(item);
+                }
+                assert_eq!(items.last(), Some(&RleItem::EndOfLine));
+                let runs = &items[..items.len() - 1];
+                assert!(runs
+                    .iter()
+                    .all(|item| matches!(item, RleItem::Run { color: run_color, .. } if *run_color == color)));
+                let total = runs.iter().fold(0, |total, item| match item {
+                    RleItem::Run { count, .. } => total + usize::from(*count),
+                    RleItem::EndOfLine => total,
+                });
+                assert_eq!(total, usize::from(count));
+
+                let mut decoded = Vec::new();
+                let diagnostics =
+                    decode_into(&data, (count, 1), DecodeMode::Strict, 0, &mut decoded).unwrap();
+                assert!(diagnostics.is_empty());
+                assert_eq!(decoded, pixels, "color {color}, count {count}");
+            }
+        }
+    }
+
+    #[test]
+    fn decode_into_buffer() {
+        let pixels = [0, 0, 3, 3, 3, 1, 2, 2, 255, 0, 0, 7];
+        let data = encode(&pixels, 4);
+        let mut decoded = vec![9];
+        decode_into(&data, (4, 3), DecodeMode::Strict, 0, &mut decoded).unwrap();
+        assert_eq!(decoded[1..], pixels);
+
+        // A missing line.
+        let truncated = &data[..data.len() - 6];
+        assert_matches!(
+            decode_into(truncated, (4, 3), DecodeMode::Strict, 0, &mut Vec::new()),
+            Err(RleError::PixelCountMismatch {
+                decoded: 8,
+                expected: 12
+            })
+        );
+        let mut decoded = Vec::new();
+        let diagnostics =
+            decode_into(truncated, (4, 3), DecodeMode::Lenient, 5, &mut decoded).unwrap();
+        assert_eq!(decoded[8..], [5; 4]);
+        assert_eq!(
+            diagnostics,
+            [Diagnostic::PixelCountMismatch {
+                decoded: 8,
+                expected: 12
+            }]
+        );
+    }
+
+    #[test]
+    fn zero_length_run_policy() {
+        // Long count marker with a count of 0, as written by some broken encoders.
+        let data = [0x00, 0xc0, 0x00, 0x01, 0x00, 0x84, 0x01, 0x00, 0x00];
+        assert_matches!(
+            runs(&data, 4, DecodeMode::Strict),
+            Err(RleError::ZeroLengthRun { line: 0 })
+        );
+        assert_eq!(runs(&data, 4, DecodeMode::Lenient).unwrap(), [(1, 4)]);
+    }
+
+    #[test]
+    fn run_overflow_policy() {
+        // Line of width 4 encoded with 6 pixels.
+        let data = [0x00, 0x86, 0x01, 0x00, 0x00, 0x00, 0x84, 0x02, 0x00, 0x00];
+        assert_matches!(
+            runs(&data, 4, DecodeMode::Strict),
+            Err(RleError::RunOverflow {
+                line: 0,
+                x: 0,
+                count: 6,
+                width: 4
+            })
+        );
+        assert_eq!(
+            runs(&data, 4, DecodeMode::Lenient).unwrap(),
+            [(1, 4), (2, 4)]
+        );
+    }
+
+    #[test]
+    fn line_width_policy() {
+        // Short first line, followed by a line without end of line marker.
+        let data = [0x00, 0x83, 0x01, 0x00, 0x00, 0x00, 0x84, 0x02];
+        assert_matches!(
+            runs(&data, 4, DecodeMode::Strict),
+            Err(RleError::LineWidthMismatch {
+                line: 0,
+                decoded: 3,
+                width: 4
+            })
+        );
+
+        let mut reader = RunReader::new(&data, 4, DecodeMode::Lenient);
+        let mut lenient_runs = Vec::new();
+        while let Some(run) = reader.next_run().unwrap() {
+            lenient_runs.push(run);
+        }
+        let run = |color, count| Run { color, count };
+        assert_eq!(
+            lenient_runs,
+            [run(Some(1), 3), run(None, 1), run(Some(2), 4)]
+        );
+        assert_eq!(
+            reader.diagnostics(),
+            [Diagnostic::LineWidthMismatch {
+                line: 0,
+                decoded: 3,
+                width: 4
+            }]
+        );
+    }
+}
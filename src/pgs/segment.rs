@@ -1,4 +1,4 @@
-use super::{PgsError, ReadExt as _};
+use super::{DecodeMode, Diagnostic, PgsError, ReadExt as _};
 use std::{
     fmt,
     io::{BufRead, ErrorKind, Seek},
@@ -87,10 +87,47 @@ const HEADER_LEN: usize = 2 + 4 + 4 + 1 + 2;
 
 /// Read the segment header
 pub fn read_header<R: BufRead>(reader: &mut R) -> Result<Option<SegmentHeader>, PgsError> {
+    read_header_buffer(reader)?.map_or(Ok(None), parse_segment_header)
+}
+
+/// Read the header of the next segment, applying the `mode` policy on unknown segment types.
+///
+/// In [`DecodeMode::Lenient`], segments of unknown type are skipped using their declared
+/// size, and reported in `diagnostics`.
+pub fn read_header_with_mode<R: BufRead + Seek>(
+    reader: &mut R,
+    mode: DecodeMode,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Option<SegmentHeader>, PgsError> {
+    loop {
+        let Some(buffer) = read_header_buffer(reader)? else {
+            return Ok(None);
+        };
+        match parse_segment_header(buffer) {
+            Err(PgsError::SegmentInvalidTypeCode { value }) if mode == DecodeMode::Lenient => {
+                let size = u16::from_be_bytes([buffer[11], buffer[12]]);
+                reader.skip_data(usize::from(size)).map_err(|source| {
+                    PgsError::UnknownSegmentSkip {
+                        source,
+                        type_code: value,
+                    }
+                })?;
+                diagnostics.push(Diagnostic::UnknownSegment {
+                    type_code: value,
+                    size,
+                });
+            }
+            header => return header,
+        }
+    }
+}
+
+/// Read the bytes of a segment header, or `None` at the end of the stream.
+fn read_header_buffer<R: BufRead>(reader: &mut R) -> Result<Option<[u8; HEADER_LEN]>, PgsError> {
     let mut buffer = [0u8; HEADER_LEN];
 
     match reader.read_exact(&mut buffer) {
-        Ok(()) => parse_segment_header(buffer),
+        Ok(()) => Ok(Some(buffer)),
         Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
             // Buffer is empty, just return to end parsing
             Ok(None)
@@ -117,6 +154,32 @@ fn parse_segment_header(buffer: [u8; HEADER_LEN]) -> Result<Option<SegmentHeader
     }))
 }
 
+/// Buffer reused to read the content of the segments, to avoid an allocation by segment
+/// when the content is only inspected.
+#[derive(Debug, Default)]
+pub(crate) struct SegmentBuffer {
+    data: Vec<u8>,
+}
+
+impl SegmentBuffer {
+    /// Read the content of the segment of `header`, replacing the previous content.
+    pub fn read<R: BufRead + Seek>(
+        &mut self,
+        reader: &mut R,
+        header: &SegmentHeader,
+    ) -> Result<&[u8], PgsError> {
+        self.data.clear();
+        self.data.resize(usize::from(header.size()), 0);
+        reader
+            .read_buffer(&mut self.data)
+            .map_err(|source| PgsError::SegmentRead {
+                source,
+                type_code: header.type_code(),
+            })?;
+        Ok(&self.data)
+    }
+}
+
 /// skip segment
 pub fn skip_segment<R: BufRead + Seek>(
     reader: &mut R,
@@ -1,9 +1,13 @@
+#[cfg(any(feature = "rayon", feature = "parallel"))]
+use super::{DecodeMode, DecodeTimeImage, DecodedImage};
 use super::{PgsDecoder, PgsError};
+use crate::content::{CueFormat, CueId};
+#[cfg(any(feature = "rayon", feature = "parallel"))]
+use crate::{time::TimeSpan, util::par_map};
 use std::{
     fs::{self, File},
     io::{BufRead, BufReader, Seek},
     iter::FusedIterator,
-    marker::PhantomData,
     path::Path,
 };
 
@@ -14,19 +18,19 @@ where
     Decoder: PgsDecoder,
 {
     reader: Reader,
-    phantom_data: PhantomData<Decoder>,
+    decoder: Decoder,
 }
 
 impl<Reader, Decoder> SupParser<Reader, Decoder>
 where
     Reader: BufRead + Seek,
-    Decoder: PgsDecoder,
+    Decoder: PgsDecoder + Default,
 {
     /// create a parser of from a buffered reader (impl [`std::io::BufRead`] trait).
-    pub const fn new(reader: Reader) -> Self {
+    pub fn new(reader: Reader) -> Self {
         Self {
             reader,
-            phantom_data: PhantomData,
+            decoder: Decoder::default(),
         }
     }
 
@@ -47,6 +51,62 @@ where
     }
 }
 
+impl<Reader, Decoder> SupParser<Reader, Decoder>
+where
+    Reader: BufRead + Seek,
+    Decoder: PgsDecoder,
+{
+    /// Create a parser from a buffered reader, decoding with a configured `decoder`
+    /// (for example with custom memory limits).
+    pub const fn with_decoder(reader: Reader, decoder: Decoder) -> Self {
+        Self { reader, decoder }
+    }
+
+    /// The decoder, to get its state (the diagnostics for example).
+    pub const fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// The decoder, to update its state (take the diagnostics for example).
+    pub fn decoder_mut(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    /// Iterate on the cues with their [`CueId`].
+    ///
+    /// The offset of a cue is the position of the first display set read to decode it.
+    pub const fn with_ids(self) -> IdentifiedCues<Reader, Decoder> {
+        IdentifiedCues {
+            parser: self,
+            sequence: 0,
+        }
+    }
+}
+
+#[cfg(any(feature = "rayon", feature = "parallel"))]
+impl<Reader> SupParser<Reader, DecodeTimeImage>
+where
+    Reader: BufRead + Seek,
+{
+    /// Read all the remaining cues, and decode their images on the `rayon` thread pool
+    /// (or on scoped threads with only the `parallel` feature), applying the `mode`
+    /// policy on malformed image data.
+    ///
+    /// The display sets are read sequentially, as the cues depend on the previous ones,
+    /// then the images are decompressed and their palette applied in parallel.
+    /// The results are in the order of the stream.
+    pub fn par_decode(
+        &mut self,
+        mode: DecodeMode,
+    ) -> Vec<Result<(TimeSpan, DecodedImage), PgsError>> {
+        let cues = self.collect::<Vec<_>>();
+        par_map(cues, |cue| {
+            let (time, image) = cue?;
+            Ok((time, image.decode(mode)?))
+        })
+    }
+}
+
 impl<Reader, Decoder> Iterator for SupParser<Reader, Decoder>
 where
     Reader: BufRead + Seek,
@@ -55,7 +115,14 @@ where
     type Item = Result<Decoder::Output, PgsError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        Decoder::parse_next(&mut self.reader).transpose()
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("pgs_cue").entered();
+        let cue = self.decoder.parse_next(&mut self.reader).transpose();
+        #[cfg(feature = "tracing")]
+        if let Some(Err(error)) = &cue {
+            tracing::warn!(%error, "failed to decode cue");
+        }
+        cue
     }
 
     // Set lower bound to promote the allocation of a minimum number of elements.
@@ -71,16 +138,55 @@ where
 {
 }
 
+/// Iterator on the cues of a [`SupParser`] with their [`CueId`], see [`SupParser::with_ids`].
+pub struct IdentifiedCues<Reader, Decoder>
+where
+    Reader: BufRead,
+    Decoder: PgsDecoder,
+{
+    parser: SupParser<Reader, Decoder>,
+    sequence: usize,
+}
+
+impl<Reader, Decoder> Iterator for IdentifiedCues<Reader, Decoder>
+where
+    Reader: BufRead + Seek,
+    Decoder: PgsDecoder,
+{
+    type Item = Result<(CueId, Decoder::Output), PgsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = match self.parser.reader.stream_position() {
+            Ok(offset) => offset,
+            Err(err) => return Some(Err(PgsError::StreamPosition(err))),
+        };
+        let id = CueId::new(CueFormat::Pgs, offset, self.sequence);
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("identified_cue", cue_id = %id).entered();
+        let cue = self.parser.next()?;
+        self.sequence += 1;
+        Some(cue.map(|cue| (id, cue)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use assert_matches2::assert_matches;
-
     use super::SupParser;
     use crate::{
-        pgs::{DecodeTimeImage, DecodeTimeOnly, PgsError},
-        time::{TimePoint, TimeSpan},
+        content::{CueFormat, CueId},
+        image::ToOcrImageOpt,
+        pgs::{
+            DecodeMode, DecodeTimeDualImage, DecodeTimeImage, DecodeTimeOnly, Diagnostic, FadeMode,
+            PgsError,
+        },
+        time::{span, TimePoint, TimeSpan},
+    };
+    use assert_matches2::assert_matches;
+    use image::Rgba;
+    use std::{
+        fs::File,
+        io::{BufReader, Cursor},
     };
-    use std::{fs::File, io::BufReader};
 
     #[test]
     fn parse_only_one_sub() {
@@ -96,63 +202,304 @@ mod tests {
         let file_subtitles = parser.map(|sub| sub.unwrap()).collect::<Vec<_>>();
         assert!(file_subtitles.iter().eq(controls.iter()));
         assert!(file_subtitles.len() == 1);
+
+        let parser =
+            SupParser::<BufReader<File>, DecodeTimeOnly>::from_file("./fixtures/only_one.sup")
+                .unwrap();
+        let ids = parser
+            .with_ids()
+            .map(|sub| sub.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [CueId::new(CueFormat::Pgs, 0, 0)]);
+    }
+
+    #[test]
+    fn indexed_image_match_decoded_image() {
+        let mut parser =
+            SupParser::<BufReader<File>, DecodeTimeImage>::from_file("./fixtures/only_one.sup")
+                .unwrap();
+        let (_, image) = parser.next().unwrap().unwrap();
+        let decoded = image.decode(DecodeMode::Lenient).unwrap();
+        let indexed = image.to_indexed();
+        let alphas = indexed
+            .indexes()
+            .iter()
+            .map(|&index| indexed.palette()[usize::from(index)][3]);
+        assert!(alphas.eq(decoded.pixels().iter().map(|pixel| pixel[1])));
+    }
+
+    #[test]
+    fn decode_dual_images() {
+        let path = "./fixtures/only_one.sup";
+        let mut parser = SupParser::<BufReader<File>, DecodeTimeImage>::from_file(path).unwrap();
+        let (time, image) = parser.next().unwrap().unwrap();
+
+        let opt = ToOcrImageOpt::new();
+        let text_if_opaque = |Rgba([.., alpha]): Rgba<u8>| {
+            if alpha > 0 {
+                opt.text_color
+            } else {
+                opt.background_color
+            }
+        };
+        let decoder = DecodeTimeDualImage::new(DecodeTimeImage::default(), text_if_opaque, opt);
+        let reader = BufReader::new(File::open(path).unwrap());
+        let mut parser = SupParser::with_decoder(reader, decoder);
+        let (dual_time, dual) = parser.next().unwrap().unwrap();
+        assert_eq!(dual_time, time);
+        assert_eq!(dual.indexed, image.to_indexed());
+        assert_eq!(dual.ocr, dual.indexed.to_ocr_image(text_if_opaque, &opt));
+        assert!(parser.next().is_none());
+    }
+
+    #[cfg(any(feature = "rayon", feature = "parallel"))]
+    #[test]
+    fn par_decode_images() {
+        let path = "./fixtures/sequence_without_ods.sup";
+        let decoded = SupParser::<BufReader<File>, DecodeTimeImage>::from_file(path)
+            .unwrap()
+            .map(|cue| {
+                let (time, image) = cue.unwrap();
+                (time, image.decode(DecodeMode::Lenient).unwrap())
+            })
+            .collect::<Vec<_>>();
+        let par_decoded = SupParser::<BufReader<File>, DecodeTimeImage>::from_file(path)
+            .unwrap()
+            .par_decode(DecodeMode::Lenient)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(par_decoded.len(), 7);
+        assert!(par_decoded == decoded);
     }
 
     #[test]
     fn parse_sequence_without_ods() {
-        let controls = &[
-            Ok(TimeSpan::new(
-                TimePoint::from_msecs(4209),
-                TimePoint::from_msecs(7421),
-            )),
-            Ok(TimeSpan::new(
-                TimePoint::from_msecs(11717),
-                TimePoint::from_msecs(14511),
-            )),
-            Ok(TimeSpan::new(
-                TimePoint::from_msecs(16638),
-                TimePoint::from_msecs(18891),
-            )),
-            Ok(TimeSpan::new(
-                TimePoint::from_msecs(18974),
-                TimePoint::from_msecs(23228),
-            )),
-            Err(PgsError::MissingImage),
-            Ok(TimeSpan::new(
-                TimePoint::from_msecs(501_373),
-                TimePoint::from_msecs(505_543),
-            )),
-            Ok(TimeSpan::new(
-                TimePoint::from_msecs(506_378),
-                TimePoint::from_msecs(510_632),
-            )),
-            Ok(TimeSpan::new(
-                TimePoint::from_msecs(510_715),
-                TimePoint::from_msecs(516_513),
-            )),
-        ];
+        let controls = [
+            (4209, 7421),
+            (11717, 14511),
+            (16638, 18891),
+            (18974, 23228),
+            (501_373, 505_543),
+            (506_378, 510_632),
+            (510_715, 516_513),
+        ]
+        .map(|(start, end)| span(start, end));
 
         let parser = SupParser::<BufReader<File>, DecodeTimeImage>::from_file(
             "./fixtures/sequence_without_ods.sup",
         )
         .unwrap();
-        let file_subtitles = parser.collect::<Vec<_>>();
-        assert_eq!(file_subtitles.len(), controls.len());
-        for (idx, (expected, actual)) in controls.iter().zip(file_subtitles).enumerate() {
-            match (expected, actual) {
-                (Ok(expected), Ok((actual_time, _))) => {
-                    assert_eq!(expected, &actual_time);
-                }
-                (Err(_), Err(actual)) => {
-                    assert_matches!(actual, PgsError::MissingImage);
-                }
-                (Err(err), Ok((actual_time, _))) => {
-                    panic!("assertion `expected == actual` failed for subtitle `{idx}`\nexpected: Err({err:?})\n  actual: Ok({actual_time:?},_)");
-                }
-                (Ok(expected), Err(err)) => {
-                    panic!("assertion `expected == actual` failed for subtitle `{idx}`\nexpected: Ok({expected:?})\n  actual: Err({err:?})");
-                }
-            }
+        let file_subtitles = parser
+            .map(|sub| sub.map(|(times, _)| times))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(file_subtitles, controls);
+    }
+
+    fn segment(time: u32, type_code: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = b"PG".to_vec();
+        segment.extend((time * 90).to_be_bytes());
+        segment.extend([0; 4]);
+        segment.push(type_code);
+        segment.extend(u16::try_from(payload.len()).unwrap().to_be_bytes());
+        segment.extend(payload);
+        segment
+    }
+
+    fn display_set(time: u32, state: u8, nb_objects: u8) -> Vec<u8> {
+        display_set_with_segments(time, state, nb_objects, 0, &[])
+    }
+
+    fn display_set_with_segments(
+        time: u32,
+        state: u8,
+        nb_objects: u8,
+        palette_id: u8,
+        segments: &[(u8, &[u8])],
+    ) -> Vec<u8> {
+        let pcs = [
+            0x07, 0x80, 0x04, 0x38, 0x10, 0x00, 0x00, state, 0x00, palette_id, nb_objects,
+        ];
+        let mut data = segment(time, 0x16, &pcs);
+        for (type_code, payload) in segments {
+            data.extend(segment(time, *type_code, payload));
         }
+        data.extend(segment(time, 0x80, &[]));
+        data
+    }
+
+    fn palette_update(time: u32, pds: &[u8]) -> Vec<u8> {
+        let pcs = [
+            0x07, 0x80, 0x04, 0x38, 0x10, 0x00, 0x00, 0x00, 0x80, 0x00, 0x01,
+        ];
+        let mut data = segment(time, 0x16, &pcs);
+        data.extend(segment(time, 0x14, pds));
+        data.extend(segment(time, 0x80, &[]));
+        data
+    }
+
+    #[test]
+    fn select_palette_of_composition() {
+        // Palettes 0 and 1 define the color 1, and an object of 1 pixel of this color.
+        let pds_0 = [0x00, 0x00, 0x01, 100, 128, 128, 255];
+        let pds_1 = [0x01, 0x00, 0x01, 200, 128, 128, 255];
+        let ods = [
+            0x00, 0x00, 0x00, 0xc0, 0x00, 0x00, 0x07, 0x00, 0x01, 0x00, 0x01, 0x01, 0x00, 0x00,
+        ];
+        let data = [
+            display_set_with_segments(
+                1000,
+                0x80,
+                1,
+                0,
+                &[(0x14, &pds_0), (0x14, &pds_1), (0x15, &ods)],
+            ),
+            // Show the object of the epoch with the palette 1.
+            display_set_with_segments(2000, 0x00, 1, 1, &[]),
+            display_set(3000, 0x00, 0),
+        ]
+        .concat();
+
+        let parser = SupParser::<_, DecodeTimeImage>::new(Cursor::new(data));
+        let luminances = parser
+            .map(|sub| sub.unwrap().1.palette().get(1).unwrap().luminance)
+            .collect::<Vec<_>>();
+        assert_eq!(luminances, [100, 200]);
+    }
+
+    #[test]
+    fn skip_unknown_segments() {
+        let data = [
+            display_set(1000, 0x80, 1),
+            segment(1500, 0x42, &[0xde, 0xad]),
+            display_set(2000, 0x00, 0),
+        ]
+        .concat();
+
+        let mut parser = SupParser::<_, DecodeTimeOnly>::new(Cursor::new(data.clone()));
+        assert_matches!(
+            parser.next(),
+            Some(Err(PgsError::SegmentInvalidTypeCode { value: 0x42 }))
+        );
+
+        let decoder = DecodeTimeOnly::default().with_decode_mode(DecodeMode::Lenient);
+        let mut parser = SupParser::with_decoder(Cursor::new(data), decoder);
+        let time = parser.next().unwrap().unwrap();
+        assert_eq!((time.start.msecs(), time.end.msecs()), (1000, 2000));
+        assert!(parser.next().is_none());
+        assert_eq!(
+            parser.decoder_mut().take_diagnostics(),
+            [Diagnostic::UnknownSegment {
+                type_code: 0x42,
+                size: 2
+            }]
+        );
+        assert!(parser.decoder().diagnostics().is_empty());
+    }
+
+    #[test]
+    fn place_image_at_its_object() {
+        // Two objects of 1 pixel, the image kept is the last one, of id 1.
+        let pcs = [
+            0x07, 0x80, 0x04, 0x38, 0x10, 0x00, 0x00, 0x80, 0x00, 0x00, 0x02, //
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x14, // object 0 at (10, 20)
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x1e, 0x00, 0x28, // object 1 at (30, 40)
+        ];
+        let pds = [0x00, 0x00, 0x01, 100, 128, 128, 255];
+        let ods = |id| {
+            [
+                0x00, id, 0x00, 0xc0, 0x00, 0x00, 0x07, 0x00, 0x01, 0x00, 0x01, 0x01, 0x00, 0x00,
+            ]
+        };
+        let data = [
+            segment(1000, 0x16, &pcs),
+            segment(1000, 0x14, &pds),
+            segment(1000, 0x15, &ods(0)),
+            segment(1000, 0x15, &ods(1)),
+            segment(1000, 0x80, &[]),
+            display_set(2000, 0x00, 0),
+        ]
+        .concat();
+
+        let mut parser = SupParser::<_, DecodeTimeImage>::new(Cursor::new(data));
+        let (_, image) = parser.next().unwrap().unwrap();
+        assert_eq!(image.position(), (30, 40));
+    }
+
+    #[test]
+    fn ignore_invalid_windows() {
+        // Defines 1 window, without its fields.
+        let wds = [0x01, 0x00];
+        let data = [
+            display_set_with_segments(1000, 0x80, 1, 0, &[(0x17, &wds)]),
+            display_set(2000, 0x00, 0),
+        ]
+        .concat();
+
+        let mut parser = SupParser::<_, DecodeTimeOnly>::new(Cursor::new(data.clone()));
+        assert_matches!(parser.next(), Some(Err(PgsError::WDSParse(_))));
+
+        let decoder = DecodeTimeOnly::default().with_decode_mode(DecodeMode::Lenient);
+        let mut parser = SupParser::with_decoder(Cursor::new(data), decoder);
+        let time = parser.next().unwrap().unwrap();
+        assert_eq!((time.start.msecs(), time.end.msecs()), (1000, 2000));
+        assert_eq!(
+            parser.decoder().diagnostics(),
+            [Diagnostic::InvalidWindowDefinition { size: 2 }]
+        );
+    }
+
+    #[test]
+    fn fade_modes() {
+        // Fade in of an object of 1 pixel of color 1, with palette updates.
+        let pds = |alpha| [0x00, 0x00, 0x01, 100, 128, 128, alpha];
+        let ods = [
+            0x00, 0x00, 0x00, 0xc0, 0x00, 0x00, 0x07, 0x00, 0x01, 0x00, 0x01, 0x01, 0x00, 0x00,
+        ];
+        let data = [
+            display_set_with_segments(1000, 0x80, 1, 0, &[(0x14, &pds(0)), (0x15, &ods)]),
+            palette_update(1100, &pds(128)),
+            palette_update(1200, &pds(255)),
+            display_set(3000, 0x00, 0),
+        ]
+        .concat();
+
+        let cues = |fade_mode| {
+            let decoder = DecodeTimeImage::default().with_fade_mode(fade_mode);
+            SupParser::with_decoder(Cursor::new(data.clone()), decoder)
+                .map(|cue| {
+                    let (time, image) = cue.unwrap();
+                    let alpha = image.palette().get(1).unwrap().transparency;
+                    (time.start.msecs(), time.end.msecs(), alpha)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(cues(FadeMode::Collapse), [(1000, 3000, 255)]);
+        assert_eq!(
+            cues(FadeMode::Faithful),
+            [(1000, 1100, 0), (1100, 1200, 128), (1200, 3000, 255)]
+        );
+    }
+
+    #[test]
+    fn parse_redundant_clears_and_refresh() {
+        let data = [
+            display_set(1000, 0x80, 1),
+            display_set(1500, 0x40, 1), // acquisition point refreshing the display
+            display_set(2000, 0x00, 0),
+            display_set(2000, 0x00, 0), // duplicate clear
+            segment(2100, 0x80, &[]),   // `END` only display set
+            display_set(3000, 0x80, 1),
+            display_set(4000, 0x00, 1), // new object replace the previous one
+            display_set(5000, 0x00, 0),
+        ]
+        .concat();
+
+        let parser = SupParser::<_, DecodeTimeOnly>::new(Cursor::new(data));
+        let subtitles = parser.map(Result::unwrap).collect::<Vec<_>>();
+        let expected =
+            [(1000, 2000), (3000, 4000), (4000, 5000)].map(|(start, end)| span(start, end));
+        assert_eq!(subtitles, expected);
     }
 }
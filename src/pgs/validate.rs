@@ -0,0 +1,324 @@
+//! Validation of a `Presentation Graphic Stream` against the constraints of `BluRay` players.
+//!
+//! Hardware players have fixed resources, and muxers (`tsMuxeR`, `mkvmerge`) don't check
+//! them : a stream exceeding them is muxed successfully, but is displayed incorrectly, or
+//! not at all, by the players. [`validate_sup`] reports all the violations of a stream.
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::{BufRead, Seek},
+};
+
+use super::{
+    layout::DECODED_OBJECT_BUFFER_SIZE,
+    ods::ObjectDefinitionView,
+    pcs::{self, CompositionState},
+    pds::PaletteDefinitionView,
+    segment::{read_header, SegmentBuffer, SegmentTypeCode},
+    PgsError,
+};
+use crate::time::TimePoint;
+
+/// Length of a segment header.
+const SEGMENT_HEADER_LEN: u64 = 13;
+
+/// Limits of the `BluRay` players checked by [`validate_sup`].
+///
+/// Create them with [`BdLimits::new`] (or [`Default`]) and the `with_*` methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BdLimits {
+    /// Maximum number of windows of an epoch.
+    pub max_windows: usize,
+    /// Maximum number of objects displayed by a display set.
+    pub max_objects: usize,
+    /// Maximum number of entries of a palette.
+    pub max_palette_entries: usize,
+    /// Size of the decoded object buffer, containing the objects of an epoch.
+    pub object_buffer_size: usize,
+    /// Maximum bitrate of the stream, in bits per second, measured over one second.
+    pub max_bitrate: u64,
+}
+
+impl Default for BdLimits {
+    /// The limits of the `BluRay` specification.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BdLimits {
+    /// Create the limits of the `BluRay` specification.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_windows: 2,
+            max_objects: 2,
+            max_palette_entries: 256,
+            object_buffer_size: DECODED_OBJECT_BUFFER_SIZE,
+            max_bitrate: 16_000_000,
+        }
+    }
+
+    /// Set the maximum number of windows of an epoch.
+    #[must_use]
+    pub const fn with_max_windows(mut self, max_windows: usize) -> Self {
+        self.max_windows = max_windows;
+        self
+    }
+
+    /// Set the maximum number of objects displayed by a display set.
+    #[must_use]
+    pub const fn with_max_objects(mut self, max_objects: usize) -> Self {
+        self.max_objects = max_objects;
+        self
+    }
+
+    /// Set the maximum number of entries of a palette.
+    #[must_use]
+    pub const fn with_max_palette_entries(mut self, max_palette_entries: usize) -> Self {
+        self.max_palette_entries = max_palette_entries;
+        self
+    }
+
+    /// Set the size of the decoded object buffer.
+    #[must_use]
+    pub const fn with_object_buffer_size(mut self, object_buffer_size: usize) -> Self {
+        self.object_buffer_size = object_buffer_size;
+        self
+    }
+
+    /// Set the maximum bitrate of the stream, in bits per second.
+    #[must_use]
+    pub const fn with_max_bitrate(mut self, max_bitrate: u64) -> Self {
+        self.max_bitrate = max_bitrate;
+        self
+    }
+}
+
+/// A violation of the [`BdLimits`], found in a display set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// A `Window Definition Segment` define too many windows.
+    TooManyWindows {
+        /// Time of the display set.
+        time: TimePoint,
+        /// Number of windows.
+        count: usize,
+    },
+
+    /// A `Presentation Composition Segment` display too many objects.
+    TooManyObjects {
+        /// Time of the display set.
+        time: TimePoint,
+        /// Number of objects.
+        count: usize,
+    },
+
+    /// A `Palette Definition Segment` define too many entries.
+    TooManyPaletteEntries {
+        /// Time of the display set.
+        time: TimePoint,
+        /// Number of entries.
+        count: usize,
+    },
+
+    /// The decoded objects of the epoch don't fit in the object buffer.
+    ObjectBufferOverflow {
+        /// Time of the display set.
+        time: TimePoint,
+        /// Size of the decoded objects of the epoch.
+        bytes: usize,
+    },
+
+    /// The stream exceed the maximum bitrate during the second before the display set.
+    BitrateExceeded {
+        /// Time of the display set.
+        time: TimePoint,
+        /// Bitrate of the stream during the second before the display set.
+        bits_per_second: u64,
+    },
+}
+
+impl Violation {
+    /// Time of the display set containing the violation.
+    #[must_use]
+    pub const fn time(&self) -> TimePoint {
+        match self {
+            Self::TooManyWindows { time, .. }
+            | Self::TooManyObjects { time, .. }
+            | Self::TooManyPaletteEntries { time, .. }
+            | Self::ObjectBufferOverflow { time, .. }
+            | Self::BitrateExceeded { time, .. } => *time,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.time().msecs();
+        match self {
+            Self::TooManyWindows { count, .. } => {
+                write!(f, "{time}ms: {count} windows defined")
+            }
+            Self::TooManyObjects { count, .. } => {
+                write!(f, "{time}ms: {count} objects displayed")
+            }
+            Self::TooManyPaletteEntries { count, .. } => {
+                write!(f, "{time}ms: {count} palette entries defined")
+            }
+            Self::ObjectBufferOverflow { bytes, .. } => {
+                write!(f, "{time}ms: {bytes} bytes of decoded objects in the epoch")
+            }
+            Self::BitrateExceeded {
+                bits_per_second, ..
+            } => write!(f, "{time}ms: bitrate of {bits_per_second} bits/s"),
+        }
+    }
+}
+
+/// Result of the validation of a stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    display_sets: usize,
+    violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    /// Number of display sets checked.
+    #[must_use]
+    pub const fn display_sets(&self) -> usize {
+        self.display_sets
+    }
+
+    /// Violations found, in stream order.
+    #[must_use]
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Return `true` if the stream respect all the limits.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Check all the display sets of a `SUP` stream against the `limits`.
+///
+/// # Errors
+///
+/// Will return a [`PgsError`] if the segments of the stream can't be read. Violations of
+/// the limits are not errors, but are listed in the returned [`ValidationReport`].
+pub fn validate_sup<R: BufRead + Seek>(
+    reader: &mut R,
+    limits: &BdLimits,
+) -> Result<ValidationReport, PgsError> {
+    let mut report = ValidationReport::default();
+    // Decoded size of the objects of the current epoch, by object id.
+    let mut objects = HashMap::<u16, usize>::new();
+    // Time and size of the display sets of the last second.
+    let mut recent = VecDeque::<(i64, u64)>::new();
+    let mut display_set: Option<(TimePoint, u64)> = None;
+    // The segments are only inspected, their content is read in a reused buffer.
+    let mut buffer = SegmentBuffer::default();
+
+    while let Some(header) = read_header(reader)? {
+        let time = TimePoint::from_msecs(i64::from(header.presentation_time()));
+        let (_, bytes) = display_set.get_or_insert((time, 0));
+        *bytes += u64::from(header.size()) + SEGMENT_HEADER_LEN;
+
+        let data = buffer.read(reader, &header)?;
+        let violations = &mut report.violations;
+        match header.type_code() {
+            SegmentTypeCode::Pcs => {
+                let pcs = pcs::parse(data)?;
+                if pcs.composition_state == CompositionState::EpochStart {
+                    objects.clear();
+                }
+                let count = usize::from(pcs.number_of_objects);
+                if count > limits.max_objects {
+                    violations.push(Violation::TooManyObjects { time, count });
+                }
+            }
+            SegmentTypeCode::Wds => {
+                let count = data.first().map_or(0, |&count| usize::from(count));
+                if count > limits.max_windows {
+                    violations.push(Violation::TooManyWindows { time, count });
+                }
+            }
+            SegmentTypeCode::Pds => {
+                let count = PaletteDefinitionView::parse(data).map_or(0, |pds| pds.entry_count());
+                if count > limits.max_palette_entries {
+                    violations.push(Violation::TooManyPaletteEntries { time, count });
+                }
+            }
+            SegmentTypeCode::Ods => {
+                if let Some(ObjectDefinitionView {
+                    object_id,
+                    size: Some((width, height)),
+                }) = ObjectDefinitionView::parse(data)
+                {
+                    objects.insert(object_id, usize::from(width) * usize::from(height));
+                    let bytes = objects.values().sum();
+                    if bytes > limits.object_buffer_size {
+                        violations.push(Violation::ObjectBufferOverflow { time, bytes });
+                    }
+                }
+            }
+            SegmentTypeCode::End => {
+                if let Some((time, bytes)) = display_set.take() {
+                    report.display_sets += 1;
+                    let msecs = time.msecs();
+                    recent.push_back((msecs, bytes));
+                    while recent
+                        .front()
+                        .is_some_and(|&(start, _)| start <= msecs - 1000)
+                    {
+                        recent.pop_front();
+                    }
+                    let bits_per_second = recent.iter().map(|(_, bytes)| bytes * 8).sum();
+                    if bits_per_second > limits.max_bitrate {
+                        report.violations.push(Violation::BitrateExceeded {
+                            time,
+                            bits_per_second,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Cursor};
+
+    #[test]
+    fn validate_fixture() {
+        let data = fs::read("./fixtures/only_one.sup").unwrap();
+        let report = validate_sup(&mut Cursor::new(&data), &BdLimits::default()).unwrap();
+        assert_eq!(report.display_sets(), 2);
+        assert!(report.is_valid());
+
+        let limits = BdLimits::new()
+            .with_max_windows(0)
+            .with_object_buffer_size(100)
+            .with_max_bitrate(1000);
+        let report = validate_sup(&mut Cursor::new(&data), &limits).unwrap();
+        assert!(!report.is_valid());
+        let violations = report.violations();
+        assert!(violations
+            .iter()
+            .any(|violation| matches!(violation, Violation::TooManyWindows { count: 1, .. })));
+        assert!(violations
+            .iter()
+            .any(|violation| matches!(violation, Violation::ObjectBufferOverflow { .. })));
+        assert!(violations
+            .iter()
+            .any(|violation| matches!(violation, Violation::BitrateExceeded { .. })));
+    }
+}
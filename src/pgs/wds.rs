@@ -0,0 +1,130 @@
+use crate::content::{Area, AreaValues};
+use std::io::{self, Read};
+use thiserror::Error;
+
+/// Error `WDS` (Window Definition Segment) handling.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Read `WindowDefinitionSegment` in a buffer failed.
+    #[error("failed to read buffer with `WindowDefinitionSegment`")]
+    BufferParse(#[source] io::Error),
+
+    /// The segment is too short to contain the windows it defines.
+    #[error("`WindowDefinitionSegment` of size {size} is too short for {count} windows")]
+    TooShort {
+        /// Size of the segment.
+        size: usize,
+        /// Number of windows defined by the segment.
+        count: u8,
+    },
+}
+
+/// Length of the definition of a window.
+const WINDOW_LEN: usize = 1 + 2 + 2 + 2 + 2;
+
+/// A window of the screen, in which the objects of the compositions are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Window {
+    /// Id of the window, referenced by the composition objects.
+    pub id: u8,
+    /// Horizontal position of the top left pixel of the window on the screen.
+    pub x: u16,
+    /// Vertical position of the top left pixel of the window on the screen.
+    pub y: u16,
+    /// Width of the window.
+    pub width: u16,
+    /// Height of the window.
+    pub height: u16,
+}
+
+impl Window {
+    /// Area of the window on the screen.
+    ///
+    /// Return `None` if the window is less than 2 pixels wide or high, or if it goes
+    /// beyond the largest coordinates.
+    #[must_use]
+    pub fn area(&self) -> Option<Area> {
+        position_area(self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Area of the size `width` x `height`, with the top left pixel at `x`, `y`.
+pub(crate) fn position_area(x: u16, y: u16, width: u16, height: u16) -> Option<Area> {
+    let x2 = x.checked_add(width.checked_sub(1)?)?;
+    let y2 = y.checked_add(height.checked_sub(1)?)?;
+    Area::try_from(AreaValues {
+        x1: x,
+        y1: y,
+        x2,
+        y2,
+    })
+    .ok()
+}
+
+pub(crate) fn read<R: Read>(reader: &mut R, segments_size: usize) -> Result<Vec<Window>, Error> {
+    let mut wds_buf = vec![0; segments_size];
+    reader
+        .read_exact(&mut wds_buf)
+        .map_err(Error::BufferParse)?;
+    parse(&wds_buf)
+}
+
+/// Parse the content of a segment, read in `wds_buf`.
+pub(crate) fn parse(wds_buf: &[u8]) -> Result<Vec<Window>, Error> {
+    let count = wds_buf.first().copied().unwrap_or_default();
+    let windows = wds_buf.get(1..).unwrap_or_default();
+    if wds_buf.is_empty() || windows.len() < usize::from(count) * WINDOW_LEN {
+        return Err(Error::TooShort {
+            size: wds_buf.len(),
+            count,
+        });
+    }
+
+    let field =
+        |window: &[u8], offset: usize| u16::from_be_bytes([window[offset], window[offset + 1]]);
+    Ok(windows
+        .chunks_exact(WINDOW_LEN)
+        .take(usize::from(count))
+        .map(|window| Window {
+            id: window[0],
+            x: field(window, 1),
+            y: field(window, 3),
+            width: field(window, 5),
+            height: field(window, 7),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches2::assert_matches;
+
+    #[test]
+    fn read_windows() {
+        let data = [
+            0x01, 0x00, 0x01, 0xf1, 0x03, 0x93, 0x00, 0x4e, 0x00, 0x24, //
+        ];
+        let windows = read(&mut data.as_slice(), data.len()).unwrap();
+        let window = Window {
+            id: 0,
+            x: 497,
+            y: 915,
+            width: 78,
+            height: 36,
+        };
+        assert_eq!(windows, [window]);
+        let area = window.area().unwrap();
+        assert_eq!((area.left(), area.top()), (497, 915));
+        assert_eq!((area.width(), area.height()), (78, 36));
+        assert_eq!(Window { width: 1, ..window }.area(), None);
+
+        assert_matches!(
+            parse(&data[..9]),
+            Err(Error::TooShort { size: 9, count: 1 })
+        );
+        assert_matches!(parse(&[]), Err(Error::TooShort { size: 0, count: 0 }));
+    }
+}
@@ -0,0 +1,430 @@
+//! Writing of `Presentation Graphic Stream` subtitles (`.sup` file).
+//!
+//! Each cue is shown by a display set with its window, palette and object, and hidden
+//! by a display set without object. The windows and epochs are allocated by
+//! [`plan_layout`], so the cues are buffered and written when the writer is finished.
+use std::{collections::HashMap, io, mem};
+
+use image::{Rgba, RgbaImage};
+use thiserror::Error;
+
+use super::{
+    layout::{plan_layout, CueLayout, LayoutConfig, LayoutError},
+    ods::LastInSequenceFlag,
+    rle,
+    segment::SegmentTypeCode,
+    CompositionState, PaletteEntry,
+};
+use crate::{
+    content::{Area, ContentError},
+    raster::PlacedBitmap,
+    sink::CueSink,
+    time::{TimePoint, TimeSpan},
+};
+
+/// Maximum size of the content of a segment.
+const MAX_SEGMENT_LEN: usize = 0xffff;
+/// Size of the `ODS` fields before the object data in the first segment of an object.
+const FIRST_OBJECT_HEADER_LEN: usize = 11;
+/// Size of the `ODS` fields before the object data in the next segments of an object.
+const NEXT_OBJECT_HEADER_LEN: usize = 4;
+/// Frame rate field of the `PCS`, ignored by the players.
+const FRAME_RATE: u8 = 0x10;
+/// Frequency of the presentation timestamps.
+const PTS_FREQUENCY_KHZ: i64 = 90;
+/// Color id of the transparent background of the objects.
+const TRANSPARENT_ID: u8 = 0;
+
+/// Error of [`SupWriter`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// The cues can't be placed in windows and epochs.
+    #[error("failed to layout the cues")]
+    Layout(#[from] LayoutError),
+
+    /// The bitmap of a cue doesn't define a valid area.
+    #[error("bitmap of cue {index} doesn't define a valid area")]
+    InvalidArea {
+        /// Index of the cue.
+        index: usize,
+        /// Error of the area creation.
+        #[source]
+        source: ContentError,
+    },
+
+    /// The time of a cue is negative, ends before its start, or is too large for a `PTS`.
+    #[error("time of cue {index} can't be encoded")]
+    InvalidTime {
+        /// Index of the cue.
+        index: usize,
+    },
+
+    /// A cue starts before the previous one.
+    #[error("cue {index} starts before the previous cue")]
+    Unsorted {
+        /// Index of the cue.
+        index: usize,
+    },
+
+    /// Writing the `*.sup` content failed.
+    #[error("failed to write PGS output")]
+    Io(#[from] io::Error),
+}
+
+/// A cue quantized and encoded, waiting for the layout.
+struct EncodedCue {
+    time: TimeSpan,
+    area: Area,
+    palette: Vec<PaletteEntry>,
+    object_data: Vec<u8>,
+}
+
+/// Writer of `PGS` subtitles in `sup` format, see [`CueSink`].
+///
+/// The bitmaps of the cues are quantized to a palette of 256 colors. Cues must be
+/// written in order of their start time, and are placed with the [`LayoutConfig`].
+pub struct SupWriter<W: io::Write> {
+    writer: W,
+    config: LayoutConfig,
+    cues: Vec<EncodedCue>,
+}
+
+impl<W: io::Write> SupWriter<W> {
+    /// Create a writer of `sup` subtitles in `writer`, with the default [`LayoutConfig`].
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            config: LayoutConfig::default(),
+            cues: Vec::new(),
+        }
+    }
+
+    /// Set the layout of the cues : screen size, windows and epochs.
+    #[must_use]
+    pub const fn with_layout(mut self, config: LayoutConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write> CueSink<PlacedBitmap> for SupWriter<W> {
+    type Error = EncodeError;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &PlacedBitmap) -> Result<(), EncodeError> {
+        let index = self.cues.len();
+        if pts(time.start).is_none() || pts(time.end).is_none() || time.end < time.start {
+            return Err(EncodeError::InvalidTime { index });
+        }
+        if self
+            .cues
+            .last()
+            .is_some_and(|previous| previous.time.start > time.start)
+        {
+            return Err(EncodeError::Unsorted { index });
+        }
+        let area = cue
+            .area()
+            .map_err(|source| EncodeError::InvalidArea { index, source })?;
+
+        let (colors, pixels) = quantize(&cue.image);
+        let palette = colors
+            .into_iter()
+            .zip(0..=u8::MAX)
+            .map(|(color, id)| PaletteEntry::from_rgba(id, color))
+            .collect();
+        self.cues.push(EncodedCue {
+            time,
+            area,
+            palette,
+            object_data: rle::encode(&pixels, area.width()),
+        });
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), EncodeError> {
+        let cues = mem::take(&mut self.cues);
+        let areas = cues
+            .iter()
+            .map(|cue| (cue.time, cue.area))
+            .collect::<Vec<_>>();
+        let layouts = plan_layout(&self.config, &areas)?;
+
+        let mut stream = StreamState::default();
+        for (position, (cue, layout)) in cues.iter().zip(&layouts).enumerate() {
+            let mut data = Vec::new();
+            stream.write_cue(&mut data, &self.config, cue, layout);
+            // The next cue of the epoch replaces this one if displayed before its end.
+            let replaced = layouts.get(position + 1).is_some_and(|next| {
+                next.epoch == layout.epoch && next.time.start <= layout.time.end
+            });
+            if !replaced {
+                stream.write_clear(&mut data, &self.config, layout);
+            }
+            self.writer.write_all(&data)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Numbering of the display sets, objects and palettes of the stream.
+#[derive(Default)]
+struct StreamState {
+    composition_number: u16,
+    object_version: u8,
+    palette_version: u8,
+}
+
+impl StreamState {
+    /// Write the display set showing the `cue`.
+    fn write_cue(
+        &mut self,
+        data: &mut Vec<u8>,
+        config: &LayoutConfig,
+        cue: &EncodedCue,
+        layout: &CueLayout,
+    ) {
+        if layout.composition_state == CompositionState::EpochStart {
+            self.object_version = 0;
+            self.palette_version = 0;
+        } else {
+            self.object_version = self.object_version.wrapping_add(1);
+            self.palette_version = self.palette_version.wrapping_add(1);
+        }
+        let pts = pts(layout.time.start).unwrap_or_default();
+
+        let mut pcs = self.composition(config, layout.composition_state);
+        pcs.push(1); // number of objects
+        pcs.extend([0, 0, 0, 0]); // object id, window id, not cropped
+        pcs.extend(layout.object.left().to_be_bytes());
+        pcs.extend(layout.object.top().to_be_bytes());
+        write_segment(data, pts, SegmentTypeCode::Pcs, &pcs);
+        write_segment(
+            data,
+            pts,
+            SegmentTypeCode::Wds,
+            &window_definition(layout.window),
+        );
+
+        let mut pds = vec![0, self.palette_version];
+        pds.extend(cue.palette.iter().flat_map(PaletteEntry::to_bytes));
+        write_segment(data, pts, SegmentTypeCode::Pds, &pds);
+        self.write_object(data, pts, cue);
+        write_segment(data, pts, SegmentTypeCode::End, &[]);
+    }
+
+    /// Write the display set hiding the cue of `layout`.
+    fn write_clear(&mut self, data: &mut Vec<u8>, config: &LayoutConfig, layout: &CueLayout) {
+        let pts = pts(layout.time.end).unwrap_or_default();
+        let mut pcs = self.composition(config, CompositionState::Normal);
+        pcs.push(0); // number of objects
+        write_segment(data, pts, SegmentTypeCode::Pcs, &pcs);
+        write_segment(
+            data,
+            pts,
+            SegmentTypeCode::Wds,
+            &window_definition(layout.window),
+        );
+        write_segment(data, pts, SegmentTypeCode::End, &[]);
+    }
+
+    /// Fields of a `PCS` before the number of objects, for the next display set.
+    fn composition(&mut self, config: &LayoutConfig, state: CompositionState) -> Vec<u8> {
+        let mut pcs = Vec::new();
+        pcs.extend(config.screen_width.to_be_bytes());
+        pcs.extend(config.screen_height.to_be_bytes());
+        pcs.push(FRAME_RATE);
+        pcs.extend(self.composition_number.to_be_bytes());
+        pcs.push(state.into());
+        pcs.extend([0, 0]); // not a palette update, palette id
+        self.composition_number = self.composition_number.wrapping_add(1);
+        pcs
+    }
+
+    /// Write the object of the `cue`, split in as many segments as needed.
+    fn write_object(&self, data: &mut Vec<u8>, pts: u32, cue: &EncodedCue) {
+        let object_data = cue.object_data.as_slice();
+        let (first, rest) = object_data.split_at(
+            object_data
+                .len()
+                .min(MAX_SEGMENT_LEN - FIRST_OBJECT_HEADER_LEN),
+        );
+        let mut fragments = vec![first];
+        fragments.extend(rest.chunks(MAX_SEGMENT_LEN - NEXT_OBJECT_HEADER_LEN));
+
+        let last = fragments.len() - 1;
+        for (position, fragment) in fragments.into_iter().enumerate() {
+            let flag = match (position == 0, position == last) {
+                (true, true) => LastInSequenceFlag::FirstAndLast.into(),
+                (true, false) => LastInSequenceFlag::First.into(),
+                (false, true) => LastInSequenceFlag::Last.into(),
+                (false, false) => LastInSequenceFlag::Middle.into(),
+            };
+            let mut ods = vec![0, 0, self.object_version, flag];
+            if position == 0 {
+                // The object data length include the size fields. The layout limits
+                // the size of the objects, far below the 24 bits of the field.
+                let length = u32::try_from(object_data.len() + 4).unwrap_or(u32::MAX);
+                ods.extend(&length.to_be_bytes()[1..]);
+                ods.extend(cue.area.width().to_be_bytes());
+                ods.extend(cue.area.height().to_be_bytes());
+            }
+            ods.extend(fragment);
+            write_segment(data, pts, SegmentTypeCode::Ods, &ods);
+        }
+    }
+}
+
+/// Content of a `WDS` defining the single `window`.
+fn window_definition(window: Area) -> Vec<u8> {
+    let mut wds = vec![1, 0]; // number of windows, window id
+    wds.extend(window.left().to_be_bytes());
+    wds.extend(window.top().to_be_bytes());
+    wds.extend(window.width().to_be_bytes());
+    wds.extend(window.height().to_be_bytes());
+    wds
+}
+
+/// Write a segment of `content`, shorter than [`MAX_SEGMENT_LEN`].
+fn write_segment(data: &mut Vec<u8>, pts: u32, type_code: SegmentTypeCode, content: &[u8]) {
+    data.extend(b"PG");
+    data.extend(pts.to_be_bytes());
+    data.extend(0_u32.to_be_bytes()); // decoding timestamp, unused
+    data.push(type_code.into());
+    data.extend(
+        u16::try_from(content.len())
+            .unwrap_or(u16::MAX)
+            .to_be_bytes(),
+    );
+    data.extend(content);
+}
+
+/// Presentation timestamp of `time`, or `None` if it can't be encoded.
+fn pts(time: TimePoint) -> Option<u32> {
+    time.msecs()
+        .checked_mul(PTS_FREQUENCY_KHZ)
+        .and_then(|pts| u32::try_from(pts).ok())
+}
+
+/// Quantize the `image` to a palette of at most 256 colors, the first being the transparent
+/// background. Return the colors of the palette and the color ids of the pixels.
+///
+/// The precision of the colors is reduced until they fit in the palette.
+fn quantize(image: &RgbaImage) -> (Vec<Rgba<u8>>, Vec<u8>) {
+    // With 1 bit by channel, there are at most 16 colors.
+    (0..u8::BITS)
+        .find_map(|shift| index_colors(image, shift))
+        .expect("colors with 1 bit by channel fit in the palette")
+}
+
+/// Index the colors of `image` with `shift` bits of precision removed from the channels,
+/// or `None` if there are too many colors.
+fn index_colors(image: &RgbaImage, shift: u32) -> Option<(Vec<Rgba<u8>>, Vec<u8>)> {
+    let mut colors = vec![Rgba([0, 0, 0, 0])];
+    let mut ids = HashMap::new();
+    let pixels = image
+        .pixels()
+        .map(|pixel| {
+            if pixel[3] == 0 {
+                return Some(TRANSPARENT_ID);
+            }
+            let color = Rgba(pixel.0.map(|value| reduce_precision(value, shift)));
+            if let Some(&id) = ids.get(&color) {
+                return Some(id);
+            }
+            let id = u8::try_from(colors.len()).ok()?;
+            ids.insert(color, id);
+            colors.push(color);
+            Some(id)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((colors, pixels))
+}
+
+/// Round `value` to the nearest level with `shift` bits of precision removed, keeping
+/// the full range : `0` and `255` are unchanged.
+fn reduce_precision(value: u8, shift: u32) -> u8 {
+    let levels = u16::from(u8::MAX >> shift);
+    let level = (u16::from(value) * levels + 127) / 255;
+    u8::try_from(level * 255 / levels).unwrap_or(u8::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+    use crate::{
+        image::ToImage as _,
+        pgs::{DecodeTimeImage, SupParser},
+    };
+    use std::io::Cursor;
+
+    fn bitmap(x: u16, y: u16, color: Rgba<u8>) -> PlacedBitmap {
+        let image = RgbaImage::from_fn(40, 20, |x, y| {
+            if (5..35).contains(&x) && (5..15).contains(&y) {
+                color
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        PlacedBitmap::new(x, y, image)
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let cues = [
+            (
+                span(1000, 2500),
+                bitmap(100, 900, Rgba([255, 255, 255, 255])),
+            ),
+            (span(3000, 4000), bitmap(200, 50, Rgba([235, 200, 20, 128]))),
+        ];
+        let mut writer = SupWriter::new(Vec::new());
+        writer.write_all(cues.iter().cloned()).unwrap();
+        let sup = writer.into_inner();
+
+        let decoded = SupParser::<_, DecodeTimeImage>::new(Cursor::new(sup))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded.len(), cues.len());
+        for ((time, image), (expected_time, bitmap)) in decoded.iter().zip(&cues) {
+            assert_eq!(time, expected_time);
+            assert_eq!(image.position(), (bitmap.x, bitmap.y));
+            let image = image.to_indexed().to_image();
+            assert_eq!(image.dimensions(), bitmap.image.dimensions());
+            for (pixel, expected) in image.pixels().zip(bitmap.image.pixels()) {
+                assert_eq!(pixel[3], expected[3]);
+                for channel in 0..3 {
+                    assert!(pixel[channel].abs_diff(expected[channel]) <= 2 || pixel[3] == 0);
+                }
+            }
+        }
+
+        let mut writer = SupWriter::new(Vec::new());
+        writer.write_cue(cues[1].0, &cues[1].1).unwrap();
+        assert!(matches!(
+            writer.write_cue(cues[0].0, &cues[0].1),
+            Err(EncodeError::Unsorted { index: 1 })
+        ));
+    }
+
+    #[test]
+    fn quantize_many_colors() {
+        let image = RgbaImage::from_fn(64, 64, |x, y| {
+            let [x, y] = [x, y].map(|value| u8::try_from(value * 4).unwrap());
+            Rgba([x, y, 128, 255])
+        });
+        let (colors, pixels) = quantize(&image);
+        assert!(colors.len() <= 256);
+        assert_eq!(pixels.len(), 64 * 64);
+        assert_eq!(colors[0], Rgba([0, 0, 0, 0]));
+        assert_eq!(reduce_precision(0, 7), 0);
+        assert_eq!(reduce_precision(255, 3), 255);
+    }
+}
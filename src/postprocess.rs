@@ -0,0 +1,304 @@
+//! Post-processing of the times of decoded subtitles.
+//!
+//! The times found in the bitmap formats are often approximate : `VobSub` subtitles
+//! without stop date are displayed for a default duration, regardless of the next
+//! subtitle, and some `PGS` streams clear a subtitle at the time it's displayed.
+//! [`fix_times`] corrects these times before writing them in a text format.
+//!
+//! [`PulldownTransform`] matches times authored for a telecined video to the frames
+//! of its progressive encode.
+use crate::time::{FrameRate, TimeDuration, TimePoint, TimeSpan};
+use std::f64::consts::TAU;
+
+/// Options of the time corrections of [`fix_times`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingOptions {
+    min_duration: TimeDuration,
+    max_duration: Option<TimeDuration>,
+    snap_gap: Option<TimeDuration>,
+}
+
+impl Default for TimingOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimingOptions {
+    /// Default minimum duration of a cue : half a second.
+    pub const DEFAULT_MIN_DURATION: TimeDuration = TimeDuration::from_msecs(500);
+
+    /// Fix the overlaps and extend the cues shorter than [`Self::DEFAULT_MIN_DURATION`],
+    /// without maximum duration or snapping.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            min_duration: Self::DEFAULT_MIN_DURATION,
+            max_duration: None,
+            snap_gap: None,
+        }
+    }
+
+    /// Extend the cues shorter than `duration`, as long as they don't overlap the next one.
+    #[must_use]
+    pub const fn with_min_duration(mut self, duration: TimeDuration) -> Self {
+        self.min_duration = duration;
+        self
+    }
+
+    /// Shorten the cues longer than `duration`.
+    #[must_use]
+    pub const fn with_max_duration(mut self, duration: TimeDuration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Extend a cue to the start of the next one, if the gap between them is not
+    /// longer than `gap`. Avoids flickering between consecutive subtitles.
+    #[must_use]
+    pub const fn with_snap_gap(mut self, gap: TimeDuration) -> Self {
+        self.snap_gap = Some(gap);
+        self
+    }
+}
+
+/// Fix the times of the `cues` with the `options`, and return the cues sorted by start time.
+///
+/// For each cue, in this order :
+/// - the cue is shortened to the maximum duration, if any,
+/// - the cue is extended to the minimum duration,
+/// - the cue is shortened to end at the start of the next cue, if they overlap,
+/// - the cue is extended to the start of the next cue, if the gap between them is
+///   not longer than the snap gap.
+///
+/// The next cue of a cue is the next one starting later : the cues starting at the
+/// same time are displayed together, and are not shortened by each other.
+/// The start times are never changed, and the cues are never removed.
+pub fn fix_times<T, I>(cues: I, options: &TimingOptions) -> Vec<(TimeSpan, T)>
+where
+    I: IntoIterator<Item = (TimeSpan, T)>,
+{
+    let mut cues = cues.into_iter().collect::<Vec<_>>();
+    cues.sort_by_key(|(time, _)| time.start);
+
+    // Start of the next cue starting later, built from the last cue.
+    let mut next_starts = cues
+        .iter()
+        .rev()
+        .scan((None, None), |(start, later_start), (time, _)| {
+            if *start != Some(time.start) {
+                *later_start = *start;
+                *start = Some(time.start);
+            }
+            Some(*later_start)
+        })
+        .collect::<Vec<_>>();
+    next_starts.reverse();
+    for ((time, _), next_start) in cues.iter_mut().zip(next_starts) {
+        let start = time.start;
+        let mut end = time.end.max(start);
+        if let Some(max_duration) = options.max_duration {
+            end = end.min(start.saturating_add(max_duration));
+        }
+        end = end.max(start.saturating_add(options.min_duration));
+        if let Some(next_start) = next_start {
+            end = end.min(next_start);
+            if options.snap_gap.is_some_and(|gap| next_start - end <= gap) {
+                end = next_start;
+            }
+        }
+        *time = TimeSpan::new(start, end);
+    }
+    cues
+}
+
+/// Conversion of subtitle times authored for a telecined video (`29.97i` with a `3:2`
+/// pulldown) to the frames of the progressive encode of the film (`23.976p`).
+///
+/// The times are scaled by a constant ratio (`1` by default, the pulldown keeps the
+/// speed of the film), shifted by the phase of the cadence and snapped to the frames of
+/// the encode. Without the phase, times in the middle of two frames are rounded
+/// inconsistently, moving some cues by a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PulldownTransform {
+    rate: FrameRate,
+    ratio: f64,
+    phase: TimeDuration,
+}
+
+impl PulldownTransform {
+    /// Transform the times to the frames of a video at `rate`, without scaling or phase.
+    #[must_use]
+    pub const fn new(rate: FrameRate) -> Self {
+        Self {
+            rate,
+            ratio: 1.,
+            phase: TimeDuration::ZERO,
+        }
+    }
+
+    /// Scale the times by `ratio` before snapping them, for example
+    /// `1001. / 1000.` for times computed with a `30` fps time base.
+    #[must_use]
+    pub const fn with_ratio(mut self, ratio: f64) -> Self {
+        self.ratio = ratio;
+        self
+    }
+
+    /// Set the offset of the frames of the source from the frames of the encode,
+    /// negative if the frames of the source are before the ones of the encode.
+    #[must_use]
+    pub const fn with_phase(mut self, phase: TimeDuration) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Detect the phase from the (scaled) `times` of the first `count` cues,
+    /// as their mean offset from the frames of the encode.
+    ///
+    /// The phase is unchanged if there are no times.
+    #[must_use]
+    pub fn with_detected_phase<I>(self, times: I, count: usize) -> Self
+    where
+        I: IntoIterator<Item = TimePoint>,
+    {
+        let frame_msecs = 1000. * f64::from(self.rate.den()) / f64::from(self.rate.num());
+        // Circular mean of the offsets, to not split offsets close to a frame between
+        // the start and the end of the frame.
+        let (sin, cos) = times
+            .into_iter()
+            .take(count)
+            .map(|time| {
+                let offset = time.scale(self.ratio).to_secs() * 1000. % frame_msecs;
+                (offset / frame_msecs * TAU).sin_cos()
+            })
+            .fold((0., 0.), |(sin, cos), (s, c)| (sin + s, cos + c));
+        if sin == 0. && cos == 0. {
+            return self;
+        }
+        // The phase is in `(-frame / 2, frame / 2]`, negative for times before the frames.
+        let phase = sin.atan2(cos) / TAU * frame_msecs;
+        self.with_phase(TimeDuration::from_msecs(
+            cast::i64(phase.round()).unwrap_or_default(),
+        ))
+    }
+
+    /// Offset of the frames of the source, removed from the times.
+    #[must_use]
+    pub const fn phase(&self) -> TimeDuration {
+        self.phase
+    }
+
+    /// Convert a `time` of the source to the time of the nearest frame of the encode.
+    #[must_use]
+    pub fn apply_point(&self, time: TimePoint) -> TimePoint {
+        let time = time.scale(self.ratio).saturating_sub(self.phase);
+        TimePoint::from_frames(time.to_frames(self.rate), self.rate)
+    }
+
+    /// Convert the start and end times of a cue, see [`Self::apply_point`].
+    #[must_use]
+    pub fn apply(&self, time: TimeSpan) -> TimeSpan {
+        TimeSpan::new(self.apply_point(time.start), self.apply_point(time.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+
+    #[test]
+    fn fix_cue_times() {
+        let cues = [
+            (span(10_000, 10_000), "empty"),
+            (span(0, 5000), "default length"),
+            (span(4000, 6000), "overlapped"),
+            (span(11_000, 30_000), "too long"),
+            (span(20_100, 22_000), "last"),
+        ];
+
+        let fixed = fix_times(cues, &TimingOptions::new());
+        let times = fixed.iter().map(|(time, _)| *time).collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [
+                span(0, 4000),
+                span(4000, 6000),
+                span(10_000, 10_500),
+                span(11_000, 20_100),
+                span(20_100, 22_000),
+            ]
+        );
+        assert_eq!(fixed[0].1, "default length");
+
+        let options = TimingOptions::new()
+            .with_max_duration(TimeDuration::from_msecs(7000))
+            .with_snap_gap(TimeDuration::from_msecs(1000));
+        let times = fix_times(cues, &options)
+            .into_iter()
+            .map(|(time, _)| time)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [
+                span(0, 4000),
+                span(4000, 6000),
+                span(10_000, 11_000),
+                span(11_000, 18_000),
+                span(20_100, 22_000),
+            ]
+        );
+
+        // Cues starting together are clamped to the next cue starting later.
+        let cues = [
+            (span(1000, 3000), "top"),
+            (span(1000, 2500), "bottom"),
+            (span(2000, 4000), "next"),
+        ];
+        let times = fix_times(cues, &TimingOptions::new())
+            .into_iter()
+            .map(|(time, _)| time)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [span(1000, 2000), span(1000, 2000), span(2000, 4000)]
+        );
+    }
+
+    #[test]
+    fn remove_pulldown_phase() {
+        let rate = FrameRate::NTSC_FILM;
+        // Film frames, 15 ms after the frames of the encode.
+        let times = [3, 50, 121, 1000, 2399]
+            .map(|frame| TimePoint::from_frames(frame, rate) + TimeDuration::from_msecs(15));
+
+        let transform = PulldownTransform::new(rate).with_detected_phase(times, 3);
+        assert_eq!(transform.phase(), TimeDuration::from_msecs(15));
+        let frames = times.map(|time| transform.apply_point(time).to_frames(rate));
+        assert_eq!(frames, [3, 50, 121, 1000, 2399]);
+        // A late time is kept on its frame, without the phase it is rounded to the next.
+        let late = times[0] + TimeDuration::from_msecs(10);
+        assert_eq!(transform.apply_point(late).to_frames(rate), 3);
+        let transform = PulldownTransform::new(rate);
+        assert_eq!(transform.apply_point(late).to_frames(rate), 4);
+
+        // More than half a frame after the frames is the same as before the next ones.
+        let times = times.map(|time| time + TimeDuration::from_msecs(10));
+        let transform = PulldownTransform::new(rate).with_detected_phase(times, 3);
+        assert_eq!(transform.phase(), TimeDuration::from_msecs(-17));
+    }
+
+    #[test]
+    fn remove_negative_pulldown_phase() {
+        let rate = FrameRate::NTSC_FILM;
+        // Film frames, 5 ms before the frames of the encode.
+        let times = [3, 50, 121, 1000, 2399]
+            .map(|frame| TimePoint::from_frames(frame, rate) - TimeDuration::from_msecs(5));
+
+        let transform = PulldownTransform::new(rate).with_detected_phase(times, 5);
+        assert_eq!(transform.phase(), TimeDuration::from_msecs(-5));
+        let frames = times.map(|time| transform.apply_point(time).to_frames(rate));
+        assert_eq!(frames, [3, 50, 121, 1000, 2399]);
+    }
+}
@@ -0,0 +1,358 @@
+//! Conversion of text cues to bitmap cues, for the encoding of image subtitles.
+//!
+//! The rendering of the text (fonts, layout, styles) is done by a user-provided
+//! [`Rasterizer`]; this module handles the rest of the pipeline, keeping the timing
+//! unchanged :
+//! - [`rasterize_cues`] quantizes the rendered images to the 4 colors of `VobSub`
+//!   images, placed where the rasterizer put them,
+//! - [`rasterize_into`] forwards the rendered images to a sink of bitmaps, like the
+//!   [`SupWriter`] of `PGS` which quantizes them and places them in windows and epochs.
+//!
+//! [`SupWriter`]: crate::pgs::SupWriter
+use std::{cmp::Reverse, collections::HashMap, convert::Infallible};
+
+use image::{Rgb, RgbaImage};
+use thiserror::Error;
+
+use crate::{
+    content::{Area, AreaValues, ContentError},
+    sink::CueSink,
+    time::TimeSpan,
+    vobsub::{color_distance, nearest_palette_entry, Palette, VobSubIndexedImage},
+};
+
+/// Maximum value of a `VobSub` alpha.
+const MAX_ALPHA: u8 = 15;
+
+/// Error of the rasterization of text cues, with the error `E` of the rasterizer and
+/// the error `S` of the sink receiving the bitmaps, if any.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RasterError<E, S = Infallible> {
+    /// The rasterizer failed.
+    #[error("rasterizer failed on cue {index}")]
+    Rasterizer {
+        /// Index of the cue.
+        index: usize,
+        /// Error of the rasterizer.
+        #[source]
+        source: E,
+    },
+
+    /// The placed bitmap doesn't define a valid area (it should be at least 2x2 pixels
+    /// and fit in `u16` coordinates).
+    #[error("bitmap of cue {index} doesn't define a valid area")]
+    InvalidArea {
+        /// Index of the cue.
+        index: usize,
+        /// Error of the area creation.
+        #[source]
+        source: ContentError,
+    },
+
+    /// The sink receiving the bitmaps failed.
+    #[error("sink of the bitmaps failed")]
+    Sink(#[source] S),
+}
+
+/// A rendered text, with its position on the screen.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PlacedBitmap {
+    /// Horizontal position of the left side of the bitmap.
+    pub x: u16,
+    /// Vertical position of the top side of the bitmap.
+    pub y: u16,
+    /// Rendered text, with a transparent background.
+    pub image: RgbaImage,
+}
+
+impl PlacedBitmap {
+    /// Create a bitmap displayed with its top left corner at `x`,`y`.
+    #[must_use]
+    pub const fn new(x: u16, y: u16, image: RgbaImage) -> Self {
+        Self { x, y, image }
+    }
+
+    /// Area covered by the bitmap on the screen.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`ContentError`] if the bitmap doesn't define a valid area (it should be
+    /// at least 2x2 pixels and fit in `u16` coordinates).
+    pub fn area(&self) -> Result<Area, ContentError> {
+        let (width, height) = self.image.dimensions();
+        let coord = |position: u16, size: u32| {
+            u16::try_from(size)
+                .ok()
+                .and_then(|size| position.checked_add(size.checked_sub(1)?))
+                .ok_or(ContentError::InvalidAreaBounding)
+        };
+        Area::try_from(AreaValues {
+            x1: self.x,
+            y1: self.y,
+            x2: coord(self.x, width)?,
+            y2: coord(self.y, height)?,
+        })
+    }
+}
+
+impl AsRef<Self> for PlacedBitmap {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+/// Render the text of the cues to bitmaps.
+pub trait Rasterizer {
+    /// Error returned by the rasterizer.
+    type Error;
+
+    /// Render `text`, displayed during `time`. Return `None` if nothing has to be displayed.
+    ///
+    /// # Errors
+    /// Return the error happened during the rendering.
+    fn rasterize(
+        &mut self,
+        time: TimeSpan,
+        text: &str,
+    ) -> Result<Option<PlacedBitmap>, Self::Error>;
+}
+
+/// Render the text `cues` with the `rasterizer`, and quantize the bitmaps to `VobSub` images
+/// using the colors of the `palette`.
+///
+/// Each image use the 4 most frequent colors (`palette` entry and alpha), other pixels
+/// taking the nearest of them. The times of the cues are kept, and cues without bitmap are skipped.
+///
+/// # Errors
+///
+/// Will return [`RasterError::Rasterizer`] if the rasterizer fails, or
+/// [`RasterError::InvalidArea`] if a bitmap can't be placed.
+pub fn rasterize_cues<R, I, T>(
+    rasterizer: &mut R,
+    palette: &Palette,
+    cues: I,
+) -> Result<Vec<(TimeSpan, VobSubIndexedImage)>, RasterError<R::Error>>
+where
+    R: Rasterizer,
+    I: IntoIterator<Item = (TimeSpan, T)>,
+    T: AsRef<str>,
+{
+    let mut images = Vec::new();
+    for (index, (time, text)) in cues.into_iter().enumerate() {
+        let bitmap = rasterizer
+            .rasterize(time, text.as_ref())
+            .map_err(|source| RasterError::Rasterizer { index, source })?;
+        if let Some(bitmap) = bitmap {
+            let image = quantize(&bitmap, palette)
+                .map_err(|source| RasterError::InvalidArea { index, source })?;
+            images.push((time, image));
+        }
+    }
+    Ok(images)
+}
+
+/// Render the text `cues` with the `rasterizer`, and write the bitmaps to the `sink`.
+///
+/// The sink handles the encoding of the bitmaps, for example a [`SupWriter`] quantizes
+/// them and allocates the windows and epochs of the `PGS` stream. The times of the cues
+/// are kept, cues without bitmap are skipped, and the sink is finished after the last cue.
+///
+/// # Errors
+///
+/// Will return [`RasterError::Rasterizer`] if the rasterizer fails, or
+/// [`RasterError::Sink`] if the sink fails.
+///
+/// [`SupWriter`]: crate::pgs::SupWriter
+pub fn rasterize_into<R, S, I, T>(
+    rasterizer: &mut R,
+    sink: &mut S,
+    cues: I,
+) -> Result<(), RasterError<R::Error, S::Error>>
+where
+    R: Rasterizer,
+    S: CueSink<PlacedBitmap>,
+    I: IntoIterator<Item = (TimeSpan, T)>,
+    T: AsRef<str>,
+{
+    for (index, (time, text)) in cues.into_iter().enumerate() {
+        let bitmap = rasterizer
+            .rasterize(time, text.as_ref())
+            .map_err(|source| RasterError::Rasterizer { index, source })?;
+        if let Some(bitmap) = bitmap {
+            sink.write_cue(time, &bitmap).map_err(RasterError::Sink)?;
+        }
+    }
+    sink.finish().map_err(RasterError::Sink)
+}
+
+/// A color of a `VobSub` image : an entry of the palette and an alpha.
+type IndexedColor = (u8, u8);
+
+/// Quantize a placed bitmap to a `VobSub` image.
+fn quantize(bitmap: &PlacedBitmap, palette: &Palette) -> Result<VobSubIndexedImage, ContentError> {
+    let area = bitmap.area()?;
+
+    let pixels = bitmap
+        .image
+        .pixels()
+        .map(|pixel| {
+            let [red, green, blue, alpha] = pixel.0;
+            let alpha = alpha / 17; // 0..=255 to 0..=15
+            if alpha == 0 {
+                (0, 0)
+            } else {
+                (
+                    nearest_palette_entry(palette, Rgb([red, green, blue])),
+                    alpha,
+                )
+            }
+        })
+        .collect::<Vec<IndexedColor>>();
+
+    // The transparent color is always the first, as background of the image.
+    let mut counts = HashMap::<IndexedColor, usize>::new();
+    for color in pixels.iter().filter(|(_, alpha)| *alpha > 0) {
+        *counts.entry(*color).or_default() += 1;
+    }
+    let mut colors = counts.into_iter().collect::<Vec<_>>();
+    colors.sort_unstable_by_key(|&(color, count)| (Reverse(count), color));
+    let mut used = vec![(0, 0)];
+    used.extend(colors.into_iter().take(3).map(|(color, _)| color));
+
+    let raw = pixels
+        .iter()
+        .map(|color| nearest_used_color(palette, &used, *color))
+        .collect();
+    let mut entries = [0; 4];
+    let mut alphas = [0; 4];
+    for (idx, (entry, alpha)) in used.into_iter().enumerate() {
+        entries[idx] = entry;
+        alphas[idx] = alpha;
+    }
+    Ok(VobSubIndexedImage::new(area, entries, alphas, raw))
+}
+
+/// Index in `used` of the color nearest of `color`.
+fn nearest_used_color(palette: &Palette, used: &[IndexedColor], color: IndexedColor) -> u8 {
+    let (entry, alpha) = color;
+    let nearest = (0..used.len())
+        .min_by_key(|&idx| {
+            let (used_entry, used_alpha) = used[idx];
+            let rgb = color_distance(
+                palette[usize::from(entry)],
+                palette[usize::from(used_entry)],
+            );
+            let alpha = u32::from(alpha.abs_diff(used_alpha)) * 255 / u32::from(MAX_ALPHA);
+            rgb + alpha * alpha
+        })
+        .unwrap_or_default();
+    u8::try_from(nearest).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{ImageArea as _, ImageSize as _},
+        pgs::{DecodeTimeImage, EncodeError, SupParser, SupWriter},
+        time::TimePoint,
+    };
+    use assert_matches2::assert_matches;
+    use image::Rgba;
+    use std::io::Cursor;
+
+    /// Render each character as a 2x4 white box with a black outline pixel, skip empty texts.
+    struct BoxRasterizer;
+    impl Rasterizer for BoxRasterizer {
+        type Error = Infallible;
+
+        fn rasterize(
+            &mut self,
+            _: TimeSpan,
+            text: &str,
+        ) -> Result<Option<PlacedBitmap>, Self::Error> {
+            let width = u32::try_from(text.len()).unwrap() * 2;
+            Ok((width > 0).then(|| {
+                let mut image = RgbaImage::from_pixel(width, 4, Rgba([255, 255, 255, 255]));
+                image.put_pixel(0, 0, Rgba([10, 10, 10, 255]));
+                image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+                PlacedBitmap::new(10, 20, image)
+            }))
+        }
+    }
+
+    /// Render a bitmap of 1 pixel width.
+    struct ThinRasterizer;
+    impl Rasterizer for ThinRasterizer {
+        type Error = Infallible;
+
+        fn rasterize(&mut self, _: TimeSpan, _: &str) -> Result<Option<PlacedBitmap>, Self::Error> {
+            let image = RgbaImage::new(1, 4);
+            Ok(Some(PlacedBitmap::new(0, 0, image)))
+        }
+    }
+
+    #[test]
+    fn rasterize_and_quantize() {
+        let time = |start| {
+            TimeSpan::new(
+                TimePoint::from_msecs(start),
+                TimePoint::from_msecs(start + 1000),
+            )
+        };
+        let palette = crate::vobsub::palette(
+            b"000000, f0f0f0, cccccc, 999999, 3333fa, 1111bb, fa3333, bb1111, \
+              33fa33, 11bb11, fafa33, bbbb11, fa33fa, bb11bb, 33fafa, 11bbbb",
+        )
+        .unwrap()
+        .1;
+        let cues = [(time(0), "ab"), (time(2000), ""), (time(3000), "c")];
+        let images = rasterize_cues(&mut BoxRasterizer, &palette, cues).unwrap();
+
+        assert_eq!(images.len(), 2);
+        let (span, image) = &images[0];
+        assert_eq!(*span, time(0));
+        assert_eq!((image.area().left(), image.area().top()), (10, 20));
+        assert_eq!((image.area().width(), image.area().height()), (4, 4));
+        // Transparent background, then white (most frequent) and black.
+        assert_eq!(image.palette(), &[0, 1, 0, 0]);
+        assert_eq!(image.alpha(), &[0, 15, 15, 0]);
+        assert_eq!(&image.raw_image()[..4], &[2, 0, 1, 1]);
+
+        // A bitmap of 1 pixel width doesn't define a valid area.
+        assert_matches!(
+            rasterize_cues(&mut ThinRasterizer, &palette, [(time(0), "a")]),
+            Err(RasterError::InvalidArea { index: 0, .. })
+        );
+    }
+
+    #[test]
+    fn rasterize_into_sup() {
+        let time = |start| {
+            TimeSpan::new(
+                TimePoint::from_msecs(start),
+                TimePoint::from_msecs(start + 1000),
+            )
+        };
+        let cues = [(time(0), "ab"), (time(2000), ""), (time(3000), "c")];
+        let mut writer = SupWriter::new(Vec::new());
+        rasterize_into(&mut BoxRasterizer, &mut writer, cues).unwrap();
+
+        let parser = SupParser::<_, DecodeTimeImage>::new(Cursor::new(writer.into_inner()));
+        let images = parser
+            .map(|cue| {
+                let (time, image) = cue.unwrap();
+                (time.start.msecs(), image.position(), image.width())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(images, [(0, (10, 20), 4), (3000, (10, 20), 2)]);
+
+        let mut writer = SupWriter::new(Vec::new());
+        assert_matches!(
+            rasterize_into(&mut ThinRasterizer, &mut writer, [(time(0), "a")]),
+            Err(RasterError::Sink(EncodeError::InvalidArea { index: 0, .. }))
+        );
+    }
+}
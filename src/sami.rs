@@ -0,0 +1,142 @@
+//! `SAMI` (`.smi`) functionality
+//!
+//! A `SAMI` file is an `HTML` like document, where each `<SYNC Start=...>` tag
+//! start a new text displayed until the next `SYNC`. A `SYNC` with only
+//! blank text (usually `&nbsp;`) clears the screen.
+use std::sync::LazyLock;
+
+use regex::Regex;
+use thiserror::Error;
+
+use crate::time::{TimePoint, TimeSpan};
+
+/// Display duration of the last subtitle, if it isn't followed by another `SYNC`.
+const DEFAULT_LAST_DURATION: i64 = 5000;
+
+/// Error of `SAMI` parsing.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SamiError {
+    /// A `SYNC` tag doesn't have a valid `Start` attribute.
+    #[error("`SYNC` tag at offset {offset} doesn't have a valid `Start` attribute")]
+    InvalidSyncStart {
+        /// Offset of the tag in the content.
+        offset: usize,
+    },
+}
+
+/// Parse the content of a `SAMI` file.
+///
+/// The text of all the `<P>` tags of a `SYNC` is kept (the language classes are not
+/// distinguished), `<BR>` tags are converted to line breaks, other tags are removed
+/// and the common `HTML` entities are decoded. A subtitle is displayed until the next `SYNC`,
+/// or for 5 seconds if it is the last one.
+///
+/// # Errors
+///
+/// Will return [`SamiError::InvalidSyncStart`] if the start time of a `SYNC` can't be parsed.
+pub fn parse_sami(content: &str) -> Result<Vec<(TimeSpan, String)>, SamiError> {
+    static SYNC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<sync\b[^>]*>").unwrap());
+    static START: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"(?i)\bstart\s*=\s*["']?(\d+)"#).unwrap());
+    static BODY_END: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)</body\s*>").unwrap());
+
+    let content = BODY_END
+        .find(content)
+        .map_or(content, |body_end| &content[..body_end.start()]);
+    let mut syncs = Vec::new();
+    let tags = SYNC.find_iter(content).collect::<Vec<_>>();
+    for (idx, tag) in tags.iter().enumerate() {
+        let start = START
+            .captures(tag.as_str())
+            .and_then(|captures| captures[1].parse().ok())
+            .ok_or_else(|| SamiError::InvalidSyncStart {
+                offset: tag.start(),
+            })?;
+        let text_end = tags.get(idx + 1).map_or(content.len(), regex::Match::start);
+        syncs.push((start, html_to_text(&content[tag.end()..text_end])));
+    }
+
+    let subtitles = syncs
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, text))| !text.is_empty())
+        .map(|(idx, (start, text))| {
+            let end = syncs
+                .get(idx + 1)
+                .map_or(start + DEFAULT_LAST_DURATION, |(next, _)| *next);
+            let time = TimeSpan::new(TimePoint::from_msecs(*start), TimePoint::from_msecs(end));
+            (time, text.clone())
+        })
+        .collect();
+    Ok(subtitles)
+}
+
+/// Convert the `HTML` content of a `SYNC` to text.
+fn html_to_text(html: &str) -> String {
+    static BREAK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<br\s*/?>").unwrap());
+    static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+    static ENTITY: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"&(#\d+|#x[0-9a-fA-F]+|[a-zA-Z]+);").unwrap());
+
+    let text = BREAK.replace_all(html, "\n");
+    let text = TAG.replace_all(&text, "");
+    let text = ENTITY.replace_all(&text, |captures: &regex::Captures| {
+        decode_entity(&captures[1]).map_or_else(|| captures[0].to_owned(), String::from)
+    });
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Decode the name or number of an `HTML` entity. Non breaking spaces are decoded as spaces.
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    match entity.to_ascii_lowercase().as_str() {
+        "nbsp" => Some(' '),
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+    use assert_matches2::assert_matches;
+
+    #[test]
+    fn parse_syncs() {
+        let content = r#"<SAMI><HEAD><TITLE>Test</TITLE></HEAD><BODY>
+<SYNC Start=1000><P Class=ENCC>Hello<br>
+  <i>big</i>   World &amp; co
+<SYNC Start="3000"><P Class=ENCC>&nbsp;
+<sync start=4000><p class=ENCC>Last &#233;t&#xE9;
+</BODY></SAMI>"#;
+        let subtitles = parse_sami(content).unwrap();
+        assert_eq!(
+            subtitles,
+            [
+                (span(1000, 3000), "Hello\nbig World & co".to_owned()),
+                (span(4000, 9000), "Last été".to_owned()),
+            ]
+        );
+
+        assert_matches!(
+            parse_sami("<BODY><SYNC Start=abc><P>Oops</BODY>"),
+            Err(SamiError::InvalidSyncStart { offset: 6 })
+        );
+    }
+}
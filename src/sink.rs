@@ -0,0 +1,477 @@
+//! Streaming output of cues.
+//!
+//! A [`CueSink`] receives the cues one by one, and is finalized at the end of the stream.
+//! This allows pipelines to decode, transform and write cues without holding
+//! all of them in memory.
+//! A [`Tee`] forwards each cue to two sinks, and can be nested to write several outputs
+//! in a single pass over the source. For cues with a forced flag ([`ForcedText`]),
+//! a [`MarkForced`] annotates the forced cues for a text sink, and a [`ForcedOnly`] keeps
+//! only the forced cues, to write a forced-only output along the full one.
+//! A [`SplitLong`] splits the cues longer than a maximum duration.
+//! A [`FilterText`] transforms the text of the cues (see [`TextFilter`]) before writing them.
+//! A [`Deterministic`] sorts the cues by time, for byte-identical outputs across runs, and
+//! [`deterministic`] also normalizes the line endings for the text writers.
+use std::{borrow::Borrow as _, convert::Infallible, mem};
+use thiserror::Error;
+
+use crate::{
+    text::{ForcedText, LineEndingNormalizer, TextFilter},
+    time::TimeSpan,
+};
+
+/// Destination of a stream of cues of type `C`.
+pub trait CueSink<C: ?Sized> {
+    /// Error returned by the sink.
+    type Error;
+
+    /// Write a cue displayed during `time`.
+    ///
+    /// # Errors
+    /// Return the error happened during the write of the cue.
+    fn write_cue(&mut self, time: TimeSpan, cue: &C) -> Result<(), Self::Error>;
+
+    /// Finalize the output, after the last cue.
+    ///
+    /// # Errors
+    /// Return the error happened during the finalization.
+    fn finish(&mut self) -> Result<(), Self::Error>;
+
+    /// Write all the `cues` and finalize the output.
+    ///
+    /// # Errors
+    /// Return the first error returned by [`CueSink::write_cue`] or [`CueSink::finish`].
+    fn write_all<I, T>(&mut self, cues: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = (TimeSpan, T)>,
+        T: AsRef<C>,
+    {
+        cues.into_iter()
+            .try_for_each(|(time, cue)| self.write_cue(time, cue.as_ref()))?;
+        self.finish()
+    }
+}
+
+/// Collect owned copies of the cues in memory.
+impl<C: ?Sized + ToOwned> CueSink<C> for Vec<(TimeSpan, C::Owned)> {
+    type Error = Infallible;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &C) -> Result<(), Self::Error> {
+        self.push((time, cue.to_owned()));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Error of a [`Tee`], identifying the sink which failed.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TeeError<A, B> {
+    /// The first sink failed.
+    #[error("first sink of the tee failed")]
+    First(#[source] A),
+
+    /// The second sink failed.
+    #[error("second sink of the tee failed")]
+    Second(#[source] B),
+}
+
+/// Sink forwarding each cue to two sinks.
+///
+/// Cues are written to the `first` sink, then to the `second`. Writing stops at the first error,
+/// but both sinks are finished by [`CueSink::finish`] even if the first one failed.
+/// More outputs can be handled by nesting tees : `Tee::new(srt, Tee::new(vtt, stats))`.
+#[derive(Debug, Clone)]
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Tee<A, B> {
+    /// Create a tee forwarding cues to `first` and `second`.
+    #[must_use]
+    pub const fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    /// Get back the two sinks.
+    #[must_use]
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<C, A, B> CueSink<C> for Tee<A, B>
+where
+    C: ?Sized,
+    A: CueSink<C>,
+    B: CueSink<C>,
+{
+    type Error = TeeError<A::Error, B::Error>;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &C) -> Result<(), Self::Error> {
+        self.first.write_cue(time, cue).map_err(TeeError::First)?;
+        self.second.write_cue(time, cue).map_err(TeeError::Second)
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        let first = self.first.finish().map_err(TeeError::First);
+        let second = self.second.finish().map_err(TeeError::Second);
+        first.and(second)
+    }
+}
+
+/// Sink forwarding the text of cues with a forced flag to a text sink, marking the forced ones.
+///
+/// The marking is format specific, like [`webvtt::mark_forced`].
+/// Without marking function, the text of all the cues is forwarded unchanged, as
+/// for the `srt` format which has no way to mark them (see [`SrtWriter`]).
+///
+/// [`webvtt::mark_forced`]: crate::webvtt::mark_forced
+/// [`SrtWriter`]: crate::srt::SrtWriter
+#[derive(Debug, Clone)]
+pub struct MarkForced<S> {
+    sink: S,
+    mark: Option<fn(&str) -> String>,
+}
+
+impl<S> MarkForced<S> {
+    /// Create a sink forwarding the text of all the cues to `sink`, without marking.
+    #[must_use]
+    pub const fn new(sink: S) -> Self {
+        Self { sink, mark: None }
+    }
+
+    /// Mark the text of the forced cues with `mark`.
+    #[must_use]
+    pub const fn with_mark(mut self, mark: fn(&str) -> String) -> Self {
+        self.mark = Some(mark);
+        self
+    }
+
+    /// Get back the underlying sink.
+    #[must_use]
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<'a, S: CueSink<str>> CueSink<ForcedText<'a>> for MarkForced<S> {
+    type Error = S::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &ForcedText<'a>) -> Result<(), Self::Error> {
+        match self.mark.filter(|_| cue.forced) {
+            Some(mark) => self.sink.write_cue(time, &mark(cue.text)),
+            None => self.sink.write_cue(time, cue.text),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.sink.finish()
+    }
+}
+
+/// Sink forwarding the text of the forced cues to a text sink, and dropping the others.
+///
+/// Combined with a [`Tee`], it writes the full output and the forced-only output in one pass :
+/// `Tee::new(MarkForced::new(full), ForcedOnly::new(forced))`.
+#[derive(Debug, Clone)]
+pub struct ForcedOnly<S> {
+    sink: S,
+}
+
+impl<S> ForcedOnly<S> {
+    /// Create a sink forwarding the forced cues to `sink`.
+    #[must_use]
+    pub const fn new(sink: S) -> Self {
+        Self { sink }
+    }
+
+    /// Get back the underlying sink.
+    #[must_use]
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<'a, S: CueSink<str>> CueSink<ForcedText<'a>> for ForcedOnly<S> {
+    type Error = S::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &ForcedText<'a>) -> Result<(), Self::Error> {
+        if cue.forced {
+            self.sink.write_cue(time, cue.text)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.sink.finish()
+    }
+}
+
+/// Sink splitting the cues longer than a maximum duration in repeated cues with the same
+/// content, for the platforms rejecting long cues.
+///
+/// The repeated cues cover the same span as the original (see [`TimeSpan::split`]).
+#[derive(Debug, Clone)]
+pub struct SplitLong<S> {
+    sink: S,
+    max_msecs: i64,
+}
+
+impl<S> SplitLong<S> {
+    /// Create a sink forwarding to `sink` cues of at most `max_msecs` milliseconds.
+    #[must_use]
+    pub const fn new(sink: S, max_msecs: i64) -> Self {
+        Self { sink, max_msecs }
+    }
+
+    /// Get back the underlying sink.
+    #[must_use]
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<C: ?Sized, S: CueSink<C>> CueSink<C> for SplitLong<S> {
+    type Error = S::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &C) -> Result<(), Self::Error> {
+        time.split(self.max_msecs)
+            .try_for_each(|time| self.sink.write_cue(time, cue))
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.sink.finish()
+    }
+}
+
+/// Sink forwarding the text of the cues to a text sink, after applying a [`TextFilter`].
+///
+/// Cues whose filtered text is empty are dropped.
+///
+/// Filters can be chained by nesting : `FilterText::new(FilterText::new(srt, hints), case)`
+/// applies `case`, then `hints`.
+#[derive(Debug, Clone)]
+pub struct FilterText<S, F> {
+    sink: S,
+    filter: F,
+}
+
+impl<S, F> FilterText<S, F> {
+    /// Create a sink forwarding the text of the cues filtered by `filter` to `sink`.
+    #[must_use]
+    pub const fn new(sink: S, filter: F) -> Self {
+        Self { sink, filter }
+    }
+
+    /// Get back the underlying sink.
+    #[must_use]
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: CueSink<str>, F: TextFilter> CueSink<str> for FilterText<S, F> {
+    type Error = S::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, text: &str) -> Result<(), Self::Error> {
+        let text = self.filter.filter(text);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.sink.write_cue(time, &text)
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.sink.finish()
+    }
+}
+
+/// Sink buffering the cues, and forwarding them sorted by time when finished, for
+/// byte-identical outputs across runs and platforms (checksum-based validation of
+/// archived conversions for example).
+///
+/// The cues are sorted by start, then by end time, and the cues with the same times keep
+/// their order. The writers of the crate have no option to be deterministic, because they
+/// are always : they don't depend on the locale or the platform, format the times with
+/// integers and write `\n` line endings. Only the order of the cues and the line endings
+/// of their texts can differ between runs, see [`deterministic`] for the text writers.
+pub struct Deterministic<S, C: ?Sized + ToOwned> {
+    sink: S,
+    cues: Vec<(TimeSpan, C::Owned)>,
+}
+
+impl<S, C: ?Sized + ToOwned> Deterministic<S, C> {
+    /// Create a sink forwarding the sorted cues to `sink`.
+    #[must_use]
+    pub const fn new(sink: S) -> Self {
+        Self {
+            sink,
+            cues: Vec::new(),
+        }
+    }
+
+    /// Get back the underlying sink.
+    #[must_use]
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+/// Make a text writer deterministic : the cues are sorted by [`Deterministic`] and their
+/// line endings are normalized by a [`LineEndingNormalizer`], so the output only
+/// depends on the input cues.
+///
+/// [`LineEndingNormalizer`]: crate::text::LineEndingNormalizer
+#[must_use]
+pub const fn deterministic<S: CueSink<str>>(
+    sink: S,
+) -> Deterministic<FilterText<S, LineEndingNormalizer>, str> {
+    Deterministic::new(FilterText::new(sink, LineEndingNormalizer))
+}
+
+impl<C: ?Sized + ToOwned, S: CueSink<C>> CueSink<C> for Deterministic<S, C> {
+    type Error = S::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &C) -> Result<(), Self::Error> {
+        self.cues.push((time, cue.to_owned()));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        let mut cues = mem::take(&mut self.cues);
+        cues.sort_by_key(|(time, _)| (time.start, time.end));
+        cues.iter()
+            .try_for_each(|(time, cue)| self.sink.write_cue(*time, cue.borrow()))?;
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::SrtWriter;
+    use crate::time::span;
+    use assert_matches2::assert_matches;
+    use std::io;
+
+    /// Sink failing on every operation.
+    struct Failing;
+    impl CueSink<str> for Failing {
+        type Error = io::Error;
+
+        fn write_cue(&mut self, _: TimeSpan, _: &str) -> Result<(), Self::Error> {
+            Err(io::ErrorKind::Other.into())
+        }
+
+        fn finish(&mut self) -> Result<(), Self::Error> {
+            Err(io::ErrorKind::Other.into())
+        }
+    }
+
+    #[test]
+    fn tee_writes_to_both_sinks() {
+        let mut tee = Tee::new(SrtWriter::new(Vec::new()), Vec::<(TimeSpan, String)>::new());
+        tee.write_all([(span(0, 1000), "Hello"), (span(2000, 3000), "World")])
+            .unwrap();
+        let (srt, collected) = tee.into_inner();
+        let srt = String::from_utf8(srt.into_inner()).unwrap();
+        assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:01,000\nHello\n"));
+        assert_eq!(
+            collected,
+            [
+                (span(0, 1000), "Hello".to_owned()),
+                (span(2000, 3000), "World".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn split_long_cues() {
+        let mut sink = SplitLong::new(Vec::<(TimeSpan, String)>::new(), 7000);
+        CueSink::<str>::write_all(
+            &mut sink,
+            [(span(0, 10_000), "Long"), (span(10_000, 12_000), "Short")],
+        )
+        .unwrap();
+        let collected = sink.into_inner();
+        let times = collected.iter().map(|(time, _)| *time).collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [span(0, 5000), span(5000, 10_000), span(10_000, 12_000)]
+        );
+        assert_eq!(collected[1].1, "Long");
+    }
+
+    #[test]
+    fn tee_reports_failing_sink() {
+        let mut tee = Tee::new(Vec::<(TimeSpan, String)>::new(), Failing);
+        assert_matches!(
+            tee.write_cue(span(0, 1000), "Hello"),
+            Err(TeeError::Second(_))
+        );
+        assert_eq!(tee.into_inner().0.len(), 1);
+
+        let mut tee = Tee::new(Failing, Vec::<(TimeSpan, String)>::new());
+        assert_matches!(tee.finish(), Err(TeeError::First(_)));
+    }
+
+    #[test]
+    fn forced_cues_after_mark_and_forced_only() {
+        let cues = [
+            (span(0, 1000), ForcedText::new("Hello", false)),
+            (span(2000, 3000), ForcedText::new("Bonjour", true)),
+        ];
+        let full =
+            MarkForced::new(Vec::<(TimeSpan, String)>::new()).with_mark(crate::webvtt::mark_forced);
+        let forced = ForcedOnly::new(Vec::<(TimeSpan, String)>::new());
+        let mut tee = Tee::new(full, forced);
+        tee.write_all(cues).unwrap();
+        let (full, forced) = tee.into_inner();
+        assert_eq!(
+            full.into_inner(),
+            [
+                (span(0, 1000), "Hello".to_owned()),
+                (span(2000, 3000), "<c.forced>Bonjour</c>".to_owned())
+            ]
+        );
+        assert_eq!(
+            forced.into_inner(),
+            [(span(2000, 3000), "Bonjour".to_owned())]
+        );
+
+        // Without marking function, the forced cue is forwarded unchanged.
+        let mut unmarked = MarkForced::new(Vec::<(TimeSpan, String)>::new());
+        unmarked.write_all(cues).unwrap();
+        assert_eq!(
+            unmarked.into_inner()[1],
+            (span(2000, 3000), "Bonjour".to_owned())
+        );
+    }
+
+    #[test]
+    fn deterministic_output() {
+        let cues = [
+            (span(2000, 3000), "Second\r\nline "),
+            (span(0, 1000), "First"),
+            (span(0, 1000), "First bis"),
+        ];
+        let write = |cues: &[(TimeSpan, &str)]| {
+            let mut sink = deterministic(SrtWriter::new(Vec::new()));
+            sink.write_all(cues.iter().copied()).unwrap();
+            sink.into_inner().into_inner().into_inner()
+        };
+        let output = write(&cues);
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "1\n00:00:00,000 --> 00:00:01,000\nFirst\n\n\
+             2\n00:00:00,000 --> 00:00:01,000\nFirst bis\n\n\
+             3\n00:00:02,000 --> 00:00:03,000\nSecond\nline\n\n"
+        );
+        // Same output whatever the order of the cues with different times.
+        assert_eq!(write(&[cues[1], cues[2], cues[0]]), output);
+    }
+}
@@ -1,7 +1,15 @@
 //! SubRip/Srt functionality
-use std::{fmt, io};
+use std::{fmt, io, iter::FusedIterator, str::Lines, sync::LazyLock};
 
-use crate::time::{TimePoint, TimeSpan};
+use regex::Regex;
+use thiserror::Error;
+
+use crate::{
+    format::SubtitleSink,
+    sink::CueSink,
+    text::TextHints,
+    time::{TimePoint, TimeSpan},
+};
 
 /// Extend `TimePoint` for implement `Srt` specific `Display`.
 #[repr(transparent)]
@@ -19,6 +27,130 @@ impl fmt::Display for TimePointSrt {
     }
 }
 
+/// Error of `srt` parsing.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SrtError {
+    /// A block doesn't start with a timing line, optionally preceded by an index line.
+    #[error("block at line {line} has no `srt` timing line")]
+    MissingTiming {
+        /// Number of the first line of the block, starting at 1.
+        line: usize,
+    },
+
+    /// A timing line can't be parsed.
+    #[error("line {line} is not a valid `srt` timing")]
+    InvalidTiming {
+        /// Number of the line, starting at 1.
+        line: usize,
+    },
+}
+
+/// Parse the content of a `srt` file.
+///
+/// # Errors
+///
+/// Will return the first error of the blocks, see [`SrtParser`].
+pub fn parse_srt(content: &str) -> Result<Vec<(TimeSpan, String)>, SrtError> {
+    SrtParser::new(content).collect()
+}
+
+/// Iterator over the subtitles of the content of a `srt` file.
+///
+/// A subtitle is a block of lines ended by an empty line : an optional index, the timing
+/// (`00:00:01,500 --> 00:00:03,000`, with a comma or a dot before the milliseconds and
+/// ignoring the position coordinates after it) and the lines of text.
+/// The byte order mark and carriage returns are ignored.
+///
+/// A malformed block returns an error, and the parsing continues at the next block.
+pub struct SrtParser<'a> {
+    lines: Lines<'a>,
+    line_idx: usize,
+}
+
+impl<'a> SrtParser<'a> {
+    /// Create a parser of the `srt` `content`.
+    #[must_use]
+    pub fn new(content: &'a str) -> Self {
+        Self {
+            lines: content.strip_prefix('\u{FEFF}').unwrap_or(content).lines(),
+            line_idx: 0,
+        }
+    }
+
+    /// Next line, trimmed of a carriage return, with its number.
+    fn next_line(&mut self) -> Option<(usize, &'a str)> {
+        let line = self.lines.next()?;
+        self.line_idx += 1;
+        Some((self.line_idx, line.strip_suffix('\r').unwrap_or(line)))
+    }
+
+    /// Parse a block whose lines are returned by `block`.
+    fn parse_block(
+        mut block: impl Iterator<Item = (usize, &'a str)>,
+        first: (usize, &'a str),
+    ) -> Result<(TimeSpan, String), SrtError> {
+        static TIMING: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^\s*(\d+):(\d{2}):(\d{2})[,.](\d{3})\s*-->\s*(\d+):(\d{2}):(\d{2})[,.](\d{3})(\s.*)?$")
+                .unwrap()
+        });
+
+        let (block_line, first_line) = first;
+        let is_index = first_line.trim().parse::<u64>().is_ok();
+        let (line, timing) = if is_index {
+            block
+                .next()
+                .ok_or(SrtError::MissingTiming { line: block_line })?
+        } else {
+            first
+        };
+        if !timing.contains("-->") {
+            return Err(SrtError::MissingTiming { line: block_line });
+        }
+        let captures = TIMING
+            .captures(timing)
+            .ok_or(SrtError::InvalidTiming { line })?;
+        let time = |first: usize| {
+            let field = |group: usize| captures[group].parse::<i64>().ok();
+            let (hours, mins, secs, msecs) = (
+                field(first)?,
+                field(first + 1)?,
+                field(first + 2)?,
+                field(first + 3)?,
+            );
+            Some(TimePoint::from_msecs(
+                ((hours * 60 + mins) * 60 + secs) * 1000 + msecs,
+            ))
+        };
+        let (Some(start), Some(end)) = (time(1), time(5)) else {
+            return Err(SrtError::InvalidTiming { line });
+        };
+        let text = block.map(|(_, line)| line).collect::<Vec<_>>().join("\n");
+        Ok((TimeSpan::new(start, end), text))
+    }
+}
+
+impl Iterator for SrtParser<'_> {
+    type Item = Result<(TimeSpan, String), SrtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = loop {
+            let line = self.next_line()?;
+            if !line.1.trim().is_empty() {
+                break line;
+            }
+        };
+        let block =
+            std::iter::from_fn(|| self.next_line().filter(|(_, line)| !line.trim().is_empty()));
+        let mut block = block.fuse();
+        let subtitle = Self::parse_block(&mut block, first);
+        // Skip the rest of a malformed block.
+        block.for_each(drop);
+        Some(subtitle)
+    }
+}
+impl FusedIterator for SrtParser<'_> {}
+
 /// Write subtitles in `srt` format
 /// # Errors
 ///
@@ -38,6 +170,24 @@ pub fn write_srt(
     Ok(())
 }
 
+/// Write subtitles in `srt` format, applying the text `hints` on each subtitle.
+/// # Errors
+///
+/// Will return `Err` if write in `writer` return an `Err`.
+pub fn write_srt_with_hints(
+    writer: &mut impl io::Write,
+    subtitles: &[(TimeSpan, String)],
+    hints: &TextHints,
+) -> Result<(), io::Error> {
+    subtitles
+        .iter()
+        .enumerate()
+        .try_for_each(|(idx, (time_span, text))| {
+            let line_num = idx + 1;
+            write_line(writer, line_num, time_span, &hints.apply(text))
+        })
+}
+
 /// Write a subtitle line in `srt` format
 /// # Errors
 ///
@@ -52,3 +202,166 @@ pub fn write_line(
     let end = TimePointSrt(time.end);
     writeln!(writer, "{line_idx}\n{start} --> {end}\n{text}\n")
 }
+
+/// Streaming writer of subtitles in `srt` format, see [`CueSink`].
+///
+/// `srt` has no forced flag, and no markup hidden by all the players : the forced
+/// cues are not marked in the text. They are written in a separate file, usually
+/// named `*.forced.srt`, with a [`ForcedOnly`] sink.
+///
+/// [`ForcedOnly`]: crate::sink::ForcedOnly
+pub struct SrtWriter<W: io::Write> {
+    writer: W,
+    hints: Option<TextHints>,
+    line_idx: usize,
+}
+
+impl<W: io::Write> SrtWriter<W> {
+    /// Create a writer of `srt` subtitles in `writer`.
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            hints: None,
+            line_idx: 0,
+        }
+    }
+
+    /// Create a writer applying the text `hints` on each subtitle.
+    pub const fn with_hints(writer: W, hints: TextHints) -> Self {
+        Self {
+            writer,
+            hints: Some(hints),
+            line_idx: 0,
+        }
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write> CueSink<str> for SrtWriter<W> {
+    type Error = io::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, text: &str) -> Result<(), Self::Error> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            start_ms = time.start.msecs(),
+            end_ms = time.end.msecs(),
+            format = "srt",
+            "write cue"
+        );
+        self.line_idx += 1;
+        match &self.hints {
+            Some(hints) => write_line(&mut self.writer, self.line_idx, &time, &hints.apply(text)),
+            None => write_line(&mut self.writer, self.line_idx, &time, text),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.writer.flush()
+    }
+}
+
+/// `srt` has no header.
+impl<W: io::Write> SubtitleSink for SrtWriter<W> {
+    const EXTENSION: &'static str = "srt";
+
+    fn write_header(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+    use assert_matches2::assert_matches;
+
+    #[test]
+    fn parse_srt_blocks() {
+        let content = "\u{FEFF}1\r\n00:00:00,500 --> 00:00:01,499\r\nHello\r\nWorld\r\n\r\n\
+                       00:00:02.000 --> 00:00:03,500 X1:10 X2:20 Y1:0 Y2:5\n\
+                       No index\n\n\n\
+                       3\nnot a timing\nignored\n\n\
+                       4\n00:00:04,000 --> 00:00:05,000\n\n\
+                       5\n00:00:06,000 --> 00:00:07,000\nLast";
+        let mut parser = SrtParser::new(content);
+        assert_eq!(
+            parser.next().unwrap().unwrap(),
+            (span(500, 1499), "Hello\nWorld".to_owned())
+        );
+        assert_eq!(
+            parser.next().unwrap().unwrap(),
+            (span(2000, 3500), "No index".to_owned())
+        );
+        assert_matches!(
+            parser.next(),
+            Some(Err(SrtError::MissingTiming { line: 10 }))
+        );
+        assert_eq!(
+            parser.next().unwrap().unwrap(),
+            (span(4000, 5000), String::new())
+        );
+        assert_eq!(
+            parser.next().unwrap().unwrap(),
+            (span(6000, 7000), "Last".to_owned())
+        );
+        assert!(parser.next().is_none());
+
+        // Round trip with the writer.
+        let subtitles = [(span(500, 1499), "Hello\nWorld".to_owned())];
+        let mut output = Vec::new();
+        write_srt(&mut output, &subtitles).unwrap();
+        assert_eq!(
+            parse_srt(&String::from_utf8(output).unwrap()).unwrap(),
+            subtitles
+        );
+        assert_matches!(
+            parse_srt("1\n00:00:01,000 --> 00:00:61"),
+            Err(SrtError::InvalidTiming { line: 2 })
+        );
+    }
+
+    #[test]
+    fn stream_srt() {
+        let subtitles = [(span(500, 1499), "Hello"), (span(2000, 3500), "World")];
+
+        let mut writer = SrtWriter::new(Vec::new());
+        writer.write_all(subtitles).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "1\n00:00:00,500 --> 00:00:01,499\nHello\n\n\
+             2\n00:00:02,000 --> 00:00:03,500\nWorld\n\n"
+        );
+    }
+
+    #[test]
+    fn stream_srt_with_forced_only_output() {
+        use crate::{
+            sink::{ForcedOnly, MarkForced, Tee},
+            text::ForcedText,
+        };
+
+        let cues = [
+            (span(500, 1499), ForcedText::new("Hello", false)),
+            (span(2000, 3500), ForcedText::new("Bonjour", true)),
+        ];
+
+        let full = MarkForced::new(SrtWriter::new(Vec::new()));
+        let forced = ForcedOnly::new(SrtWriter::new(Vec::new()));
+        let mut tee = Tee::new(full, forced);
+        tee.write_all(cues).unwrap();
+        let (full, forced) = tee.into_inner();
+        assert_eq!(
+            String::from_utf8(full.into_inner().into_inner()).unwrap(),
+            "1\n00:00:00,500 --> 00:00:01,499\nHello\n\n\
+             2\n00:00:02,000 --> 00:00:03,500\nBonjour\n\n"
+        );
+        assert_eq!(
+            String::from_utf8(forced.into_inner().into_inner()).unwrap(),
+            "1\n00:00:02,000 --> 00:00:03,500\nBonjour\n\n"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Builder of synthetic subtitles, to write hermetic and deterministic tests.
+//!
+//! Cues are simple bitmaps (solid boxes or rendered numbers) displayed at the
+//! specified times and areas, produced as `VobSub` indexed images.
+//! They can be written in `VobSub` or `SUP` files by encoders, to test downstream tools
+//! without depending on real subtitle files.
+use std::iter;
+
+use crate::{
+    content::{Area, AreaValues},
+    time::TimeSpan,
+    vobsub::VobSubIndexedImage,
+};
+
+/// Width of a digit glyph, in font pixels.
+const DIGIT_WIDTH: u16 = 3;
+/// Height of a digit glyph, in font pixels.
+const DIGIT_HEIGHT: u16 = 5;
+
+/// Digit glyphs bitmaps, one row of 3 bits by line (most significant bit on the left).
+const DIGITS: [[u8; DIGIT_HEIGHT as usize]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Index of the background color in the synthetic images.
+pub const BACKGROUND_INDEX: u8 = 0;
+/// Index of the foreground color in the synthetic images.
+pub const FOREGROUND_INDEX: u8 = 1;
+
+/// Builder of synthetic subtitle cues.
+#[derive(Debug, Clone)]
+pub struct SyntheticBuilder {
+    palette: [u8; 4],
+    alpha: [u8; 4],
+    scale: u16,
+    cues: Vec<(TimeSpan, VobSubIndexedImage)>,
+}
+
+impl Default for SyntheticBuilder {
+    fn default() -> Self {
+        Self {
+            palette: [0, 1, 2, 3],
+            alpha: [0, 15, 15, 15], // transparent background, opaque colors
+            scale: 4,
+            cues: Vec::new(),
+        }
+    }
+}
+
+impl SyntheticBuilder {
+    /// Create a builder without cues, with a transparent background and a scale of 4.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the `VobSub` palette indexes and alpha values of the following cues.
+    #[must_use]
+    pub const fn colors(mut self, palette: [u8; 4], alpha: [u8; 4]) -> Self {
+        self.palette = palette;
+        self.alpha = alpha;
+        self
+    }
+
+    /// Set the size in pixels of a font pixel for the following rendered numbers.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `scale` is 0.
+    #[must_use]
+    pub const fn scale(mut self, scale: u16) -> Self {
+        assert!(scale > 0, "scale of synthetic digits should not be 0");
+        self.scale = scale;
+        self
+    }
+
+    /// Add a cue filled with the foreground color on all its `area`.
+    #[must_use]
+    pub fn solid_box(mut self, time: TimeSpan, area: Area) -> Self {
+        let size = area.size();
+        let pixels = vec![FOREGROUND_INDEX; size.w * size.h];
+        self.push(time, area, pixels);
+        self
+    }
+
+    /// Add a cue with `number` rendered in the foreground color, from the top left corner `x`, `y`.
+    ///
+    /// Digits are separated and surrounded by one font pixel of background.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the rendered number area doesn't fit in `u16` coordinates.
+    #[must_use]
+    pub fn number(mut self, time: TimeSpan, x: u16, y: u16, number: u32) -> Self {
+        let digits = number.to_string().into_bytes();
+        let nb_digits = u16::try_from(digits.len()).unwrap();
+        let scale = self.scale;
+        let width = (nb_digits * (DIGIT_WIDTH + 1) + 1) * scale;
+        let height = (DIGIT_HEIGHT + 2) * scale;
+
+        let font_lines = (0..DIGIT_HEIGHT + 2).map(|row| {
+            (0..width)
+                .map(|column| {
+                    if is_digit_pixel(&digits, row, column / scale) {
+                        FOREGROUND_INDEX
+                    } else {
+                        BACKGROUND_INDEX
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+        let pixels = font_lines
+            .flat_map(|line| iter::repeat(line).take(usize::from(scale)).flatten())
+            .collect();
+
+        let area = Area::try_from(AreaValues {
+            x1: x,
+            y1: y,
+            x2: x.checked_add(width - 1).unwrap(),
+            y2: y.checked_add(height - 1).unwrap(),
+        })
+        .unwrap();
+        self.push(time, area, pixels);
+        self
+    }
+
+    /// Get the built cues.
+    #[must_use]
+    pub fn build(self) -> Vec<(TimeSpan, VobSubIndexedImage)> {
+        self.cues
+    }
+
+    fn push(&mut self, time: TimeSpan, area: Area, pixels: Vec<u8>) {
+        let image = VobSubIndexedImage::new(area, self.palette, self.alpha, pixels);
+        self.cues.push((time, image));
+    }
+}
+
+/// Is the font pixel at `row`, `column` set for the rendered `digits`,
+/// taking into account the background border and digits separation.
+fn is_digit_pixel(digits: &[u8], row: u16, column: u16) -> bool {
+    if row == 0 || row > DIGIT_HEIGHT || column == 0 {
+        return false;
+    }
+    let digit_idx = usize::from((column - 1) / (DIGIT_WIDTH + 1));
+    let digit_column = (column - 1) % (DIGIT_WIDTH + 1);
+    digit_column < DIGIT_WIDTH
+        && digits.get(digit_idx).is_some_and(|digit| {
+            let glyph = DIGITS[usize::from(digit - b'0')];
+            glyph[usize::from(row - 1)] & (0b100 >> digit_column) != 0
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::ImageArea as _;
+    use crate::time::span;
+
+    #[test]
+    fn build_solid_box() {
+        let area = Area::try_from(AreaValues {
+            x1: 10,
+            y1: 20,
+            x2: 19,
+            y2: 24,
+        })
+        .unwrap();
+        let cues = SyntheticBuilder::new()
+            .solid_box(span(0, 1000), area)
+            .build();
+        assert_eq!(cues.len(), 1);
+        let (time, image) = &cues[0];
+        assert_eq!(*time, span(0, 1000));
+        assert_eq!(image.area(), area);
+        assert_eq!(image.raw_image(), [FOREGROUND_INDEX; 50]);
+    }
+
+    #[test]
+    fn build_rendered_number() {
+        let cues = SyntheticBuilder::new()
+            .scale(1)
+            .number(span(0, 1000), 0, 0, 17)
+            .scale(2)
+            .number(span(1000, 2000), 100, 50, 4)
+            .build();
+
+        let (_, image) = &cues[0];
+        let rendered = image
+            .raw_image()
+            .chunks(9)
+            .map(|line| {
+                line.iter()
+                    .map(|&pixel| if pixel == FOREGROUND_INDEX { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            [
+                ".........",
+                "..#..###.",
+                ".##....#.",
+                "..#...#..",
+                "..#...#..",
+                ".###..#..",
+                ".........",
+            ]
+        );
+
+        let (_, image) = &cues[1];
+        assert_eq!((image.area().left(), image.area().top()), (100, 50));
+        assert_eq!((image.area().width(), image.area().height()), (10, 14));
+        assert_eq!(image.raw_image().len(), 140);
+    }
+}
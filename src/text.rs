@@ -0,0 +1,502 @@
+//! Text subtitles utilities, shared by the text formats writers.
+
+use regex::Regex;
+use std::{borrow::Cow, sync::LazyLock};
+
+/// Right-to-left mark (`RLM`).
+const RLM: char = '\u{200F}';
+/// Left-to-right mark (`LRM`).
+const LRM: char = '\u{200E}';
+
+/// Direction of the text of a subtitle stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextDirection {
+    /// Text is written from left to right (latin, `CJK`, ...).
+    #[default]
+    LeftToRight,
+    /// Text is written from right to left (arabic, hebrew, ...).
+    RightToLeft,
+}
+
+/// Hints about the text of a subtitle stream, used by the writers.
+///
+/// Create them with [`TextHints::from_lang`] (or [`Default`]) and the `with_*` methods.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TextHints {
+    /// Direction of the text.
+    pub direction: TextDirection,
+    /// Language of the text, as a `BCP 47` tag (`ar`, `he`, `ja`, ...).
+    pub lang: Option<String>,
+    /// Add explicit direction marks at the start and the end of each line.
+    ///
+    /// Players use the first strong character of a line to define its direction,
+    /// so a line starting or ending with punctuation or digits is often displayed
+    /// with misplaced characters. The marks fix the direction of each line,
+    /// without any reordering of the characters or of the lines.
+    pub direction_marks: bool,
+}
+
+impl TextHints {
+    /// Create hints for a language with the direction guessed from the `lang` tag.
+    #[must_use]
+    pub fn from_lang(lang: &str) -> Self {
+        let primary = lang.split(['-', '_']).next().unwrap_or_default();
+        let direction = if is_rtl_lang(primary) {
+            TextDirection::RightToLeft
+        } else {
+            TextDirection::LeftToRight
+        };
+        Self {
+            direction,
+            lang: Some(lang.to_owned()),
+            direction_marks: direction == TextDirection::RightToLeft,
+        }
+    }
+
+    /// Set the direction of the text.
+    #[must_use]
+    pub const fn with_direction(mut self, direction: TextDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Set the language of the text, as a `BCP 47` tag.
+    #[must_use]
+    pub fn with_lang(mut self, lang: impl Into<String>) -> Self {
+        self.lang = Some(lang.into());
+        self
+    }
+
+    /// Enable or disable the explicit direction marks.
+    #[must_use]
+    pub const fn with_direction_marks(mut self, direction_marks: bool) -> Self {
+        self.direction_marks = direction_marks;
+        self
+    }
+
+    /// Apply the hints to the text of a subtitle.
+    ///
+    /// The text is returned unchanged if no direction mark is needed.
+    #[must_use]
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !self.direction_marks {
+            return Cow::Borrowed(text);
+        }
+        let mark = match self.direction {
+            TextDirection::LeftToRight => LRM,
+            TextDirection::RightToLeft => RLM,
+        };
+        let marked = text
+            .lines()
+            .map(|line| {
+                let line = line.trim_matches([RLM, LRM]);
+                format!("{mark}{line}{mark}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Cow::Owned(marked)
+    }
+}
+
+/// Check if the primary language subtag is of a language written right-to-left.
+fn is_rtl_lang(primary: &str) -> bool {
+    const RTL_LANGS: [&str; 12] = [
+        "ar", "arc", "dv", "fa", "ha", "he", "iw", "ks", "ku", "ps", "ur", "yi",
+    ];
+    RTL_LANGS
+        .iter()
+        .any(|rtl| rtl.eq_ignore_ascii_case(primary))
+}
+
+/// Transformation of the text of the cues, applied by a [`FilterText`] sink before writing.
+///
+/// [`FilterText`]: crate::sink::FilterText
+pub trait TextFilter {
+    /// Return the filtered `text`, borrowed if unchanged.
+    fn filter<'a>(&self, text: &'a str) -> Cow<'a, str>;
+}
+
+impl TextFilter for TextHints {
+    fn filter<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.apply(text)
+    }
+}
+
+/// Filter converting the all-caps text (frequent in `OCR` output) to sentence case.
+///
+/// The first word of each sentence is capitalized and the other words are lowercased,
+/// with the case rules of the language (dotted and dotless `i` in turkish, `IJ` digraph in
+/// dutch, pronoun `I` in english). The protected words (names, acronyms, ...) are written
+/// as given in the list, whatever their case in the text.
+///
+/// Text containing lowercase letters is left unchanged, as its case is assumed correct.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaseNormalizer {
+    lang: String,
+    protected: Vec<String>,
+}
+
+impl CaseNormalizer {
+    /// Create a normalizer for a language, from its `BCP 47` tag.
+    #[must_use]
+    pub fn from_lang(lang: &str) -> Self {
+        let primary = lang.split(['-', '_']).next().unwrap_or_default();
+        Self {
+            lang: primary.to_ascii_lowercase(),
+            protected: Vec::new(),
+        }
+    }
+
+    /// Add words to keep as written in `words`.
+    #[must_use]
+    pub fn with_protected_words<I>(mut self, words: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.protected.extend(words.into_iter().map(Into::into));
+        self
+    }
+
+    /// Convert an all-caps `text` to sentence case.
+    ///
+    /// The text is returned unchanged if it contains lowercase letters.
+    #[must_use]
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if text.chars().any(char::is_lowercase) || !text.chars().any(char::is_uppercase) {
+            return Cow::Borrowed(text);
+        }
+
+        let mut normalized = String::with_capacity(text.len());
+        let mut sentence_start = true;
+        let mut rest = text;
+        while let Some(first) = rest.chars().next() {
+            let is_word = first.is_alphanumeric();
+            let len = rest
+                .find(|c: char| c.is_alphanumeric() != is_word)
+                .unwrap_or(rest.len());
+            let (token, tail) = rest.split_at(len);
+            if is_word {
+                normalized.push_str(&self.word(token, sentence_start));
+                sentence_start = false;
+            } else {
+                normalized.push_str(token);
+                let line_start = token.rsplit('\n').next().unwrap_or_default();
+                sentence_start |= token.contains(['.', '!', '?', '…'])
+                    || (token.contains('\n') || normalized.len() == token.len())
+                        && line_start.trim_start().starts_with('-');
+            }
+            rest = tail;
+        }
+        Cow::Owned(normalized)
+    }
+
+    /// Case of a `word` of an all-caps text.
+    fn word(&self, word: &str, sentence_start: bool) -> String {
+        let lower = self.lowercase(word);
+        if let Some(protected) = self
+            .protected
+            .iter()
+            .find(|protected| self.lowercase(protected) == lower)
+        {
+            return protected.clone();
+        }
+        if self.lang == "en" && word == "I" {
+            return word.to_owned();
+        }
+        if !sentence_start {
+            return lower;
+        }
+        if self.lang == "nl" && lower.starts_with("ij") {
+            return format!("IJ{}", &lower[2..]);
+        }
+        let mut chars = lower.chars();
+        chars.next().map_or_else(String::new, |first| {
+            let mut capitalized = match first {
+                'i' if self.is_turkic() => "İ".to_owned(),
+                first => first.to_uppercase().collect(),
+            };
+            capitalized.push_str(chars.as_str());
+            capitalized
+        })
+    }
+
+    fn lowercase(&self, word: &str) -> String {
+        if self.is_turkic() {
+            word.replace('I', "ı").replace('İ', "i").to_lowercase()
+        } else {
+            word.to_lowercase()
+        }
+    }
+
+    fn is_turkic(&self) -> bool {
+        matches!(self.lang.as_str(), "tr" | "az")
+    }
+}
+
+impl TextFilter for CaseNormalizer {
+    fn filter<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.apply(text)
+    }
+}
+
+/// Filter removing the annotations for the hearing-impaired (`SDH`) from the text :
+/// sound descriptions in brackets or parentheses, speaker labels (`JOHN:`), and lines of
+/// lyrics marked by music notes.
+///
+/// The lines left empty are removed, and the dialogue dash of a line left alone is removed.
+/// A cue containing only annotations gets an empty text, which [`FilterText`] doesn't write.
+///
+/// Create it with [`SdhFilter::new`] (or [`Default`]) and the `with_*` methods.
+///
+/// [`FilterText`]: crate::sink::FilterText
+#[derive(Debug, Clone)]
+pub struct SdhFilter {
+    brackets: bool,
+    speaker_labels: bool,
+    music_lines: bool,
+    patterns: Vec<Regex>,
+}
+
+impl Default for SdhFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SdhFilter {
+    /// Create a filter removing all the kinds of annotations.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            brackets: true,
+            speaker_labels: true,
+            music_lines: true,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Enable or disable the removal of the text in brackets and parentheses.
+    #[must_use]
+    pub const fn with_brackets(mut self, brackets: bool) -> Self {
+        self.brackets = brackets;
+        self
+    }
+
+    /// Enable or disable the removal of the uppercase speaker labels at the start of lines.
+    #[must_use]
+    pub const fn with_speaker_labels(mut self, speaker_labels: bool) -> Self {
+        self.speaker_labels = speaker_labels;
+        self
+    }
+
+    /// Enable or disable the removal of the lines containing music notes (`♪`, `♫`).
+    #[must_use]
+    pub const fn with_music_lines(mut self, music_lines: bool) -> Self {
+        self.music_lines = music_lines;
+        self
+    }
+
+    /// Also remove the text matching `pattern`.
+    #[must_use]
+    pub fn with_pattern(mut self, pattern: Regex) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    /// Remove the annotations from `text`.
+    ///
+    /// # Panics
+    /// Panic if the Regex creation failed
+    #[must_use]
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        static BRACKETS: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"\[[^\]]*\]|\([^)]*\)").unwrap());
+        static SPEAKER_LABEL: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"(?m)^([ \t]*-?[ \t]*)\p{Lu}[\p{Lu}\d .'-]*:[ \t]*").unwrap()
+        });
+
+        let mut filtered = Cow::Borrowed(text);
+        if self.music_lines && filtered.contains(['♪', '♫']) {
+            let lines = filtered.lines().filter(|line| !line.contains(['♪', '♫']));
+            filtered = Cow::Owned(lines.collect::<Vec<_>>().join("\n"));
+        }
+        let brackets = self.brackets.then_some(&*BRACKETS);
+        for pattern in brackets.into_iter().chain(&self.patterns) {
+            if let Cow::Owned(replaced) = pattern.replace_all(&filtered, "") {
+                filtered = Cow::Owned(replaced);
+            }
+        }
+        if self.speaker_labels {
+            if let Cow::Owned(replaced) = SPEAKER_LABEL.replace_all(&filtered, "$1") {
+                filtered = Cow::Owned(replaced);
+            }
+        }
+        if matches!(filtered, Cow::Borrowed(_)) {
+            return filtered;
+        }
+
+        let lines = filtered
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && *line != "-")
+            .collect::<Vec<_>>();
+        match lines.as_slice() {
+            [line] => Cow::Owned(line.trim_start_matches('-').trim_start().to_owned()),
+            lines => Cow::Owned(lines.join("\n")),
+        }
+    }
+}
+
+impl TextFilter for SdhFilter {
+    fn filter<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.apply(text)
+    }
+}
+
+/// Filter normalizing the line endings of the text, for outputs identical whatever the
+/// platform the text comes from.
+///
+/// The `\r\n` and `\r` line endings are converted to `\n`, and the trailing whitespaces
+/// of the lines and the empty lines at the end of the text are removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineEndingNormalizer;
+
+impl LineEndingNormalizer {
+    /// Normalize the line endings of `text`.
+    ///
+    /// The text is returned unchanged if already normalized.
+    #[must_use]
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        let is_normalized = !text.contains('\r')
+            && text.lines().all(|line| line.trim_end() == line)
+            && !text.ends_with('\n');
+        if is_normalized {
+            return Cow::Borrowed(text);
+        }
+        let lines = text
+            .split("\r\n")
+            .flat_map(|line| line.split(['\r', '\n']))
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        Cow::Owned(lines.join("\n").trim_end().to_owned())
+    }
+}
+
+impl TextFilter for LineEndingNormalizer {
+    fn filter<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.apply(text)
+    }
+}
+
+/// Text of a cue with its forced flag, written by the forced-aware writers.
+///
+/// Forced cues must be displayed even when subtitles are disabled (translation of
+/// signs or of dialogues in a foreign language).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ForcedText<'a> {
+    /// Text of the cue.
+    pub text: &'a str,
+    /// Is the cue forced.
+    pub forced: bool,
+}
+
+impl<'a> ForcedText<'a> {
+    /// Create the text of a cue, `forced` or not.
+    #[must_use]
+    pub const fn new(text: &'a str, forced: bool) -> Self {
+        Self { text, forced }
+    }
+}
+
+impl AsRef<Self> for ForcedText<'_> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches2::assert_matches;
+
+    #[test]
+    fn hints_from_lang() {
+        let hints = TextHints::from_lang("he-IL");
+        assert_eq!(hints.direction, TextDirection::RightToLeft);
+        assert!(hints.direction_marks);
+
+        let hints = TextHints::from_lang("ja");
+        assert_eq!(hints.direction, TextDirection::LeftToRight);
+        assert!(!hints.direction_marks);
+    }
+
+    #[test]
+    fn apply_direction_marks() {
+        let hints = TextHints::from_lang("ar");
+        assert_eq!(
+            hints.apply("مرحبا!\n...نعم"),
+            "\u{200F}مرحبا!\u{200F}\n\u{200F}...نعم\u{200F}"
+        );
+        // Already marked text don't get duplicated marks.
+        assert_eq!(hints.apply("\u{200F}نعم\u{200F}"), "\u{200F}نعم\u{200F}");
+        assert_eq!(TextHints::default().apply("Hi!\nyes"), "Hi!\nyes");
+    }
+
+    #[test]
+    fn hints_builder() {
+        let hints = TextHints::default()
+            .with_lang("fa")
+            .with_direction(TextDirection::RightToLeft)
+            .with_direction_marks(true);
+        assert_eq!(hints, TextHints::from_lang("fa"));
+        assert_eq!(hints.apply("سلام!"), "\u{200F}سلام!\u{200F}");
+
+        let unmarked = hints.with_direction_marks(false);
+        assert_eq!(unmarked.apply("سلام!"), "سلام!");
+    }
+
+    #[test]
+    fn normalize_case() {
+        let normalizer = CaseNormalizer::from_lang("en-US").with_protected_words(["NASA", "John"]);
+        assert_eq!(
+            normalizer.apply("I TOLD JOHN... NASA CALLED!\n- WHAT? WHY"),
+            "I told John... NASA called!\n- What? Why"
+        );
+        assert_eq!(normalizer.apply("Already fine."), "Already fine.");
+        assert_eq!(
+            CaseNormalizer::from_lang("tr").apply("İSTANBUL'DA KIŞ"),
+            "İstanbul'da kış"
+        );
+        assert_eq!(CaseNormalizer::from_lang("nl").apply("IJSLAND"), "IJsland");
+    }
+
+    #[test]
+    fn strip_sdh_annotations() {
+        let sdh = SdhFilter::new();
+        assert_eq!(
+            sdh.apply("- [DOOR SLAMS]\n- JOHN: Who's there?"),
+            "Who's there?"
+        );
+        assert_eq!(sdh.apply("- MARY: Hello (sighs)\n- Hi."), "- Hello\n- Hi.");
+        assert_eq!(sdh.apply("♪ Happy birthday ♪\n(CHEERING)"), "");
+        assert_matches!(sdh.apply("At 12:30, ok"), Cow::Borrowed(_));
+
+        let sdh = SdhFilter::new()
+            .with_brackets(false)
+            .with_pattern(Regex::new(r"\*[^*]*\*").unwrap());
+        assert_eq!(sdh.apply("*laughs* [Yes]"), "[Yes]");
+    }
+
+    #[test]
+    fn normalize_line_endings() {
+        let normalizer = LineEndingNormalizer;
+        assert_eq!(
+            normalizer.apply("Hello \r\nold\rMac\n\n"),
+            "Hello\nold\nMac"
+        );
+        assert_matches!(normalizer.apply("Hello\nWorld"), Cow::Borrowed(_));
+    }
+}
@@ -0,0 +1,122 @@
+use super::TimeSpan;
+
+/// Cues of several tracks overlapping in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AlignedCues {
+    /// Time covered by the cues.
+    pub time: TimeSpan,
+    /// For each track, indices of its cues in the group.
+    pub cues: Vec<Vec<usize>>,
+}
+
+impl AlignedCues {
+    /// Return `true` if the track at `track` index has at least one cue in the group.
+    #[must_use]
+    pub fn has_track(&self, track: usize) -> bool {
+        self.cues.get(track).is_some_and(|cues| !cues.is_empty())
+    }
+
+    /// Indices of the tracks without cue in the group.
+    pub fn missing_tracks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.cues
+            .iter()
+            .enumerate()
+            .filter_map(|(track, cues)| cues.is_empty().then_some(track))
+    }
+}
+
+/// Alignment of the cues of several tracks (different languages of the same video for example)
+/// based only on their times.
+///
+/// Cues overlapping in time are grouped, each group giving the cues of each track
+/// displayed at this time. This allows to spot missing translations, or forced-only tracks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackAlignment {
+    nb_tracks: usize,
+    groups: Vec<AlignedCues>,
+}
+
+impl TrackAlignment {
+    /// Align the cues of the `tracks`, each track being the times of its cues.
+    #[must_use]
+    pub fn new(tracks: &[&[TimeSpan]]) -> Self {
+        let mut cues = tracks
+            .iter()
+            .enumerate()
+            .flat_map(|(track, times)| {
+                times
+                    .iter()
+                    .enumerate()
+                    .map(move |(idx, time)| (track, idx, *time))
+            })
+            .collect::<Vec<_>>();
+        cues.sort_by_key(|(track, idx, time)| (time.start, time.end, *track, *idx));
+
+        let mut groups: Vec<AlignedCues> = Vec::new();
+        for (track, idx, time) in cues {
+            match groups.last_mut() {
+                Some(group) if time.start < group.time.end => {
+                    group.time.end = group.time.end.max(time.end);
+                    group.cues[track].push(idx);
+                }
+                Some(_) | None => {
+                    let mut group = AlignedCues {
+                        time,
+                        cues: vec![Vec::new(); tracks.len()],
+                    };
+                    group.cues[track].push(idx);
+                    groups.push(group);
+                }
+            }
+        }
+        Self {
+            nb_tracks: tracks.len(),
+            groups,
+        }
+    }
+
+    /// Groups of aligned cues, sorted by time.
+    #[must_use]
+    pub fn groups(&self) -> &[AlignedCues] {
+        &self.groups
+    }
+
+    /// For each track, the number of groups where the track has at least one cue.
+    #[must_use]
+    pub fn presence_counts(&self) -> Vec<usize> {
+        (0..self.nb_tracks)
+            .map(|track| {
+                self.groups
+                    .iter()
+                    .filter(|group| group.has_track(track))
+                    .count()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+
+    fn spans(spans: &[(i64, i64)]) -> Vec<TimeSpan> {
+        spans.iter().map(|&(start, end)| span(start, end)).collect()
+    }
+
+    #[test]
+    fn align_tracks() {
+        let english = spans(&[(0, 1000), (2000, 3000), (4000, 5000)]);
+        let french = spans(&[(100, 1100), (2100, 2500), (2500, 3100)]);
+        let forced = spans(&[(4200, 4800)]);
+        let alignment = TrackAlignment::new(&[&english, &french, &forced]);
+
+        let groups = alignment.groups();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].time, spans(&[(0, 1100)])[0]);
+        assert_eq!(groups[1].cues, [vec![1], vec![1, 2], vec![]]);
+        assert_eq!(groups[2].missing_tracks().collect::<Vec<_>>(), [1]);
+        assert_eq!(alignment.presence_counts(), [3, 2, 1]);
+    }
+}
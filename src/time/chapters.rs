@@ -0,0 +1,147 @@
+use super::{TimePoint, TimeSpan, TimeSpanSet};
+
+/// Subtitles coverage of a chapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ChapterCoverage {
+    /// Index of the chapter, in the order of the chapter marks.
+    pub index: usize,
+    /// Time of the chapter, from its mark to the next one.
+    pub time: TimeSpan,
+    /// Number of cues displayed during the chapter.
+    pub cue_count: usize,
+    /// Times of the chapter without subtitle longer than the maximum gap.
+    pub gaps: Vec<TimeSpan>,
+}
+
+impl ChapterCoverage {
+    /// Return `true` if no cue is displayed during the chapter.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.cue_count == 0
+    }
+
+    /// Return `true` if the chapter is empty or has long gaps.
+    #[must_use]
+    pub fn has_issue(&self) -> bool {
+        self.is_empty() || !self.gaps.is_empty()
+    }
+}
+
+/// Report of the subtitles coverage of the chapters of a video.
+///
+/// A quick sanity check of a conversion : a chapter without subtitles, or with a long
+/// time without subtitles, may reveal a reel silently dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChapterGapReport {
+    chapters: Vec<ChapterCoverage>,
+}
+
+impl ChapterGapReport {
+    /// Check the `cues` times against the chapters starting at the `marks`, the last
+    /// chapter ending at `end` (the duration of the video).
+    ///
+    /// The times without subtitle longer than `max_gap_msecs` milliseconds are reported
+    /// as gaps. The chapters of zero duration are ignored.
+    #[must_use]
+    pub fn new(marks: &[TimePoint], end: TimePoint, cues: &[TimeSpan], max_gap_msecs: i64) -> Self {
+        let mut marks = marks.to_vec();
+        marks.sort_unstable();
+        let covered = cues.iter().copied().collect::<TimeSpanSet>();
+
+        let ends = marks.iter().skip(1).copied().chain([end]);
+        let chapters = marks
+            .iter()
+            .zip(ends)
+            .map(|(start, end)| TimeSpan::new(*start, end.max(*start)))
+            .enumerate()
+            .filter(|(_, time)| time.start < time.end)
+            .map(|(index, time)| {
+                let cue_count = cues
+                    .iter()
+                    .filter(|cue| cue.start < time.end && cue.end > time.start)
+                    .count();
+                let gaps = TimeSpanSet::from_iter([time])
+                    .difference(&covered)
+                    .spans()
+                    .iter()
+                    .filter(|gap| gap.end.msecs() - gap.start.msecs() > max_gap_msecs)
+                    .copied()
+                    .collect();
+                ChapterCoverage {
+                    index,
+                    time,
+                    cue_count,
+                    gaps,
+                }
+            })
+            .collect();
+        Self { chapters }
+    }
+
+    /// Coverage of all the chapters, sorted by time.
+    #[must_use]
+    pub fn chapters(&self) -> &[ChapterCoverage] {
+        &self.chapters
+    }
+
+    /// Chapters without subtitles.
+    pub fn empty_chapters(&self) -> impl Iterator<Item = &ChapterCoverage> {
+        self.chapters.iter().filter(|chapter| chapter.is_empty())
+    }
+
+    /// Chapters with subtitles, but with long gaps.
+    pub fn chapters_with_gaps(&self) -> impl Iterator<Item = &ChapterCoverage> {
+        self.chapters
+            .iter()
+            .filter(|chapter| !chapter.is_empty() && !chapter.gaps.is_empty())
+    }
+
+    /// Return `true` if no chapter is empty or has long gaps.
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        !self.chapters.iter().any(ChapterCoverage::has_issue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+
+    #[test]
+    fn report_chapter_gaps() {
+        let time = TimePoint::from_msecs;
+        let marks = [time(0), time(60_000), time(120_000), time(120_000)];
+        let cues = [
+            span(1000, 5000),
+            span(6000, 58_000),
+            // Last cue of the first chapter, ending in the second one.
+            span(59_000, 61_000),
+            span(130_000, 140_000),
+            span(170_000, 178_000),
+        ];
+        let report = ChapterGapReport::new(&marks, time(180_000), &cues, 20_000);
+
+        let chapters = report.chapters();
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[0].cue_count, 3);
+        assert!(!chapters[0].has_issue());
+        // The only cue of the second chapter ends just after its start.
+        assert_eq!(chapters[1].cue_count, 1);
+        assert_eq!(chapters[1].gaps, [span(61_000, 120_000)]);
+        assert_eq!(chapters[2].index, 3);
+        assert_eq!(chapters[2].gaps, [span(140_000, 170_000)]);
+
+        assert!(report.empty_chapters().next().is_none());
+        assert_eq!(report.chapters_with_gaps().count(), 2);
+        assert!(!report.is_ok());
+
+        let empty = ChapterGapReport::new(&marks, time(180_000), &cues[..3], 20_000);
+        let empty_indexes = empty
+            .empty_chapters()
+            .map(|chapter| chapter.index)
+            .collect::<Vec<_>>();
+        assert_eq!(empty_indexes, [3]);
+    }
+}
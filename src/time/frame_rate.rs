@@ -0,0 +1,194 @@
+use super::TimePoint;
+use std::fmt;
+
+/// Frame rate of a video, as an exact fraction of frames per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameRate {
+    num: u32,
+    den: u32,
+}
+
+impl FrameRate {
+    /// `23.976` fps (`24000/1001`), film on `NTSC` video.
+    pub const NTSC_FILM: Self = Self::from_ratio(24000, 1001);
+    /// `24` fps, film.
+    pub const FILM: Self = Self::from_ratio(24, 1);
+    /// `25` fps, `PAL` video.
+    pub const PAL: Self = Self::from_ratio(25, 1);
+    /// `29.97` fps (`30000/1001`), `NTSC` video.
+    pub const NTSC: Self = Self::from_ratio(30000, 1001);
+
+    const fn from_ratio(num: u32, den: u32) -> Self {
+        Self { num, den }
+    }
+
+    /// Create a frame rate of `num / den` frames per second.
+    ///
+    /// Return `None` if `num` or `den` is zero.
+    #[must_use]
+    pub const fn new(num: u32, den: u32) -> Option<Self> {
+        if num == 0 || den == 0 {
+            return None;
+        }
+        let gcd = gcd(num, den);
+        Some(Self::from_ratio(num / gcd, den / gcd))
+    }
+
+    /// Create a frame rate from a number of frames per second, with a precision of
+    /// a thousandth of frame. The usual rates `23.976` and `29.97` are recognized as
+    /// [`Self::NTSC_FILM`] and [`Self::NTSC`].
+    ///
+    /// Return `None` if `fps` isn't positive, or is too big.
+    #[must_use]
+    pub fn from_fps(fps: f64) -> Option<Self> {
+        [Self::NTSC_FILM, Self::NTSC]
+            .into_iter()
+            .find(|rate| (rate.fps() - fps).abs() < 0.001)
+            .or_else(|| Self::new(cast::u32((fps * 1000.).round()).ok()?, 1000))
+    }
+
+    /// Numerator of the frame rate.
+    #[must_use]
+    pub const fn num(self) -> u32 {
+        self.num
+    }
+
+    /// Denominator of the frame rate.
+    #[must_use]
+    pub const fn den(self) -> u32 {
+        self.den
+    }
+
+    /// Number of frames per second.
+    #[must_use]
+    pub fn fps(self) -> f64 {
+        f64::from(self.num) / f64::from(self.den)
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3} fps", self.fps())
+    }
+}
+
+const fn gcd(mut left: u32, mut right: u32) -> u32 {
+    while right != 0 {
+        (left, right) = (right, left % right);
+    }
+    left
+}
+
+/// Frame rates tried by [`detect_frame_rate`] : `23.976`, `24`, `25` and `29.97` fps.
+pub const CANDIDATE_FRAME_RATES: [FrameRate; 4] = [
+    FrameRate::NTSC_FILM,
+    FrameRate::FILM,
+    FrameRate::PAL,
+    FrameRate::NTSC,
+];
+
+/// Frame rate detected by [`detect_frame_rate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct FrameRateEstimate {
+    /// Detected frame rate.
+    pub rate: FrameRate,
+    /// Confidence of the detection, from `0` (the times fit equally another frame rate)
+    /// to `1` (the times fit perfectly this frame rate, and not the others).
+    pub confidence: f64,
+}
+
+/// Detect the frame rate of the video of subtitles from their `times` (the start and end
+/// times of cues from an `idx` file, or the presentation times of `SUP` display sets).
+///
+/// Subtitles are displayed on video frames, so their times are (rounded) multiples of
+/// the frame duration. Each candidate of [`CANDIDATE_FRAME_RATES`] is scored by the mean
+/// distance between the times and the nearest frame, relative to the first time
+/// (so a constant offset doesn't matter). The distinction between close frame rates
+/// (`23.976` and `24`) need times spread over at least a few minutes.
+///
+/// Return `None` if there isn't at least two distinct times.
+#[must_use]
+pub fn detect_frame_rate<I>(times: I) -> Option<FrameRateEstimate>
+where
+    I: IntoIterator<Item = TimePoint>,
+{
+    let mut times = times
+        .into_iter()
+        .map(TimePoint::to_secs)
+        .collect::<Vec<_>>();
+    times.sort_unstable_by(f64::total_cmp);
+    times.dedup();
+    let (&first, others) = times.split_first()?;
+    if others.is_empty() {
+        return None;
+    }
+
+    // Fit of each candidate : 1 if all times are on frames, 0 for randomly placed times
+    // (mean distance of a quarter of frame).
+    let mut fits = CANDIDATE_FRAME_RATES
+        .iter()
+        .map(|&rate| {
+            let fps = rate.fps();
+            let distance = others
+                .iter()
+                .map(|time| {
+                    let frames = (time - first) * fps;
+                    (frames - frames.round()).abs()
+                })
+                .sum::<f64>()
+                / cast::f64(others.len());
+            (rate, (1. - distance * 4.).clamp(0., 1.))
+        })
+        .collect::<Vec<_>>();
+    fits.sort_unstable_by(|(_, left), (_, right)| right.total_cmp(left));
+
+    let (rate, best) = fits[0];
+    let second = fits[1].1;
+    Some(FrameRateEstimate {
+        rate,
+        confidence: best - second,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Times of `count` frames spread over 10 minutes, at `fps`, rounded to milliseconds.
+    fn frame_times(fps: f64, count: u32) -> Vec<TimePoint> {
+        (0..count)
+            .map(|idx| {
+                let frame = f64::from(idx * 7919 % 14_400);
+                TimePoint::from_msecs(cast::i64((frame / fps * 1000.).round()).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_rate_creation() {
+        assert_eq!(FrameRate::new(50, 2), Some(FrameRate::PAL));
+        assert_eq!(FrameRate::new(25, 0), None);
+        assert_eq!(FrameRate::from_fps(23.976), Some(FrameRate::NTSC_FILM));
+        assert_eq!(FrameRate::from_fps(29.97), Some(FrameRate::NTSC));
+        assert_eq!(FrameRate::from_fps(24.), Some(FrameRate::FILM));
+        assert_eq!(FrameRate::from_fps(12.5), FrameRate::new(25, 2));
+        assert_eq!(FrameRate::from_fps(-1.), None);
+        assert_eq!(FrameRate::NTSC_FILM.to_string(), "23.976 fps");
+    }
+
+    #[test]
+    fn detect_frame_rates() {
+        for rate in CANDIDATE_FRAME_RATES {
+            let estimate = detect_frame_rate(frame_times(rate.fps(), 200)).unwrap();
+            assert_eq!(estimate.rate, rate, "{rate}: {estimate:?}");
+            assert!(estimate.confidence > 0.5, "{rate}: {estimate:?}");
+        }
+
+        // Times close together don't distinguish 23.976 and 24 fps.
+        let times = (0..10).map(|idx| TimePoint::from_msecs(idx * 1000));
+        assert!(detect_frame_rate(times).unwrap().confidence < 0.1);
+
+        assert_eq!(detect_frame_rate([TimePoint::from_msecs(10)]), None);
+    }
+}
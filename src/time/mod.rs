@@ -1,6 +1,24 @@
 //! Subtitle Time management
+mod alignment;
+mod chapters;
+mod frame_rate;
+mod time_duration;
 mod time_point;
 mod time_span;
+mod time_span_set;
+mod timeline;
 
+pub use alignment::{AlignedCues, TrackAlignment};
+pub use chapters::{ChapterCoverage, ChapterGapReport};
+pub use frame_rate::{detect_frame_rate, FrameRate, FrameRateEstimate, CANDIDATE_FRAME_RATES};
+pub use time_duration::TimeDuration;
 pub use time_point::TimePoint;
 pub use time_span::TimeSpan;
+pub use time_span_set::TimeSpanSet;
+pub use timeline::{timeline_events, TimelineEvent};
+
+/// Span from `start` to `end`, in milliseconds.
+#[cfg(test)]
+pub(crate) const fn span(start: i64, end: i64) -> TimeSpan {
+    TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end))
+}
@@ -0,0 +1,93 @@
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// Define a signed duration in milliseconds, like the difference between two
+/// [`TimePoint`](super::TimePoint)s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeDuration(i64);
+
+impl TimeDuration {
+    /// A null duration.
+    pub const ZERO: Self = Self(0);
+
+    /// Create a `TimeDuration` from milliseconds
+    #[must_use]
+    pub const fn from_msecs(duration: i64) -> Self {
+        Self(duration)
+    }
+
+    /// Create a `TimeDuration` from seconds
+    ///
+    /// # Panics
+    ///
+    /// Will panics if the `seconds` value is to big to be store as millisecond in a [`i64`].
+    #[must_use]
+    pub fn from_secs(seconds: f64) -> Self {
+        Self(cast::i64(seconds * 1000.0).unwrap())
+    }
+
+    /// Get milliseconds corresponding to `TimeDuration`.
+    #[must_use]
+    pub const fn msecs(self) -> i64 {
+        self.0
+    }
+
+    /// Return `true` if the duration is negative.
+    #[must_use]
+    pub const fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Absolute value of the duration.
+    #[must_use]
+    pub const fn abs(self) -> Self {
+        Self(self.0.saturating_abs())
+    }
+
+    /// Add two durations, returning `None` on overflow.
+    #[must_use]
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_add(rhs.0) {
+            Some(duration) => Some(Self(duration)),
+            None => None,
+        }
+    }
+
+    /// Add two durations, saturating at the bounds instead of overflowing.
+    #[must_use]
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Add for TimeDuration {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for TimeDuration {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl AddAssign for TimeDuration {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for TimeDuration {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for TimeDuration {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
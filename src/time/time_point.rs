@@ -1,5 +1,7 @@
 use core::fmt;
-use std::ops::Neg;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+use super::{FrameRate, TimeDuration};
 
 /// Define a time in milliseconds
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -24,6 +26,29 @@ impl TimePoint {
         Self(msecs)
     }
 
+    /// Create a `TimePoint` from the time of the frame `frame` of a video at `rate`,
+    /// rounded to the nearest millisecond.
+    ///
+    /// The result saturates at the bounds of the time.
+    #[must_use]
+    pub fn from_frames(frame: i64, rate: FrameRate) -> Self {
+        let msecs = div_round(
+            i128::from(frame) * 1000 * i128::from(rate.den()),
+            i128::from(rate.num()),
+        );
+        Self(saturate_i64(msecs))
+    }
+
+    /// Convert to the number of the nearest frame of a video at `rate`.
+    #[must_use]
+    pub fn to_frames(self, rate: FrameRate) -> i64 {
+        let frames = div_round(
+            i128::from(self.0) * i128::from(rate.num()),
+            1000 * i128::from(rate.den()),
+        );
+        saturate_i64(frames)
+    }
+
     /// Convert to seconds
     #[must_use]
     #[expect(clippy::cast_precision_loss)]
@@ -59,6 +84,53 @@ impl TimePoint {
     const fn msecs_comp(self) -> i64 {
         self.msecs() % 1000
     }
+
+    /// Move the time by `duration`, returning `None` on overflow.
+    #[must_use]
+    pub const fn checked_add(self, duration: TimeDuration) -> Option<Self> {
+        match self.0.checked_add(duration.msecs()) {
+            Some(time) => Some(Self(time)),
+            None => None,
+        }
+    }
+
+    /// Move the time by `duration`, saturating at the bounds instead of overflowing.
+    #[must_use]
+    pub const fn saturating_add(self, duration: TimeDuration) -> Self {
+        Self(self.0.saturating_add(duration.msecs()))
+    }
+
+    /// Move the time back by `duration`, saturating at the bounds instead of overflowing.
+    #[must_use]
+    pub const fn saturating_sub(self, duration: TimeDuration) -> Self {
+        Self(self.0.saturating_sub(duration.msecs()))
+    }
+
+    /// Multiply the time by `factor`, rounded to the millisecond, like for a frame rate
+    /// conversion (`25 / 23.976` to convert from 23.976 to 25 fps).
+    ///
+    /// The result saturates at the bounds of the time.
+    #[must_use]
+    #[expect(clippy::cast_precision_loss)]
+    pub fn scale(self, factor: f64) -> Self {
+        let scaled = (self.0 as f64 * factor).round();
+        Self(cast::i64(scaled).unwrap_or(if scaled < 0. { i64::MIN } else { i64::MAX }))
+    }
+}
+
+/// Divide `value` by the positive `divisor`, rounding to the nearest integer
+/// (halves away from zero).
+const fn div_round(value: i128, divisor: i128) -> i128 {
+    let half = divisor / 2;
+    if value < 0 {
+        (value - half) / divisor
+    } else {
+        (value + half) / divisor
+    }
+}
+
+fn saturate_i64(value: i128) -> i64 {
+    i64::try_from(value).unwrap_or(if value < 0 { i64::MIN } else { i64::MAX })
 }
 
 impl Neg for TimePoint {
@@ -68,6 +140,39 @@ impl Neg for TimePoint {
     }
 }
 
+impl Add<TimeDuration> for TimePoint {
+    type Output = Self;
+    fn add(self, rhs: TimeDuration) -> Self {
+        Self(self.0 + rhs.msecs())
+    }
+}
+
+impl Sub<TimeDuration> for TimePoint {
+    type Output = Self;
+    fn sub(self, rhs: TimeDuration) -> Self {
+        Self(self.0 - rhs.msecs())
+    }
+}
+
+impl Sub for TimePoint {
+    type Output = TimeDuration;
+    fn sub(self, rhs: Self) -> TimeDuration {
+        TimeDuration::from_msecs(self.0 - rhs.0)
+    }
+}
+
+impl AddAssign<TimeDuration> for TimePoint {
+    fn add_assign(&mut self, rhs: TimeDuration) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign<TimeDuration> for TimePoint {
+    fn sub_assign(&mut self, rhs: TimeDuration) {
+        *self = *self - rhs;
+    }
+}
+
 impl TimePoint {
     ///TODO
     /// # Errors
@@ -91,6 +196,53 @@ impl TimePoint {
 mod tests {
     use super::*;
 
+    #[test]
+    fn time_point_frames() {
+        assert_eq!(TimePoint::from_frames(25, FrameRate::PAL).msecs(), 1000);
+        assert_eq!(TimePoint::from_frames(1, FrameRate::NTSC_FILM).msecs(), 42);
+        assert_eq!(TimePoint::from_frames(-1, FrameRate::NTSC).msecs(), -33);
+        // One hour of `NTSC` video.
+        let hour = TimePoint::from_msecs(3_600_000);
+        assert_eq!(hour.to_frames(FrameRate::NTSC), 107_892);
+        assert_eq!(
+            TimePoint::from_frames(107_892, FrameRate::NTSC).msecs(),
+            3_599_996
+        );
+        assert_eq!(
+            TimePoint::from_frames(i64::MAX, FrameRate::NTSC_FILM),
+            TimePoint::from_msecs(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn time_point_arithmetic() {
+        let time = TimePoint::from_msecs(10_000);
+        let duration = TimeDuration::from_msecs(1500);
+        assert_eq!(time + duration, TimePoint::from_msecs(11_500));
+        assert_eq!(time - duration, TimePoint::from_msecs(8500));
+        assert_eq!(
+            time - TimePoint::from_msecs(12_000),
+            -TimeDuration::from_secs(2.)
+        );
+        let mut moved = time;
+        moved += duration;
+        moved -= duration + duration;
+        assert_eq!(moved, TimePoint::from_msecs(8500));
+
+        let max = TimePoint::from_msecs(i64::MAX);
+        assert_eq!(max.checked_add(duration), None);
+        assert_eq!(max.saturating_add(duration), max);
+        assert_eq!(
+            TimePoint::from_msecs(i64::MIN).saturating_sub(duration),
+            TimePoint::from_msecs(i64::MIN)
+        );
+        assert_eq!(
+            TimePoint::from_msecs(1001).scale(25. / 24.),
+            TimePoint::from_msecs(1043)
+        );
+        assert_eq!(max.scale(2.), max);
+    }
+
     #[test]
     fn time_point_creation() {
         assert_eq!(TimePoint::from_msecs(6751), TimePoint(6751));
@@ -1,4 +1,4 @@
-use super::TimePoint;
+use super::{FrameRate, TimeDuration, TimePoint};
 use core::fmt::{self, Debug};
 
 /// Define a time span with a start time and an end time.
@@ -16,6 +16,74 @@ impl TimeSpan {
     pub const fn new(start: TimePoint, end: TimePoint) -> Self {
         Self { start, end }
     }
+
+    /// Split the span in consecutive spans of at most `max_msecs` milliseconds, covering
+    /// exactly the same time.
+    ///
+    /// The spans have the same duration (to the millisecond), to avoid a very short last one.
+    /// A span not longer than `max_msecs` (or a `max_msecs` not positive) is returned alone.
+    pub fn split(self, max_msecs: i64) -> impl Iterator<Item = Self> {
+        let duration = self.end.msecs() - self.start.msecs();
+        let count = if max_msecs > 0 && duration > max_msecs {
+            (duration + max_msecs - 1) / max_msecs
+        } else {
+            1
+        };
+        let at = move |idx: i64| TimePoint::from_msecs(self.start.msecs() + duration * idx / count);
+        (0..count).map(move |idx| Self::new(at(idx), at(idx + 1)))
+    }
+
+    /// Duration of the span, negative if it ends before its start.
+    #[must_use]
+    pub fn duration(self) -> TimeDuration {
+        self.end - self.start
+    }
+
+    /// Return `true` if the spans share some time. Spans are half-open intervals :
+    /// a span ending at the start of the other doesn't overlap it.
+    #[must_use]
+    pub fn overlaps(self, other: Self) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// Time shared by the two spans, if they overlap.
+    #[must_use]
+    pub fn intersect(self, other: Self) -> Option<Self> {
+        self.overlaps(other)
+            .then(|| Self::new(self.start.max(other.start), self.end.min(other.end)))
+    }
+
+    /// Move the span by `offset`.
+    #[must_use]
+    pub fn shift(self, offset: TimeDuration) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+
+    /// Move the span by `offset`, saturating at the bounds instead of overflowing.
+    #[must_use]
+    pub const fn saturating_shift(self, offset: TimeDuration) -> Self {
+        Self::new(
+            self.start.saturating_add(offset),
+            self.end.saturating_add(offset),
+        )
+    }
+
+    /// Multiply the start and end times by `factor`, see [`TimePoint::scale`].
+    #[must_use]
+    pub fn scale(self, factor: f64) -> Self {
+        Self::new(self.start.scale(factor), self.end.scale(factor))
+    }
+
+    /// Convert the times of subtitles timed for a video at `from_rate` to the same
+    /// frames of the video at `to_rate`.
+    ///
+    /// Useful to fix the speedup of subtitles extracted from a `PAL` video of a film,
+    /// 4% faster than the original at [`FrameRate::NTSC_FILM`]
+    /// (or [`FrameRate::FILM`]), or the reverse.
+    #[must_use]
+    pub fn rescale(self, from_rate: FrameRate, to_rate: FrameRate) -> Self {
+        self.scale(from_rate.fps() / to_rate.fps())
+    }
 }
 
 impl Debug for TimeSpan {
@@ -27,6 +95,7 @@ impl Debug for TimeSpan {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::time::span;
 
     #[test]
     fn time_span_creation() {
@@ -56,6 +125,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn time_span_split() {
+        assert_eq!(
+            span(1000, 16_000).split(7000).collect::<Vec<_>>(),
+            [span(1000, 6000), span(6000, 11_000), span(11_000, 16_000)]
+        );
+        assert_eq!(
+            span(0, 7000).split(7000).collect::<Vec<_>>(),
+            [span(0, 7000)]
+        );
+    }
+
+    #[test]
+    fn time_span_operations() {
+        let a = span(1000, 3000);
+        assert_eq!(a.duration(), TimeDuration::from_msecs(2000));
+        assert!(a.overlaps(span(2999, 4000)));
+        assert!(!a.overlaps(span(3000, 4000)));
+        assert_eq!(a.intersect(span(2000, 4000)), Some(span(2000, 3000)));
+        assert_eq!(a.intersect(span(0, 1000)), None);
+        assert_eq!(a.shift(TimeDuration::from_msecs(-500)), span(500, 2500));
+        assert_eq!(
+            span(0, i64::MAX).saturating_shift(TimeDuration::from_msecs(10)),
+            span(10, i64::MAX)
+        );
+        assert_eq!(a.scale(0.5), span(500, 1500));
+    }
+
+    #[test]
+    fn time_span_rescale() {
+        // Frames 600 to 650 of a `PAL` video.
+        let pal = TimeSpan::new(TimePoint::from_msecs(24_000), TimePoint::from_msecs(26_000));
+        let film = pal.rescale(FrameRate::PAL, FrameRate::NTSC_FILM);
+        assert_eq!(
+            film.start,
+            TimePoint::from_frames(600, FrameRate::NTSC_FILM)
+        );
+        assert_eq!(film.end, TimePoint::from_frames(650, FrameRate::NTSC_FILM));
+        assert_eq!(film.rescale(FrameRate::NTSC_FILM, FrameRate::PAL), pal);
+    }
+
     #[test]
     fn time_span_nequality() {
         let time_span_0_1 = TimeSpan::new(TimePoint::from_msecs(0), TimePoint::from_secs(1.34));
@@ -0,0 +1,189 @@
+use super::{TimePoint, TimeSpan};
+
+/// A set of time, stored as sorted and disjoint [`TimeSpan`]s.
+///
+/// Spans are handled as half-open intervals (`end` is excluded) : empty spans are ignored,
+/// and overlapping or adjacent spans are merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeSpanSet {
+    spans: Vec<TimeSpan>,
+}
+
+impl TimeSpanSet {
+    /// Create an empty set.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { spans: Vec::new() }
+    }
+
+    /// Add a span to the set.
+    pub fn insert(&mut self, span: TimeSpan) {
+        if span.start < span.end {
+            self.spans.push(span);
+            self.normalize();
+        }
+    }
+
+    /// Sorted and disjoint spans of the set.
+    #[must_use]
+    pub fn spans(&self) -> &[TimeSpan] {
+        &self.spans
+    }
+
+    /// Return `true` if the set doesn't contain any time.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Return `true` if the `time` is in the set.
+    #[must_use]
+    pub fn contains(&self, time: TimePoint) -> bool {
+        let idx = self.spans.partition_point(|span| span.end <= time);
+        self.spans.get(idx).is_some_and(|span| span.start <= time)
+    }
+
+    /// Total duration of the set, in milliseconds.
+    #[must_use]
+    pub fn total_msecs(&self) -> i64 {
+        self.spans
+            .iter()
+            .map(|span| span.end.msecs() - span.start.msecs())
+            .sum()
+    }
+
+    /// Times in `self` or in `other`.
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        self.spans.iter().chain(&other.spans).copied().collect()
+    }
+
+    /// Times both in `self` and in `other`.
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut spans = Vec::new();
+        let (mut lhs, mut rhs) = (self.spans.iter().peekable(), other.spans.iter().peekable());
+        while let (Some(a), Some(b)) = (lhs.peek(), rhs.peek()) {
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if start < end {
+                spans.push(TimeSpan::new(start, end));
+            }
+            if a.end < b.end {
+                lhs.next();
+            } else {
+                rhs.next();
+            }
+        }
+        Self { spans }
+    }
+
+    /// Times in `self` but not in `other`.
+    #[must_use]
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut spans = Vec::new();
+        let mut removed = other.spans.iter().peekable();
+        for span in &self.spans {
+            let mut start = span.start;
+            while let Some(cut) = removed.peek() {
+                if cut.end <= start {
+                    removed.next();
+                    continue;
+                }
+                if cut.start >= span.end {
+                    break;
+                }
+                if cut.start > start {
+                    spans.push(TimeSpan::new(start, cut.start));
+                }
+                start = cut.end;
+                if cut.end > span.end {
+                    break;
+                }
+                removed.next();
+            }
+            if start < span.end {
+                spans.push(TimeSpan::new(start, span.end));
+            }
+        }
+        Self { spans }
+    }
+
+    /// Sort and merge the spans.
+    fn normalize(&mut self) {
+        self.spans.sort_unstable_by_key(|span| span.start);
+        let mut merged: Vec<TimeSpan> = Vec::with_capacity(self.spans.len());
+        for span in self.spans.drain(..) {
+            match merged.last_mut() {
+                Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+                Some(_) | None => merged.push(span),
+            }
+        }
+        self.spans = merged;
+    }
+}
+
+impl FromIterator<TimeSpan> for TimeSpanSet {
+    fn from_iter<T: IntoIterator<Item = TimeSpan>>(iter: T) -> Self {
+        let mut set = Self {
+            spans: iter
+                .into_iter()
+                .filter(|span| span.start < span.end)
+                .collect(),
+        };
+        set.normalize();
+        set
+    }
+}
+
+impl Extend<TimeSpan> for TimeSpanSet {
+    fn extend<T: IntoIterator<Item = TimeSpan>>(&mut self, iter: T) {
+        self.spans
+            .extend(iter.into_iter().filter(|span| span.start < span.end));
+        self.normalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+
+    fn set(spans: &[(i64, i64)]) -> TimeSpanSet {
+        spans.iter().map(|&(start, end)| span(start, end)).collect()
+    }
+
+    #[test]
+    fn normalize_spans() {
+        let spans = set(&[(50, 60), (0, 10), (10, 20), (15, 30), (40, 40)]);
+        assert_eq!(spans, set(&[(0, 30), (50, 60)]));
+        assert_eq!(spans.spans().len(), 2);
+        assert_eq!(spans.total_msecs(), 40);
+        assert!(spans.contains(TimePoint::from_msecs(29)));
+        assert!(!spans.contains(TimePoint::from_msecs(30)));
+        assert!(!spans.contains(TimePoint::from_msecs(45)));
+
+        let mut inserted = spans;
+        inserted.insert(TimeSpan::new(
+            TimePoint::from_msecs(30),
+            TimePoint::from_msecs(50),
+        ));
+        assert_eq!(inserted, set(&[(0, 60)]));
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = set(&[(0, 100), (200, 300)]);
+        let b = set(&[(50, 250), (280, 290), (400, 500)]);
+
+        assert_eq!(a.union(&b), set(&[(0, 300), (400, 500)]));
+        assert_eq!(
+            a.intersection(&b),
+            set(&[(50, 100), (200, 250), (280, 290)])
+        );
+        assert_eq!(a.difference(&b), set(&[(0, 50), (250, 280), (290, 300)]));
+        assert_eq!(b.difference(&a), set(&[(100, 200), (400, 500)]));
+        assert!(a.difference(&a).is_empty());
+        assert_eq!(a.difference(&TimeSpanSet::new()), a);
+    }
+}
@@ -0,0 +1,99 @@
+use super::{TimePoint, TimeSpan};
+
+/// An event of the timeline of a subtitle track.
+///
+/// `Show` is the start of the display of a cue, and `Hide` its end. This maps to the
+/// start and stop dates of `VobSub` subtitles, and to the display sets showing
+/// and clearing the `PGS` objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimelineEvent<C> {
+    /// Start displaying the cue identified by `id`.
+    Show {
+        /// Time of the event.
+        time: TimePoint,
+        /// Identifier of the cue : its index in the source track.
+        id: usize,
+        /// Content of the cue.
+        cue: C,
+    },
+    /// Stop displaying the cue identified by `id`.
+    Hide {
+        /// Time of the event.
+        time: TimePoint,
+        /// Identifier of the cue : its index in the source track.
+        id: usize,
+    },
+}
+
+impl<C> TimelineEvent<C> {
+    /// Time of the event.
+    #[must_use]
+    pub const fn time(&self) -> TimePoint {
+        match self {
+            Self::Show { time, .. } | Self::Hide { time, .. } => *time,
+        }
+    }
+
+    /// Identifier of the cue concerned by the event.
+    #[must_use]
+    pub const fn id(&self) -> usize {
+        match self {
+            Self::Show { id, .. } | Self::Hide { id, .. } => *id,
+        }
+    }
+}
+
+/// Convert the cues of a track in a stream of [`TimelineEvent`]s, ordered by time.
+///
+/// Each cue is identified by its index in `cues`. At the same time, `Hide` events come
+/// before `Show` events, so a player never displays two consecutive cues together,
+/// and events of the same kind keep the order of the cues.
+pub fn timeline_events<C, I>(cues: I) -> Vec<TimelineEvent<C>>
+where
+    I: IntoIterator<Item = (TimeSpan, C)>,
+{
+    let mut events = Vec::new();
+    for (id, (time, cue)) in cues.into_iter().enumerate() {
+        events.push(TimelineEvent::Show {
+            time: time.start,
+            id,
+            cue,
+        });
+        events.push(TimelineEvent::Hide { time: time.end, id });
+    }
+    events.sort_by_key(|event| (event.time(), matches!(event, TimelineEvent::Show { .. })));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+
+    #[test]
+    fn events_in_time_order() {
+        let cues = [
+            (span(1000, 2000), "first"),
+            (span(2000, 3000), "second"),
+            (span(1500, 2500), "overlap"),
+        ];
+        let events = timeline_events(cues)
+            .into_iter()
+            .map(|event| match event {
+                TimelineEvent::Show { time, id, cue } => (time.msecs(), id, Some(cue)),
+                TimelineEvent::Hide { time, id } => (time.msecs(), id, None),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (1000, 0, Some("first")),
+                (1500, 2, Some("overlap")),
+                (2000, 0, None),
+                (2000, 1, Some("second")),
+                (2500, 2, None),
+                (3000, 1, None),
+            ]
+        );
+    }
+}
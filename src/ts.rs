@@ -0,0 +1,741 @@
+//! Demuxing of subtitle streams from MPEG Transport Stream (`*.ts` and `*.m2ts`) files.
+//!
+//! The `PAT` and `PMT` tables are parsed to find the subtitle streams of the programs,
+//! and the `PES` packets of the selected streams are reassembled. The payload of a
+//! `PGS` packet can be converted in `sup` format with [`PesPacket::to_sup`] to feed
+//! the [`SupParser`](crate::pgs::SupParser).
+//!
+//! The `PSI` sections are expected to fit in a single transport packet, which is the
+//! case of the tables of the usual captures.
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::time::TimePoint;
+
+/// Size of a transport packet.
+const PACKET_LEN: usize = 188;
+/// Size of the timestamp prefixing the transport packets in a `*.m2ts` file.
+const M2TS_PREFIX_LEN: usize = 4;
+/// First byte of a transport packet.
+const SYNC_BYTE: u8 = 0x47;
+/// `PID` of the Program Association Table.
+const PAT_PID: u16 = 0x0000;
+/// Stream type of the `PGS` streams in a `PMT`.
+const PGS_STREAM_TYPE: u8 = 0x90;
+/// Stream type of the `PES` private data, used by the `DVB` subtitles.
+const PRIVATE_PES_STREAM_TYPE: u8 = 0x06;
+/// Tag of the `ISO 639` language descriptor.
+const LANGUAGE_DESCRIPTOR: u8 = 0x0a;
+/// Tag of the `DVB` subtitling descriptor.
+const SUBTITLING_DESCRIPTOR: u8 = 0x59;
+/// Size of the `PES` header before the optional fields.
+const PES_HEADER_LEN: usize = 9;
+/// Frequency of the presentation timestamps.
+const PTS_FREQUENCY_KHZ: u64 = 90;
+
+/// Error of the [`TsDemuxer`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TsError {
+    /// Io error on a path.
+    #[error("Io error on '{path}'")]
+    Io {
+        /// Source error
+        source: io::Error,
+        /// Path of the file we tried to read
+        path: PathBuf,
+    },
+
+    /// Reading the transport stream failed.
+    #[error("failed to read the transport stream")]
+    Read(#[from] io::Error),
+
+    /// No sync byte at the start of a transport packet.
+    #[error("lost sync with the transport packets at offset {offset}")]
+    LostSync {
+        /// Offset of the packet in the stream.
+        offset: u64,
+    },
+
+    /// A reassembled `PES` packet doesn't start with a valid header.
+    #[error("invalid PES header in stream {pid:#06x}")]
+    InvalidPes {
+        /// `PID` of the stream.
+        pid: u16,
+    },
+}
+
+/// Kind of an elementary stream, from its declaration in the `PMT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    /// `BluRay` Presentation Graphic Stream subtitles.
+    Pgs,
+    /// `DVB` subtitles.
+    DvbSubtitle,
+    /// Other stream, with its stream type.
+    Other(u8),
+}
+
+impl StreamKind {
+    /// Return `true` for the subtitle streams.
+    #[must_use]
+    pub const fn is_subtitle(self) -> bool {
+        matches!(self, Self::Pgs | Self::DvbSubtitle)
+    }
+}
+
+/// An elementary stream declared in a `PMT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TsStream {
+    pid: u16,
+    kind: StreamKind,
+    lang: Option<String>,
+}
+
+impl TsStream {
+    /// `PID` of the transport packets of the stream.
+    #[must_use]
+    pub const fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Kind of the stream.
+    #[must_use]
+    pub const fn kind(&self) -> StreamKind {
+        self.kind
+    }
+
+    /// `ISO 639` language code of the stream, if declared.
+    #[must_use]
+    pub fn lang(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
+}
+
+/// A reassembled `PES` packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PesPacket {
+    pid: u16,
+    pts: Option<u64>,
+    dts: Option<u64>,
+    data: Vec<u8>,
+}
+
+impl PesPacket {
+    /// `PID` of the stream of the packet.
+    #[must_use]
+    pub const fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Presentation timestamp, in 90 kHz ticks.
+    #[must_use]
+    pub const fn pts(&self) -> Option<u64> {
+        self.pts
+    }
+
+    /// Decoding timestamp, in 90 kHz ticks.
+    #[must_use]
+    pub const fn dts(&self) -> Option<u64> {
+        self.dts
+    }
+
+    /// Payload of the packet, after the `PES` header.
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Presentation time of the packet.
+    #[must_use]
+    pub fn time(&self) -> Option<TimePoint> {
+        let msecs = i64::try_from(self.pts? / PTS_FREQUENCY_KHZ).ok()?;
+        Some(TimePoint::from_msecs(msecs))
+    }
+
+    /// Convert the `PGS` segments of the payload in `sup` format, with the timestamps
+    /// of the packet. A truncated last segment is dropped.
+    #[must_use]
+    pub fn to_sup(&self) -> Vec<u8> {
+        // The `sup` timestamps are the 32 lower bits of the 33 bits clock.
+        let timestamp = |ts: Option<u64>| (ts.unwrap_or(0) & u64::from(u32::MAX)).to_be_bytes();
+        let (pts, dts) = (timestamp(self.pts), timestamp(self.dts));
+        let mut sup = Vec::with_capacity(self.data.len() + self.data.len() / 8);
+        let mut remaining = self.data.as_slice();
+        while let [type_code, size_high, size_low, rest @ ..] = remaining {
+            let size = usize::from(u16::from_be_bytes([*size_high, *size_low]));
+            let Some(content) = rest.get(..size) else {
+                break;
+            };
+            sup.extend(b"PG");
+            sup.extend(&pts[4..]);
+            sup.extend(&dts[4..]);
+            sup.extend([*type_code, *size_high, *size_low]);
+            sup.extend(content);
+            remaining = &rest[size..];
+        }
+        sup
+    }
+}
+
+/// A `PES` packet being reassembled.
+struct PesBuffer {
+    data: Vec<u8>,
+    continuity_counter: u8,
+}
+
+/// Demuxer of the `PES` packets of a transport stream, see the [module](self) doc.
+///
+/// By default, the packets of all the subtitle streams declared in the `PMT` are
+/// returned. The size of the transport packets, 188 bytes or 192 bytes for `*.m2ts`
+/// files, is detected from the start of the stream.
+///
+/// After a [`TsError::LostSync`], the iteration can go on: the demuxer skips to the
+/// next sync byte followed by another one a packet further.
+pub struct TsDemuxer<R: BufRead> {
+    reader: R,
+    packet_len: Option<usize>,
+    offset: u64,
+    pids: Vec<u16>,
+    pmt_pids: Vec<u16>,
+    streams: Vec<TsStream>,
+    buffers: HashMap<u16, PesBuffer>,
+    ready: VecDeque<PesPacket>,
+    lost_sync: bool,
+    done: bool,
+}
+
+impl<R: BufRead> TsDemuxer<R> {
+    /// Create a demuxer of the transport stream of `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            packet_len: None,
+            offset: 0,
+            pids: Vec::new(),
+            pmt_pids: Vec::new(),
+            streams: Vec::new(),
+            buffers: HashMap::new(),
+            ready: VecDeque::new(),
+            lost_sync: false,
+            done: false,
+        }
+    }
+
+    /// Only return the packets of the stream `pid`, whatever its kind.
+    /// Can be called several times to select several streams.
+    #[must_use]
+    pub fn with_pid(mut self, pid: u16) -> Self {
+        self.pids.push(pid);
+        self
+    }
+
+    /// Streams declared in the `PMT` read so far.
+    #[must_use]
+    pub fn streams(&self) -> &[TsStream] {
+        &self.streams
+    }
+
+    /// Read the next transport packet, return `false` at the end of the stream.
+    fn read_packet(&mut self, packet: &mut [u8; PACKET_LEN]) -> Result<bool, TsError> {
+        let head = self.reader.fill_buf()?;
+        if head.is_empty() {
+            return Ok(false);
+        }
+        let packet_len = if let Some(packet_len) = self.packet_len {
+            packet_len
+        } else {
+            let packet_len = detect_packet_len(head).ok_or(TsError::LostSync { offset: 0 })?;
+            self.packet_len = Some(packet_len);
+            packet_len
+        };
+        if self.lost_sync {
+            if !self.resync(packet_len)? {
+                return Ok(false);
+            }
+            self.lost_sync = false;
+        }
+
+        let offset = self.offset;
+        let sync_at = packet_len - PACKET_LEN;
+        let head = self.reader.fill_buf()?;
+        if head.get(sync_at).is_some_and(|byte| *byte != SYNC_BYTE) {
+            // Leave the packet in the reader, the next packet may start inside it.
+            self.lost_sync = true;
+            return Err(TsError::LostSync { offset });
+        }
+        if packet_len > PACKET_LEN {
+            let mut prefix = [0; M2TS_PREFIX_LEN];
+            self.reader.read_exact(&mut prefix)?;
+        }
+        self.reader.read_exact(packet)?;
+        self.offset += u64::try_from(packet_len).unwrap_or(u64::MAX);
+        if packet[0] == SYNC_BYTE {
+            Ok(true)
+        } else {
+            self.lost_sync = true;
+            Err(TsError::LostSync { offset })
+        }
+    }
+
+    /// Skip the bytes before the next sync byte followed by another one a packet
+    /// further, or by the end of the buffered data. Return `false` at the end of the
+    /// stream.
+    fn resync(&mut self, packet_len: usize) -> Result<bool, TsError> {
+        let sync_at = packet_len - PACKET_LEN;
+        loop {
+            let head = self.reader.fill_buf()?;
+            if head.is_empty() {
+                return Ok(false);
+            }
+            let found = (sync_at..head.len()).find(|&at| {
+                head[at] == SYNC_BYTE
+                    && head
+                        .get(at + packet_len)
+                        .map_or(true, |byte| *byte == SYNC_BYTE)
+            });
+            let skip = found.map_or(head.len(), |at| at - sync_at);
+            self.reader.consume(skip);
+            self.offset += u64::try_from(skip).unwrap_or(u64::MAX);
+            if found.is_some() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Handle a transport packet.
+    fn process_packet(&mut self, packet: &[u8; PACKET_LEN]) -> Result<(), TsError> {
+        let transport_error = packet[1] & 0x80 != 0;
+        let unit_start = packet[1] & 0x40 != 0;
+        let pid = (u16::from(packet[1] & 0x1f) << 8) | u16::from(packet[2]);
+        let adaptation_field = packet[3] & 0x20 != 0;
+        let has_payload = packet[3] & 0x10 != 0;
+        let continuity_counter = packet[3] & 0x0f;
+        if transport_error || !has_payload {
+            return Ok(());
+        }
+        let start = if adaptation_field {
+            5 + usize::from(packet[4])
+        } else {
+            4
+        };
+        let Some(payload) = packet.get(start..) else {
+            return Ok(());
+        };
+
+        if pid == PAT_PID || self.pmt_pids.contains(&pid) {
+            if let Some((table_id, section)) = psi_section(unit_start, payload) {
+                match table_id {
+                    0x00 => self.parse_pat(section),
+                    0x02 => self.parse_pmt(section),
+                    _ => {}
+                }
+            }
+        } else if self.is_selected(pid) {
+            return self.reassemble(pid, unit_start, continuity_counter, payload);
+        }
+        Ok(())
+    }
+
+    fn is_selected(&self, pid: u16) -> bool {
+        if self.pids.is_empty() {
+            self.streams
+                .iter()
+                .any(|stream| stream.pid == pid && stream.kind.is_subtitle())
+        } else {
+            self.pids.contains(&pid)
+        }
+    }
+
+    /// Register the `PMT` of the programs of a `PAT` section.
+    fn parse_pat(&mut self, section: &[u8]) {
+        for program in section.chunks_exact(4) {
+            let program_number = u16::from_be_bytes([program[0], program[1]]);
+            let pid = (u16::from(program[2] & 0x1f) << 8) | u16::from(program[3]);
+            // The program 0 is the network information table.
+            if program_number != 0 && !self.pmt_pids.contains(&pid) {
+                self.pmt_pids.push(pid);
+            }
+        }
+    }
+
+    /// Register the elementary streams of a `PMT` section.
+    fn parse_pmt(&mut self, section: &[u8]) {
+        let Some(&[_, _, info_high, info_low]) = section.get(..4) else {
+            return;
+        };
+        let info_len = (usize::from(info_high & 0x0f) << 8) | usize::from(info_low);
+        let mut remaining = section.get(4 + info_len..).unwrap_or_default();
+        while let [stream_type, pid_high, pid_low, info_high, info_low, rest @ ..] = remaining {
+            let pid = (u16::from(pid_high & 0x1f) << 8) | u16::from(*pid_low);
+            let info_len = (usize::from(info_high & 0x0f) << 8) | usize::from(*info_low);
+            let descriptors = rest.get(..info_len).unwrap_or(rest);
+            remaining = rest.get(info_len..).unwrap_or_default();
+            if self.streams.iter().any(|stream| stream.pid == pid) {
+                continue;
+            }
+            self.streams.push(stream(*stream_type, pid, descriptors));
+        }
+    }
+
+    /// Append a `payload` to the `PES` packet of the stream `pid`.
+    fn reassemble(
+        &mut self,
+        pid: u16,
+        unit_start: bool,
+        continuity_counter: u8,
+        payload: &[u8],
+    ) -> Result<(), TsError> {
+        if unit_start {
+            if let Some(buffer) = self.buffers.remove(&pid) {
+                self.complete(pid, &buffer.data)?;
+            }
+            self.buffers.insert(
+                pid,
+                PesBuffer {
+                    data: payload.to_vec(),
+                    continuity_counter,
+                },
+            );
+        } else if let Some(buffer) = self.buffers.get_mut(&pid) {
+            if continuity_counter == buffer.continuity_counter {
+                // Duplicated packet.
+                return Ok(());
+            }
+            if continuity_counter != (buffer.continuity_counter + 1) & 0x0f {
+                // Packets were lost, the `PES` packet can't be reassembled.
+                self.buffers.remove(&pid);
+                return Ok(());
+            }
+            buffer.continuity_counter = continuity_counter;
+            buffer.data.extend(payload);
+        }
+
+        // Complete the packet as soon as its declared length is reached.
+        if let Some(buffer) = self.buffers.get(&pid) {
+            if pes_len(&buffer.data).is_some_and(|len| buffer.data.len() >= len) {
+                if let Some(buffer) = self.buffers.remove(&pid) {
+                    self.complete(pid, &buffer.data)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the header of a reassembled `PES` packet.
+    fn complete(&mut self, pid: u16, data: &[u8]) -> Result<(), TsError> {
+        let packet = parse_pes(pid, data).ok_or(TsError::InvalidPes { pid })?;
+        self.ready.push_back(packet);
+        Ok(())
+    }
+
+    /// Complete the packets being reassembled at the end of the stream.
+    fn flush(&mut self) -> Result<(), TsError> {
+        let mut buffers = self.buffers.drain().collect::<Vec<_>>();
+        buffers.sort_unstable_by_key(|(pid, _)| *pid);
+        for (pid, buffer) in buffers {
+            self.complete(pid, &buffer.data)?;
+        }
+        Ok(())
+    }
+}
+
+impl TsDemuxer<BufReader<File>> {
+    /// Create a demuxer for a `*.ts` or `*.m2ts` file from the path of the file.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`TsError::Io`] if the file can't be opened.
+    pub fn from_file<P>(path: P) -> Result<Self, TsError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|source| TsError::Io {
+            source,
+            path: path.into(),
+        })?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead> Iterator for TsDemuxer<R> {
+    type Item = Result<PesPacket, TsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut packet = [0; PACKET_LEN];
+        loop {
+            if let Some(packet) = self.ready.pop_front() {
+                return Some(Ok(packet));
+            }
+            if self.done {
+                return None;
+            }
+            let result = match self.read_packet(&mut packet) {
+                Ok(true) => self.process_packet(&packet),
+                Ok(false) => {
+                    self.done = true;
+                    self.flush()
+                }
+                Err(err) => {
+                    // Only a lost sync can be recovered from.
+                    self.done = !self.lost_sync;
+                    Err(err)
+                }
+            };
+            if let Err(err) = result {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// Size of the transport packets from the start of the stream, with or without the
+/// `*.m2ts` prefix.
+fn detect_packet_len(head: &[u8]) -> Option<usize> {
+    let at = |offset: usize| head.get(offset) == Some(&SYNC_BYTE);
+    if at(0) && (head.len() <= PACKET_LEN || at(PACKET_LEN)) {
+        Some(PACKET_LEN)
+    } else if at(M2TS_PREFIX_LEN) {
+        Some(M2TS_PREFIX_LEN + PACKET_LEN)
+    } else {
+        None
+    }
+}
+
+/// Table id and content of the `PSI` section starting in a `payload`, without its
+/// header and `CRC`.
+fn psi_section(unit_start: bool, payload: &[u8]) -> Option<(u8, &[u8])> {
+    if !unit_start {
+        return None;
+    }
+    let (&pointer, payload) = payload.split_first()?;
+    let section = payload.get(usize::from(pointer)..)?;
+    let &[table_id, length_high, length_low, ..] = section else {
+        return None;
+    };
+    let length = (usize::from(length_high & 0x0f) << 8) | usize::from(length_low);
+    // Skip the 5 bytes of the extended header, and the 4 bytes of `CRC`.
+    let content = section.get(8..(3 + length).checked_sub(4)?)?;
+    Some((table_id, content))
+}
+
+/// Elementary stream of `stream_type` with the `descriptors` of the `PMT`.
+fn stream(stream_type: u8, pid: u16, descriptors: &[u8]) -> TsStream {
+    let mut kind = match stream_type {
+        PGS_STREAM_TYPE => StreamKind::Pgs,
+        _ => StreamKind::Other(stream_type),
+    };
+    let mut lang = None;
+    let mut remaining = descriptors;
+    while let [tag, len, rest @ ..] = remaining {
+        let content = rest.get(..usize::from(*len)).unwrap_or(rest);
+        remaining = rest.get(usize::from(*len)..).unwrap_or_default();
+        match *tag {
+            LANGUAGE_DESCRIPTOR => {}
+            SUBTITLING_DESCRIPTOR if stream_type == PRIVATE_PES_STREAM_TYPE => {
+                kind = StreamKind::DvbSubtitle;
+            }
+            _ => continue,
+        }
+        // Both descriptors start with a language code.
+        if let Some(code) = content.get(..3) {
+            lang = Some(String::from_utf8_lossy(code).into_owned());
+        }
+    }
+    TsStream { pid, kind, lang }
+}
+
+/// Full length of the `PES` packet in `data`, if declared.
+fn pes_len(data: &[u8]) -> Option<usize> {
+    let len = usize::from(u16::from_be_bytes([*data.get(4)?, *data.get(5)?]));
+    (len != 0).then_some(6 + len)
+}
+
+/// Parse the `PES` packet in `data`.
+fn parse_pes(pid: u16, data: &[u8]) -> Option<PesPacket> {
+    if data.get(..3)? != [0x00, 0x00, 0x01] {
+        return None;
+    }
+    let data = pes_len(data).map_or(data, |len| &data[..len.min(data.len())]);
+    let flags = *data.get(7)?;
+    let header_len = usize::from(*data.get(8)?);
+    let timestamp = |position: usize| {
+        let bytes = data.get(position..position + 5)?;
+        Some(
+            (u64::from(bytes[0] & 0x0e) << 29)
+                | (u64::from(bytes[1]) << 22)
+                | (u64::from(bytes[2] & 0xfe) << 14)
+                | (u64::from(bytes[3]) << 7)
+                | (u64::from(bytes[4]) >> 1),
+        )
+    };
+    let pts = (flags & 0x80 != 0)
+        .then(|| timestamp(PES_HEADER_LEN))
+        .flatten();
+    let dts = (flags & 0x40 != 0)
+        .then(|| timestamp(PES_HEADER_LEN + 5))
+        .flatten();
+    Some(PesPacket {
+        pid,
+        pts,
+        dts,
+        data: data.get(PES_HEADER_LEN + header_len..)?.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+    use crate::{
+        pgs::{DecodeTimeOnly, SupParser, SupWriter},
+        raster::PlacedBitmap,
+        sink::CueSink as _,
+        vobsub::pts_bytes,
+    };
+    use assert_matches2::assert_matches;
+    use image::{Rgba, RgbaImage};
+    use std::io::Cursor;
+
+    const PMT_PID: u16 = 0x0100;
+    const PGS_PID: u16 = 0x1200;
+
+    /// Transport packets of the `payload` of the stream `pid`, padded with an
+    /// adaptation field.
+    fn packetize(ts: &mut Vec<Vec<u8>>, pid: u16, counter: &mut u8, payload: &[u8]) {
+        for (position, chunk) in payload.chunks(PACKET_LEN - 4).enumerate() {
+            let mut packet = vec![SYNC_BYTE, 0, 0, 0];
+            packet[1..3].copy_from_slice(&pid.to_be_bytes());
+            if position == 0 {
+                packet[1] |= 0x40;
+            }
+            packet[3] = 0x10 | *counter;
+            *counter = (*counter + 1) & 0x0f;
+            let padding = PACKET_LEN - 4 - chunk.len();
+            if padding > 0 {
+                packet[3] |= 0x20;
+                packet.push(u8::try_from(padding - 1).unwrap());
+                if padding > 1 {
+                    packet.push(0x00);
+                    packet.extend(std::iter::repeat(0xff).take(padding - 2));
+                }
+            }
+            packet.extend(chunk);
+            ts.push(packet);
+        }
+    }
+
+    /// `PSI` section with its pointer field and a dummy `CRC`.
+    fn section(table_id: u8, content: &[u8]) -> Vec<u8> {
+        let length = u16::try_from(5 + content.len() + 4).unwrap() | 0xb000;
+        let mut section = vec![0, table_id];
+        section.extend(length.to_be_bytes());
+        section.extend([0x00, 0x01, 0xc1, 0x00, 0x00]);
+        section.extend(content);
+        section.extend([0; 4]);
+        section
+    }
+
+    #[test]
+    fn demux_pgs_stream() {
+        let image = RgbaImage::from_pixel(300, 60, Rgba([255, 255, 255, 255]));
+        let times = [span(1000, 2000), span(3000, 4500)];
+        let mut writer = SupWriter::new(Vec::new());
+        for time in times {
+            writer
+                .write_cue(time, &PlacedBitmap::new(100, 900, image.clone()))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        let sup = writer.into_inner();
+
+        // Group the segments of the same display time in a `PES` packet.
+        let mut pes_packets: Vec<(u32, Vec<u8>)> = Vec::new();
+        let mut remaining = sup.as_slice();
+        while !remaining.is_empty() {
+            let pts = u32::from_be_bytes(remaining[2..6].try_into().unwrap());
+            let size = usize::from(u16::from_be_bytes([remaining[11], remaining[12]]));
+            let segment = &remaining[10..13 + size];
+            match pes_packets.last_mut() {
+                Some((last_pts, data)) if *last_pts == pts => data.extend(segment),
+                _ => pes_packets.push((pts, segment.to_vec())),
+            }
+            remaining = &remaining[13 + size..];
+        }
+
+        let mut packets = Vec::new();
+        let mut pmt = vec![0xe1, 0x00, 0xf0, 0x00]; // PCR PID, no program info
+        pmt.extend([PGS_STREAM_TYPE, 0xf2, 0x00, 0xf0, 0x06]);
+        pmt.extend([LANGUAGE_DESCRIPTOR, 4, b'f', b'r', b'a', 0]);
+        packetize(
+            &mut packets,
+            PAT_PID,
+            &mut 0,
+            &section(0x00, &[0, 1, 0xe1, 0x00]),
+        );
+        packetize(&mut packets, PMT_PID, &mut 0, &section(0x02, &pmt));
+        let mut counter = 0;
+        for (pts, data) in &pes_packets {
+            let mut pes = vec![0x00, 0x00, 0x01, 0xbd];
+            pes.extend(u16::try_from(8 + data.len()).unwrap().to_be_bytes());
+            pes.extend([0x81, 0x80, 5]);
+            pes.extend(pts_bytes(u64::from(*pts)));
+            pes.extend(data);
+            packetize(&mut packets, PGS_PID, &mut counter, &pes);
+        }
+
+        // Plain transport stream, and `m2ts` with the timestamp prefix.
+        for prefix in [None, Some([0x47, 0, 0, 0])] {
+            let ts = packets
+                .iter()
+                .flat_map(|packet| prefix.iter().flatten().chain(packet).copied())
+                .collect::<Vec<_>>();
+            let mut demuxer = TsDemuxer::new(ts.as_slice());
+            let pes_read = demuxer.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(demuxer.streams().len(), 1);
+            assert_eq!(demuxer.streams()[0].kind(), StreamKind::Pgs);
+            assert_eq!(demuxer.streams()[0].lang(), Some("fra"));
+            assert_eq!(pes_read.len(), pes_packets.len());
+            assert_eq!(pes_read[0].time(), Some(times[0].start));
+
+            let sup_demuxed = pes_read
+                .iter()
+                .flat_map(PesPacket::to_sup)
+                .collect::<Vec<_>>();
+            assert_eq!(sup_demuxed, sup);
+            let decoded = SupParser::<_, DecodeTimeOnly>::new(Cursor::new(sup_demuxed))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(decoded, times);
+        }
+
+        // Garbage between the tables and the first `PES` packet.
+        let garbage = [0x00, 0x47, 0x12, 0x00, 0x10];
+        let mut ts = packets[..2].concat();
+        ts.extend(garbage);
+        ts.extend(packets[2..].concat());
+        let mut demuxer = TsDemuxer::new(ts.as_slice());
+        let offset = u64::try_from(2 * PACKET_LEN).unwrap();
+        assert_matches!(
+            demuxer.next(),
+            Some(Err(TsError::LostSync { offset: lost }))
+        );
+        assert_eq!(lost, offset);
+        let pes_read = demuxer.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(pes_read.len(), pes_packets.len());
+        assert_eq!(
+            pes_read
+                .iter()
+                .flat_map(PesPacket::to_sup)
+                .collect::<Vec<_>>(),
+            sup
+        );
+    }
+}
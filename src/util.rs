@@ -2,6 +2,41 @@
 
 use std::fmt;
 
+/// Compute the `FNV-1a` hash of bytes, stable across runs and platforms.
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.into_iter().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Apply `f` to each of the `items` in parallel, and return the results in the order of
+/// the items : on the `rayon` thread pool with the `rayon` feature, on scoped threads
+/// otherwise.
+#[cfg(any(feature = "rayon", feature = "parallel"))]
+pub fn par_map<T, U, F>(items: Vec<T>, f: F) -> Vec<U>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> U + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+        items.into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        use crate::parallel::{available_workers, map_ordered};
+        map_ordered(items, available_workers(), f)
+    }
+}
+
+/// Compile-time check that a type can be moved to and shared between threads.
+pub const fn assert_send_sync<T: Send + Sync>() {}
+
 /// Wrapper to force a `&[u8]` to display as nicely-formatted hexadecimal
 /// bytes with only the the first line or so of bytes shown.
 pub struct BytesFormatter<'a>(pub &'a [u8]);
@@ -0,0 +1,236 @@
+//! Compact storage of the decoded subtitles of a whole stream.
+//!
+//! Collecting thousands of [`VobSubCue`] makes as many small allocations for their
+//! pixels. A [`CueArena`] stores the pixels of all the cues in one buffer, next to a
+//! table of their metadata, which reduces the peak memory and keeps the images
+//! contiguous for the tools keeping a whole stream in memory, like editors.
+
+use core::ops::Range;
+
+use super::{sub::VobsubParser, VobSubCue, VobSubError, VobSubIndexedImage};
+use crate::{content::Area, image::ImageArea as _, time::TimeSpan};
+
+/// Metadata of a cue of a [`CueArena`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CueEntry {
+    time: TimeSpan,
+    area: Area,
+    palette: [u8; 4],
+    alpha: [u8; 4],
+    forced: bool,
+    /// Position of the pixels of the cue in the data of the arena.
+    pixels: Range<usize>,
+}
+
+/// Decoded subtitles stored in a single buffer of pixels and a table of metadata.
+///
+/// Filled by the `collect_cues` method of the iterator returned by
+/// [`Sub::subtitles`](super::Sub::subtitles), or with [`Self::push`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CueArena {
+    entries: Vec<CueEntry>,
+    data: Vec<u8>,
+}
+
+impl CueArena {
+    /// Create an empty arena.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Create an empty arena, with room for `cues` cues and `bytes` bytes of pixels
+    /// (one byte by pixel) before reallocating.
+    #[must_use]
+    pub fn with_capacity(cues: usize, bytes: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(cues),
+            data: Vec::with_capacity(bytes),
+        }
+    }
+
+    /// Number of cues in the arena.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the arena has no cue.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of bytes of pixels of all the cues.
+    #[must_use]
+    pub fn data_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Number of cues and of bytes of pixels the arena can hold without reallocating.
+    #[must_use]
+    pub fn capacity(&self) -> (usize, usize) {
+        (self.entries.capacity(), self.data.capacity())
+    }
+
+    /// Reserve room for at least `cues` more cues and `bytes` more bytes of pixels.
+    pub fn reserve(&mut self, cues: usize, bytes: usize) {
+        self.entries.reserve(cues);
+        self.data.reserve(bytes);
+    }
+
+    /// Release the unused capacity, once all the cues are added.
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        self.data.shrink_to_fit();
+    }
+
+    /// Copy a `cue` at the end of the arena.
+    pub fn push(&mut self, cue: &VobSubCue) {
+        let start = self.data.len();
+        self.data.extend_from_slice(cue.image.raw_image());
+        self.entries.push(CueEntry {
+            time: cue.time,
+            area: cue.image.area(),
+            palette: *cue.image.palette(),
+            alpha: *cue.image.alpha(),
+            forced: cue.forced,
+            pixels: start..self.data.len(),
+        });
+    }
+
+    /// The cue at `index`, `None` if out of bounds.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<ArenaCue<'_>> {
+        self.entries.get(index).map(|entry| ArenaCue {
+            entry,
+            pixels: &self.data[entry.pixels.clone()],
+        })
+    }
+
+    /// Iterate on the cues, in the order of their addition.
+    #[must_use]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = ArenaCue<'_>> {
+        self.entries.iter().map(|entry| ArenaCue {
+            entry,
+            pixels: &self.data[entry.pixels.clone()],
+        })
+    }
+}
+
+/// A cue stored in a [`CueArena`], borrowing its pixels from the arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaCue<'a> {
+    entry: &'a CueEntry,
+    pixels: &'a [u8],
+}
+
+impl ArenaCue<'_> {
+    /// Display time of the cue.
+    #[must_use]
+    pub const fn time(&self) -> TimeSpan {
+        self.entry.time
+    }
+
+    /// Coordinates at which to display the cue.
+    #[must_use]
+    pub const fn area(&self) -> Area {
+        self.entry.area
+    }
+
+    /// Indexes of the 4 colors of the cue in the palette of the `*.idx` file.
+    #[must_use]
+    pub const fn palette(&self) -> &[u8; 4] {
+        &self.entry.palette
+    }
+
+    /// Alpha values of the 4 colors of the cue.
+    #[must_use]
+    pub const fn alpha(&self) -> &[u8; 4] {
+        &self.entry.alpha
+    }
+
+    /// If the cue must be displayed even when the subtitles are disabled.
+    #[must_use]
+    pub const fn forced(&self) -> bool {
+        self.entry.forced
+    }
+
+    /// Pixels of the cue, as in [`VobSubIndexedImage::raw_image`].
+    #[must_use]
+    pub const fn raw_image(&self) -> &[u8] {
+        self.pixels
+    }
+
+    /// Copy the cue out of the arena.
+    #[must_use]
+    pub fn to_cue(&self) -> VobSubCue {
+        VobSubCue {
+            time: self.entry.time,
+            image: VobSubIndexedImage::new(
+                self.entry.area,
+                self.entry.palette,
+                self.entry.alpha,
+                self.pixels.to_vec(),
+            ),
+            forced: self.entry.forced,
+        }
+    }
+}
+
+impl VobsubParser<'_, VobSubCue> {
+    /// Decode all the remaining subtitles into `arena`, and return it.
+    ///
+    /// The capacity of the arena is chosen by the caller, with
+    /// [`CueArena::with_capacity`], to avoid its reallocations when the size of the
+    /// stream is known. The buffer used to decode the subtitles is reused from one to
+    /// the next, the only allocations are the ones of the arena.
+    ///
+    /// # Errors
+    ///
+    /// Will return the error of the first subtitle failing to be decoded.
+    pub fn collect_cues(mut self, mut arena: CueArena) -> Result<CueArena, VobSubError> {
+        profiling::scope!("VobsubParser collect_cues");
+        while let Some(cue) = self.decode_next::<VobSubCue>() {
+            let cue = cue?;
+            arena.push(&cue);
+            self.recycle(cue.image);
+        }
+        Ok(arena)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn collect_cues_in_arena() {
+        let buffer = fs::read("./fixtures/example.sub").unwrap();
+        let cues = VobsubParser::new(&buffer)
+            .cues()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let bytes = cues.iter().map(|cue| cue.image.raw_image().len()).sum();
+
+        let arena = VobsubParser::new(&buffer)
+            .collect_cues(CueArena::with_capacity(cues.len(), bytes))
+            .unwrap();
+        assert_eq!(arena.len(), cues.len());
+        assert_eq!(arena.data_len(), bytes);
+        // The capacity was enough, no reallocation grew it.
+        assert_eq!(arena.capacity().1, bytes);
+        assert!(arena
+            .iter()
+            .map(|cue| cue.to_cue())
+            .eq(cues.iter().cloned()));
+        let last = arena.get(cues.len() - 1).unwrap();
+        assert_eq!(last.raw_image(), cues[cues.len() - 1].image.raw_image());
+        assert!(arena.get(cues.len()).is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use super::{img::VobSubRleImage, VobSubIndexedImage};
+use super::{img::VobSubRleImage, VobSubError, VobSubIndexedImage};
 use crate::time::{TimePoint, TimeSpan};
 
 /// The default length of a subtitle if no end time is provided and no
@@ -12,12 +12,15 @@ const DEFAULT_SUBTITLE_LENGTH: f64 = 5.0;
 pub trait VobSubDecoder<'a> {
     type Output;
 
+    /// Create the output from the parsed data, decompressing the image in `buffer`
+    /// if needed, to reuse its allocation.
     fn from_data(
         start_time: f64,
         end_time: Option<f64>,
         force: bool,
         image: VobSubRleImage<'a>,
-    ) -> Self::Output;
+        buffer: Vec<u8>,
+    ) -> Result<Self::Output, VobSubError>;
 }
 
 /// Implement creation of a tuple of [`TimeSpan`] and [`VobSubIndexedImage`] from parsing.
@@ -29,14 +32,50 @@ impl<'a> VobSubDecoder<'a> for (TimeSpan, VobSubIndexedImage) {
         end_time: Option<f64>,
         _force: bool,
         rle_image: VobSubRleImage<'a>,
-    ) -> Self::Output {
-        (
+        buffer: Vec<u8>,
+    ) -> Result<Self::Output, VobSubError> {
+        Ok((
             TimeSpan::new(
                 TimePoint::from_secs(start_time),
                 TimePoint::from_secs(end_time.unwrap_or(DEFAULT_SUBTITLE_LENGTH)),
             ),
-            VobSubIndexedImage::from(rle_image),
-        )
+            VobSubIndexedImage::decompress_with_buffer(&rle_image, buffer)?,
+        ))
+    }
+}
+
+/// A subtitle with its forced flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VobSubCue {
+    /// Display time of the subtitle.
+    pub time: TimeSpan,
+    /// Image of the subtitle.
+    pub image: VobSubIndexedImage,
+    /// The subtitle is forced : it should be displayed even if the subtitles are
+    /// disabled, like the translation of the foreign language parts of a movie.
+    pub forced: bool,
+}
+
+/// Implement creation of a [`VobSubCue`] from parsing, keeping the forced flag.
+impl<'a> VobSubDecoder<'a> for VobSubCue {
+    type Output = Self;
+
+    fn from_data(
+        start_time: f64,
+        end_time: Option<f64>,
+        force: bool,
+        rle_image: VobSubRleImage<'a>,
+        buffer: Vec<u8>,
+    ) -> Result<Self::Output, VobSubError> {
+        let (time, image) = <(TimeSpan, VobSubIndexedImage)>::from_data(
+            start_time, end_time, force, rle_image, buffer,
+        )?;
+        Ok(Self {
+            time,
+            image,
+            forced: force,
+        })
     }
 }
 
@@ -49,10 +88,11 @@ impl<'a> VobSubDecoder<'a> for TimeSpan {
         end_time: Option<f64>,
         _force: bool,
         _rle_image: VobSubRleImage<'a>,
-    ) -> Self::Output {
-        Self::new(
+        _buffer: Vec<u8>,
+    ) -> Result<Self::Output, VobSubError> {
+        Ok(Self::new(
             TimePoint::from_secs(start_time),
             TimePoint::from_secs(end_time.unwrap_or(DEFAULT_SUBTITLE_LENGTH)),
-        )
+        ))
     }
 }
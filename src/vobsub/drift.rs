@@ -0,0 +1,176 @@
+//! Compare the timestamps of an `*.idx` file with the `PTS` of the `*.sub` file.
+
+use std::collections::BTreeMap;
+
+use super::{Index, Sub, VobSubError};
+use crate::time::TimePoint;
+
+/// Timing of one subtitle, according to both the `*.idx` and the `*.sub` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CueDrift {
+    filepos: u64,
+    idx: TimePoint,
+    pts: TimePoint,
+}
+
+impl CueDrift {
+    /// Offset of the subtitle in the `*.sub` file.
+    #[must_use]
+    pub const fn filepos(&self) -> u64 {
+        self.filepos
+    }
+
+    /// Timestamp from the `*.idx` file.
+    #[must_use]
+    pub const fn idx(&self) -> TimePoint {
+        self.idx
+    }
+
+    /// `PTS` of the first `PES` packet of the subtitle.
+    #[must_use]
+    pub const fn pts(&self) -> TimePoint {
+        self.pts
+    }
+
+    /// Difference `idx - pts`, in milliseconds.
+    #[must_use]
+    pub const fn delta(&self) -> i64 {
+        self.idx.msecs() - self.pts.msecs()
+    }
+}
+
+/// Summary of the drift between the `*.idx` timestamps and the `*.sub` `PTS`.
+///
+/// A constant delta is usually a harmless `delay`, while a delta growing with time,
+/// or jumping at some point, means the `*.idx` file was generated for a different
+/// cut of the film, and neither source should be trusted blindly.
+#[derive(Debug, Clone, Default)]
+pub struct DriftReport {
+    cues: Vec<CueDrift>,
+    unmatched_idx: usize,
+    unmatched_sub: usize,
+}
+
+impl DriftReport {
+    /// The subtitles found in both files, in `*.sub` order.
+    #[must_use]
+    pub fn cues(&self) -> &[CueDrift] {
+        &self.cues
+    }
+
+    /// Number of `*.idx` entries without a subtitle at their `filepos`.
+    #[must_use]
+    pub const fn unmatched_idx(&self) -> usize {
+        self.unmatched_idx
+    }
+
+    /// Number of subtitles of the `*.sub` file without an `*.idx` entry.
+    #[must_use]
+    pub const fn unmatched_sub(&self) -> usize {
+        self.unmatched_sub
+    }
+
+    /// The delta with the largest magnitude, in milliseconds.
+    #[must_use]
+    pub fn max(&self) -> Option<i64> {
+        self.cues
+            .iter()
+            .map(CueDrift::delta)
+            .max_by_key(|delta| delta.unsigned_abs())
+    }
+
+    /// The mean of the deltas, in milliseconds.
+    #[must_use]
+    pub fn mean(&self) -> Option<f64> {
+        if self.cues.is_empty() {
+            return None;
+        }
+        let sum: f64 = self.cues.iter().map(|cue| cast::f64(cue.delta())).sum();
+        Some(sum / cast::f64(self.cues.len()))
+    }
+
+    /// The slope of the deltas over time, in milliseconds per minute, from a
+    /// least-squares fit against the `PTS`.
+    ///
+    /// Return `None` if there are less than two distinct `PTS`.
+    #[must_use]
+    pub fn trend(&self) -> Option<f64> {
+        let mean = self.mean()?;
+        let count = cast::f64(self.cues.len());
+        let minutes = |cue: &CueDrift| cue.pts.to_secs() / 60.;
+        let mean_time = self.cues.iter().map(minutes).sum::<f64>() / count;
+        let (covariance, variance) = self.cues.iter().fold((0., 0.), |(cov, var), cue| {
+            let time = minutes(cue) - mean_time;
+            let delta = cast::f64(cue.delta()) - mean;
+            (time.mul_add(delta, cov), time.mul_add(time, var))
+        });
+        (variance > 0.).then(|| covariance / variance)
+    }
+}
+
+/// Compare the `timestamp` entries of `index` with the `PTS` of the subtitles of `sub`.
+///
+/// Entries and subtitles are matched on their position in the `*.sub` file.
+///
+/// # Errors
+/// Will return an error if the packets of a subtitle can't be read from `sub`.
+#[profiling::function]
+pub fn drift_report(index: &Index, sub: &Sub) -> Result<DriftReport, VobSubError> {
+    let mut entries = index
+        .entries()
+        .iter()
+        .map(|entry| (entry.filepos(), entry.timestamp()))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut report = DriftReport::default();
+    let mut parser = sub.subtitles::<()>();
+    while let Some(packet) = parser.next_sub_packet() {
+        let (pts, _) = packet?;
+        let filepos = parser.offset();
+        match entries.remove(&filepos) {
+            Some(idx) => report.cues.push(CueDrift {
+                filepos,
+                idx,
+                pts: TimePoint::from_secs(pts),
+            }),
+            None => report.unmatched_sub += 1,
+        }
+    }
+    report.unmatched_idx = entries.len();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::BufReader};
+
+    use super::*;
+
+    fn read_index(content: &str) -> Index {
+        Index::read_index(BufReader::new(content.as_bytes()), &|source| {
+            VobSubError::Io {
+                source,
+                path: "test.idx".into(),
+            }
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn drift_report_of_example() {
+        let content = fs::read_to_string("./fixtures/example.idx").unwrap();
+        let sub = Sub::open("./fixtures/example.sub").unwrap();
+
+        let report = drift_report(&read_index(&content), &sub).unwrap();
+        assert_eq!(report.cues().len(), 2);
+        assert_eq!((report.unmatched_idx(), report.unmatched_sub()), (0, 0));
+        assert_eq!(report.max(), Some(1));
+
+        // The idx of a longer cut: the second cue is 6 seconds late.
+        let content = content.replace("00:00:52:636", "00:00:58:636");
+        let report = drift_report(&read_index(&content), &sub).unwrap();
+        assert_eq!(report.max(), Some(6001));
+        assert!((report.mean().unwrap() - 3000.5).abs() < f64::EPSILON);
+        assert!(report.trend().unwrap() > 100_000.);
+    }
+}
@@ -0,0 +1,522 @@
+//! Encoding of subtitle packets (`SPU`).
+//!
+//! A packet contains the run-length encoded image, with its two interlaced fields, followed
+//! by two control sequences : the first one shows the image, the second one hides it.
+use std::{cmp::Reverse, ops::Range};
+
+use thiserror::Error;
+
+use super::{DvdLimits, VobSubIndexedImage};
+use crate::{
+    content::{Area, AreaValues},
+    image::ImageArea as _,
+    time::{TimePoint, TimeSpan},
+};
+
+/// Size of the control sequence showing the image.
+const SHOW_SEQUENCE_LEN: usize = 4 + 1 + 3 + 3 + 7 + 5 + 1;
+/// Size of the control sequence hiding the image.
+const HIDE_SEQUENCE_LEN: usize = 4 + 1 + 1;
+/// Maximum count of a run, above it a run is encoded in several parts.
+const MAX_RUN: usize = 255;
+
+/// Error of subtitle packet encoding.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// The display time is negative, or too long to be encoded (more than 655 seconds).
+    #[error("display duration of {msecs}ms can't be encoded")]
+    InvalidDuration {
+        /// Duration of the subtitle, in milliseconds.
+        msecs: i64,
+    },
+
+    /// The encoded packet is larger than the maximum size of a packet.
+    #[error("encoded packet of {size} bytes is too large")]
+    PacketTooLarge {
+        /// Size of the encoded packet.
+        size: usize,
+    },
+}
+
+/// Alteration applied by [`encode_spu_adaptive`] to fit a subtitle in packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Alteration {
+    /// Colors were merged, only `colors` colors (including the background) remain.
+    ColorsReduced {
+        /// Number of remaining colors.
+        colors: usize,
+    },
+    /// The image was split in `parts` bands, displayed one after the other.
+    Split {
+        /// Number of bands.
+        parts: usize,
+    },
+}
+
+/// Subtitle encoded by [`encode_spu_adaptive`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdaptiveSpu {
+    packets: Vec<(TimeSpan, Vec<u8>)>,
+    alterations: Vec<Alteration>,
+}
+
+impl AdaptiveSpu {
+    /// Encoded packets, with their display times.
+    #[must_use]
+    pub fn packets(&self) -> &[(TimeSpan, Vec<u8>)] {
+        &self.packets
+    }
+
+    /// Alterations applied to the subtitle, in application order. Empty if the subtitle
+    /// was encoded unchanged.
+    #[must_use]
+    pub fn alterations(&self) -> &[Alteration] {
+        &self.alterations
+    }
+
+    /// Get back the encoded packets.
+    #[must_use]
+    pub fn into_packets(self) -> Vec<(TimeSpan, Vec<u8>)> {
+        self.packets
+    }
+}
+
+/// Encode a subtitle packet (`SPU`), displaying `image` during `time`.
+///
+/// The packet must be presented at `time.start`, the dates of its control sequences are
+/// relative to it (see [`decode_spu`]).
+///
+/// # Errors
+///
+/// Will return [`EncodeError::InvalidDuration`] if the duration of `time` can't be encoded,
+/// or [`EncodeError::PacketTooLarge`] if the packet exceed the size of a packet.
+///
+/// [`decode_spu`]: super::decode_spu
+pub fn encode_spu(time: TimeSpan, image: &VobSubIndexedImage) -> Result<Vec<u8>, EncodeError> {
+    let msecs = time.end.msecs() - time.start.msecs();
+    let date = u16::try_from(msecs / 10)
+        .ok()
+        .filter(|_| msecs >= 0)
+        .ok_or(EncodeError::InvalidDuration { msecs })?;
+
+    let mut packet = vec![0; 4];
+    let size = image.area().size();
+    let rows = image.raw_image().chunks_exact(size.w.max(1));
+    let field_start_0 = packet.len();
+    encode_field(rows.clone().step_by(2), &mut packet);
+    let field_start_1 = packet.len();
+    encode_field(rows.skip(1).step_by(2), &mut packet);
+
+    let show_offset = packet.len();
+    let hide_offset = show_offset + SHOW_SEQUENCE_LEN;
+    let total = hide_offset + HIDE_SEQUENCE_LEN;
+    let to_u16 = |value: usize| {
+        u16::try_from(value).map_err(|_err| EncodeError::PacketTooLarge { size: total })
+    };
+    let (show, hide) = (to_u16(show_offset)?, to_u16(hide_offset)?);
+
+    // Show sequence : start date, colors, coordinates and fields offsets.
+    packet.extend(0u16.to_be_bytes());
+    packet.extend(hide.to_be_bytes());
+    packet.push(0x01);
+    let nibbles = |values: &[u8; 4]| [(values[3] << 4) | values[2], (values[1] << 4) | values[0]];
+    packet.push(0x03);
+    packet.extend(nibbles(image.palette()));
+    packet.push(0x04);
+    packet.extend(nibbles(image.alpha()));
+    packet.push(0x05);
+    let area = image.area();
+    let right = area.left() + area.width() - 1;
+    let bottom = area.top() + area.height() - 1;
+    let coordinates = (u64::from(area.left()) << 36)
+        | (u64::from(right) << 24)
+        | (u64::from(area.top()) << 12)
+        | u64::from(bottom);
+    packet.extend(&coordinates.to_be_bytes()[2..]);
+    packet.push(0x06);
+    packet.extend(to_u16(field_start_0)?.to_be_bytes());
+    packet.extend(to_u16(field_start_1)?.to_be_bytes());
+    packet.push(0xFF);
+
+    // Hide sequence, pointing to itself as the last one.
+    packet.extend(date.to_be_bytes());
+    packet.extend(hide.to_be_bytes());
+    packet.push(0x02);
+    packet.push(0xFF);
+
+    packet[0..2].copy_from_slice(&to_u16(total)?.to_be_bytes());
+    packet[2..4].copy_from_slice(&show.to_be_bytes());
+    Ok(packet)
+}
+
+/// Encode a subtitle like [`encode_spu`], altering it if its packet would be larger
+/// than `limits.max_packet_size`.
+///
+/// The size of a packet is stored on 16 bits, so complex images can't be encoded in a single
+/// packet. They are altered until they fit, with the strategy :
+/// 1. the least used visible colors are merged in the most used one, one by one, as
+///    anti-aliasing colors are the source of most of the runs;
+/// 2. if the image is still too large, it's split in horizontal bands between its lines
+///    of text (cut in the transparent rows separating them), displayed one after the other
+///    during an equal part of the display time. The text is never cut, so an image without
+///    transparent rows between its lines can't be split.
+///
+/// The applied alterations are reported in the returned [`AdaptiveSpu`].
+///
+/// # Errors
+///
+/// Will return [`EncodeError::InvalidDuration`] if the duration of `time` can't be encoded,
+/// or [`EncodeError::PacketTooLarge`] if the subtitle can't fit even with a band for
+/// each line of text.
+pub fn encode_spu_adaptive(
+    time: TimeSpan,
+    image: &VobSubIndexedImage,
+    limits: &DvdLimits,
+) -> Result<AdaptiveSpu, EncodeError> {
+    let fit = |packet: Vec<u8>| {
+        if packet.len() > limits.max_packet_size {
+            Err(EncodeError::PacketTooLarge { size: packet.len() })
+        } else {
+            Ok(packet)
+        }
+    };
+    let mut alterations = Vec::new();
+    let mut image = image.clone();
+    let mut last_error;
+    loop {
+        match encode_spu(time, &image).and_then(fit) {
+            Ok(packet) => {
+                return Ok(AdaptiveSpu {
+                    packets: vec![(time, packet)],
+                    alterations,
+                })
+            }
+            Err(err @ EncodeError::PacketTooLarge { .. }) => last_error = err,
+            Err(err) => return Err(err),
+        }
+        match merge_least_used_color(&image) {
+            Some((merged, colors)) => {
+                image = merged;
+                alterations.push(Alteration::ColorsReduced { colors });
+            }
+            None => break,
+        }
+    }
+
+    let lines = text_lines(&image);
+    for parts in 2..=lines.len() {
+        let Some(bands) = split_bands(time, &image, &lines, parts) else {
+            continue;
+        };
+        match bands
+            .into_iter()
+            .map(|(time, band)| Ok((time, encode_spu(time, &band).and_then(fit)?)))
+            .collect::<Result<Vec<_>, EncodeError>>()
+        {
+            Ok(packets) => {
+                alterations.push(Alteration::Split { parts });
+                return Ok(AdaptiveSpu {
+                    packets,
+                    alterations,
+                });
+            }
+            Err(err @ EncodeError::PacketTooLarge { .. }) => last_error = err,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(last_error)
+}
+
+/// Write a run-length encoded field, from its `rows`.
+fn encode_field<'a>(rows: impl Iterator<Item = &'a [u8]>, packet: &mut Vec<u8>) {
+    let mut nibbles = NibbleWriter::new(packet);
+    for row in rows {
+        let mut x = 0;
+        while x < row.len() {
+            let color = row[x] & 0b11;
+            let run = row[x..]
+                .iter()
+                .take_while(|&&pixel| pixel & 0b11 == color)
+                .count();
+            x += run;
+            if x == row.len() && run > MAX_RUN {
+                // Fill to the end of the line.
+                nibbles.write(u16::from(color), 4);
+                continue;
+            }
+            let mut remaining = run;
+            while remaining > 0 {
+                let count = remaining.min(MAX_RUN);
+                remaining -= count;
+                let code = (u16::try_from(count).unwrap() << 2) | u16::from(color);
+                let len = match count {
+                    1..=3 => 1,
+                    4..=15 => 2,
+                    16..=63 => 3,
+                    _ => 4,
+                };
+                nibbles.write(code, len);
+            }
+        }
+        nibbles.align();
+    }
+}
+
+/// Writer of 4 bits values.
+struct NibbleWriter<'a> {
+    data: &'a mut Vec<u8>,
+    half: bool,
+}
+
+impl<'a> NibbleWriter<'a> {
+    fn new(data: &'a mut Vec<u8>) -> Self {
+        Self { data, half: false }
+    }
+
+    /// Write the `len` lower nibbles of `value`, most significant first.
+    fn write(&mut self, value: u16, len: u32) {
+        for idx in (0..len).rev() {
+            let nibble = ((value >> (idx * 4)) & 0x0F) as u8;
+            if self.half {
+                *self.data.last_mut().unwrap() |= nibble;
+            } else {
+                self.data.push(nibble << 4);
+            }
+            self.half = !self.half;
+        }
+    }
+
+    /// Pad the data to the next byte.
+    fn align(&mut self) {
+        self.half = false;
+    }
+}
+
+/// Merge the least used visible color of `image` in the most used one, and return the
+/// new image with its number of remaining colors. Return `None` if there is at most one
+/// visible color.
+fn merge_least_used_color(image: &VobSubIndexedImage) -> Option<(VobSubIndexedImage, usize)> {
+    let mut counts = [0usize; 4];
+    for &pixel in image.raw_image() {
+        counts[usize::from(pixel & 0b11)] += 1;
+    }
+    let background = (0..4u8).max_by_key(|&idx| (counts[usize::from(idx)], Reverse(idx)))?;
+    let mut visible = (0..4u8)
+        .filter(|&idx| idx != background && counts[usize::from(idx)] > 0)
+        .collect::<Vec<_>>();
+    if visible.len() < 2 {
+        return None;
+    }
+    visible.sort_by_key(|&idx| (Reverse(counts[usize::from(idx)]), idx));
+    let (target, merged) = (visible[0], visible[visible.len() - 1]);
+    let raw = image
+        .raw_image()
+        .iter()
+        .map(|&pixel| if pixel == merged { target } else { pixel })
+        .collect();
+    let image = VobSubIndexedImage::new(image.area(), *image.palette(), *image.alpha(), raw);
+    Some((image, visible.len()))
+}
+
+/// Rows of the lines of text of `image` : the groups of rows with visible pixels,
+/// separated by transparent rows.
+fn text_lines(image: &VobSubIndexedImage) -> Vec<Range<usize>> {
+    let width = usize::from(image.area().width());
+    let visible = |&pixel: &u8| image.alpha()[usize::from(pixel & 0b11)] > 0;
+    let mut lines: Vec<Range<usize>> = Vec::new();
+    for (y, row) in image.raw_image().chunks(width).enumerate() {
+        if !row.iter().any(visible) {
+            continue;
+        }
+        match lines.last_mut() {
+            Some(line) if line.end == y => line.end = y + 1,
+            _ => lines.push(y..y + 1),
+        }
+    }
+    lines
+}
+
+/// Split `image` in `parts` horizontal bands, each displayed during a part of `time`.
+///
+/// The text `lines` are distributed in the bands, which are cut in the middle of
+/// the transparent rows between them. Return `None` if a band is too small to be
+/// displayed (less than 2 rows).
+fn split_bands(
+    time: TimeSpan,
+    image: &VobSubIndexedImage,
+    lines: &[Range<usize>],
+    parts: usize,
+) -> Option<Vec<(TimeSpan, VobSubIndexedImage)>> {
+    let area = image.area();
+    let (width, height) = (usize::from(area.width()), usize::from(area.height()));
+    let duration = time.end.msecs() - time.start.msecs();
+    let part_time = |idx: usize| {
+        let msecs = duration * i64::try_from(idx).unwrap() / i64::try_from(parts).unwrap();
+        TimePoint::from_msecs(time.start.msecs() + msecs)
+    };
+    // First row of each band, after the first one : the middle of the gap before
+    // its first line.
+    let cut = |idx: usize| {
+        let next = &lines[idx * lines.len() / parts];
+        let previous = &lines[idx * lines.len() / parts - 1];
+        previous.end + (next.start - previous.end).div_ceil(2)
+    };
+    (0..parts)
+        .map(|idx| {
+            let first = if idx == 0 { 0 } else { cut(idx) };
+            let last = if idx + 1 == parts {
+                height
+            } else {
+                cut(idx + 1)
+            };
+            let band_area = Area::try_from(AreaValues {
+                x1: area.left(),
+                y1: area.top() + u16::try_from(first).ok()?,
+                x2: area.left() + area.width() - 1,
+                y2: area.top() + u16::try_from(last).ok()? - 1,
+            })
+            .ok()?;
+            let raw = image.raw_image()[first * width..last * width].to_vec();
+            let band = VobSubIndexedImage::new(band_area, *image.palette(), *image.alpha(), raw);
+            Some((TimeSpan::new(part_time(idx), part_time(idx + 1)), band))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+    use crate::vobsub::decode_spu;
+    use assert_matches2::assert_matches;
+
+    /// An image of `width`x`height` pixels, with colors depending on `pattern`.
+    fn image(width: u16, height: u16, pattern: impl Fn(usize, usize) -> u8) -> VobSubIndexedImage {
+        let area = Area::try_from(AreaValues {
+            x1: 10,
+            y1: 20,
+            x2: 10 + width - 1,
+            y2: 20 + height - 1,
+        })
+        .unwrap();
+        let raw = (0..usize::from(height))
+            .flat_map(|y| (0..usize::from(width)).map(move |x| (x, y)))
+            .map(|(x, y)| pattern(x, y))
+            .collect();
+        VobSubIndexedImage::new(area, [0, 1, 2, 3], [0, 15, 15, 15], raw)
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let image = image(300, 5, |x, y| match (x + y) % 97 {
+            0 => 1,
+            1..=60 => 0,
+            61..=70 => 2,
+            _ => 3,
+        });
+        let time = span(1000, 3500);
+        let packet = encode_spu(time, &image).unwrap();
+        let (decoded_time, decoded) = decode_spu(&packet, time.start).unwrap();
+        assert_eq!(decoded_time, time);
+        assert_eq!(decoded, image);
+
+        assert_eq!(
+            encode_spu(span(1000, 0), &image),
+            Err(EncodeError::InvalidDuration { msecs: -1000 })
+        );
+    }
+
+    #[test]
+    fn adaptive_encoding() {
+        // Lines of text like strokes, with anti-aliasing colors on their sides.
+        let image = image(100, 40, |x, y| match (y % 10, x % 20) {
+            (7.., _) => 0,
+            (_, 10) => 2,
+            (_, 11..=17) => 1,
+            (_, 18) => 3,
+            _ => 0,
+        });
+        let time = span(0, 4000);
+        let full_size = encode_spu(time, &image).unwrap().len();
+
+        let limits = DvdLimits::default();
+        let encoded = encode_spu_adaptive(time, &image, &limits).unwrap();
+        assert!(encoded.alterations().is_empty());
+
+        let limits = DvdLimits::PAL.with_max_packet_size(full_size * 3 / 4);
+        let encoded = encode_spu_adaptive(time, &image, &limits).unwrap();
+        assert_eq!(
+            encoded.alterations(),
+            [
+                Alteration::ColorsReduced { colors: 3 },
+                Alteration::ColorsReduced { colors: 2 },
+            ]
+        );
+        assert_eq!(encoded.packets().len(), 1);
+
+        let limits = DvdLimits::PAL.with_max_packet_size(full_size / 4);
+        let encoded = encode_spu_adaptive(time, &image, &limits).unwrap();
+        let Some(Alteration::Split { parts }) = encoded.alterations().last() else {
+            panic!("image should be split: {:?}", encoded.alterations());
+        };
+        let packets = encoded.packets();
+        assert_eq!(packets.len(), *parts);
+        assert_eq!(packets[0].0.start, time.start);
+        assert_eq!(packets[packets.len() - 1].0.end, time.end);
+        for (time, packet) in packets {
+            assert!(packet.len() <= limits.max_packet_size);
+            decode_spu(packet, time.start).unwrap();
+        }
+
+        let limits = DvdLimits::PAL.with_max_packet_size(10);
+        assert_matches!(
+            encode_spu_adaptive(time, &image, &limits),
+            Err(EncodeError::PacketTooLarge { .. })
+        );
+    }
+
+    #[test]
+    fn adaptive_encoding_keep_lines() {
+        let visible = |image: &VobSubIndexedImage| {
+            image
+                .raw_image()
+                .iter()
+                .filter(|&&pixel| pixel != 0)
+                .count()
+        };
+        let time = span(0, 4000);
+
+        // Two lines of text of 6 and 8 rows, in the lower half of the image.
+        let lines = image(100, 40, |x, y| match (y, x % 20) {
+            (22..28 | 30..38, 10..=17) => 1,
+            _ => 0,
+        });
+        let full_size = encode_spu(time, &lines).unwrap().len();
+        let limits = DvdLimits::PAL.with_max_packet_size(full_size * 3 / 4);
+        let encoded = encode_spu_adaptive(time, &lines, &limits).unwrap();
+        assert_eq!(encoded.alterations(), [Alteration::Split { parts: 2 }]);
+        let bands = encoded
+            .packets()
+            .iter()
+            .map(|(time, packet)| decode_spu(packet, time.start).unwrap().1)
+            .collect::<Vec<_>>();
+        // Each band displays a whole line of text of 5 strokes.
+        assert_eq!(
+            bands.iter().map(visible).collect::<Vec<_>>(),
+            [5 * 8 * 6, 5 * 8 * 8]
+        );
+        assert_eq!(bands[1].area().top(), 20 + 29);
+
+        // Without transparent rows between the lines, the text can't be split.
+        let block = image(100, 40, |x, y| u8::from(y >= 20 && x % 20 < 8));
+        let full_size = encode_spu(time, &block).unwrap().len();
+        let limits = DvdLimits::PAL.with_max_packet_size(full_size / 2);
+        assert_matches!(
+            encode_spu_adaptive(time, &block, &limits),
+            Err(EncodeError::PacketTooLarge { size })
+        );
+        assert_eq!(size, full_size);
+    }
+}
@@ -1,12 +1,13 @@
 //! Parse a file in `*.idx` format.
 
 use compact_str::CompactString;
-use log::trace;
+use log::{trace, warn};
 use regex::Regex;
 use std::{
     fmt, fs,
     io::{self, prelude::*, BufReader},
     path::Path,
+    str::FromStr,
     sync::LazyLock,
 };
 
@@ -14,13 +15,20 @@ use super::{
     palette::{palette, DEFAULT_PALETTE},
     Palette, VobSubError,
 };
-use crate::{time::TimePoint, vobsub::IResultExt as _};
+use crate::{content::Size, time::TimePoint, vobsub::IResultExt as _};
 
 /// Lang of a subtitle as reported in `VobSub` idx file.
 #[derive(Debug, Clone)]
 pub struct Lang(CompactString);
 
 impl Lang {
+    /// Create a lang from its code (`en`, `fr`, ...).
+    pub(super) fn from_code(code: &str) -> Self {
+        Self(code.into())
+    }
+
+    /// Code of the lang (`en`, `fr`, ...).
+    #[must_use]
     #[allow(clippy::missing_const_for_fn)]
     pub fn lang(&self) -> &str {
         &self.0
@@ -42,6 +50,84 @@ impl TryFrom<&str> for Lang {
     }
 }
 
+impl Lang {
+    /// Parse the value of an `id` key, like `en, index: 0`, with the index of the track.
+    fn parse_with_index(value: &str) -> Option<(Self, u8)> {
+        let lang = Self::try_from(value).ok()?;
+        let (_, index) = value.split_once(',')?;
+        let index = index.trim().strip_prefix("index:")?.trim().parse().ok()?;
+        Some((lang, index))
+    }
+}
+
+/// A subtitle track of an `*.idx` file : an `id` block with its `timestamp` lines.
+#[derive(Debug, Clone)]
+pub struct IdxTrack {
+    lang: Lang,
+    index: u8,
+    entries: Vec<IdxEntry>,
+}
+
+impl IdxTrack {
+    /// Lang of the track.
+    #[must_use]
+    pub const fn lang(&self) -> &Lang {
+        &self.lang
+    }
+
+    /// Index of the track, used to select its subtitles in the `*.sub` file
+    /// (see [`Sub::track_subtitles`]).
+    ///
+    /// [`Sub::track_subtitles`]: super::Sub::track_subtitles
+    #[must_use]
+    pub const fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// The `timestamp` entries of the track, in file order.
+    #[must_use]
+    pub fn entries(&self) -> &[IdxEntry] {
+        &self.entries
+    }
+
+    /// Get the entry of the subtitle of the track shown at `time` : the last subtitle
+    /// starting at or before `time`, as the `*.idx` file doesn't store the end times.
+    ///
+    /// The entries of a track are expected in time order, as written by the muxers.
+    /// Use [`Sub::subtitles_from`] or [`VobsubReader::seek`] to decode it.
+    ///
+    /// [`Sub::subtitles_from`]: super::Sub::subtitles_from
+    /// [`VobsubReader::seek`]: super::VobsubReader::seek
+    #[must_use]
+    pub fn subtitle_at(&self, time: TimePoint) -> Option<&IdxEntry> {
+        subtitle_at(&self.entries, time)
+    }
+
+    /// Iterate on the entries of the track from the subtitle shown at `time`
+    /// (see [`Self::subtitle_at`]), or from the first subtitle starting after `time`.
+    pub fn iter_from(&self, time: TimePoint) -> impl Iterator<Item = &IdxEntry> {
+        iter_from(&self.entries, time)
+    }
+}
+
+/// Last of the sorted `entries` starting at or before `time`.
+fn subtitle_at(entries: &[IdxEntry], time: TimePoint) -> Option<&IdxEntry> {
+    started_before(entries, time)
+        .checked_sub(1)
+        .map(|pos| &entries[pos])
+}
+
+/// Iterate on the sorted `entries` from the one shown at `time`.
+fn iter_from(entries: &[IdxEntry], time: TimePoint) -> impl Iterator<Item = &IdxEntry> {
+    let start = started_before(entries, time).saturating_sub(1);
+    entries[start..].iter()
+}
+
+/// Number of the sorted `entries` starting at or before `time`.
+fn started_before(entries: &[IdxEntry], time: TimePoint) -> usize {
+    entries.partition_point(|entry| entry.timestamp() <= time)
+}
+
 /// Extend `TimePoint` to implement `idx` specific `Display`.
 #[repr(transparent)]
 pub struct TimePointIdx(TimePoint);
@@ -58,19 +144,159 @@ impl fmt::Display for TimePointIdx {
     }
 }
 
+/// A `timestamp` line of an `*.idx` file, locating a subtitle in the `*.sub` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdxEntry {
+    timestamp: TimePoint,
+    filepos: u64,
+}
+
+impl IdxEntry {
+    /// Create an entry from its `timestamp` and the `filepos` of the subtitle.
+    #[must_use]
+    pub const fn new(timestamp: TimePoint, filepos: u64) -> Self {
+        Self { timestamp, filepos }
+    }
+
+    /// Time at which the subtitle is displayed, according to the `*.idx` file.
+    #[must_use]
+    pub const fn timestamp(&self) -> TimePoint {
+        self.timestamp
+    }
+
+    /// Offset of the first packet of the subtitle in the `*.sub` file.
+    #[must_use]
+    pub const fn filepos(&self) -> u64 {
+        self.filepos
+    }
+
+    /// Parse the value of a `timestamp` key, like `00:00:49:466, filepos: 000001000`.
+    fn parse(value: &str) -> Option<Self> {
+        let (timestamp, filepos) = value.trim().split_once(',')?;
+        let filepos = filepos.trim_start().strip_prefix("filepos:")?.trim_start();
+        let filepos = u64::from_str_radix(filepos, 16).ok()?;
+        Some(Self::new(parse_time(timestamp)?, filepos))
+    }
+}
+
+/// Parse a time like `00:00:49:466` (`HH:MM:SS:mmm`), possibly negative.
+fn parse_time(value: &str) -> Option<TimePoint> {
+    static TIME: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(-?)(\d+):(\d+):(\d+):(\d+)$").unwrap());
+    let cap = TIME.captures(value.trim())?;
+    let field = |i| cap.get(i).unwrap().as_str().parse::<i64>().ok();
+    let msecs = ((field(2)? * 60 + field(3)?) * 60 + field(4)?) * 1000 + field(5)?;
+    let msecs = if cap[1].is_empty() { msecs } else { -msecs };
+    Some(TimePoint::from_msecs(msecs))
+}
+
+/// Parse a pair of values separated by `separator`, like `720x576` or `0, 0`.
+fn parse_pair<T: FromStr>(value: &str, separator: char) -> Option<(T, T)> {
+    let (first, second) = value.split_once(separator)?;
+    Some((first.trim().parse().ok()?, second.trim().parse().ok()?))
+}
+
+/// Parse a percentage, like `100%`.
+fn parse_percent(value: &str) -> Option<u16> {
+    value.trim().strip_suffix('%')?.trim_end().parse().ok()
+}
+
+/// Parse an `ON`/`OFF` switch.
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.trim() {
+        v if v.eq_ignore_ascii_case("ON") => Some(true),
+        v if v.eq_ignore_ascii_case("OFF") => Some(false),
+        _ => None,
+    }
+}
+
+/// Set a rendering parameter of the index, or keep its previous value with a warning
+/// if the value is invalid.
+fn set_param<T>(field: &mut Option<T>, value: Option<T>, line: &str) {
+    match value {
+        Some(value) => *field = Some(value),
+        None => warn!("invalid idx line `{line}`, ignored"),
+    }
+}
+
+/// Smoothing of the subtitles, from the `smooth` key of an `*.idx` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Smoothing {
+    /// No smoothing.
+    Off,
+    /// Smoothing enabled.
+    On,
+    /// Smoothing of the old `VobSub` versions.
+    Old,
+}
+
+impl Smoothing {
+    fn parse(value: &str) -> Option<Self> {
+        match parse_switch(value) {
+            Some(true) => Some(Self::On),
+            Some(false) => Some(Self::Off),
+            None => value
+                .trim()
+                .eq_ignore_ascii_case("OLD")
+                .then_some(Self::Old),
+        }
+    }
+}
+
 /// A `*.idx` file describing the subtitles in a `*.sub` file.
+///
+/// The rendering parameters are `None` when their key is missing or invalid.
 #[derive(Debug)]
 pub struct Index {
-    // Frame size.
-    //size: Size,
+    /// Frame size.
+    size: Option<Size>,
+    /// Position of the upper-left corner of the subtitles.
+    origin: Option<(i32, i32)>,
+    /// Horizontal and vertical scaling, in percent.
+    scale: Option<(u16, u16)>,
+    /// Alpha blending, in percent.
+    alpha: Option<u16>,
+    smooth: Option<Smoothing>,
+    /// Fade in and fade out durations, in milliseconds.
+    fade: Option<(u32, u32)>,
+    time_offset: Option<TimePoint>,
+    forced_subs: Option<bool>,
     /// The colors used for the subtitles.
     palette: Palette,
-    /// Lang of the subtitles
+    /// Lang of the subtitles, of the first track for a read file.
     lang: Option<Lang>,
+    /// The `timestamp` lines, in file order.
+    entries: Vec<IdxEntry>,
+    /// The tracks defined by the `id` lines.
+    tracks: Vec<IdxTrack>,
 }
 
+const SIZE_KEY: &str = "size";
+const ORIGIN_KEY: &str = "org";
+const SCALE_KEY: &str = "scale";
+const ALPHA_KEY: &str = "alpha";
+const SMOOTH_KEY: &str = "smooth";
+const FADE_KEY: &str = "fadein/out";
+const TIME_OFFSET_KEY: &str = "time offset";
+const FORCED_SUBS_KEY: &str = "forced subs";
 const PALETTE_KEY: &str = "palette";
 const LANG_KEY: &str = "id";
+const TIMESTAMP_KEY: &str = "timestamp";
+
+/// Keys expected once in an `*.idx` file.
+///
+/// The `id` key starts a track, it is repeated in the files with several tracks.
+const UNIQUE_KEYS: [&str; 9] = [
+    SIZE_KEY,
+    ORIGIN_KEY,
+    SCALE_KEY,
+    ALPHA_KEY,
+    SMOOTH_KEY,
+    FADE_KEY,
+    TIME_OFFSET_KEY,
+    FORCED_SUBS_KEY,
+    PALETTE_KEY,
+];
 
 impl Index {
     /// Open an `*.idx` file and the associated `*.sub` file.
@@ -92,6 +318,11 @@ impl Index {
 
     /// Read the palette in `*.idx` file content
     ///
+    /// The whole content is read before building the index, so keys can appear in any order,
+    /// before or after the timestamp lines. If a key appears several times, the last value
+    /// is used, and a warning is logged. The `id` key is the exception : each one starts
+    /// a new track.
+    ///
     /// # Errors
     /// Will return `VobSubError::MissingKey` if the palette key/value is not present
     /// Will return `VobSubError::PaletteError` if failed to read and parse palette value.
@@ -107,15 +338,49 @@ impl Index {
         static KEY_VALUE: LazyLock<Regex> =
             LazyLock::new(|| Regex::new("^([A-Za-z/ ]+): (.*)").unwrap());
 
+        let mut index = Self::init(DEFAULT_PALETTE, None);
         let mut palette_val = None;
-        let mut lang = None;
+        let mut seen_keys = Vec::new();
         let mut buf = String::with_capacity(256);
         while input.read_line(&mut buf).map_err(mkerr)? > 0 {
             let line = buf.trim_end();
             if let Some(cap) = KEY_VALUE.captures(line) {
                 let key = cap.get(1).unwrap().as_str();
                 let val = cap.get(2).unwrap().as_str();
+                if let Some(&known) = UNIQUE_KEYS.iter().find(|known| **known == key) {
+                    if seen_keys.contains(&known) {
+                        warn!("duplicate idx key `{key}`, the last value is used");
+                    } else {
+                        seen_keys.push(known);
+                    }
+                }
                 match key {
+                    SIZE_KEY => set_param(
+                        &mut index.size,
+                        parse_pair(val, 'x').map(|(w, h)| Size { w, h }),
+                        line,
+                    ),
+                    ORIGIN_KEY => set_param(&mut index.origin, parse_pair(val, ','), line),
+                    SCALE_KEY => set_param(
+                        &mut index.scale,
+                        val.split_once(',').and_then(|(horizontal, vertical)| {
+                            Some((parse_percent(horizontal)?, parse_percent(vertical)?))
+                        }),
+                        line,
+                    ),
+                    ALPHA_KEY => set_param(&mut index.alpha, parse_percent(val), line),
+                    SMOOTH_KEY => set_param(&mut index.smooth, Smoothing::parse(val), line),
+                    FADE_KEY => set_param(&mut index.fade, parse_pair(val, ','), line),
+                    TIME_OFFSET_KEY => set_param(
+                        &mut index.time_offset,
+                        val.trim()
+                            .parse()
+                            .ok()
+                            .map(TimePoint::from_msecs)
+                            .or_else(|| parse_time(val)),
+                        line,
+                    ),
+                    FORCED_SUBS_KEY => set_param(&mut index.forced_subs, parse_switch(val), line),
                     PALETTE_KEY => {
                         palette_val = Some(
                             palette(val.as_bytes())
@@ -125,8 +390,27 @@ impl Index {
                     }
                     LANG_KEY => {
                         //TODO: reporte missing lang ?
-                        lang = Lang::try_from(val).ok();
+                        if index.tracks.is_empty() {
+                            index.lang = Lang::try_from(val).ok();
+                        }
+                        match Lang::parse_with_index(val) {
+                            Some((lang, track)) => index.tracks.push(IdxTrack {
+                                lang,
+                                index: track,
+                                entries: Vec::new(),
+                            }),
+                            None => warn!("invalid idx track line `{line}`, ignored"),
+                        }
                     }
+                    TIMESTAMP_KEY => match IdxEntry::parse(val) {
+                        Some(entry) => {
+                            if let Some(track) = index.tracks.last_mut() {
+                                track.entries.push(entry);
+                            }
+                            index.entries.push(entry);
+                        }
+                        None => warn!("invalid idx timestamp line `{line}`, ignored"),
+                    },
                     _ => trace!("Unimplemented idx key: {key}"),
                 }
             }
@@ -134,15 +418,77 @@ impl Index {
         }
 
         //TODO: report missing palette ?
-        let palette = palette_val.unwrap_or(DEFAULT_PALETTE);
-
-        Ok(Self { palette, lang })
+        if let Some(palette) = palette_val {
+            index.palette = palette;
+        }
+        Ok(index)
     }
 
     /// Create an Index from a palette and sub data
     #[must_use]
     pub const fn init(palette: Palette, lang: Option<Lang>) -> Self {
-        Self { palette, lang }
+        Self {
+            size: None,
+            origin: None,
+            scale: None,
+            alpha: None,
+            smooth: None,
+            fade: None,
+            time_offset: None,
+            forced_subs: None,
+            palette,
+            lang,
+            entries: Vec::new(),
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Get the frame size (`size` key).
+    #[must_use]
+    pub const fn size(&self) -> Option<Size> {
+        self.size
+    }
+
+    /// Get the position of the upper-left corner of the subtitles (`org` key).
+    #[must_use]
+    pub const fn origin(&self) -> Option<(i32, i32)> {
+        self.origin
+    }
+
+    /// Get the horizontal and vertical scaling, in percent (`scale` key).
+    #[must_use]
+    pub const fn scale(&self) -> Option<(u16, u16)> {
+        self.scale
+    }
+
+    /// Get the alpha blending, in percent (`alpha` key).
+    #[must_use]
+    pub const fn alpha(&self) -> Option<u16> {
+        self.alpha
+    }
+
+    /// Get the smoothing of the subtitles (`smooth` key).
+    #[must_use]
+    pub const fn smooth(&self) -> Option<Smoothing> {
+        self.smooth
+    }
+
+    /// Get the fade in and fade out durations, in milliseconds (`fadein/out` key).
+    #[must_use]
+    pub const fn fade(&self) -> Option<(u32, u32)> {
+        self.fade
+    }
+
+    /// Get the offset to apply to the subtitle times (`time offset` key).
+    #[must_use]
+    pub const fn time_offset(&self) -> Option<TimePoint> {
+        self.time_offset
+    }
+
+    /// Get if only the forced subtitles are displayed (`forced subs` key).
+    #[must_use]
+    pub const fn forced_subs(&self) -> Option<bool> {
+        self.forced_subs
     }
 
     /// Get the palette associated with this `*.idx` file.
@@ -150,18 +496,79 @@ impl Index {
     pub const fn palette(&self) -> &Palette {
         &self.palette
     }
-    /// Get the lang associated with this `*.idx` file.
+    /// Get the lang associated with this `*.idx` file : the lang of its first track.
+    ///
+    /// See [`Self::tracks`] for the lang of each track.
     #[must_use]
     pub const fn lang(&self) -> &Option<Lang> {
         &self.lang
     }
+    /// Get the `timestamp` entries of this `*.idx` file, in file order.
+    ///
+    /// With several tracks, it contains the entries of all tracks, see [`Self::tracks`].
+    #[must_use]
+    pub fn entries(&self) -> &[IdxEntry] {
+        &self.entries
+    }
+
+    /// Get the tracks of this `*.idx` file, one for each `id` line, in file order.
+    #[must_use]
+    pub fn tracks(&self) -> &[IdxTrack] {
+        &self.tracks
+    }
+
+    /// Get the first track with the lang `code` (`en`, `fr`, ...).
+    #[must_use]
+    pub fn track(&self, code: &str) -> Option<&IdxTrack> {
+        self.tracks.iter().find(|track| track.lang.lang() == code)
+    }
+
+    /// Get the entry of the subtitle of the first track shown at `time`, see
+    /// [`IdxTrack::subtitle_at`].
+    ///
+    /// Without `id` line, the entries of the file are searched. Use
+    /// [`Self::track`] to search the subtitles of another track.
+    #[must_use]
+    pub fn subtitle_at(&self, time: TimePoint) -> Option<&IdxEntry> {
+        subtitle_at(self.first_track_entries(), time)
+    }
+
+    /// Iterate on the entries of the first track from the subtitle shown at `time`,
+    /// see [`IdxTrack::iter_from`].
+    pub fn iter_from(&self, time: TimePoint) -> impl Iterator<Item = &IdxEntry> {
+        iter_from(self.first_track_entries(), time)
+    }
+
+    /// Entries of the first track, or all the entries without track.
+    ///
+    /// The entries of all the tracks are not sorted as a whole, only the ones of a track.
+    fn first_track_entries(&self) -> &[IdxEntry] {
+        self.tracks
+            .first()
+            .map_or(&self.entries, |track| &track.entries)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use image::Rgb;
+    use std::io::BufReader;
 
-    use crate::vobsub::Index;
+    use crate::{
+        content::Size,
+        time::TimePoint,
+        vobsub::{IdxEntry, Index, Smoothing, VobSubError},
+    };
+
+    fn read_index(content: &str) -> Index {
+        Index::read_index(BufReader::new(content.as_bytes()), &|source| {
+            VobSubError::Io {
+                source,
+                path: "test.idx".into(),
+            }
+        })
+        .unwrap()
+    }
 
     #[test]
     fn parse_index() {
@@ -172,5 +579,173 @@ mod tests {
         //assert_eq!(idx.size(), Size { w: 1920, h: 1080 });
         assert_eq!(idx.palette()[0], Rgb([0x00, 0x00, 0x00]));
         assert_eq!(idx.palette()[15], Rgb([0x11, 0xbb, 0xbb]));
+        assert_eq!(idx.size(), Some(Size { w: 1920, h: 1080 }));
+        assert_eq!(idx.origin(), Some((0, 0)));
+        assert_eq!(idx.scale(), Some((100, 100)));
+        assert_eq!(idx.alpha(), Some(100));
+        assert_eq!(idx.smooth(), Some(Smoothing::Off));
+        assert_eq!(idx.fade(), Some((0, 0)));
+        assert_eq!(idx.time_offset(), Some(TimePoint::from_msecs(0)));
+        assert_eq!(idx.forced_subs(), Some(false));
+        assert_eq!(
+            idx.entries(),
+            [
+                IdxEntry::new(TimePoint::from_msecs(49_466), 0),
+                IdxEntry::new(TimePoint::from_msecs(52_636), 0x1000),
+            ]
+        );
+    }
+
+    #[test]
+    fn rendering_parameters() {
+        let idx = read_index(
+            "org: -10, 20\n\
+             scale: 50%, 75 %\n\
+             smooth: OLD\n\
+             fadein/out: 50, 120\n\
+             time offset: -00:00:01:500\n\
+             forced subs: ON\n\
+             alpha: full\n",
+        );
+        assert_eq!(idx.origin(), Some((-10, 20)));
+        assert_eq!(idx.scale(), Some((50, 75)));
+        assert_eq!(idx.smooth(), Some(Smoothing::Old));
+        assert_eq!(idx.fade(), Some((50, 120)));
+        assert_eq!(idx.time_offset(), Some(TimePoint::from_msecs(-1500)));
+        assert_eq!(idx.forced_subs(), Some(true));
+        assert_eq!(idx.alpha(), None);
+        assert_eq!(
+            read_index("time offset: 250\n").time_offset(),
+            Some(TimePoint::from_msecs(250))
+        );
+    }
+
+    #[test]
+    fn seek_by_time() {
+        let idx = Index::open("./fixtures/example.idx").unwrap();
+        let [first, second] = idx.entries() else {
+            panic!("expected 2 entries");
+        };
+        assert_eq!(idx.subtitle_at(TimePoint::from_msecs(10_000)), None);
+        assert_eq!(idx.subtitle_at(TimePoint::from_msecs(49_466)), Some(first));
+        assert_eq!(idx.subtitle_at(TimePoint::from_msecs(60_000)), Some(second));
+        assert_eq!(idx.iter_from(TimePoint::from_msecs(10_000)).count(), 2);
+        assert_eq!(
+            idx.iter_from(TimePoint::from_msecs(52_000))
+                .collect::<Vec<_>>(),
+            [first, second]
+        );
+        assert_eq!(
+            idx.iter_from(TimePoint::from_msecs(53_000))
+                .collect::<Vec<_>>(),
+            [second]
+        );
+    }
+
+    #[test]
+    fn keys_after_timestamps_and_duplicates() {
+        let palette = |first| {
+            let mut colors = vec![first];
+            colors.extend(["000000"; 15]);
+            colors.join(", ")
+        };
+        let content = format!(
+            "size: 720x576\n\
+             id: en, index: 0\n\
+             timestamp: 00:00:01:000, filepos: 000000000\n\
+             palette: {}\n\
+             id: fr, index: 1\n\
+             palette: {}\n\
+             size: 720x\n",
+            palette("ff0000"),
+            palette("00ff00"),
+        );
+        let idx = read_index(&content);
+        assert_eq!(idx.palette()[0], Rgb([0x00, 0xff, 0x00]));
+        // The invalid duplicate doesn't clear the valid value.
+        assert_eq!(idx.size(), Some(Size { w: 720, h: 576 }));
+        let tracks = idx
+            .tracks()
+            .iter()
+            .map(|track| (track.lang().lang(), track.index(), track.entries().len()))
+            .collect::<Vec<_>>();
+        assert_eq!(tracks, [("en", 0, 1), ("fr", 1, 0)]);
+        assert_eq!(idx.track("fr").unwrap().index(), 1);
+        assert!(idx.track("de").is_none());
+        assert_eq!(idx.alpha(), None);
+        assert_eq!(idx.lang().as_ref().unwrap().lang(), "en");
+    }
+
+    #[test]
+    fn invalid_duplicate_keeps_valid_value() {
+        let idx = read_index(
+            "size: 720x576\n\
+             org: 10, 20\n\
+             alpha: 50%\n\
+             size: 720x\n\
+             org: 10\n\
+             alpha: full\n",
+        );
+        assert_eq!(idx.size(), Some(Size { w: 720, h: 576 }));
+        assert_eq!(idx.origin(), Some((10, 20)));
+        assert_eq!(idx.alpha(), Some(50));
+        // A valid duplicate still replaces the value.
+        assert_eq!(read_index("alpha: 50%\nalpha: 80%\n").alpha(), Some(80));
+    }
+
+    #[test]
+    fn lang_of_id_line() {
+        // Missing : no `id` line, or an `id` line without lang.
+        let idx = read_index("size: 720x576\n");
+        assert!(idx.lang().is_none());
+        assert!(idx.tracks().is_empty());
+        let idx = read_index("id: , index: 0\ntimestamp: 00:00:01:000, filepos: 000000000\n");
+        assert!(idx.lang().is_none());
+        assert!(idx.tracks().is_empty());
+        assert_eq!(idx.entries().len(), 1);
+
+        // Unknown : the code is kept as is.
+        let idx = read_index("id: zz, index: 0\n");
+        assert_eq!(idx.lang().as_ref().unwrap().lang(), "zz");
+        assert_eq!(idx.track("zz").unwrap().index(), 0);
+
+        // The `index:` part gives the index of the track, and is required.
+        let idx = read_index("id: de, index: 12\nid: it\n");
+        assert_eq!(idx.lang().as_ref().unwrap().lang(), "de");
+        let tracks = idx
+            .tracks()
+            .iter()
+            .map(|track| (track.lang().lang(), track.index()))
+            .collect::<Vec<_>>();
+        assert_eq!(tracks, [("de", 12)]);
+        assert!(read_index("id: en, index: x\n").tracks().is_empty());
+    }
+
+    #[test]
+    fn seek_by_time_in_tracks() {
+        let idx = read_index(
+            "id: en, index: 0\n\
+             timestamp: 00:00:01:000, filepos: 000000000\n\
+             timestamp: 00:00:05:000, filepos: 000001000\n\
+             id: fr, index: 1\n\
+             timestamp: 00:00:02:000, filepos: 000002000\n\
+             timestamp: 00:00:03:000, filepos: 000003000\n",
+        );
+        let time = TimePoint::from_msecs(4000);
+        let entry = |secs: i64, filepos| IdxEntry::new(TimePoint::from_msecs(secs * 1000), filepos);
+        assert_eq!(idx.subtitle_at(time), Some(&entry(1, 0)));
+        let fr = idx.track("fr").unwrap();
+        assert_eq!(fr.subtitle_at(time), Some(&entry(3, 0x3000)));
+        assert_eq!(fr.subtitle_at(TimePoint::from_msecs(1000)), None);
+        assert_eq!(
+            fr.iter_from(TimePoint::from_msecs(2500))
+                .copied()
+                .collect::<Vec<_>>(),
+            [entry(2, 0x2000), entry(3, 0x3000)]
+        );
+        assert_eq!(
+            idx.iter_from(time).copied().collect::<Vec<_>>(),
+            [entry(1, 0), entry(5, 0x1000)]
+        );
     }
 }
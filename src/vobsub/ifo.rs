@@ -0,0 +1,269 @@
+//! Parse the subtitles information of a DVD `VTS_*_0.IFO` file.
+//!
+//! The subtitles embedded in a DVD video (`*.VOB` files) are described by the `IFO` file
+//! of their title set, instead of an `*.idx` file : the palette is stored in the program
+//! chains, and the languages in the sub-picture attributes.
+//!
+//! Useful reference: [DVD IFO](http://dvd.sourceforge.net/dvdinfo/ifo.html).
+
+use image::Rgb;
+use std::{fs, path::Path};
+
+use super::{idx::Lang, Index, Palette, VobSubError};
+use crate::time::{FrameRate, TimeDuration, TimePoint};
+
+/// Magic bytes at the start of a video title set `IFO` file.
+const VTS_MAGIC: &[u8] = b"DVDVIDEO-VTS";
+/// Size of a DVD sector, unit of the `IFO` pointers.
+const SECTOR_SIZE: usize = 2048;
+/// Offset of the sector of the program chains table (`VTS_PGCI`).
+const VTS_PGCI_SECTOR: usize = 0xCC;
+/// Offset of the number of sub-picture streams, followed by their attributes.
+const SUBPICTURE_COUNT: usize = 0x254;
+/// Size of the attributes of a sub-picture stream.
+const SUBPICTURE_ATTR_LEN: usize = 6;
+/// Offset of the playback time in a program chain.
+const PGC_PLAYBACK_TIME: usize = 0x04;
+/// Offset of the sub-picture streams control in a program chain.
+const PGC_SUBPICTURE_CONTROL: usize = 0x1C;
+/// Offset of the palette in a program chain.
+const PGC_PALETTE: usize = 0xA4;
+
+/// A sub-picture (subtitle) stream declared in an `IFO` file.
+#[derive(Debug, Clone)]
+pub struct IfoSubtitleStream {
+    index: usize,
+    lang: Option<Lang>,
+    substream_id: Option<u8>,
+    wide_substream_id: Option<u8>,
+}
+
+impl IfoSubtitleStream {
+    /// Index of the stream in the title set.
+    #[must_use]
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Language of the stream, if declared.
+    #[must_use]
+    pub const fn lang(&self) -> &Option<Lang> {
+        &self.lang
+    }
+
+    /// Id of the `PES` sub-stream of the stream for 4:3 display, if it's available.
+    #[must_use]
+    pub const fn substream_id(&self) -> Option<u8> {
+        self.substream_id
+    }
+
+    /// Id of the `PES` sub-stream of the stream for wide screen display, if it's available.
+    #[must_use]
+    pub const fn wide_substream_id(&self) -> Option<u8> {
+        self.wide_substream_id
+    }
+}
+
+/// The subtitles information of a video title set `IFO` file.
+#[derive(Debug, Clone)]
+pub struct Ifo {
+    palette: Palette,
+    duration: TimeDuration,
+    streams: Vec<IfoSubtitleStream>,
+}
+
+impl Ifo {
+    /// Open and parse a `VTS_*_0.IFO` file.
+    ///
+    /// # Errors
+    /// Will return [`VobSubError::Io`] if the file can't be read, or
+    /// [`VobSubError::InvalidIfo`] if it can't be parsed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, VobSubError> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|source| VobSubError::Io {
+            source,
+            path: path.into(),
+        })?;
+        Self::parse(&data)
+    }
+
+    /// Parse the content of a `VTS_*_0.IFO` file.
+    ///
+    /// The palette and the duration are the ones of the first program chain, as tools
+    /// generating `*.idx` files do.
+    ///
+    /// # Errors
+    /// Will return [`VobSubError::InvalidIfo`] if the content is not a video title set `IFO`,
+    /// or is truncated.
+    pub fn parse(data: &[u8]) -> Result<Self, VobSubError> {
+        if !data.starts_with(VTS_MAGIC) {
+            return Err(VobSubError::InvalidIfo("missing `DVDVIDEO-VTS` header"));
+        }
+        let truncated = || VobSubError::InvalidIfo("truncated file");
+        let bytes =
+            |offset: usize, len: usize| data.get(offset..offset + len).ok_or_else(truncated);
+        let be_u16 =
+            |offset| bytes(offset, 2).map(|b| usize::from(u16::from_be_bytes([b[0], b[1]])));
+        let be_u32 =
+            |offset| bytes(offset, 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+        let usize_from = |value: u32| usize::try_from(value).map_err(|_overflow| truncated());
+
+        // First program chain, with the palette and the sub-picture streams control.
+        let pgci = usize_from(be_u32(VTS_PGCI_SECTOR)?)? * SECTOR_SIZE;
+        if be_u16(pgci)? == 0 {
+            return Err(VobSubError::InvalidIfo("no program chain"));
+        }
+        let pgc = pgci + usize_from(be_u32(pgci + 8 + 4)?)?;
+        let palette = bytes(pgc + PGC_PALETTE, 16 * 4)?;
+        let palette: Palette = std::array::from_fn(|idx| {
+            ycrcb_to_rgb(
+                palette[idx * 4 + 1],
+                palette[idx * 4 + 2],
+                palette[idx * 4 + 3],
+            )
+        });
+
+        let duration = playback_time(bytes(pgc + PGC_PLAYBACK_TIME, 4)?);
+
+        let count = be_u16(SUBPICTURE_COUNT)?.min(32);
+        let streams = (0..count)
+            .map(|index| {
+                let attr = bytes(
+                    SUBPICTURE_COUNT + 2 + index * SUBPICTURE_ATTR_LEN,
+                    SUBPICTURE_ATTR_LEN,
+                )?;
+                let lang = (attr[0] & 0x03 == 1)
+                    .then(|| std::str::from_utf8(&attr[2..4]).ok())
+                    .flatten()
+                    .filter(|code| code.bytes().all(|c| c.is_ascii_alphabetic()))
+                    .map(|code| Lang::from_code(&code.to_ascii_lowercase()));
+                let control = be_u32(pgc + PGC_SUBPICTURE_CONTROL + index * 4)?;
+                let available = control & 0x8000_0000 != 0;
+                let id = |shift: u32| {
+                    let number = u8::try_from((control >> shift) & 0x1F).unwrap_or_default();
+                    available.then_some(0x20 + number)
+                };
+                Ok(IfoSubtitleStream {
+                    index,
+                    lang,
+                    substream_id: id(24),
+                    wide_substream_id: id(16),
+                })
+            })
+            .collect::<Result<_, VobSubError>>()?;
+
+        Ok(Self {
+            palette,
+            duration,
+            streams,
+        })
+    }
+
+    /// Palette of the subtitles.
+    #[must_use]
+    pub const fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Playback time of the first program chain of the title set.
+    #[must_use]
+    pub const fn duration(&self) -> TimeDuration {
+        self.duration
+    }
+
+    /// The sub-picture streams of the title set.
+    #[must_use]
+    pub fn streams(&self) -> &[IfoSubtitleStream] {
+        &self.streams
+    }
+
+    /// Build the [`Index`] of a stream, as it would be read from an `*.idx` file.
+    #[must_use]
+    pub fn index(&self, stream: &IfoSubtitleStream) -> Index {
+        Index::init(self.palette, stream.lang.clone())
+    }
+}
+
+/// Decode a playback time : `BCD` hours, minutes, seconds and frames, with the frame
+/// rate in the 2 high bits of the frames.
+fn playback_time(bytes: &[u8]) -> TimeDuration {
+    let bcd = |value: u8| i64::from(value >> 4) * 10 + i64::from(value & 0x0F);
+    let seconds = (bcd(bytes[0]) * 60 + bcd(bytes[1])) * 60 + bcd(bytes[2]);
+    let rate = match bytes[3] >> 6 {
+        0b01 => FrameRate::PAL,
+        _ => FrameRate::NTSC,
+    };
+    let frames = TimePoint::from_frames(bcd(bytes[3] & 0x3F), rate);
+    TimeDuration::from_msecs(seconds * 1000 + frames.msecs())
+}
+
+/// Convert a DVD palette color (`ITU-R BT.601`, limited range) to `RGB`.
+fn ycrcb_to_rgb(y: u8, cr: u8, cb: u8) -> Rgb<u8> {
+    let y = 1.164 * (f64::from(y) - 16.);
+    let (cr, cb) = (f64::from(cr) - 128., f64::from(cb) - 128.);
+    let channel = |value: f64| cast::u8(value.round().clamp(0., 255.)).unwrap_or_default();
+    Rgb([
+        channel(1.596f64.mul_add(cr, y)),
+        channel(0.391f64.mul_add(-cb, 0.813f64.mul_add(-cr, y))),
+        channel(2.018f64.mul_add(cb, y)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches2::assert_matches;
+
+    /// Minimal `IFO` with one program chain, and an english and an undeclared streams.
+    fn ifo_data() -> Vec<u8> {
+        let mut data = vec![0; 2 * SECTOR_SIZE];
+        data[..VTS_MAGIC.len()].copy_from_slice(VTS_MAGIC);
+        data[VTS_PGCI_SECTOR..VTS_PGCI_SECTOR + 4].copy_from_slice(&1u32.to_be_bytes());
+        data[SUBPICTURE_COUNT + 1] = 2;
+        data[SUBPICTURE_COUNT + 2] = 0x01;
+        data[SUBPICTURE_COUNT + 4..SUBPICTURE_COUNT + 6].copy_from_slice(b"en");
+
+        let pgci = SECTOR_SIZE;
+        data[pgci + 1] = 1;
+        data[pgci + 15] = 0x10;
+        let pgc = pgci + 0x10;
+        let control = pgc + PGC_SUBPICTURE_CONTROL;
+        data[control..control + 4].copy_from_slice(&[0x80, 0x00, 0x01, 0x00]);
+        data[control + 4..control + 8].copy_from_slice(&[0x81, 0x02, 0x02, 0x00]);
+        // 1:02:03 and 12 frames at 25 fps.
+        let time = pgc + PGC_PLAYBACK_TIME;
+        data[time..time + 4].copy_from_slice(&[0x01, 0x02, 0x03, 0x52]);
+        let palette = pgc + PGC_PALETTE;
+        data[palette..palette + 8].copy_from_slice(&[0, 16, 128, 128, 0, 235, 128, 128]);
+        data
+    }
+
+    #[test]
+    fn parse_ifo() {
+        let ifo = Ifo::parse(&ifo_data()).unwrap();
+        assert_eq!(ifo.palette()[0], Rgb([0, 0, 0]));
+        assert_eq!(ifo.palette()[1], Rgb([255, 255, 255]));
+        assert_eq!(ifo.duration().msecs(), 3_723_480);
+
+        let [english, other] = ifo.streams() else {
+            panic!("two streams expected");
+        };
+        assert_eq!(english.lang().as_ref().unwrap().lang(), "en");
+        assert_eq!(english.substream_id(), Some(0x20));
+        assert_eq!(other.lang().as_ref().map(Lang::lang), None);
+        assert_eq!(other.wide_substream_id(), Some(0x22));
+
+        let index = ifo.index(english);
+        assert_eq!(index.palette(), ifo.palette());
+        assert_eq!(index.lang().as_ref().unwrap().lang(), "en");
+
+        assert_matches!(
+            Ifo::parse(&ifo_data()[..SECTOR_SIZE]),
+            Err(VobSubError::InvalidIfo(_))
+        );
+        assert_matches!(
+            Ifo::parse(b"# VobSub index file"),
+            Err(VobSubError::InvalidIfo(_))
+        );
+    }
+}
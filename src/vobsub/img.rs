@@ -1,6 +1,11 @@
 //! Run-length encoded image format for subtitles.
 
 use core::fmt::{self, Debug};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
 use image::{ImageBuffer, Luma, Pixel, Rgb, Rgba};
 use iter_fixed::IntoIteratorFixed as _;
 use log::trace;
@@ -13,15 +18,22 @@ use nom::{
 };
 use thiserror::Error;
 
-use super::{palette::PaletteLuma, IResultExt as _, NomError, VobSubError};
+use super::{
+    palette::{nearest_palette_entry, Palette, PaletteLuma},
+    IResultExt as _, NomError, VobSubError,
+};
 use crate::{
-    content::{Area, Size},
-    image::{ImageArea, ImageSize as _, ToImage, ToOcrImage, ToOcrImageOpt},
+    content::{Area, AreaValues, ContentError, Size},
+    image::{
+        crop, ocr, ImageArea, ImageSize as _, IndexedImage, RawImage, RawPixel, ToImage,
+        ToImageInto, ToOcrImage, ToOcrImageOpt, ToRawImage, Transform,
+    },
     util::BytesFormatter,
 };
 
 /// Errors of `vobsub` image management.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// If there is more data to write than the space in output.
     #[error("output parameter is too small (size:{output_size}) for write scanline data (size:{data_size})")]
@@ -100,7 +112,7 @@ impl<'a> VobSubRleImageData<'a> {
         let start_0 = usize::from(rle_offsets[0]);
         let start_1 = usize::from(rle_offsets[1]);
 
-        if start_0 > start_1 || start_1 > end {
+        if start_0 > start_1 || start_1 > end || end > raw_data.len() {
             Err(VobSubError::InvalidScanLineOffsets {
                 start_0,
                 start_1,
@@ -146,18 +158,35 @@ fn rle(input: (&[u8], usize)) -> IResult<(&[u8], usize), Rle> {
     Ok((input, Rle { cnt, val }))
 }
 
+/// Maximal size in bits of an encoded `Rle` (4-nibble count and value).
+const RLE_MAX_BITS: usize = 16;
+
 /// Decompress the scan-line `input` into `output`, returning the number of
 /// input bytes consumed.
-fn scan_line(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
+///
+/// Each line is aligned on a byte boundary : the trailing bits of the last byte are ignored.
+/// If `last_line` is set, the encoded data of the line can legitimately stop before the end
+/// of the line, the remaining pixels are then filled with the background (value `0`).
+fn scan_line(input: &[u8], output: &mut [u8], last_line: bool) -> Result<usize, Error> {
     trace!("scan line starting with {:?}", BytesFormatter(input));
     let width = output.len();
     let mut x = 0;
     let mut pos = (input, 0);
     while x < width {
-        let (new_pos, run) = rle(pos).to_result().map_err(Error::ScanLineParsing)?;
+        let run = match rle(pos).to_result() {
+            Ok((new_pos, run)) => {
+                pos = new_pos;
+                run
+            }
+            Err(_) if last_line && pos.0.len() * 8 - pos.1 < RLE_MAX_BITS => {
+                trace!("last scan line underflow, filled from {x} to {width}");
+                output[x..].fill(0);
+                return Ok(input.len());
+            }
+            Err(err) => return Err(Error::ScanLineParsing(err)),
+        };
 
         //trace!("RLE: {:?}", &run);
-        pos = new_pos;
         let count = if run.cnt == 0 {
             width - x
         } else {
@@ -185,27 +214,46 @@ fn scan_line(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
 /// Decompress a run-length encoded image, and return a vector in row-major
 /// order, starting at the upper-left and scanning right and down, with one
 /// byte for each 2-bit value.
-#[profiling::function]
+///
+/// Lines are interlaced : even lines are read from the first field, odd lines from the second.
+/// With an odd height, the first field contain one more line than the second.
+/// The last line of each field may be truncated (see [`scan_line`]).
 pub fn decompress(size: Size, data: &VobSubRleImageData) -> Result<Vec<u8>, Error> {
+    let mut img = Vec::new();
+    decompress_into(size, data, &mut img)?;
+    Ok(img)
+}
+
+/// Decompress a run-length encoded image like [`decompress`], in the `img` buffer
+/// instead of a new one, to reuse its allocation.
+#[profiling::function]
+pub fn decompress_into(
+    size: Size,
+    data: &VobSubRleImageData,
+    img: &mut Vec<u8>,
+) -> Result<(), Error> {
     trace!(
         "decompressing image {:?}, max: [0x{:x}, 0x{:x}]",
         &size,
         data.data[0].len(),
         data.data[1].len()
     );
-    let mut img = vec![0; size.w * size.h];
+    img.clear();
+    img.resize(size.w * size.h, 0);
     let mut offsets = [0; 2];
     for y in 0..size.h {
         let odd = y % 2;
+        let last_line = y + 2 >= size.h;
         trace!("line {:?}, offset 0x{:x}", y, offsets[odd]);
         let consumed = scan_line(
             &data.data[odd][offsets[odd]..],
             &mut img[y * size.w..(y + 1) * size.w],
+            last_line,
         )?;
         offsets[odd] += consumed;
     }
     // TODO: Warn if we didn't consume everything.
-    Ok(img)
+    Ok(())
 }
 
 /// Manage image data from `VobSub` file.
@@ -252,6 +300,98 @@ impl VobSubIndexedImage {
     pub fn raw_image(&self) -> &[u8] {
         self.raw_image.as_slice()
     }
+
+    /// Take the pixel raw data of the image, to reuse its buffer.
+    #[must_use]
+    pub fn into_raw_image(self) -> Vec<u8> {
+        self.raw_image
+    }
+
+    /// Transform the image, and its area on a `screen`, see [`Transform::apply_area`].
+    ///
+    /// # Errors
+    ///
+    /// Will return [`ContentError::InvalidAreaBounding`] if the transformed area doesn't
+    /// fit on the screen.
+    pub fn transformed(&self, transform: Transform, screen: &Size) -> Result<Self, ContentError> {
+        let area = transform.apply_area(&self.area, screen)?;
+        let raw_image = transform.apply_indexes(&self.raw_image, self.width(), self.height());
+        Ok(Self::new(area, self.palette, self.alpha, raw_image))
+    }
+
+    /// Crop the transparent borders of the image, and reduce its area accordingly.
+    ///
+    /// Return `None` if all the pixels are transparent.
+    #[must_use]
+    pub fn crop_to_content(&self) -> Option<Self> {
+        let width = self.width();
+        let bbox = crop::content_box(width, self.height(), |x, y| {
+            let index = self.raw_image[y as usize * width as usize + x as usize];
+            self.alpha[usize::from(index & 0b11)] & 0x0F > 0
+        })?;
+        let area = crop::crop_area(self.area, bbox);
+        let raw_image = self
+            .raw_image
+            .chunks_exact(width as usize)
+            .skip(bbox.y as usize)
+            .take(bbox.height as usize)
+            .flat_map(|row| &row[bbox.x as usize..(bbox.x + bbox.width) as usize])
+            .copied()
+            .collect();
+        Some(Self::new(area, self.palette, self.alpha, raw_image))
+    }
+
+    /// Convert to a generic [`IndexedImage`], with the colors of the `idx` `palette`.
+    ///
+    /// The pixel indexes are kept, and the 4-bit alpha values are scaled to 8 bits.
+    #[must_use]
+    pub fn to_indexed(&self, palette: &Palette) -> IndexedImage<4> {
+        let colors = [0, 1, 2, 3].map(|idx| {
+            let Rgb([red, green, blue]) = palette[usize::from(self.palette[idx] & 0x0F)];
+            Rgba([red, green, blue, (self.alpha[idx] & 0x0F) * 17])
+        });
+        IndexedImage::new(
+            self.width(),
+            self.height(),
+            colors,
+            self.raw_image.iter().map(|&pixel| pixel & 0b11).collect(),
+        )
+    }
+
+    /// Create an image from a generic [`IndexedImage`], displayed at `left`,`top`.
+    ///
+    /// The pixel indexes are kept, each color is mapped to the nearest entry of
+    /// the `idx` `palette`, and alpha values are reduced to 4 bits.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`ContentError::InvalidAreaBounding`] if the image is empty or
+    /// doesn't fit in the coordinates range.
+    pub fn from_indexed(
+        image: &IndexedImage<4>,
+        left: u16,
+        top: u16,
+        palette: &Palette,
+    ) -> Result<Self, ContentError> {
+        let coord = |position: u16, size: u32| {
+            u16::try_from(size)
+                .ok()
+                .and_then(|size| position.checked_add(size.checked_sub(1)?))
+                .ok_or(ContentError::InvalidAreaBounding)
+        };
+        let area = Area::try_from(AreaValues {
+            x1: left,
+            y1: top,
+            x2: coord(left, image.width())?,
+            y2: coord(top, image.height())?,
+        })?;
+        let colors = image.palette();
+        let entries = colors.map(|Rgba([red, green, blue, _])| {
+            nearest_palette_entry(palette, Rgb([red, green, blue]))
+        });
+        let alphas = colors.map(|Rgba([.., alpha])| alpha / 17 + u8::from(alpha % 17 > 8));
+        Ok(Self::new(area, entries, alphas, image.indexes().to_vec()))
+    }
 }
 
 impl fmt::Debug for VobSubIndexedImage {
@@ -264,21 +404,45 @@ impl fmt::Debug for VobSubIndexedImage {
     }
 }
 
+impl AsRef<Self> for VobSubIndexedImage {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
 impl ImageArea for VobSubIndexedImage {
     fn area(&self) -> Area {
         self.area
     }
 }
 
-impl From<VobSubRleImage<'_>> for VobSubIndexedImage {
-    fn from(rle_image: VobSubRleImage) -> Self {
-        let decompressed_image = decompress(rle_image.size(), rle_image.raw_data()).unwrap();
-        Self::new(
+impl VobSubIndexedImage {
+    /// Decompress `rle_image` in `buffer`, reusing its allocation for the pixels.
+    pub(super) fn decompress_with_buffer(
+        rle_image: &VobSubRleImage,
+        mut buffer: Vec<u8>,
+    ) -> Result<Self, Error> {
+        decompress_into(rle_image.size(), rle_image.raw_data(), &mut buffer)?;
+        Ok(Self::new(
+            rle_image.area(),
+            *rle_image.palette(),
+            *rle_image.alpha(),
+            buffer,
+        ))
+    }
+}
+
+impl TryFrom<VobSubRleImage<'_>> for VobSubIndexedImage {
+    type Error = Error;
+
+    fn try_from(rle_image: VobSubRleImage) -> Result<Self, Self::Error> {
+        let decompressed_image = decompress(rle_image.size(), rle_image.raw_data())?;
+        Ok(Self::new(
             rle_image.area(),
             *rle_image.palette(),
             *rle_image.alpha(),
             decompressed_image,
-        )
+        ))
     }
 }
 
@@ -293,6 +457,78 @@ pub fn conv_to_rgba(color: Rgb<u8>, alpha: u8) -> Rgba<u8> {
     ])
 }
 
+/// Key of the output colors of an image : its palette indexes and alpha values,
+/// with the text and background colors for `OCR` images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PaletteKey {
+    palette: [u8; 4],
+    alpha: [u8; 4],
+    ocr_colors: Option<[u8; 2]>,
+}
+
+impl PaletteKey {
+    const fn new(image: &VobSubIndexedImage, ocr_colors: Option<[u8; 2]>) -> Self {
+        Self {
+            palette: *image.palette(),
+            alpha: *image.alpha(),
+            ocr_colors,
+        }
+    }
+}
+
+/// Cache of the output colors of [`VobSubIndexedImage`]s, to share between the conversions
+/// of a batch of images.
+///
+/// Most cues of a `VobSub` track use the same palette indexes and alpha values, so the
+/// output colors can be computed once for all of them.
+/// See [`VobSubToImage::with_cache`] and [`VobSubOcrImage::with_cache`].
+///
+/// The cache can be shared between threads converting images in parallel.
+#[derive(Debug)]
+pub struct PaletteColorCache<P> {
+    colors: Mutex<HashMap<PaletteKey, [P; 4]>>,
+}
+
+impl<P> Default for PaletteColorCache<P> {
+    fn default() -> Self {
+        Self {
+            colors: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<P: Copy> PaletteColorCache<P> {
+    /// Create an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of the cached palettes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.colors().len()
+    }
+
+    /// Return `true` if no palette is cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.colors().is_empty()
+    }
+
+    fn get_or_insert_with<F>(&self, key: PaletteKey, compute: F) -> [P; 4]
+    where
+        F: FnOnce() -> [P; 4],
+    {
+        *self.colors().entry(key).or_insert_with(compute)
+    }
+
+    fn colors(&self) -> MutexGuard<'_, HashMap<PaletteKey, [P; 4]>> {
+        // The map is always left consistent, even by a panicking `compute`.
+        self.colors.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 /// This struct implement [`ToImage`] to generate an `ImageBuffer` from
 /// a [`VobSubIndexedImage`], a palette and a pixel conversion function.
 pub struct VobSubToImage<'a, I, P>
@@ -302,6 +538,7 @@ where
     indexed_img: &'a VobSubIndexedImage,
     palette: &'a [I; 16],
     conv_fn: fn(I, u8) -> P,
+    cache: Option<&'a PaletteColorCache<P>>,
 }
 
 impl<'a, I, P> VobSubToImage<'a, I, P>
@@ -316,14 +553,60 @@ where
             indexed_img: img,
             palette,
             conv_fn,
+            cache: None,
+        }
+    }
+
+    /// Use `cache` to reuse the output colors computed for previous images.
+    ///
+    /// The cache must only be shared by converters using the same `palette` and
+    /// conversion function, as they are not part of the cache key.
+    #[must_use]
+    pub const fn with_cache(mut self, cache: &'a PaletteColorCache<P>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn palette_color(&self) -> [P; 4]
+    where
+        I: Clone,
+    {
+        let key = PaletteKey::new(self.indexed_img, None);
+        self.cache.map_or_else(
+            || self.compute_palette_color(),
+            |cache| cache.get_or_insert_with(key, || self.compute_palette_color()),
+        )
+    }
+
+    /// Bytes of the converted pixels, line after line.
+    fn raw_pixels(&self) -> Vec<u8>
+    where
+        I: Clone,
+    {
+        let mut buf = Vec::new();
+        self.write_raw_pixels(&mut buf);
+        buf
+    }
+
+    /// Write the bytes of the converted pixels in `buf`, replacing its content.
+    fn write_raw_pixels(&self, buf: &mut Vec<u8>)
+    where
+        I: Clone,
+    {
+        let out_color_palette = self.palette_color();
+        let raw_image = self.indexed_img.raw_image();
+        buf.clear();
+        buf.reserve(raw_image.len() * usize::from(P::CHANNEL_COUNT));
+        for &idx in raw_image {
+            buf.extend_from_slice(out_color_palette[usize::from(idx)].channels());
         }
     }
 
-    fn compute_palette_color(&self, conv: fn(I, u8) -> P) -> [P; 4]
+    fn compute_palette_color(&self) -> [P; 4]
     where
         I: Clone,
-        P: Pixel<Subpixel = u8>,
     {
+        let conv = self.conv_fn;
         self.indexed_img
             .palette()
             .into_iter_fixed()
@@ -347,14 +630,35 @@ where
     {
         let width = self.indexed_img.width();
         let height = self.indexed_img.height();
-        let out_color_palette = self.compute_palette_color(self.conv_fn);
+        ImageBuffer::from_vec(width, height, self.raw_pixels())
+            .expect("Failed to create image buffer")
+    }
+}
 
-        let image = ImageBuffer::from_fn(width, height, |x, y| {
-            let offset = y * width + x;
-            let sub_palette_idx = self.indexed_img.raw_image()[offset as usize] as usize;
-            out_color_palette[sub_palette_idx]
-        });
-        image
+impl<I, P> ToImageInto for VobSubToImage<'_, I, P>
+where
+    I: Clone,
+    P: Pixel<Subpixel = u8>,
+{
+    #[profiling::function]
+    fn to_image_into(&self, mut buffer: Vec<u8>) -> ImageBuffer<P, Vec<u8>> {
+        let width = self.indexed_img.width();
+        let height = self.indexed_img.height();
+        self.write_raw_pixels(&mut buffer);
+        ImageBuffer::from_vec(width, height, buffer).expect("Failed to create image buffer")
+    }
+}
+
+impl<I, P> ToRawImage for VobSubToImage<'_, I, P>
+where
+    I: Clone,
+    P: RawPixel,
+{
+    #[profiling::function]
+    fn to_raw_image(&self) -> RawImage {
+        let width = self.indexed_img.width();
+        let height = self.indexed_img.height();
+        RawImage::new(self.raw_pixels(), width, height, P::FORMAT)
     }
 }
 
@@ -362,6 +666,7 @@ where
 pub struct VobSubOcrImage<'a> {
     indexed_img: &'a VobSubIndexedImage,
     palette: &'a PaletteLuma,
+    cache: Option<&'a PaletteColorCache<Luma<u8>>>,
 }
 
 impl<'a> VobSubOcrImage<'a> {
@@ -371,9 +676,29 @@ impl<'a> VobSubOcrImage<'a> {
         Self {
             indexed_img,
             palette,
+            cache: None,
         }
     }
 
+    /// Use `cache` to reuse the output colors computed for previous images.
+    ///
+    /// The cache must only be shared by converters using the same `palette`,
+    /// as it is not part of the cache key.
+    #[must_use]
+    pub const fn with_cache(mut self, cache: &'a PaletteColorCache<Luma<u8>>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn palette_color(&self, opt: ToOcrImageOpt) -> [Luma<u8>; 4] {
+        let colors = [opt.text_color.0[0], opt.background_color.0[0]];
+        let key = PaletteKey::new(self.indexed_img, Some(colors));
+        self.cache.map_or_else(
+            || self.compute_palette_color(opt),
+            |cache| cache.get_or_insert_with(key, || self.compute_palette_color(opt)),
+        )
+    }
+
     // Compute the output palette color
     fn compute_palette_color(&self, opt: ToOcrImageOpt) -> [Luma<u8>; 4] {
         const LUMA_BLACK: [u8; 1] = [0; 1];
@@ -398,18 +723,232 @@ impl ToOcrImage for VobSubOcrImage<'_> {
     fn image(&self, opt: &ToOcrImageOpt) -> image::GrayImage {
         let width = self.indexed_img.width();
         let height = self.indexed_img.height();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("ocr_image", width, height).entered();
         let border = opt.border;
-        let out_color_palette = self.compute_palette_color(*opt);
+        let out_color_palette = self.palette_color(*opt);
+        let pixel_at = |x: u32, y: u32| {
+            let offset = y * width + x;
+            let sub_palette_idx = self.indexed_img.raw_image()[offset as usize] as usize;
+            out_color_palette[sub_palette_idx]
+        };
+        let border_color = opt
+            .border_fill
+            .color(opt.background_color, width, height, pixel_at);
 
         let image = ImageBuffer::from_fn(width + border * 2, height + border * 2, |x, y| {
             if x < border || x >= width + border || y < border || y >= height + border {
-                opt.background_color
+                border_color
             } else {
-                let offset = (y - border) * width + (x - border);
-                let sub_palette_idx = self.indexed_img.raw_image()[offset as usize] as usize;
-                out_color_palette[sub_palette_idx]
+                pixel_at(x - border, y - border)
             }
         });
-        image
+        ocr::post_process(image, *opt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image::PixelFormat, vobsub::palette::DEFAULT_PALETTE};
+
+    // Lines of 4 pixels encoded with a 2-nibble `Rle` : `00` + count `0100` + value.
+    const LINE_OF_1: u8 = 0x11;
+    const LINE_OF_2: u8 = 0x12;
+
+    fn size(w: usize, h: usize) -> Size {
+        Size { w, h }
+    }
+
+    #[test]
+    fn crop_transparent_borders() {
+        let area = Area::try_from(AreaValues {
+            x1: 10,
+            y1: 20,
+            x2: 13,
+            y2: 22,
+        })
+        .unwrap();
+        #[rustfmt::skip]
+        let raw = vec![
+            0, 0, 0, 0,
+            0, 1, 2, 0,
+            0, 0, 3, 0,
+        ];
+        let image = VobSubIndexedImage::new(area, [0, 1, 2, 3], [0, 15, 15, 15], raw);
+        let cropped = image.crop_to_content().unwrap();
+        assert_eq!(
+            cropped.area(),
+            Area::try_from(AreaValues {
+                x1: 11,
+                y1: 21,
+                x2: 12,
+                y2: 22,
+            })
+            .unwrap()
+        );
+        assert_eq!(cropped.raw_image(), [1, 2, 0, 3]);
+
+        let transparent = VobSubIndexedImage::new(area, [0; 4], [0; 4], vec![1; 12]);
+        assert!(transparent.crop_to_content().is_none());
+    }
+
+    #[test]
+    fn palette_colors_are_cached() {
+        let area = Area::try_from(crate::content::AreaValues {
+            x1: 0,
+            y1: 0,
+            x2: 1,
+            y2: 1,
+        })
+        .unwrap();
+        let image = |alpha| VobSubIndexedImage::new(area, [0, 1, 2, 3], alpha, vec![0, 1, 1, 0]);
+        let first = image([0, 15, 15, 15]);
+        let second = image([0, 15, 15, 15]);
+        let other = image([15, 15, 15, 15]);
+        let palette = [Rgb([255, 255, 255]); 16];
+
+        let cache = PaletteColorCache::new();
+        let images = [&first, &second, &other]
+            .map(|img| VobSubToImage::new(img, &palette, conv_to_rgba).with_cache(&cache));
+        let pixels = images.each_ref().map(|img| *img.to_image().get_pixel(0, 0));
+        assert_eq!(pixels[0], Rgba([255, 255, 255, 0]));
+        assert_eq!(pixels[1], pixels[0]);
+        assert_eq!(pixels[2], Rgba([255, 255, 255, 15]));
+        assert_eq!(cache.len(), 2);
+
+        let raw = images[0].to_raw_image();
+        assert_eq!(raw.format(), PixelFormat::Rgba);
+        assert_eq!((raw.width(), raw.height(), raw.stride()), (2, 2, 8));
+        assert_eq!(raw.data(), images[0].to_image().as_raw().as_slice());
+
+        let luma = [Luma([255]); 16];
+        let cache = PaletteColorCache::new();
+        let opt = ToOcrImageOpt::default();
+        VobSubOcrImage::new(&first, &luma)
+            .with_cache(&cache)
+            .image(&opt);
+        VobSubOcrImage::new(&second, &luma)
+            .with_cache(&cache)
+            .image(&opt);
+        assert_eq!(cache.len(), 1);
+        let inverted = opt
+            .with_text_color(Luma([255]))
+            .with_background_color(Luma([0]));
+        VobSubOcrImage::new(&first, &luma)
+            .with_cache(&cache)
+            .image(&inverted);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn share_cache_between_threads() {
+        let area = Area::try_from(crate::content::AreaValues {
+            x1: 0,
+            y1: 0,
+            x2: 1,
+            y2: 1,
+        })
+        .unwrap();
+        let images = [[0, 15, 15, 15], [15, 15, 15, 15]]
+            .map(|alpha| VobSubIndexedImage::new(area, [0, 1, 2, 3], alpha, vec![0, 1, 1, 0]));
+        let palette = [Rgb([255, 255, 255]); 16];
+
+        let cache = PaletteColorCache::new();
+        let converted = std::thread::scope(|scope| {
+            let workers = (0..4)
+                .map(|worker| {
+                    let (image, cache) = (&images[worker % 2], &cache);
+                    scope.spawn(move || {
+                        VobSubToImage::new(image, &palette, conv_to_rgba)
+                            .with_cache(cache)
+                            .to_image()
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(cache.len(), 2);
+        assert_eq!(converted[0], converted[2]);
+        assert_eq!(converted[1], converted[3]);
+        assert_eq!(*converted[1].get_pixel(0, 0), Rgba([255, 255, 255, 15]));
+    }
+
+    #[test]
+    fn decompress_odd_height() {
+        let raw = [LINE_OF_1, LINE_OF_1, LINE_OF_2];
+        let data = VobSubRleImageData::new(&raw, [0, 2], 3).unwrap();
+        let image = decompress(size(4, 3), &data).unwrap();
+        assert_eq!(image, [1, 1, 1, 1, 2, 2, 2, 2, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn decompress_last_line_underflow() {
+        // Last line of the second field stop after two pixels (`0101` and `0100`).
+        let raw = [LINE_OF_1, LINE_OF_1, LINE_OF_2, 0x54];
+        let data = VobSubRleImageData::new(&raw, [0, 2], 4).unwrap();
+        let image = decompress(size(4, 4), &data).unwrap();
+        assert_eq!(image, [1, 1, 1, 1, 2, 2, 2, 2, 1, 1, 1, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decompress_truncated_line_is_an_error() {
+        // First line of the first field is truncated, but isn't the last line of the field.
+        let raw = [0x54];
+        let data = VobSubRleImageData::new(&raw, [0, 1], 1).unwrap();
+        assert!(decompress(size(4, 3), &data).is_err());
+    }
+
+    #[test]
+    fn indexed_round_trip() {
+        let area = Area::try_from(AreaValues {
+            x1: 10,
+            y1: 20,
+            x2: 13,
+            y2: 21,
+        })
+        .unwrap();
+        let palette = DEFAULT_PALETTE;
+        let raw = vec![0, 1, 2, 3, 3, 2, 1, 0];
+        let image = VobSubIndexedImage::new(area, [0, 1, 6, 8], [0, 15, 15, 7], raw);
+
+        let indexed = image.to_indexed(&palette);
+        assert_eq!(indexed.indexes(), image.raw_image());
+        assert_eq!(indexed.palette()[1], Rgba([0xf0, 0xf0, 0xf0, 255]));
+        assert_eq!(indexed.palette()[3][3], 7 * 17);
+
+        let back = VobSubIndexedImage::from_indexed(&indexed, 10, 20, &palette).unwrap();
+        assert_eq!(back, image);
+    }
+
+    #[test]
+    fn transformed_image() {
+        let area = Area::try_from(AreaValues {
+            x1: 10,
+            y1: 20,
+            x2: 13,
+            y2: 21,
+        })
+        .unwrap();
+        let image = VobSubIndexedImage::new(
+            area,
+            [0, 1, 6, 8],
+            [0, 15, 15, 7],
+            vec![0, 1, 2, 3, 3, 2, 1, 0],
+        );
+        let screen = size(720, 576);
+
+        let flipped = image
+            .transformed(Transform::FlipHorizontal, &screen)
+            .unwrap();
+        assert_eq!(flipped.area(), area);
+        assert_eq!(flipped.raw_image(), [3, 2, 1, 0, 0, 1, 2, 3]);
+        let rotated = image.transformed(Transform::Rotate90, &screen).unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (2, 4));
+        assert_eq!((rotated.area().left(), rotated.area().top()), (11, 19));
+        assert_eq!(rotated.raw_image(), [3, 0, 2, 1, 1, 2, 0, 3]);
     }
 }
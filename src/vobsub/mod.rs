@@ -13,15 +13,15 @@
 //! use crate::subtile::{
 //!     image::{ImageSize, ImageArea, ToImage},
 //!     time::TimeSpan,
-//!     vobsub::{conv_to_rgba, VobSubIndexedImage, VobSubToImage},
+//!     vobsub::{conv_to_rgba, VobSubCue, VobSubToImage},
 //! };
 //!
 //! let idx = subtile::vobsub::Index::open("./fixtures/example.idx").unwrap();
 //! let sub = subtile::vobsub::Sub::open("./fixtures/example.sub").unwrap();
-//! for sub in sub.subtitles::<(TimeSpan, VobSubIndexedImage)>() {
-//!     let (time_span, image) = sub.unwrap();
-//!     println!("Time: {:0.3?}-{:0.3?}", time_span.start, time_span.end);
-//!     //println!("Always show: {:?}", subs.force());
+//! for cue in sub.subtitles().cues() {
+//!     let VobSubCue { time, image, forced, .. } = cue.unwrap();
+//!     println!("Time: {:0.3?}-{:0.3?}", time.start, time.end);
+//!     println!("Always show: {:?}", forced);
 //!     let area = image.area();
 //!     println!("At: {}, {}", area.left(), area.top());
 //!     println!("Size: {}x{}", image.width(), image.height());
@@ -35,8 +35,12 @@
 //!
 //! The initial version of this library is focused on extracting just the
 //! information shown above, and it does not have full support for all the
-//! options found in `*.idx` files.  It also lacks support for rapidly
-//! finding the subtitle associated with a particular time during playback.
+//! options found in `*.idx` files.  The subtitle associated with a particular
+//! time during playback can be found with [`Index::subtitle_at`], and decoded
+//! with [`Sub::subtitles_from`].
+//!
+//! Subtitle images can also be written back in a `*.sub` and `*.idx` pair
+//! with [`VobSubWriter`].
 //!
 //! ## Background & References
 //!
@@ -58,34 +62,56 @@
 //! decoders which might be useful once you get past the Program Stream and
 //! `PES` wrappers.
 //!
-//! There are two closely-related formats that this library could be
-//! extended to parse without too much work:
+//! Subtitles embedded in DVD-format video contain the same subtitle packet
+//! format, but the `*.idx` file is replaced by data stored in an `IFO` file,
+//! which can be read with [`Ifo`].
 //!
-//! - Subtitles embedded in DVD-format video.  These should contain the
-//!   same subtitle packet format, but the `*.idx` file is replaced by data
-//!   stored in an `IFO` file.
-//! - Subtitles stored in the `Matroska` container format.  Again, these use
-//!   the same basic subtitle format, but the `*.idx` file is replaced by
-//!   an internal, stripped-down version of the same data in text format.
+//! There is another closely-related format that this library could be
+//! extended to parse without too much work: subtitles stored in the `Matroska`
+//! container format.  These use the same basic subtitle format, but the `*.idx`
+//! file is replaced by an internal, stripped-down version of the same data in
+//! text format.
 //!
 
+mod arena;
 mod decoder;
+mod drift;
+mod encode;
 mod idx;
+mod ifo;
 mod img;
 mod mpeg2;
+mod mux;
 mod palette;
 mod probe;
 mod sub;
+mod titleset;
+mod validate;
 
 pub use self::{
-    idx::{Index, TimePointIdx},
-    img::{conv_to_rgba, VobSubIndexedImage, VobSubOcrImage, VobSubToImage},
+    arena::{ArenaCue, CueArena},
+    decoder::VobSubCue,
+    drift::{drift_report, CueDrift, DriftReport},
+    encode::{encode_spu, encode_spu_adaptive, AdaptiveSpu, Alteration, EncodeError},
+    idx::{IdxEntry, IdxTrack, Index, Lang, Smoothing, TimePointIdx},
+    ifo::{Ifo, IfoSubtitleStream},
+    img::{conv_to_rgba, PaletteColorCache, VobSubIndexedImage, VobSubOcrImage, VobSubToImage},
+    mux::{MuxError, VobSubWriter},
     palette::{palette, palette_rgb_to_luminance, Palette},
     probe::{is_idx_file, is_sub_file},
-    sub::{ErrorMissing, Sub},
+    sub::{
+        decode_spu, decode_spu_with_limits, Cues, DateCommand, DuplicateDate, DuplicateDatePolicy,
+        ErrorMissing, IdentifiedCues, Sub, VobsubReader,
+    },
+    titleset::{concat_segments, ifo_segment_spans, segment_spans},
+    validate::{validate_spu, validate_sub, DvdLimits, ValidationReport, Violation},
+};
+pub(crate) use self::{
+    mpeg2::pts_bytes,
+    palette::{color_distance, nearest_palette_entry},
 };
 
-use crate::content::ContentError;
+use crate::{content::ContentError, limits::LimitError, time::TimePoint};
 use nom::{IResult, Needed};
 use std::{fmt, io, path::PathBuf};
 use thiserror::Error;
@@ -98,10 +124,18 @@ pub enum VobSubError {
     #[error("error with data")]
     Content(#[from] ContentError),
 
+    /// A subtitle exceed the memory limits of the parser.
+    #[error("subtitle exceed the memory limits")]
+    Limit(#[from] LimitError),
+
     /// We were unable to find a required key in an `*.idx` file.
     #[error("could not find required key '{0}'")]
     MissingKey(&'static str),
 
+    /// The content of an `IFO` file can't be parsed.
+    #[error("invalid IFO file: {0}")]
+    InvalidIfo(&'static str),
+
     /// The lang value failed to be parsed
     #[error("failed to parse lang in idx file")]
     LangParsing,
@@ -145,6 +179,22 @@ pub enum VobSubError {
     #[error("control offset value tried to leads backwards")]
     ControlOffsetWentBackwards,
 
+    /// If a subtitle packet contains too many control sequences.
+    #[error("too many control sequences in subtitle packet (maximum: {0})")]
+    TooManyControlSequences(usize),
+
+    /// If a subtitle packet contains several dates for the same command,
+    /// with [`DuplicateDatePolicy::Error`].
+    #[error("several {command} commands in subtitle packet, at {} ms and {} ms", first.msecs(), second.msecs())]
+    DuplicateDate {
+        /// Command with several dates.
+        command: DateCommand,
+        /// First date of the command.
+        first: TimePoint,
+        /// Other date of the command.
+        second: TimePoint,
+    },
+
     /// If `control offset` is bigger than packet size.
     #[error("control offset is 0x{offset:x}, but packet is only 0x{packet:x} bytes")]
     ControlOffsetBiggerThanPacket {
@@ -178,6 +228,10 @@ pub enum VobSubError {
     #[error("could not process subtitle image")]
     Image(#[from] img::Error),
 
+    /// Reading the `*.sub` stream failed.
+    #[error("failed to read the subtitle stream")]
+    Read(#[source] io::Error),
+
     /// Io error on a path.
     #[error("Io error on '{path}'")]
     Io {
@@ -190,6 +244,7 @@ pub enum VobSubError {
 
 /// Error from `nom` handling
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum NomError {
     /// We have leftover input that we didn't expect.
     #[error("unexpected extra input")]
@@ -249,3 +304,19 @@ impl<I: Default + Eq, O, E: fmt::Debug> IResultExt<I, O, E> for IResult<I, O, E>
         }
     }
 }
+
+// Parsers, cues and images can be moved to and shared between worker threads.
+const _: () = {
+    use crate::{time::TimeSpan, util::assert_send_sync};
+    use image::{Luma, Rgb, Rgba};
+
+    assert_send_sync::<Index>();
+    assert_send_sync::<Sub>();
+    assert_send_sync::<sub::VobsubParser<'_, (TimeSpan, VobSubIndexedImage)>>();
+    assert_send_sync::<img::VobSubRleImage<'_>>();
+    assert_send_sync::<VobSubIndexedImage>();
+    assert_send_sync::<PaletteColorCache<Luma<u8>>>();
+    assert_send_sync::<VobSubToImage<'_, Rgb<u8>, Rgba<u8>>>();
+    assert_send_sync::<VobSubOcrImage<'_>>();
+    assert_send_sync::<VobSubError>();
+};
@@ -70,6 +70,20 @@ pub fn clock_and_ext(input: (&[u8], usize)) -> IResult<(&[u8], usize), Clock> {
     Ok((input, clock.with_ext(ext)))
 }
 
+/// Encode a 33-bit presentation timestamp with its marker bits, as in the 5 bytes of
+/// a `PES` header with only a `PTS`.
+pub(crate) const fn pts_bytes(pts: u64) -> [u8; 5] {
+    let value = (0b0010 << 36)
+        | ((pts >> 30) << 33)
+        | (1 << 32)
+        | (((pts >> 15) & 0x7fff) << 17)
+        | (1 << 16)
+        | ((pts & 0x7fff) << 1)
+        | 1;
+    let bytes = value.to_be_bytes();
+    [bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,3 +4,5 @@
 mod clock;
 pub mod pes;
 pub mod ps;
+
+pub(crate) use clock::pts_bytes;
@@ -11,7 +11,10 @@ use nom::{
     bytes::complete::tag as tag_bytes,
     IResult, Parser as _,
 };
-use std::fmt;
+use std::{
+    fmt,
+    io::{self, BufRead, Seek},
+};
 
 use super::{
     clock::{clock_and_ext, Clock},
@@ -19,6 +22,22 @@ use super::{
 };
 use crate::vobsub::{NomError, VobSubError};
 
+/// Start code of a Program Stream packet.
+const PS_START_CODE: [u8; 4] = [0x00, 0x00, 0x01, 0xba];
+/// Length of the Program Stream header, without stuffing bytes.
+const PS_HEADER_LEN: usize = 14;
+/// Length of the `PES` packet start code, stream id and packet length.
+const PES_PREFIX_LEN: usize = 6;
+
+/// A source of `PES` packets, read in order from an MPEG-2 Program Stream.
+pub trait PesSource {
+    /// Read the next `PES` packet, or `None` at the end of the stream.
+    fn next_packet(&mut self) -> Option<Result<PesPacket<'_>, VobSubError>>;
+
+    /// Offset in bytes, in the input, of the last returned packet.
+    fn last_offset(&self) -> u64;
+}
+
 /// A parsed [MPEG-2 Program Stream header][MPEG-PS] (MPEG-PS).
 ///
 /// [MPEG-PS]: https://en.wikipedia.org/wiki/MPEG_program_stream
@@ -109,6 +128,20 @@ pub fn pes_packet(input: &[u8]) -> IResult<&[u8], PesPacket<'_>> {
 pub struct PesPackets<'a> {
     /// The remaining input to parse.
     remaining: &'a [u8],
+    /// Length of the whole input.
+    input_len: usize,
+    /// Offset in the input of the last returned packet.
+    last_offset: usize,
+}
+
+impl PesSource for PesPackets<'_> {
+    fn next_packet(&mut self) -> Option<Result<PesPacket<'_>, VobSubError>> {
+        self.next()
+    }
+
+    fn last_offset(&self) -> u64 {
+        u64::try_from(self.last_offset).unwrap_or(u64::MAX)
+    }
 }
 
 impl<'a> Iterator for PesPackets<'a> {
@@ -117,7 +150,7 @@ impl<'a> Iterator for PesPackets<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             // Search for the start of a ProgramStream packet.
-            let needle = &[0x00, 0x00, 0x01, 0xba];
+            let needle = &PS_START_CODE;
             let start = self
                 .remaining
                 .windows(needle.len())
@@ -129,6 +162,7 @@ impl<'a> Iterator for PesPackets<'a> {
                 match pes_packet(self.remaining) {
                     // We found a packet!
                     IResult::Ok((remaining, packet)) => {
+                        self.last_offset = self.input_len - self.remaining.len();
                         self.remaining = remaining;
                         trace!("Decoded packet {:?}", &packet);
                         return Some(Ok(packet));
@@ -165,5 +199,201 @@ impl<'a> Iterator for PesPackets<'a> {
 /// Iterate over all the `PES` packets in an MPEG-2 Program Stream (or at
 /// least those which contain subtitles).
 pub const fn pes_packets(input: &[u8]) -> PesPackets<'_> {
-    PesPackets { remaining: input }
+    PesPackets {
+        remaining: input,
+        input_len: input.len(),
+        last_offset: 0,
+    }
+}
+
+/// Read the `PES` packets of an MPEG-2 Program Stream one at a time from a
+/// reader, to keep memory bounded to a single packet.
+pub struct PesPacketReader<R> {
+    reader: R,
+    /// Buffer of the last read packet.
+    buffer: Vec<u8>,
+    /// Offset in the input of the last returned packet.
+    last_offset: u64,
+    done: bool,
+}
+
+impl<R> PesPacketReader<R>
+where
+    R: BufRead + Seek,
+{
+    /// Create a packet reader from a buffered `reader`.
+    pub const fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            last_offset: 0,
+            done: false,
+        }
+    }
+
+    /// Move the reader to the `offset` in the input, to read the packets from it.
+    pub fn seek(&mut self, offset: u64) -> io::Result<()> {
+        self.reader.seek(io::SeekFrom::Start(offset))?;
+        self.done = false;
+        Ok(())
+    }
+
+    /// Skip the input up to the next Program Stream start code, included.
+    /// Return `false` if the end of the input is reached before.
+    fn sync(&mut self) -> io::Result<bool> {
+        let mut matched = 0;
+        loop {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                return Ok(false);
+            }
+            let mut found = None;
+            for (pos, &byte) in available.iter().enumerate() {
+                matched = match (matched, byte) {
+                    (3, 0xba) => {
+                        found = Some(pos + 1);
+                        break;
+                    }
+                    (2, 0x01) => 3,
+                    (0 | 1, 0x00) => matched + 1,
+                    (_, 0x00) => 1 + usize::from(matched == 2),
+                    _ => 0,
+                };
+            }
+            let consumed = found.unwrap_or(available.len());
+            self.reader.consume(consumed);
+            if found.is_some() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Read the bytes of the next Program Stream packet, and its `PES` packet, in the buffer.
+    /// Return `false` if the end of the input is reached.
+    fn read_packet(&mut self) -> io::Result<bool> {
+        if !self.sync()? {
+            return Ok(false);
+        }
+        let start = self.reader.stream_position()?;
+        // The start code is already consumed.
+        self.last_offset = start.saturating_sub(4);
+
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&PS_START_CODE);
+        self.read_to_buffer(PS_HEADER_LEN - PS_START_CODE.len())?;
+        let stuffing_length = usize::from(self.buffer[PS_HEADER_LEN - 1] & 0x07);
+        self.read_to_buffer(stuffing_length + PES_PREFIX_LEN)?;
+        let len_pos = self.buffer.len() - 2;
+        let pes_length = usize::from(u16::from_be_bytes([
+            self.buffer[len_pos],
+            self.buffer[len_pos + 1],
+        ]));
+        self.read_to_buffer(pes_length)?;
+        Ok(true)
+    }
+
+    fn read_to_buffer(&mut self, len: usize) -> io::Result<()> {
+        let start = self.buffer.len();
+        self.buffer.resize(start + len, 0);
+        self.reader.read_exact(&mut self.buffer[start..])
+    }
+}
+
+impl<R> PesSource for PesPacketReader<R>
+where
+    R: BufRead + Seek,
+{
+    fn next_packet(&mut self) -> Option<Result<PesPacket<'_>, VobSubError>> {
+        // The parsed packet can't borrow the buffer while the loop may read the next
+        // one : its fields are kept with the position of its data in the buffer.
+        let (ps_header, packet, data) = loop {
+            if self.done {
+                return None;
+            }
+            match self.read_packet() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.done = true;
+                    trace!("Reached end of data");
+                    return None;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(if err.kind() == io::ErrorKind::UnexpectedEof {
+                        warn!("Incomplete packet at offset {}", self.last_offset);
+                        VobSubError::PESPacket(NomError::IncompleteInput(nom::Needed::Unknown))
+                    } else {
+                        VobSubError::Read(err)
+                    }));
+                }
+            }
+            // We got something that looked like a packet but
+            // wasn't parseable.  Log it and keep trying.
+            match pes_packet(&self.buffer) {
+                Ok((
+                    remaining,
+                    PesPacket {
+                        ps_header,
+                        pes_packet,
+                    },
+                )) => {
+                    let end = self.buffer.len() - remaining.len();
+                    let data = end - pes_packet.data.len()..end;
+                    let pes::Packet {
+                        header,
+                        header_data,
+                        substream_id,
+                        ..
+                    } = pes_packet;
+                    break (ps_header, (header, header_data, substream_id), data);
+                }
+                Err(err) => debug!("Skipping packet {:?}", &err),
+            }
+        };
+        let (header, header_data, substream_id) = packet;
+        let packet = PesPacket {
+            ps_header,
+            pes_packet: pes::Packet {
+                header,
+                header_data,
+                substream_id,
+                data: &self.buffer[data],
+            },
+        };
+        trace!("Decoded packet {:?}", &packet);
+        Some(Ok(packet))
+    }
+
+    fn last_offset(&self) -> u64 {
+        self.last_offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Cursor};
+
+    #[test]
+    fn read_same_packets_as_slice() {
+        // A pack containing only a padding packet, skipped, before the subtitles.
+        let mut data = vec![
+            0x00, 0x00, 0x01, 0xba, 0x44, 0x00, 0x04, 0x00, 0x04, 0x01, 0x01, 0x89, 0xc3, 0xf8,
+            0x00, 0x00, 0x01, 0xbe, 0x00, 0x02, 0xff, 0xff,
+        ];
+        data.extend(fs::read("./fixtures/example.sub").unwrap());
+
+        let mut packets = pes_packets(&data);
+        let mut reader = PesPacketReader::new(Cursor::new(data.as_slice()));
+        let mut offsets = Vec::new();
+        while let Some(expected) = packets.next_packet() {
+            let expected = expected.unwrap();
+            assert_eq!(reader.next_packet().unwrap().unwrap(), expected);
+            assert_eq!(reader.last_offset(), packets.last_offset());
+            offsets.push(reader.last_offset());
+        }
+        assert!(reader.next_packet().is_none());
+        assert!(offsets.len() > 1);
+        assert_eq!(offsets[0], 22);
+    }
 }
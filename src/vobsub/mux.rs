@@ -0,0 +1,337 @@
+//! Writing of `VobSub` subtitles : the `*.sub` MPEG-2 Program Stream and its `*.idx` file.
+//!
+//! Each subtitle packet (`SPU`) is split in `PES` packets, each one in a 2048 bytes
+//! Program Stream pack, like on a DVD.
+use std::io;
+
+use thiserror::Error;
+
+use super::{
+    encode_spu, idx::TimePointIdx, pts_bytes, EncodeError, Lang, Palette, VobSubIndexedImage,
+};
+use crate::{content::Size, sink::CueSink, time::TimeSpan};
+
+/// Size of a Program Stream pack.
+const PACK_LEN: usize = 2048;
+/// Size of the Program Stream pack header.
+const PS_HEADER_LEN: usize = 14;
+/// Size of the `PES` header without timestamp : start code, length, flags and
+/// header data length.
+const PES_HEADER_LEN: usize = 9;
+/// Size of an encoded `PTS`.
+const PTS_LEN: usize = 5;
+/// Size of the header of a padding `PES` packet.
+const PADDING_HEADER_LEN: usize = 6;
+/// Program mux rate of a DVD, in units of 50 bytes per second.
+const MUX_RATE: u32 = 25_200;
+/// Substream id of the first subtitle track.
+const SUBSTREAM_ID: u8 = 0x20;
+/// Frequency of the MPEG system clock.
+const CLOCK_FREQUENCY_KHZ: i64 = 90;
+/// Maximum value of a 33-bit clock.
+const MAX_CLOCK: u64 = (1 << 33) - 1;
+
+/// Error of [`VobSubWriter`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MuxError {
+    /// A subtitle can't be encoded in a packet.
+    #[error("failed to encode a subtitle packet")]
+    Encode(#[from] EncodeError),
+
+    /// The start time of a subtitle is negative or too large for a `PTS`.
+    #[error("start time of {msecs}ms can't be encoded in a PTS")]
+    InvalidStart {
+        /// Start time, in milliseconds.
+        msecs: i64,
+    },
+
+    /// Writing the `*.sub` or `*.idx` content failed.
+    #[error("failed to write VobSub output")]
+    Io(#[from] io::Error),
+}
+
+/// Streaming writer of `VobSub` subtitles, in a `*.sub` and an `*.idx` writers,
+/// see [`CueSink`].
+///
+/// The `*.idx` header is written before the first subtitle.
+pub struct VobSubWriter<S: io::Write, I: io::Write> {
+    sub: S,
+    idx: I,
+    palette: Palette,
+    size: Size,
+    lang: Lang,
+    /// Position in the `*.sub` output.
+    filepos: u64,
+    header_written: bool,
+}
+
+impl<S: io::Write, I: io::Write> VobSubWriter<S, I> {
+    /// Create a writer of the subtitles in `sub`, and of the index in `idx`, with
+    /// the colors of `palette`. The frame size is `720x576` and the lang `en` by default.
+    pub fn new(sub: S, idx: I, palette: Palette) -> Self {
+        Self {
+            sub,
+            idx,
+            palette,
+            size: Size { w: 720, h: 576 },
+            lang: Lang::from_code("en"),
+            filepos: 0,
+            header_written: false,
+        }
+    }
+
+    /// Set the frame size written in the `*.idx` file.
+    #[must_use]
+    pub const fn with_size(mut self, size: Size) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Set the lang code (`en`, `fr`, ...) of the subtitles.
+    #[must_use]
+    pub fn with_lang(mut self, code: &str) -> Self {
+        self.lang = Lang::from_code(code);
+        self
+    }
+
+    /// Get back the underlying `*.sub` and `*.idx` writers.
+    pub fn into_inner(self) -> (S, I) {
+        (self.sub, self.idx)
+    }
+
+    fn write_header(&mut self) -> Result<(), io::Error> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.header_written = true;
+        let Size { w, h } = self.size;
+        let palette = self
+            .palette
+            .iter()
+            .map(|rgb| format!("{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            self.idx,
+            "# VobSub index file, v7 (do not modify this line!)\n\
+             size: {w}x{h}\n\
+             org: 0, 0\n\
+             scale: 100%, 100%\n\
+             alpha: 100%\n\
+             smooth: OFF\n\
+             fadein/out: 0, 0\n\
+             align: OFF at LEFT TOP\n\
+             time offset: 0\n\
+             forced subs: OFF\n\
+             palette: {palette}\n\
+             custom colors: OFF, tridx: 0000, colors: 000000, 000000, 000000, 000000\n\
+             langidx: 0\n\n\
+             id: {}, index: 0",
+            self.lang.lang()
+        )
+    }
+}
+
+impl<S: io::Write, I: io::Write> CueSink<VobSubIndexedImage> for VobSubWriter<S, I> {
+    type Error = MuxError;
+
+    fn write_cue(&mut self, time: TimeSpan, cue: &VobSubIndexedImage) -> Result<(), MuxError> {
+        let msecs = time.start.msecs();
+        let clock = msecs
+            .checked_mul(CLOCK_FREQUENCY_KHZ)
+            .and_then(|pts| u64::try_from(pts).ok())
+            .filter(|pts| *pts <= MAX_CLOCK)
+            .ok_or(MuxError::InvalidStart { msecs })?;
+        let spu = encode_spu(time, cue)?;
+
+        self.write_header()?;
+        writeln!(
+            self.idx,
+            "timestamp: {}, filepos: {:09x}",
+            TimePointIdx::from(time.start),
+            self.filepos
+        )?;
+        let mut pack = Vec::with_capacity(PACK_LEN);
+        let mut first = true;
+        let mut remaining = spu.as_slice();
+        while !remaining.is_empty() {
+            pack.clear();
+            let pts = first.then_some(clock);
+            let capacity = PACK_LEN - pack_overhead(pts.is_some());
+            let (data, rest) = remaining.split_at(remaining.len().min(capacity));
+            write_pack(&mut pack, clock, pts, data);
+            self.sub.write_all(&pack)?;
+            self.filepos += u64::try_from(pack.len()).unwrap_or(u64::MAX);
+            remaining = rest;
+            first = false;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), MuxError> {
+        self.write_header()?;
+        self.sub.flush()?;
+        self.idx.flush()?;
+        Ok(())
+    }
+}
+
+/// Size of the headers of a pack, before the subtitle data.
+const fn pack_overhead(with_pts: bool) -> usize {
+    let pts_len = if with_pts { PTS_LEN } else { 0 };
+    PS_HEADER_LEN + PES_HEADER_LEN + pts_len + 1
+}
+
+/// Write a Program Stream pack containing the subtitle `data` in a `PES` packet,
+/// padded to the size of a pack.
+///
+/// All the packs of a subtitle have its `scr` clock, only the first one has a `pts`.
+fn write_pack(pack: &mut Vec<u8>, scr: u64, pts: Option<u64>, data: &[u8]) {
+    let gap = PACK_LEN - pack_overhead(pts.is_some()) - data.len();
+    // A gap too small for a padding packet is filled with stuffing bytes in the header.
+    let (stuffing, padding) = if gap < PADDING_HEADER_LEN {
+        (gap, 0)
+    } else {
+        (0, gap)
+    };
+
+    // Program Stream header.
+    pack.extend([0x00, 0x00, 0x01, 0xba]);
+    pack.extend(&clock_bytes(scr)[2..]);
+    pack.extend(&((MUX_RATE << 2) | 0b11).to_be_bytes()[1..]);
+    pack.push(0xf8); // reserved bits, without stuffing
+
+    // `PES` header of the private stream 1.
+    let header_data_len = pts.map_or(0, |_| PTS_LEN) + stuffing;
+    let pes_len = 3 + header_data_len + 1 + data.len();
+    pack.extend([0x00, 0x00, 0x01, 0xbd]);
+    pack.extend(to_u16(pes_len).to_be_bytes());
+    pack.push(0x81); // MPEG-2 marker, original
+    pack.push(if pts.is_some() { 0x80 } else { 0x00 });
+    pack.push(u8::try_from(header_data_len).unwrap_or(u8::MAX));
+    if let Some(pts) = pts {
+        pack.extend(pts_bytes(pts));
+    }
+    pack.extend(std::iter::repeat(0xff).take(stuffing));
+    pack.push(SUBSTREAM_ID);
+    pack.extend(data);
+
+    if padding > 0 {
+        pack.extend([0x00, 0x00, 0x01, 0xbe]);
+        pack.extend(to_u16(padding - PADDING_HEADER_LEN).to_be_bytes());
+        pack.extend(std::iter::repeat(0xff).take(padding - PADDING_HEADER_LEN));
+    }
+}
+
+/// Encode a System Clock Reference with its marker bits, in the 6 lower bytes.
+const fn clock_bytes(clock: u64) -> [u8; 8] {
+    let value = (0b01 << 46)
+        | ((clock >> 30) << 43)
+        | (1 << 42)
+        | (((clock >> 15) & 0x7fff) << 27)
+        | (1 << 26)
+        | ((clock & 0x7fff) << 11)
+        | (1 << 10)
+        | 1;
+    value.to_be_bytes()
+}
+
+/// Convert a length inside a pack, always lower than the pack size.
+fn to_u16(len: usize) -> u16 {
+    u16::try_from(len).unwrap_or(u16::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        content::{Area, AreaValues},
+        time::TimePoint,
+        vobsub::{
+            mpeg2::ps::pes_packet, palette::DEFAULT_PALETTE, sub::VobsubParser, Index, Sub,
+            VobSubError,
+        },
+    };
+
+    #[test]
+    fn round_trip() {
+        let sub = Sub::open("./fixtures/example.sub").unwrap();
+        let cues = sub
+            .subtitles::<(TimeSpan, VobSubIndexedImage)>()
+            .map(|cue| {
+                let (time, image) = cue.unwrap();
+                // Times representable exactly in centiseconds and `PTS`.
+                let start = TimePoint::from_msecs(time.start.msecs() / 10 * 10);
+                let end = TimePoint::from_msecs(time.end.msecs() / 10 * 10);
+                (TimeSpan::new(start, end), image)
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer = VobSubWriter::new(Vec::new(), Vec::new(), DEFAULT_PALETTE)
+            .with_size(Size { w: 1920, h: 1080 })
+            .with_lang("fr");
+        writer.write_all(cues.iter().cloned()).unwrap();
+        let (sub_data, idx_data) = writer.into_inner();
+
+        assert_eq!(sub_data.len() % PACK_LEN, 0);
+        let decoded = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&sub_data)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded, cues);
+
+        let index = Index::read_index(io::BufReader::new(idx_data.as_slice()), &|source| {
+            VobSubError::Read(source)
+        })
+        .unwrap();
+        assert_eq!(index.palette(), &DEFAULT_PALETTE);
+        assert_eq!(index.size(), Some(Size { w: 1920, h: 1080 }));
+        assert_eq!(index.track("fr").unwrap().entries().len(), cues.len());
+        for (entry, (time, _)) in index.entries().iter().zip(&cues) {
+            assert_eq!(entry.timestamp(), time.start);
+            assert_eq!(entry.filepos() % 2048, 0);
+        }
+    }
+
+    #[test]
+    fn continuation_packs_clock() {
+        // Noise, too large for a single pack once encoded.
+        let area = Area::try_from(AreaValues {
+            x1: 0,
+            y1: 0,
+            x2: 99,
+            y2: 99,
+        })
+        .unwrap();
+        let pixels = (0..100_u32 * 100)
+            .map(|i| u8::try_from(i * 7 % 13 % 4).unwrap())
+            .collect();
+        let image = VobSubIndexedImage::new(area, [0, 1, 2, 3], [0, 15, 15, 15], pixels);
+        let time = TimeSpan::new(TimePoint::from_msecs(2000), TimePoint::from_msecs(3000));
+
+        let mut writer = VobSubWriter::new(Vec::new(), Vec::new(), DEFAULT_PALETTE);
+        writer.write_cue(time, &image).unwrap();
+        writer.finish().unwrap();
+        let (sub_data, _) = writer.into_inner();
+        assert!(sub_data.len() > PACK_LEN);
+
+        let packets = sub_data
+            .chunks(PACK_LEN)
+            .map(|pack| pes_packet(pack).unwrap().1)
+            .collect::<Vec<_>>();
+        let pts = packets[0]
+            .pes_packet
+            .header_data
+            .pts_dts
+            .as_ref()
+            .unwrap()
+            .pts;
+        assert!((pts.as_seconds() - 2.).abs() < f64::EPSILON);
+        for packet in &packets {
+            assert_eq!(packet.ps_header.scr, pts);
+        }
+        assert!(packets[1..]
+            .iter()
+            .all(|packet| packet.pes_packet.header_data.pts_dts.is_none()));
+    }
+}
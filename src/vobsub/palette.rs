@@ -84,6 +84,23 @@ pub fn palette_rgb_to_luminance(palette: &Palette) -> PaletteLuma {
     palette.map(|rgb| rgb.to_luma())
 }
 
+/// Index of the palette entry nearest of `color`.
+pub(crate) fn nearest_palette_entry(palette: &Palette, color: Rgb<u8>) -> u8 {
+    let nearest = (0..palette.len())
+        .min_by_key(|&idx| color_distance(palette[idx], color))
+        .unwrap_or_default();
+    u8::try_from(nearest).unwrap()
+}
+
+/// Squared euclidean distance between two colors.
+pub(crate) fn color_distance(left: Rgb<u8>, right: Rgb<u8>) -> u32 {
+    left.0
+        .iter()
+        .zip(right.0)
+        .map(|(&left, right)| u32::from(left.abs_diff(right)).pow(2))
+        .sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,10 +4,16 @@
 //!
 //! [subs]: http://sam.zoy.org/writings/dvd/subtitles/
 
-use super::{decoder::VobSubDecoder, img::VobSubIndexedImage, mpeg2::ps, VobSubError};
+use super::{
+    decoder::{VobSubCue, VobSubDecoder},
+    img::VobSubIndexedImage,
+    mpeg2::ps,
+    IdxEntry, IdxTrack, VobSubError,
+};
 use crate::{
-    content::{Area, AreaValues},
-    time::TimeSpan,
+    content::{Area, AreaValues, CueFormat, CueId},
+    limits::MemoryLimits,
+    time::{TimePoint, TimeSpan},
     util::BytesFormatter,
     vobsub::{
         img::{VobSubRleImage, VobSubRleImageData},
@@ -27,7 +33,14 @@ use nom::{
     IResult, Parser as _,
 };
 use std::{
-    cmp::Ordering, fmt::Debug, fs, iter::FusedIterator, marker::PhantomData, path::Path,
+    cmp::Ordering,
+    fmt::{self, Debug},
+    fs::{self, File},
+    io::{BufRead, BufReader, Seek},
+    iter::FusedIterator,
+    marker::PhantomData,
+    mem,
+    path::Path,
     slice::from_ref,
 };
 use thiserror::Error;
@@ -288,8 +301,122 @@ pub enum ErrorMissing {
     RleOffset,
 }
 
+/// Date command of a control sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateCommand {
+    /// `StartDate` command, start of the display of the subtitle.
+    Start,
+    /// `StopDate` command, end of the display of the subtitle.
+    Stop,
+}
+
+impl fmt::Display for DateCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Start => f.write_str("StartDate"),
+            Self::Stop => f.write_str("StopDate"),
+        }
+    }
+}
+
+/// Policy applied when a subtitle packet contains several `StartDate` (or `StopDate`)
+/// commands with different dates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateDatePolicy {
+    /// Keep the date of the first command.
+    #[default]
+    First,
+    /// Keep the date of the last command.
+    Last,
+    /// Fail with [`VobSubError::DuplicateDate`].
+    Error,
+}
+
+/// Different dates found for the same command in a subtitle packet,
+/// with the one kept by the [`DuplicateDatePolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DuplicateDate {
+    /// Command with several dates.
+    pub command: DateCommand,
+    /// Date used for the subtitle.
+    pub kept: TimePoint,
+    /// Date ignored.
+    pub ignored: TimePoint,
+}
+
+impl fmt::Display for DuplicateDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "several {} commands, kept {} ms, ignored {} ms",
+            self.command,
+            self.kept.msecs(),
+            self.ignored.msecs()
+        )
+    }
+}
+
+/// Set the date of a `command`, applying the `policy` if it's already set.
+fn resolve_date(
+    date: &mut Option<f64>,
+    time: f64,
+    command: DateCommand,
+    policy: DuplicateDatePolicy,
+    diagnostics: &mut Vec<DuplicateDate>,
+) -> Result<(), VobSubError> {
+    let Some(previous) = *date else {
+        *date = Some(time);
+        return Ok(());
+    };
+    let (previous, time) = (TimePoint::from_secs(previous), TimePoint::from_secs(time));
+    if previous == time {
+        return Ok(());
+    }
+    let (kept, ignored) = match policy {
+        DuplicateDatePolicy::First => (previous, time),
+        DuplicateDatePolicy::Last => (time, previous),
+        DuplicateDatePolicy::Error => {
+            return Err(VobSubError::DuplicateDate {
+                command,
+                first: previous,
+                second: time,
+            })
+        }
+    };
+    warn!("subtitle with several {command} commands, keep {kept:?} over {ignored:?}");
+    diagnostics.push(DuplicateDate {
+        command,
+        kept,
+        ignored,
+    });
+    if policy == DuplicateDatePolicy::Last {
+        *date = Some(kept.to_secs());
+    }
+    Ok(())
+}
+
+/// Maximum number of control sequences parsed in a subtitle packet.
+///
+/// Offsets of control sequences only go forward, so the loop always terminate,
+/// but a crafted packet can chain thousands of tiny sequences. Real subtitles
+/// rarely use more than a few of them (fade effects use more).
+const MAX_CONTROL_SEQUENCES: usize = 256;
+
+/// Number of bytes by pixel of the decoded indexed images, for the memory limits.
+const INDEXED_BYTES_PER_PIXEL: usize = 1;
+
 /// Parse a subtitle.
-fn subtitle<'a, D, T>(raw_data: &'a [u8], base_time: f64) -> Result<T, VobSubError>
+///
+/// The conflicting dates resolved with `date_policy` are added to `diagnostics`.
+fn subtitle<'a, D, T>(
+    raw_data: &'a [u8],
+    base_time: f64,
+    limits: &MemoryLimits,
+    date_policy: DuplicateDatePolicy,
+    diagnostics: &mut Vec<DuplicateDate>,
+    buffer: Vec<u8>,
+) -> Result<T, VobSubError>
 where
     T: Debug,
     D: VobSubDecoder<'a, Output = T>,
@@ -297,6 +424,8 @@ where
     // This parser is somewhat non-standard, because we need to work with
     // explicit offsets into `packet` in several places.
 
+    limits.check_cue(raw_data.len())?;
+
     // Figure out where our control data starts.
     if raw_data.len() < 2 {
         return Err(VobSubError::UnexpectedEndOfSubtitleData);
@@ -311,10 +440,18 @@ where
     let mut palette = None;
     let mut alpha = None;
     let mut rle_offsets = None;
+    let mut set_date = |date: &mut Option<f64>, time, command| {
+        resolve_date(date, time, command, date_policy, diagnostics)
+    };
 
     // Loop over the individual control sequences.
     let mut control_offset = initial_control_offset;
+    let mut nb_sequences = 0;
     loop {
+        nb_sequences += 1;
+        if nb_sequences > MAX_CONTROL_SEQUENCES {
+            return Err(VobSubError::TooManyControlSequences(MAX_CONTROL_SEQUENCES));
+        }
         trace!("looking for control sequence at: 0x{control_offset:x}");
         if control_offset >= raw_data.len() {
             return Err(VobSubError::ControlOffsetBiggerThanPacket {
@@ -338,10 +475,10 @@ where
                     force = true;
                 }
                 ControlCommand::StartDate => {
-                    start_time = start_time.or(Some(time));
+                    set_date(&mut start_time, time, DateCommand::Start)?;
                 }
                 ControlCommand::StopDate => {
-                    end_time = end_time.or(Some(time));
+                    set_date(&mut end_time, time, DateCommand::Stop)?;
                 }
                 ControlCommand::Palette(p) => {
                     palette = palette.or(Some(p));
@@ -385,6 +522,11 @@ where
     let palette = palette.ok_or(ErrorMissing::Palette)?;
     let alpha = alpha.ok_or(ErrorMissing::AlphaPalette)?;
     let rle_offsets = rle_offsets.ok_or(ErrorMissing::RleOffset)?;
+    limits.check_image(
+        u32::from(area.width()),
+        u32::from(area.height()),
+        INDEXED_BYTES_PER_PIXEL,
+    )?;
 
     // Decompress our image.
     let end = initial_control_offset + 2;
@@ -395,11 +537,52 @@ where
     let rle_image = VobSubRleImage::new(area, palette, alpha, image_data);
 
     // Return our parsed subtitle.
-    let result = D::from_data(start_time, end_time, force, rle_image);
+    let result = D::from_data(start_time, end_time, force, rle_image, buffer)?;
     trace!("Parsed subtitle: {:?}", &result);
     Ok(result)
 }
 
+/// Decode a single subtitle packet (`SPU`), already extracted from its container.
+///
+/// This is useful if the packets are demuxed by other means than the `VobSub`
+/// parser (`Matroska` for example). The `packet` must contain the full `SPU`,
+/// starting with its 2 bytes size, and `base_time` is the presentation time
+/// of the packet, used as reference by the start and stop dates of the subtitle.
+///
+/// The default [`MemoryLimits`] are applied, and the first date is kept if the
+/// packet contains several start (or stop) dates.
+///
+/// # Errors
+///
+/// Will return an error if the packet content or the image data is invalid.
+pub fn decode_spu(
+    packet: &[u8],
+    base_time: TimePoint,
+) -> Result<(TimeSpan, VobSubIndexedImage), VobSubError> {
+    decode_spu_with_limits(packet, base_time, &MemoryLimits::default())
+}
+
+/// Decode a single subtitle packet (`SPU`) like [`decode_spu`], with custom memory `limits`.
+///
+/// # Errors
+///
+/// Will return an error if the packet content or the image data is invalid,
+/// or [`VobSubError::Limit`] if they exceed the `limits`.
+pub fn decode_spu_with_limits(
+    packet: &[u8],
+    base_time: TimePoint,
+    limits: &MemoryLimits,
+) -> Result<(TimeSpan, VobSubIndexedImage), VobSubError> {
+    subtitle::<(TimeSpan, VobSubIndexedImage), _>(
+        packet,
+        base_time.to_secs(),
+        limits,
+        DuplicateDatePolicy::default(),
+        &mut Vec::new(),
+        Vec::new(),
+    )
+}
+
 /// Like `?` and `try!`, but assume that we're working with
 /// `Option<Result<T, E>>` instead of `Result<T, E>`, and pass through
 /// `None`.
@@ -441,6 +624,51 @@ impl Sub {
     pub fn subtitles<D>(&self) -> VobsubParser<'_, D> {
         VobsubParser::new(&self.data)
     }
+
+    /// Iterate over the subtitles of a `track` of the `*.idx` file, for example
+    /// found by its lang with [`Index::track`].
+    ///
+    /// [`Index::track`]: super::Index::track
+    #[must_use]
+    pub fn track_subtitles<D>(&self, track: &IdxTrack) -> VobsubParser<'_, D> {
+        VobsubParser::new(&self.data).with_track(track.index())
+    }
+
+    /// Iterate over the subtitles from the one of the `*.idx` `entry`, for example
+    /// found with [`Index::subtitle_at`].
+    ///
+    /// [`Index::subtitle_at`]: super::Index::subtitle_at
+    #[must_use]
+    pub fn subtitles_from<D>(&self, entry: &IdxEntry) -> VobsubParser<'_, D> {
+        let start = usize::try_from(entry.filepos())
+            .unwrap_or(usize::MAX)
+            .min(self.data.len());
+        let mut parser = VobsubParser::new(&self.data[start..]);
+        parser.base_offset = entry
+            .filepos()
+            .min(u64::try_from(start).unwrap_or(u64::MAX));
+        parser
+    }
+}
+
+/// Configuration of the parsing of the subtitles, shared by [`VobsubParser`] and
+/// [`VobsubReader`].
+#[derive(Debug, Clone, Copy)]
+struct ParserConfig {
+    limits: MemoryLimits,
+    date_policy: DuplicateDatePolicy,
+    /// Substream of the track to parse, all if `None`.
+    substream: Option<u8>,
+}
+
+impl ParserConfig {
+    const fn new() -> Self {
+        Self {
+            limits: MemoryLimits::new(),
+            date_policy: DuplicateDatePolicy::First,
+            substream: None,
+        }
+    }
 }
 
 /// An internal iterator over subtitles.  These subtitles may not have a
@@ -448,7 +676,17 @@ impl Sub {
 /// see them.
 pub struct VobsubParser<'a, Decoder> {
     pes_packets: ps::PesPackets<'a>,
-    phantom_data: PhantomData<Decoder>,
+    config: ParserConfig,
+    /// Conflicting dates found in the subtitles read since the last take.
+    diagnostics: Vec<DuplicateDate>,
+    /// Offset of the input in the file.
+    base_offset: u64,
+    /// Offset of the first packet of the last subtitle read.
+    offset: u64,
+    /// Buffer of a recycled image, reused for the next image.
+    spare_image: Vec<u8>,
+    // Only the type is used, the parser doesn't own a `Decoder`.
+    phantom_data: PhantomData<fn() -> Decoder>,
 }
 
 impl<'a, Decoder> VobsubParser<'a, Decoder> {
@@ -458,16 +696,113 @@ impl<'a, Decoder> VobsubParser<'a, Decoder> {
     pub const fn new(input: &'a [u8]) -> Self {
         Self {
             pes_packets: ps::pes_packets(input),
+            config: ParserConfig::new(),
+            diagnostics: Vec::new(),
+            base_offset: 0,
+            offset: 0,
+            spare_image: Vec::new(),
             phantom_data: PhantomData,
         }
     }
 
+    /// Apply the memory `limits` on the parsed subtitles, instead of the default ones.
+    #[must_use]
+    pub const fn with_limits(mut self, limits: MemoryLimits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    /// Parse only the subtitles of the track `index` of the `*.idx` file,
+    /// instead of the subtitles of all tracks.
+    #[must_use]
+    pub const fn with_track(mut self, index: u8) -> Self {
+        self.config.substream = Some(track_substream(index));
+        self
+    }
+
+    /// Set the policy applied on subtitles with several start (or stop) dates,
+    /// [`DuplicateDatePolicy::First`] by default.
+    ///
+    /// The conflicting dates are reported in [`Self::diagnostics`].
+    #[must_use]
+    pub const fn with_date_policy(mut self, policy: DuplicateDatePolicy) -> Self {
+        self.config.date_policy = policy;
+        self
+    }
+
+    /// Conflicting dates found in the subtitles read so far, with the date kept.
+    ///
+    /// They are kept until taken with [`Self::take_diagnostics`].
+    #[must_use]
+    pub fn diagnostics(&self) -> &[DuplicateDate] {
+        &self.diagnostics
+    }
+
+    /// Take the conflicting dates found so far, to process them while parsing a long
+    /// stream without keeping them all.
+    pub fn take_diagnostics(&mut self) -> Vec<DuplicateDate> {
+        mem::take(&mut self.diagnostics)
+    }
+
+    /// Give back an `image` returned by the parser once processed : the allocation of
+    /// its pixels is reused to decompress the next subtitle.
+    ///
+    /// This avoids an allocation by subtitle when processing large batches of them.
+    pub fn recycle(&mut self, image: VobSubIndexedImage) {
+        self.spare_image = image.into_raw_image();
+    }
+
+    /// Offset of the first packet of the last subtitle read.
+    pub(super) const fn offset(&self) -> u64 {
+        self.offset
+    }
+
     // Read all pes_packets needed to parse a subtitle.
-    fn next_sub_packet(&mut self) -> Option<Result<(f64, Vec<u8>), VobSubError>> {
+    pub(super) fn next_sub_packet(&mut self) -> Option<Result<(f64, Vec<u8>), VobSubError>> {
         profiling::scope!("VobsubParser next_sub_packet");
+        let packet = try_iter!(read_sub_packet(
+            &mut self.pes_packets,
+            self.config.substream,
+            &self.config.limits
+        ));
+        self.offset = self.base_offset + packet.offset;
+        Some(Ok((packet.base_time, packet.data)))
+    }
+}
+
+/// First substream id of the subtitle tracks.
+const FIRST_SUBSTREAM: u8 = 0x20;
 
+/// Substream id of the subtitles of the track `index`.
+const fn track_substream(index: u8) -> u8 {
+    FIRST_SUBSTREAM.wrapping_add(index)
+}
+
+/// The data of a subtitle, collected from one or more `PES` packets.
+struct SubPacket {
+    /// Offset of the first `PES` packet.
+    offset: u64,
+    base_time: f64,
+    data: Vec<u8>,
+}
+
+/// Read all `PES` packets of `source` needed to parse a subtitle,
+/// skipping the subtitles of other substreams than `substream`, if any.
+fn read_sub_packet(
+    source: &mut impl ps::PesSource,
+    substream: Option<u8>,
+    limits: &MemoryLimits,
+) -> Option<Result<SubPacket, VobSubError>> {
+    let (base_time, substream_id, wanted, mut sub_packet) = loop {
         // Get the `PES` packet containing the first chunk of our subtitle.
-        let first: ps::PesPacket = try_iter!(self.pes_packets.next());
+        let first: ps::PesPacket = try_iter!(source.next_packet());
+        if substream.is_some_and(|substream| substream != first.pes_packet.substream_id) {
+            trace!(
+                "Skip subtitle of stream 0x{:x}",
+                first.pes_packet.substream_id
+            );
+            continue;
+        }
 
         // Fetch useful information from our first packet.
         let Some(pts_dts) = first.pes_packet.header_data.pts_dts else {
@@ -483,50 +818,215 @@ impl<'a, Decoder> VobsubParser<'a, Decoder> {
         }
         let wanted =
             (usize::from(first.pes_packet.data[0]) << 8) | usize::from(first.pes_packet.data[1]);
+        if let Err(err) = limits.check_cue(wanted) {
+            return Some(Err(err.into()));
+        }
         let mut sub_packet = Vec::with_capacity(wanted);
         sub_packet.extend_from_slice(first.pes_packet.data);
+        break (base_time, substream_id, wanted, sub_packet);
+    };
+    let offset = source.last_offset();
 
-        // Keep fetching more packets until we have enough.
-        while sub_packet.len() < wanted {
-            // Get the next PES packet in the Program Stream.
-            let next: ps::PesPacket = try_iter!(self.pes_packets.next());
-
-            // Make sure this is part of the same subtitle stream.  This is
-            // mostly just paranoia; I don't expect this to happen.
-            if next.pes_packet.substream_id != substream_id {
-                warn!(
-                    "Found subtitle for stream 0x{:x} while looking for 0x{:x}",
-                    next.pes_packet.substream_id, substream_id
-                );
-                continue;
-            }
-
-            // Add the extra bytes to our buffer.
-            sub_packet.extend_from_slice(next.pes_packet.data);
-        }
+    // Keep fetching more packets until we have enough.
+    while sub_packet.len() < wanted {
+        // Get the next PES packet in the Program Stream.
+        let next: ps::PesPacket = try_iter!(source.next_packet());
 
-        // Check to make sure we didn't get too _many_ bytes.  Again, this
-        // is paranoia.
-        if sub_packet.len() > wanted {
+        // Make sure this is part of the same subtitle stream.  This is
+        // mostly just paranoia; I don't expect this to happen.
+        if next.pes_packet.substream_id != substream_id {
             warn!(
-                "Found 0x{:x} bytes of data in subtitle packet, wanted 0x{:x}",
-                sub_packet.len(),
-                wanted
+                "Found subtitle for stream 0x{:x} while looking for 0x{:x}",
+                next.pes_packet.substream_id, substream_id
             );
-            sub_packet.truncate(wanted);
+            continue;
+        }
+
+        // Add the extra bytes to our buffer.
+        sub_packet.extend_from_slice(next.pes_packet.data);
+    }
+
+    // Check to make sure we didn't get too _many_ bytes.  Again, this
+    // is paranoia.
+    if sub_packet.len() > wanted {
+        warn!(
+            "Found 0x{:x} bytes of data in subtitle packet, wanted 0x{:x}",
+            sub_packet.len(),
+            wanted
+        );
+        sub_packet.truncate(wanted);
+    }
+    Some(Ok(SubPacket {
+        offset,
+        base_time,
+        data: sub_packet,
+    }))
+}
+
+impl<'a, D> VobsubParser<'a, D> {
+    /// Iterate on the subtitles with their [`CueId`].
+    ///
+    /// The offset of a subtitle is the position of its first `PES` packet in the file.
+    pub const fn with_ids(self) -> IdentifiedCues<'a, D> {
+        IdentifiedCues {
+            parser: self,
+            sequence: 0,
+        }
+    }
+}
+
+#[cfg(any(feature = "rayon", feature = "parallel"))]
+impl<D> VobsubParser<'_, D> {
+    /// Parse and decompress all the remaining subtitles, on the `rayon` thread pool (or
+    /// on scoped threads with only the `parallel` feature).
+    ///
+    /// The `PES` packets are read sequentially, then the subtitles are parsed and their
+    /// images decompressed in parallel. The results are in the order of the file, as
+    /// returned by the iterator, and the conflicting dates are added to
+    /// [`Self::diagnostics`] in the same order.
+    pub fn par_decode(&mut self) -> Vec<Result<(TimeSpan, VobSubIndexedImage), VobSubError>> {
+        let packets = std::iter::from_fn(|| self.next_sub_packet()).collect::<Vec<_>>();
+        let (limits, date_policy) = (&self.config.limits, self.config.date_policy);
+        let decoded = crate::util::par_map(packets, |packet| {
+            let mut diagnostics = Vec::new();
+            let subtitle = packet.and_then(|(base_time, sub_packet)| {
+                subtitle::<(TimeSpan, VobSubIndexedImage), _>(
+                    &sub_packet,
+                    base_time,
+                    limits,
+                    date_policy,
+                    &mut diagnostics,
+                    Vec::new(),
+                )
+            });
+            (subtitle, diagnostics)
+        });
+        decoded
+            .into_iter()
+            .map(|(subtitle, diagnostics)| {
+                self.diagnostics.extend(diagnostics);
+                subtitle
+            })
+            .collect()
+    }
+}
+
+/// Iterator on the subtitles of a `*.sub` file with their [`CueId`], created by the
+/// `with_ids` method of the iterator returned by [`Sub::subtitles`].
+pub struct IdentifiedCues<'a, Decoder> {
+    parser: VobsubParser<'a, Decoder>,
+    sequence: usize,
+}
+
+impl<D> Iterator for IdentifiedCues<'_, D> {
+    type Item = Result<(CueId, (TimeSpan, VobSubIndexedImage)), VobSubError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The offset of the cue is only known once its first packet is read.
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("identified_cue", cue_id = tracing::field::Empty).entered();
+        let cue = self.parser.next()?;
+        let id = CueId::new(CueFormat::VobSub, self.parser.offset, self.sequence);
+        #[cfg(feature = "tracing")]
+        span.record("cue_id", tracing::field::display(&id));
+        self.sequence += 1;
+        Some(cue.map(|cue| (id, cue)))
+    }
+}
+impl<D> FusedIterator for IdentifiedCues<'_, D> {}
+
+impl<D> VobsubParser<'_, D> {
+    // Read and decode the next subtitle with the decoder `T`.
+    pub(super) fn decode_next<T>(&mut self) -> Option<Result<T, VobSubError>>
+    where
+        T: Debug + for<'b> VobSubDecoder<'b, Output = T>,
+    {
+        let (base_time, sub_packet) = try_iter!(self.next_sub_packet());
+        Some(subtitle::<T, _>(
+            &sub_packet,
+            base_time,
+            &self.config.limits,
+            self.config.date_policy,
+            &mut self.diagnostics,
+            mem::take(&mut self.spare_image),
+        ))
+    }
+}
+
+impl<'a> VobsubParser<'a, VobSubCue> {
+    /// Iterate on the subtitles as [`VobSubCue`], with their forced flag.
+    pub const fn cues(self) -> Cues<'a> {
+        Cues {
+            parser: self,
+            forced_only: false,
         }
-        Some(Ok((base_time, sub_packet)))
     }
 }
 
+/// Iterator on the subtitles of a `*.sub` file as [`VobSubCue`], created by the
+/// `cues` method of the iterator returned by [`Sub::subtitles`].
+pub struct Cues<'a> {
+    parser: VobsubParser<'a, VobSubCue>,
+    forced_only: bool,
+}
+
+impl<'a> Cues<'a> {
+    /// Keep only the forced subtitles, for example to extract the translation of
+    /// the foreign language parts of a movie. The errors are kept.
+    #[must_use]
+    pub const fn forced_only(mut self) -> Self {
+        self.forced_only = true;
+        self
+    }
+
+    /// The parser of the subtitles, to get its state (the diagnostics for example).
+    #[must_use]
+    pub const fn parser(&self) -> &VobsubParser<'a, VobSubCue> {
+        &self.parser
+    }
+
+    /// The parser of the subtitles, to update its state (take the diagnostics for example).
+    pub fn parser_mut(&mut self) -> &mut VobsubParser<'a, VobSubCue> {
+        &mut self.parser
+    }
+}
+
+impl Iterator for Cues<'_> {
+    type Item = Result<VobSubCue, VobSubError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        profiling::scope!("VobSub Cues next");
+        loop {
+            let cue = self.parser.decode_next::<VobSubCue>()?;
+            if !self.forced_only || cue.as_ref().map_or(true, |cue| cue.forced) {
+                return Some(cue);
+            }
+        }
+    }
+}
+impl FusedIterator for Cues<'_> {}
+
 impl<D> Iterator for VobsubParser<'_, D> {
     type Item = Result<(TimeSpan, VobSubIndexedImage), VobSubError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         profiling::scope!("VobsubParser next");
 
-        let (base_time, sub_packet) = try_iter!(self.next_sub_packet());
-        let subtitle = subtitle::<(TimeSpan, VobSubIndexedImage), _>(&sub_packet, base_time);
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("vobsub_cue").entered();
+        let subtitle = self.decode_next::<(TimeSpan, VobSubIndexedImage)>()?;
+        #[cfg(feature = "tracing")]
+        match &subtitle {
+            Ok((time, image)) => tracing::debug!(
+                offset = self.offset,
+                start_ms = time.start.msecs(),
+                end_ms = time.end.msecs(),
+                width = crate::image::ImageSize::width(image),
+                height = crate::image::ImageSize::height(image),
+                "decoded cue"
+            ),
+            Err(error) => tracing::warn!(offset = self.offset, %error, "failed to decode cue"),
+        }
 
         // Parse our subtitle buffer.
         Some(subtitle)
@@ -534,9 +1034,150 @@ impl<D> Iterator for VobsubParser<'_, D> {
 }
 impl<D> FusedIterator for VobsubParser<'_, D> {}
 
+/// A streaming parser of a `*.sub` file content, reading the `PES` packets
+/// incrementally from a reader instead of a whole buffer.
+///
+/// Only the packets of the subtitle being parsed are kept in memory, which
+/// allows parsing large files.
+pub struct VobsubReader<Reader, Decoder> {
+    pes_packets: ps::PesPacketReader<Reader>,
+    config: ParserConfig,
+    /// Conflicting dates found in the subtitles read since the last take.
+    diagnostics: Vec<DuplicateDate>,
+    /// Buffer of a recycled image, reused for the next image.
+    spare_image: Vec<u8>,
+    // Only the type is used, the parser doesn't own a `Decoder`.
+    phantom_data: PhantomData<fn() -> Decoder>,
+}
+
+impl<Reader, Decoder> VobsubReader<Reader, Decoder>
+where
+    Reader: BufRead + Seek,
+{
+    /// Create a parser from a buffered reader (impl [`std::io::BufRead`] trait).
+    pub const fn new(reader: Reader) -> Self {
+        Self {
+            pes_packets: ps::PesPacketReader::new(reader),
+            config: ParserConfig::new(),
+            diagnostics: Vec::new(),
+            spare_image: Vec::new(),
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Apply the memory `limits` on the parsed subtitles, instead of the default ones.
+    #[must_use]
+    pub const fn with_limits(mut self, limits: MemoryLimits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    /// Parse only the subtitles of the track `index` of the `*.idx` file,
+    /// instead of the subtitles of all tracks.
+    #[must_use]
+    pub const fn with_track(mut self, index: u8) -> Self {
+        self.config.substream = Some(track_substream(index));
+        self
+    }
+
+    /// Set the policy applied on subtitles with several start (or stop) dates,
+    /// [`DuplicateDatePolicy::First`] by default.
+    ///
+    /// The conflicting dates are reported in [`Self::diagnostics`].
+    #[must_use]
+    pub const fn with_date_policy(mut self, policy: DuplicateDatePolicy) -> Self {
+        self.config.date_policy = policy;
+        self
+    }
+
+    /// Conflicting dates found in the subtitles read so far, with the date kept.
+    ///
+    /// They are kept until taken with [`Self::take_diagnostics`].
+    #[must_use]
+    pub fn diagnostics(&self) -> &[DuplicateDate] {
+        &self.diagnostics
+    }
+
+    /// Take the conflicting dates found so far, to process them while parsing a long
+    /// stream without keeping them all.
+    pub fn take_diagnostics(&mut self) -> Vec<DuplicateDate> {
+        mem::take(&mut self.diagnostics)
+    }
+
+    /// Give back an `image` returned by the parser once processed : the allocation of
+    /// its pixels is reused to decompress the next subtitle.
+    ///
+    /// This avoids an allocation by subtitle when processing large batches of them.
+    pub fn recycle(&mut self, image: VobSubIndexedImage) {
+        self.spare_image = image.into_raw_image();
+    }
+
+    /// Move the reader to the subtitle of the `*.idx` `entry`, for example
+    /// found with [`Index::subtitle_at`]. The next subtitle read is this one.
+    ///
+    /// # Errors
+    ///
+    /// Will return `VobSubError::Read` if the seek of the reader failed.
+    ///
+    /// [`Index::subtitle_at`]: super::Index::subtitle_at
+    pub fn seek(&mut self, entry: &IdxEntry) -> Result<(), VobSubError> {
+        self.pes_packets
+            .seek(entry.filepos())
+            .map_err(VobSubError::Read)
+    }
+}
+
+impl<Decoder> VobsubReader<BufReader<File>, Decoder> {
+    /// Create a parser for a `*.sub` file from the path of the file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `VobSubError::Io` if not able to open the file from `path`.
+    pub fn from_file<P>(path: P) -> Result<Self, VobSubError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|source| VobSubError::Io {
+            source,
+            path: path.into(),
+        })?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<Reader, D> Iterator for VobsubReader<Reader, D>
+where
+    Reader: BufRead + Seek,
+{
+    type Item = Result<(TimeSpan, VobSubIndexedImage), VobSubError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        profiling::scope!("VobsubReader next");
+
+        let packet = try_iter!(read_sub_packet(
+            &mut self.pes_packets,
+            self.config.substream,
+            &self.config.limits
+        ));
+        Some(subtitle::<(TimeSpan, VobSubIndexedImage), _>(
+            &packet.data,
+            packet.base_time,
+            &self.config.limits,
+            self.config.date_policy,
+            &mut self.diagnostics,
+            mem::take(&mut self.spare_image),
+        ))
+    }
+}
+impl<Reader, D> FusedIterator for VobsubReader<Reader, D> where Reader: BufRead + Seek {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::limits::LimitError;
+    use assert_matches2::assert_matches;
+    use std::fs;
 
     #[test]
     fn parse_palette_entries() {
@@ -600,6 +1241,157 @@ mod tests {
         );
     }
 
+    #[test]
+    fn control_sequences_chain_is_capped() {
+        // Packet with a long chain of empty control sequences.
+        let nb_sequences = MAX_CONTROL_SEQUENCES + 10;
+        let mut packet = vec![0x00, 0x00, 0x00, 0x04];
+        for idx in 0..nb_sequences {
+            let next = if idx + 1 == nb_sequences {
+                packet.len()
+            } else {
+                packet.len() + 5
+            };
+            let next = u16::try_from(next).unwrap().to_be_bytes();
+            packet.extend_from_slice(&[0x00, 0x00, next[0], next[1], 0xff]);
+        }
+        let size = u16::try_from(packet.len()).unwrap().to_be_bytes();
+        packet[0..2].copy_from_slice(&size);
+
+        let res = subtitle::<TimeSpan, _>(
+            &packet,
+            0.0,
+            &MemoryLimits::new(),
+            DuplicateDatePolicy::First,
+            &mut Vec::new(),
+            Vec::new(),
+        );
+        assert!(matches!(
+            res,
+            Err(VobSubError::TooManyControlSequences(MAX_CONTROL_SEQUENCES))
+        ));
+    }
+
+    // Two control sequences with a `StartDate` command, at 0 and 100 ms.
+    const DUPLICATE_START_DATES: [u8; 16] = [
+        0x00, 0x10, 0x00, 0x04, // size and control offset
+        0x00, 0x00, 0x00, 0x0a, 0x01, 0xff, // first sequence
+        0x00, 0x0a, 0x00, 0x0a, 0x01, 0xff, // last sequence
+    ];
+
+    // A Program Stream pack containing the subtitle packet `spu`, at time 0.
+    fn sub_pack(spu: &[u8]) -> Vec<u8> {
+        let mut pack = vec![
+            0x00, 0x00, 0x01, 0xba, // Program Stream header
+            0x44, 0x00, 0x04, 0x00, 0x04, 0x01, 0x01, 0x89, 0xc3, 0xf8,
+        ];
+        pack.extend([0x00, 0x00, 0x01, 0xbd]);
+        pack.extend(u16::try_from(9 + spu.len()).unwrap().to_be_bytes());
+        pack.extend([0x81, 0x80, 0x05]);
+        pack.extend(crate::vobsub::pts_bytes(0));
+        pack.push(0x20);
+        pack.extend(spu);
+        pack
+    }
+
+    #[test]
+    fn parse_duplicate_start_dates() {
+        let parse = |policy| {
+            let mut diagnostics = Vec::new();
+            let res = subtitle::<TimeSpan, _>(
+                &DUPLICATE_START_DATES,
+                0.0,
+                &MemoryLimits::new(),
+                policy,
+                &mut diagnostics,
+                Vec::new(),
+            );
+            (res, diagnostics)
+        };
+        let time = TimePoint::from_msecs;
+
+        // The packet has no image, only the dates are checked.
+        let (res, diagnostics) = parse(DuplicateDatePolicy::First);
+        assert!(matches!(res, Err(VobSubError::MissingSubtitleParsing(_))));
+        let expected = DuplicateDate {
+            command: DateCommand::Start,
+            kept: time(0),
+            ignored: time(100),
+        };
+        assert_eq!(diagnostics, [expected]);
+
+        let (_, diagnostics) = parse(DuplicateDatePolicy::Last);
+        assert_eq!(diagnostics[0].kept, time(100));
+
+        let (res, diagnostics) = parse(DuplicateDatePolicy::Error);
+        assert!(matches!(
+            res,
+            Err(VobSubError::DuplicateDate {
+                command: DateCommand::Start,
+                ..
+            })
+        ));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn take_duplicate_dates_diagnostics() {
+        let data = [
+            sub_pack(&DUPLICATE_START_DATES),
+            sub_pack(&DUPLICATE_START_DATES),
+        ]
+        .concat();
+        let expected = DuplicateDate {
+            command: DateCommand::Start,
+            kept: TimePoint::from_msecs(0),
+            ignored: TimePoint::from_msecs(100),
+        };
+
+        let mut parser = VobsubParser::<TimeSpan>::new(&data);
+        assert_matches!(parser.next(), Some(Err(_)));
+        assert_eq!(parser.take_diagnostics(), [expected]);
+        assert!(parser.diagnostics().is_empty());
+        // Only the conflicts found since the take are kept.
+        assert_matches!(parser.next(), Some(Err(_)));
+        assert_eq!(parser.diagnostics(), [expected]);
+
+        let mut reader = VobsubReader::<_, TimeSpan>::new(std::io::Cursor::new(data));
+        assert_matches!(reader.next(), Some(Err(_)));
+        assert_eq!(reader.take_diagnostics(), [expected]);
+        assert!(reader.diagnostics().is_empty());
+        assert_matches!(reader.next(), Some(Err(_)));
+        assert_eq!(reader.diagnostics(), [expected]);
+    }
+
+    #[test]
+    fn parse_random_packets_terminate() {
+        // Simple deterministic pseudo-random generator (`LCG`).
+        let mut state: u32 = 0x1234_5678;
+        let mut next_byte = || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state.to_be_bytes()[0]
+        };
+
+        for _ in 0..2000 {
+            let len = usize::from(next_byte()) + 4;
+            let mut packet = (0..len).map(|_| next_byte()).collect::<Vec<_>>();
+            // Point the first control sequence inside the packet.
+            let offset = u16::from(next_byte()) % u16::try_from(len).unwrap();
+            packet[2..4].copy_from_slice(&offset.to_be_bytes());
+            // Only check than parsing terminate without panic.
+            let policy = DuplicateDatePolicy::First;
+            subtitle::<TimeSpan, _>(
+                &packet,
+                0.0,
+                &MemoryLimits::new(),
+                policy,
+                &mut Vec::new(),
+                Vec::new(),
+            )
+            .ok();
+        }
+    }
+
     #[test]
     fn parse_subtitles() {
         //use env_logger;
@@ -662,6 +1454,246 @@ mod tests {
         assert!(subs.next().is_none());
     }
 
+    #[cfg(any(feature = "rayon", feature = "parallel"))]
+    #[test]
+    fn par_decode_keeps_order() {
+        let buffer = fs::read("./fixtures/example.sub").unwrap();
+        let subs = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let par_subs = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+            .par_decode()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(par_subs, subs);
+    }
+
+    #[test]
+    fn recycle_image_buffers() {
+        use crate::image::{ImageSize as _, IndexedImage, ToImage as _, ToImageInto as _};
+        use image::Rgba;
+
+        let buffer = fs::read("./fixtures/example.sub").unwrap();
+        let expected = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let mut subs = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer);
+        let mut decoded = Vec::new();
+        while let Some(sub) = subs.next() {
+            let (time, image) = sub.unwrap();
+            decoded.push((time, image.clone()));
+            subs.recycle(image);
+        }
+        assert_eq!(decoded, expected);
+
+        // The buffer of an image is reused for the next one.
+        let (_, image) = &expected[0];
+        let palette = [0, 1, 2, 3].map(|level| Rgba([level, level, level, 255]));
+        let indexed = IndexedImage::new(
+            image.width(),
+            image.height(),
+            palette,
+            image.raw_image().to_vec(),
+        );
+        let first = indexed.to_image_into(Vec::new()).into_raw();
+        let ptr = first.as_ptr();
+        let second = indexed.to_image_into(first);
+        assert_eq!(second.as_ptr(), ptr);
+        assert_eq!(second, indexed.to_image());
+    }
+
+    #[test]
+    fn forced_cues() {
+        let buffer = fs::read("./fixtures/example.sub").unwrap();
+        let subs = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let cues = VobsubParser::new(&buffer)
+            .cues()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(cues.len(), subs.len());
+        assert!(cues
+            .iter()
+            .zip(&subs)
+            .all(|(cue, (time, image))| !cue.forced && cue.time == *time && cue.image == *image));
+        let mut forced = VobsubParser::new(&buffer).cues().forced_only();
+        assert!(forced.next().is_none());
+
+        // The stop date command of the last sequence replaced by a force command.
+        let (time, image) = subs.into_iter().next().unwrap();
+        let mut packet = crate::vobsub::encode_spu(time, &image).unwrap();
+        let stop_idx = packet.len() - 2;
+        packet[stop_idx] = 0x00;
+        let cue = subtitle::<VobSubCue, _>(
+            &packet,
+            time.start.to_secs(),
+            &MemoryLimits::new(),
+            DuplicateDatePolicy::First,
+            &mut Vec::new(),
+            Vec::new(),
+        )
+        .unwrap();
+        assert!(cue.forced);
+        assert_eq!(cue.image, image);
+    }
+
+    #[test]
+    fn decode_single_spu() {
+        let buffer = fs::read("./fixtures/example.sub").unwrap();
+        let mut parser = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer);
+        let (base_time, packet) = parser.next_sub_packet().unwrap().unwrap();
+        let expected = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let decoded = decode_spu(&packet, TimePoint::from_secs(base_time)).unwrap();
+        assert_eq!(decoded, expected);
+        assert!(decode_spu(&packet[..packet.len() / 2], TimePoint::default()).is_err());
+
+        let limits = MemoryLimits::new().with_max_image_bytes(1000);
+        assert_matches!(
+            decode_spu_with_limits(&packet, TimePoint::default(), &limits),
+            Err(VobSubError::Limit(LimitError::ImageTooLarge { .. }))
+        );
+        let mut parser = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+            .with_limits(MemoryLimits::new().with_max_cue_bytes(100));
+        assert_matches!(
+            parser.next(),
+            Some(Err(VobSubError::Limit(LimitError::CueTooLarge {
+                max: 100,
+                ..
+            })))
+        );
+    }
+
+    #[test]
+    fn identified_subtitles() {
+        let buffer = fs::read("./fixtures/example.sub").unwrap();
+        let ids = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+            .with_ids()
+            .map(|cue| cue.unwrap().0)
+            .collect::<Vec<_>>();
+        assert!(ids.len() > 1);
+        assert_eq!(ids[0], CueId::new(CueFormat::VobSub, 0, 0));
+        assert!(ids
+            .windows(2)
+            .all(|ids| ids[0].offset() < ids[1].offset()
+                && ids[0].sequence() + 1 == ids[1].sequence()));
+        assert_eq!(ids[1].to_string(), format!("vobsub@{}#1", ids[1].offset()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn identified_cue_spans() {
+        use std::sync::{Arc, Mutex};
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        /// Subscriber keeping the `cue_id` fields of the spans.
+        struct CueIds(Arc<Mutex<Vec<String>>>);
+        struct CueIdVisitor<'a>(&'a Mutex<Vec<String>>);
+        impl Visit for CueIdVisitor<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                if field.name() == "cue_id" {
+                    self.0.lock().unwrap().push(format!("{value:?}"));
+                }
+            }
+        }
+        impl Subscriber for CueIds {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                span.record(&mut CueIdVisitor(&self.0));
+                span::Id::from_u64(1)
+            }
+            fn record(&self, _: &span::Id, values: &span::Record<'_>) {
+                values.record(&mut CueIdVisitor(&self.0));
+            }
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let buffer = fs::read("./fixtures/example.sub").unwrap();
+        let span_ids = Arc::new(Mutex::new(Vec::new()));
+        let ids = tracing::subscriber::with_default(CueIds(Arc::clone(&span_ids)), || {
+            VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+                .with_ids()
+                .map(|cue| cue.unwrap().0.to_string())
+                .collect::<Vec<_>>()
+        });
+        assert!(ids.len() > 1);
+        assert_eq!(*span_ids.lock().unwrap(), ids);
+    }
+
+    #[test]
+    fn streaming_subtitles() {
+        let buffer = fs::read("./fixtures/example.sub").unwrap();
+        let expected = VobsubParser::<(TimeSpan, VobSubIndexedImage)>::new(&buffer)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let subs =
+            VobsubReader::<_, (TimeSpan, VobSubIndexedImage)>::from_file("./fixtures/example.sub")
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert_eq!(subs, expected);
+
+        // Garbage before the first packet, and a truncated last packet.
+        let mut data = b"garbage\x00\x00\x01".to_vec();
+        data.extend_from_slice(&buffer[..0x2810]);
+        let mut subs =
+            VobsubReader::<_, (TimeSpan, VobSubIndexedImage)>::new(std::io::Cursor::new(data));
+        assert_eq!(subs.next().unwrap().unwrap(), expected[0]);
+        assert_matches!(subs.next(), Some(Err(VobSubError::PESPacket(_))));
+        assert!(subs.next().is_none());
+    }
+
+    #[test]
+    fn subtitles_from_idx_entry() {
+        let idx = super::super::Index::open("./fixtures/example.idx").unwrap();
+        let entry = idx.subtitle_at(TimePoint::from_msecs(53_000)).unwrap();
+        let expected = Sub::open("./fixtures/example.sub")
+            .unwrap()
+            .subtitles::<(TimeSpan, VobSubIndexedImage)>()
+            .with_ids()
+            .nth(1)
+            .unwrap()
+            .unwrap();
+
+        let sub = Sub::open("./fixtures/example.sub").unwrap();
+        let mut subs = sub.subtitles_from::<(TimeSpan, VobSubIndexedImage)>(entry);
+        assert_eq!(subs.next().unwrap().unwrap(), expected.1);
+        assert_eq!(subs.offset(), expected.0.offset());
+
+        let mut reader =
+            VobsubReader::<_, (TimeSpan, VobSubIndexedImage)>::from_file("./fixtures/example.sub")
+                .unwrap();
+        reader.next().unwrap().unwrap();
+        reader.seek(entry).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), expected.1);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn track_subtitles() {
+        let idx = super::super::Index::open("./fixtures/example.idx").unwrap();
+        let sub = Sub::open("./fixtures/example.sub").unwrap();
+        let track = idx.track("de").unwrap();
+        let all = sub.subtitles::<TimeSpan>().count();
+        assert_eq!(sub.track_subtitles::<TimeSpan>(track).count(), all);
+        assert_eq!(sub.subtitles::<TimeSpan>().with_track(1).count(), 0);
+        let reader = VobsubReader::<_, TimeSpan>::from_file("./fixtures/example.sub").unwrap();
+        assert_eq!(reader.with_track(1).count(), 0);
+    }
+
     #[test]
     fn parse_subtitles_from_subtitle_edit() {
         //use env_logger;
@@ -0,0 +1,92 @@
+//! Concatenation of the subtitles of a title split across several title sets.
+//!
+//! The times of the subtitles of a DVD restart at zero in each video title set (`VTS`),
+//! so a movie split across title sets must be rebuilt from the cues of each one,
+//! shifted by the start of its segment in the title.
+
+use super::Ifo;
+use crate::time::{TimeDuration, TimePoint, TimeSpan};
+
+/// Consecutive spans of segments of `durations`, the first one starting at zero.
+///
+/// With the [`Ifo::duration`] of the title sets, in the playback order of the title.
+#[must_use]
+pub fn segment_spans<I>(durations: I) -> Vec<TimeSpan>
+where
+    I: IntoIterator<Item = TimeDuration>,
+{
+    let mut start = TimePoint::default();
+    durations
+        .into_iter()
+        .map(|duration| {
+            let end = start.saturating_add(duration.abs());
+            let span = TimeSpan::new(start, end);
+            start = end;
+            span
+        })
+        .collect()
+}
+
+/// Spans of the title sets of `ifos`, in the playback order of the title.
+#[must_use]
+pub fn ifo_segment_spans<'a, I>(ifos: I) -> Vec<TimeSpan>
+where
+    I: IntoIterator<Item = &'a Ifo>,
+{
+    segment_spans(ifos.into_iter().map(Ifo::duration))
+}
+
+/// Build a single cue stream for a title from the cues of its segments.
+///
+/// Each segment is its span in the title (see [`segment_spans`]) and its cues, timed
+/// from the start of the segment. The cues are shifted to the start of their segment
+/// and cut at its end, the cues outside of their segment are dropped.
+///
+/// The cues are returned sorted by start time.
+pub fn concat_segments<T, S, I>(segments: S) -> Vec<(TimeSpan, T)>
+where
+    S: IntoIterator<Item = (TimeSpan, I)>,
+    I: IntoIterator<Item = (TimeSpan, T)>,
+{
+    let mut cues = segments
+        .into_iter()
+        .flat_map(|(segment, cues)| {
+            let offset = segment.start - TimePoint::default();
+            cues.into_iter().filter_map(move |(time, cue)| {
+                let time = time.saturating_shift(offset).intersect(segment)?;
+                Some((time, cue))
+            })
+        })
+        .collect::<Vec<_>>();
+    cues.sort_by_key(|(time, _)| time.start);
+    cues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::span;
+
+    #[test]
+    fn concat_title_segments() {
+        let spans = segment_spans([10_000, 20_000].map(TimeDuration::from_msecs));
+        assert_eq!(spans, [span(0, 10_000), span(10_000, 30_000)]);
+
+        let first = vec![(span(1000, 2000), "a"), (span(8000, 13_000), "b")];
+        let second = vec![
+            (span(0, 1000), "c"),
+            (span(19_000, 20_000), "d"),
+            (span(25_000, 26_000), "after the end"),
+        ];
+        let cues = concat_segments(spans.into_iter().zip([first, second]));
+        assert_eq!(
+            cues,
+            [
+                (span(1000, 2000), "a"),
+                (span(8000, 10_000), "b"),
+                (span(10_000, 11_000), "c"),
+                (span(29_000, 30_000), "d"),
+            ]
+        );
+    }
+}
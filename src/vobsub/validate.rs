@@ -0,0 +1,285 @@
+//! Validation of `VobSub` subtitle packets against the constraints of DVD players.
+//!
+//! Standalone players are much less tolerant than software decoders : an oversized
+//! packet, or a subtitle outside of the video frame, can make them crash. [`validate_sub`]
+//! reports all the violations found in a `*.sub` file.
+use std::{cmp::Reverse, fmt};
+
+use super::{Sub, VobSubError, VobSubIndexedImage};
+use crate::{
+    content::Area,
+    image::ImageArea as _,
+    limits::MemoryLimits,
+    time::{TimePoint, TimeSpan},
+};
+
+/// Limits of the DVD players checked by [`validate_sub`] and [`validate_spu`].
+///
+/// Start from [`DvdLimits::PAL`] or [`DvdLimits::NTSC`], and adjust them with
+/// the `with_*` methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DvdLimits {
+    /// Maximum size of a subtitle packet, including its size header.
+    pub max_packet_size: usize,
+    /// Width of the video frame.
+    pub screen_width: u16,
+    /// Height of the video frame.
+    pub screen_height: u16,
+}
+
+impl DvdLimits {
+    /// Limits of a `PAL` (`720x576`) DVD.
+    pub const PAL: Self = Self {
+        max_packet_size: 0xFFFF,
+        screen_width: 720,
+        screen_height: 576,
+    };
+
+    /// Limits of a `NTSC` (`720x480`) DVD.
+    pub const NTSC: Self = Self {
+        screen_height: 480,
+        ..Self::PAL
+    };
+
+    /// Set the maximum size of a subtitle packet.
+    #[must_use]
+    pub const fn with_max_packet_size(mut self, max_packet_size: usize) -> Self {
+        self.max_packet_size = max_packet_size;
+        self
+    }
+
+    /// Set the size of the video frame.
+    #[must_use]
+    pub const fn with_screen_size(mut self, screen_width: u16, screen_height: u16) -> Self {
+        self.screen_width = screen_width;
+        self.screen_height = screen_height;
+        self
+    }
+}
+
+impl Default for DvdLimits {
+    /// The limits of a `PAL` DVD, which also accept `NTSC` subtitles.
+    fn default() -> Self {
+        Self::PAL
+    }
+}
+
+/// A violation of the [`DvdLimits`], found in a subtitle packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// The packet is larger than the maximum packet size.
+    PacketTooLarge {
+        /// Start time of the subtitle.
+        time: TimePoint,
+        /// Size of the packet.
+        size: usize,
+    },
+
+    /// The subtitle is not entirely inside the video frame.
+    OutsideScreen {
+        /// Start time of the subtitle.
+        time: TimePoint,
+        /// Area of the subtitle.
+        area: Area,
+    },
+
+    /// The subtitle doesn't have an even number of lines : its two interlaced fields
+    /// don't have the same number of lines.
+    OddLineAlignment {
+        /// Start time of the subtitle.
+        time: TimePoint,
+        /// Area of the subtitle.
+        area: Area,
+    },
+
+    /// The color of the background (the most used color of the image) is not transparent,
+    /// a box is displayed around the subtitle.
+    VisibleBackground {
+        /// Start time of the subtitle.
+        time: TimePoint,
+        /// Index of the background color in the subtitle colors.
+        index: u8,
+    },
+}
+
+impl Violation {
+    /// Start time of the subtitle containing the violation.
+    #[must_use]
+    pub const fn time(&self) -> TimePoint {
+        match self {
+            Self::PacketTooLarge { time, .. }
+            | Self::OutsideScreen { time, .. }
+            | Self::OddLineAlignment { time, .. }
+            | Self::VisibleBackground { time, .. } => *time,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.time().msecs();
+        match self {
+            Self::PacketTooLarge { size, .. } => write!(f, "{time}ms: packet of {size} bytes"),
+            Self::OutsideScreen { area, .. } => write!(
+                f,
+                "{time}ms: area {}x{} at {},{} is outside of the screen",
+                area.width(),
+                area.height(),
+                area.left(),
+                area.top()
+            ),
+            Self::OddLineAlignment { area, .. } => write!(
+                f,
+                "{time}ms: area of {} lines has unbalanced fields",
+                area.height()
+            ),
+            Self::VisibleBackground { index, .. } => {
+                write!(f, "{time}ms: background color {index} is not transparent")
+            }
+        }
+    }
+}
+
+/// Result of the validation of subtitle packets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    packets: usize,
+    violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    /// Number of subtitle packets checked.
+    #[must_use]
+    pub const fn packets(&self) -> usize {
+        self.packets
+    }
+
+    /// Violations found, in stream order.
+    #[must_use]
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Return `true` if the packets respect all the limits.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Check all the subtitle packets of a `*.sub` file against the `limits`.
+///
+/// # Errors
+///
+/// Will return a [`VobSubError`] if a packet can't be decoded. Violations of the limits
+/// are not errors, but are listed in the returned [`ValidationReport`].
+pub fn validate_sub(sub: &Sub, limits: &DvdLimits) -> Result<ValidationReport, VobSubError> {
+    let mut report = ValidationReport::default();
+    let mut parser = sub.subtitles::<()>().with_limits(MemoryLimits::UNLIMITED);
+    while let Some(packet) = parser.next_sub_packet() {
+        let (base_time, packet) = packet?;
+        check_packet(
+            &packet,
+            TimePoint::from_secs(base_time),
+            limits,
+            &mut report.violations,
+        )?;
+        report.packets += 1;
+    }
+    Ok(report)
+}
+
+/// Check a single subtitle packet (`SPU`) against the `limits`, see [`decode_spu`] for
+/// the content of `packet` and the meaning of `base_time`.
+///
+/// # Errors
+///
+/// Will return a [`VobSubError`] if the packet can't be decoded.
+///
+/// [`decode_spu`]: super::decode_spu
+pub fn validate_spu(
+    packet: &[u8],
+    base_time: TimePoint,
+    limits: &DvdLimits,
+) -> Result<Vec<Violation>, VobSubError> {
+    let mut violations = Vec::new();
+    check_packet(packet, base_time, limits, &mut violations)?;
+    Ok(violations)
+}
+
+fn check_packet(
+    packet: &[u8],
+    base_time: TimePoint,
+    limits: &DvdLimits,
+    violations: &mut Vec<Violation>,
+) -> Result<(), VobSubError> {
+    let (TimeSpan { start: time, .. }, image) =
+        super::decode_spu_with_limits(packet, base_time, &MemoryLimits::UNLIMITED)?;
+    if packet.len() > limits.max_packet_size {
+        violations.push(Violation::PacketTooLarge {
+            time,
+            size: packet.len(),
+        });
+    }
+    check_image(time, &image, limits, violations);
+    Ok(())
+}
+
+fn check_image(
+    time: TimePoint,
+    image: &VobSubIndexedImage,
+    limits: &DvdLimits,
+    violations: &mut Vec<Violation>,
+) {
+    let area = image.area();
+    let right = u32::from(area.left()) + u32::from(area.width());
+    let bottom = u32::from(area.top()) + u32::from(area.height());
+    if right > u32::from(limits.screen_width) || bottom > u32::from(limits.screen_height) {
+        violations.push(Violation::OutsideScreen { time, area });
+    }
+    if area.height() % 2 != 0 {
+        violations.push(Violation::OddLineAlignment { time, area });
+    }
+
+    let mut counts = [0usize; 4];
+    for &pixel in image.raw_image() {
+        counts[usize::from(pixel & 0b11)] += 1;
+    }
+    let background = (0..4u8)
+        .max_by_key(|&index| (counts[usize::from(index)], Reverse(index)))
+        .unwrap_or_default();
+    if image.alpha()[usize::from(background)] != 0 {
+        violations.push(Violation::VisibleBackground {
+            time,
+            index: background,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_fixture() {
+        let sub = Sub::open("./fixtures/tiny.sub").unwrap();
+        let report = validate_sub(&sub, &DvdLimits::default()).unwrap();
+        assert!(report.packets() > 0);
+        assert!(report.is_valid(), "{:?}", report.violations());
+
+        let limits = DvdLimits::PAL
+            .with_max_packet_size(10)
+            .with_screen_size(10, 576);
+        let report = validate_sub(&sub, &limits).unwrap();
+        assert!(report
+            .violations()
+            .iter()
+            .any(|violation| matches!(violation, Violation::PacketTooLarge { .. })));
+        assert!(report
+            .violations()
+            .iter()
+            .any(|violation| matches!(violation, Violation::OutsideScreen { .. })));
+    }
+}
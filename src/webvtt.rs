@@ -1,7 +1,12 @@
 //! `WebVTT` functionality
 use std::{fmt, io};
 
-use crate::time::{TimePoint, TimeSpan};
+use crate::{
+    format::SubtitleSink,
+    sink::CueSink,
+    text::TextHints,
+    time::{TimePoint, TimeSpan},
+};
 
 /// Extend `TimePoint` for implement `WebVTT` specific `Display`.
 #[repr(transparent)]
@@ -32,3 +37,122 @@ pub fn write_line(
     let end = TimePointVtt(time.end);
     writeln!(writer, "{start} --> {end}\n{text}\n")
 }
+
+/// Write a subtitles line in `vtt` format, applying the text `hints`.
+///
+/// If a language is provided in `hints`, the text is enclosed in a `<lang>` cue span.
+/// # Errors
+///
+/// Will return `Err` if writing in `writer` return an `Err`.
+pub fn write_line_with_hints(
+    writer: &mut impl io::Write,
+    time: &TimeSpan,
+    text: &str,
+    hints: &TextHints,
+) -> Result<(), io::Error> {
+    let text = hints.apply(text);
+    match &hints.lang {
+        Some(lang) => write_line(writer, time, &format!("<lang {lang}>{text}</lang>")),
+        None => write_line(writer, time, &text),
+    }
+}
+
+/// Enclose the text of a forced cue in a `<c.forced>` class span, for [`MarkForced`],
+/// so it can be styled or filtered by the player.
+///
+/// [`MarkForced`]: crate::sink::MarkForced
+#[must_use]
+pub fn mark_forced(text: &str) -> String {
+    format!("<c.forced>{text}</c>")
+}
+
+/// Streaming writer of subtitles in `vtt` format, see [`CueSink`].
+///
+/// The `WEBVTT` header is written before the first subtitle.
+pub struct VttWriter<W: io::Write> {
+    writer: W,
+    hints: Option<TextHints>,
+    header_written: bool,
+}
+
+impl<W: io::Write> VttWriter<W> {
+    /// Create a writer of `vtt` subtitles in `writer`.
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            hints: None,
+            header_written: false,
+        }
+    }
+
+    /// Create a writer applying the text `hints` on each subtitle.
+    pub const fn with_hints(writer: W, hints: TextHints) -> Self {
+        Self {
+            writer,
+            hints: Some(hints),
+            header_written: false,
+        }
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write> SubtitleSink for VttWriter<W> {
+    const EXTENSION: &'static str = "vtt";
+
+    fn write_header(&mut self) -> Result<(), io::Error> {
+        if !self.header_written {
+            self.header_written = true;
+            writeln!(self.writer, "WEBVTT\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: io::Write> CueSink<str> for VttWriter<W> {
+    type Error = io::Error;
+
+    fn write_cue(&mut self, time: TimeSpan, text: &str) -> Result<(), Self::Error> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            start_ms = time.start.msecs(),
+            end_ms = time.end.msecs(),
+            format = "vtt",
+            "write cue"
+        );
+        self.write_header()?;
+        match &self.hints {
+            Some(hints) => write_line_with_hints(&mut self.writer, &time, text, hints),
+            None => write_line(&mut self.writer, &time, text),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.write_header()?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_vtt() {
+        let time = TimeSpan::new(TimePoint::from_msecs(500), TimePoint::from_msecs(1499));
+
+        let mut writer = VttWriter::new(Vec::new());
+        writer.write_all([(time, "Hello")]).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "WEBVTT\n\n00:00:00.500 --> 00:00:01.499\nHello\n\n"
+        );
+
+        let mut empty = VttWriter::new(Vec::new());
+        empty.finish().unwrap();
+        assert_eq!(empty.into_inner(), b"WEBVTT\n\n");
+    }
+}
@@ -0,0 +1,174 @@
+//! Comparative tests of the decoded images against the images rendered by `ffmpeg`.
+//!
+//! `ffmpeg` is the de-facto reference for subtitles rendering; these tests render the
+//! fixtures with it and compare the result with the images decoded by `subtile`, within
+//! a tolerance. They are enabled by the `ffmpeg-golden` feature, and need the path of an
+//! `ffmpeg` binary in the `SUBTILE_FFMPEG` environment variable (they are skipped without):
+//!
+//! ```text
+//! SUBTILE_FFMPEG=/usr/bin/ffmpeg cargo test --features ffmpeg-golden --test ffmpeg_golden
+//! ```
+//!
+//! As positions are not compared, both images are cropped to their visible content. Images
+//! are compared in luminance, composed on a black background.
+#![cfg(feature = "ffmpeg-golden")]
+
+use std::{
+    env,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use image::{GenericImageView as _, GrayImage, Luma, LumaA, Rgba};
+use subtile::{
+    image::ToImage as _,
+    pgs::{DecodeTimeImage, RleToImage, SupParser},
+    time::{TimePoint, TimeSpan},
+    vobsub::{conv_to_rgba, Index, Sub, VobSubIndexedImage, VobSubToImage},
+};
+
+/// Luminance difference tolerated between two pixels, for color conversions and scaling.
+const PIXEL_TOLERANCE: u8 = 24;
+/// Proportion of pixels allowed to exceed the tolerance, for anti-aliasing differences.
+const MAX_MISMATCH_RATIO: f64 = 0.02;
+/// Luminance above which a pixel is considered as visible content.
+const CONTENT_THRESHOLD: u8 = 16;
+
+/// Path of the `ffmpeg` binary, if provided.
+fn ffmpeg() -> Option<PathBuf> {
+    let path = env::var_os("SUBTILE_FFMPEG").map(PathBuf::from);
+    if path.is_none() {
+        eprintln!("SUBTILE_FFMPEG is not set, skipping ffmpeg comparison");
+    }
+    path
+}
+
+/// Render the subtitles of `input` displayed at `time` with `ffmpeg`, on a black screen of
+/// `width`x`height` pixels.
+fn ffmpeg_frame(
+    ffmpeg: &Path,
+    input: &str,
+    (width, height): (u32, u32),
+    time: TimePoint,
+) -> GrayImage {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let output = env::temp_dir().join(format!(
+        "subtile-golden-{}-{}.png",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let duration = time.to_secs() + 1.;
+    let status = Command::new(ffmpeg)
+        .args(["-v", "error", "-f", "lavfi", "-i"])
+        .arg(format!(
+            "color=c=black:s={width}x{height}:r=25:d={duration}"
+        ))
+        .args(["-i", input, "-filter_complex"])
+        .arg("[0:v][1:s]overlay=eof_action=pass,format=gray")
+        .arg("-ss")
+        .arg(format!("{:.3}", time.to_secs()))
+        .args(["-frames:v", "1", "-y"])
+        .arg(&output)
+        .status()
+        .expect("ffmpeg should be executable");
+    assert!(status.success(), "ffmpeg failed on {input}");
+    let image = image::open(&output).unwrap().into_luma8();
+    fs::remove_file(&output).unwrap();
+    image
+}
+
+/// Crop an image to its visible content.
+fn crop_to_content(image: &GrayImage) -> Option<GrayImage> {
+    let visible = image
+        .enumerate_pixels()
+        .filter(|(_, _, Luma([luma]))| *luma > CONTENT_THRESHOLD)
+        .map(|(x, y, _)| (x, y));
+    let (mut x1, mut y1, mut x2, mut y2) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y) in visible {
+        (x1, y1, x2, y2) = (x1.min(x), y1.min(y), x2.max(x), y2.max(y));
+    }
+    (x1 <= x2).then(|| image.view(x1, y1, x2 + 1 - x1, y2 + 1 - y1).to_image())
+}
+
+/// Compare the visible content of two images, within the tolerances.
+fn assert_similar(expected: &GrayImage, actual: &GrayImage, context: &str) {
+    let expected = crop_to_content(expected).expect("reference image should not be empty");
+    let actual = crop_to_content(actual).expect("decoded image should not be empty");
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "{context}: content size differ"
+    );
+    let mismatches = expected
+        .pixels()
+        .zip(actual.pixels())
+        .filter(|(Luma([left]), Luma([right]))| left.abs_diff(*right) > PIXEL_TOLERANCE)
+        .count();
+    let ratio = cast::f64(mismatches) / f64::from(expected.width() * expected.height());
+    assert!(
+        ratio <= MAX_MISMATCH_RATIO,
+        "{context}: {mismatches} pixels differ"
+    );
+}
+
+/// Compose a pixel on black, in luminance.
+fn on_black(luma: u8, alpha: u8) -> Luma<u8> {
+    Luma([u8::try_from(u16::from(luma) * u16::from(alpha) / 255).unwrap()])
+}
+
+/// Middle of the display time of a subtitle.
+const fn middle(time: TimeSpan) -> TimePoint {
+    TimePoint::from_msecs((time.start.msecs() + time.end.msecs()) / 2)
+}
+
+#[test]
+fn vobsub_matches_ffmpeg() {
+    let Some(ffmpeg) = ffmpeg() else {
+        return;
+    };
+    let idx = Index::open("./fixtures/tiny.idx").unwrap();
+    let sub = Sub::open("./fixtures/tiny.sub").unwrap();
+    for (idx_cue, cue) in sub
+        .subtitles::<(TimeSpan, VobSubIndexedImage)>()
+        .enumerate()
+    {
+        let (time, image) = cue.unwrap();
+        let image = VobSubToImage::new(&image, idx.palette(), conv_to_rgba).to_image();
+        let decoded = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            let Rgba([red, green, blue, alpha]) = *image.get_pixel(x, y);
+            let luma =
+                (u32::from(red) * 299 + u32::from(green) * 587 + u32::from(blue) * 114) / 1000;
+            on_black(u8::try_from(luma).unwrap(), alpha)
+        });
+        let reference = ffmpeg_frame(&ffmpeg, "./fixtures/tiny.idx", (718, 480), middle(time));
+        assert_similar(&reference, &decoded, &format!("tiny.sub cue {idx_cue}"));
+    }
+}
+
+#[test]
+fn pgs_matches_ffmpeg() {
+    let Some(ffmpeg) = ffmpeg() else {
+        return;
+    };
+    let parser =
+        SupParser::<BufReader<File>, DecodeTimeImage>::from_file("./fixtures/only_one.sup")
+            .unwrap();
+    for (idx_cue, cue) in parser.enumerate() {
+        let (time, image) = cue.unwrap();
+        let image = RleToImage::new(&image, |pixel: LumaA<u8>| pixel).to_image();
+        let decoded = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            let LumaA([luma, alpha]) = *image.get_pixel(x, y);
+            on_black(luma, alpha)
+        });
+        let reference = ffmpeg_frame(
+            &ffmpeg,
+            "./fixtures/only_one.sup",
+            (1920, 1080),
+            middle(time),
+        );
+        assert_similar(&reference, &decoded, &format!("only_one.sup cue {idx_cue}"));
+    }
+}
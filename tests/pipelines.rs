@@ -0,0 +1,179 @@
+//! End-to-end pipelines over the fixtures, from the bitmap formats to the text formats,
+//! and between bitmap formats.
+//!
+//! They document how the modules fit together, and guard the APIs used across them.
+//! The `OCR` is replaced by a stub describing the characters found in the image, as the
+//! crate doesn't provide an `OCR` engine.
+
+use std::{
+    fs::File,
+    io::{BufReader, Cursor},
+};
+
+use image::{GrayImage, Luma, Rgba};
+use subtile::{
+    image::{character_lines, ReadingOrder, ToImage as _, ToOcrImageOpt},
+    pgs::{DecodeTimeImage, SupParser, SupWriter},
+    raster::PlacedBitmap,
+    sink::{CueSink as _, SplitLong},
+    srt::{parse_srt, SrtParser, SrtWriter},
+    time::{TimePoint, TimeSpan},
+    vobsub::{Index, Sub, VobSubIndexedImage},
+    webvtt::VttWriter,
+};
+
+/// `OCR` color of a palette color : opaque colors are text.
+fn ocr_color(opt: &ToOcrImageOpt) -> impl Fn(Rgba<u8>) -> Luma<u8> + '_ {
+    |Rgba([.., alpha])| {
+        if alpha > 127 {
+            opt.text_color
+        } else {
+            opt.background_color
+        }
+    }
+}
+
+/// Stub of an `OCR` engine : describe the number of characters of each line of text.
+fn stub_ocr(image: &GrayImage, opt: &ToOcrImageOpt) -> String {
+    character_lines(image, opt.text_color, ReadingOrder::LeftToRight)
+        .iter()
+        .map(|line| format!("{} characters", line.len()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Times of the subtitles of a `srt` file.
+fn srt_times(content: &str) -> Vec<TimeSpan> {
+    SrtParser::new(content)
+        .map(|subtitle| subtitle.unwrap().0)
+        .collect()
+}
+
+/// Read the `PGS` subtitles of `path` as text.
+fn sup_to_text(path: &str) -> Vec<(TimeSpan, String)> {
+    let opt = ToOcrImageOpt::new();
+    SupParser::<BufReader<File>, DecodeTimeImage>::from_file(path)
+        .unwrap()
+        .map(|subtitle| {
+            let (time, image) = subtitle.unwrap();
+            let ocr = image.to_indexed().to_ocr_image(ocr_color(&opt), &opt);
+            (time, stub_ocr(&ocr, &opt))
+        })
+        .collect()
+}
+
+#[test]
+fn sup_to_srt() {
+    let subtitles = sup_to_text("./fixtures/only_one.sup");
+    let mut writer = SrtWriter::new(Vec::new());
+    writer
+        .write_all(subtitles.iter().map(|(time, text)| (*time, text)))
+        .unwrap();
+    let srt = String::from_utf8(writer.into_inner()).unwrap();
+
+    // The reference was converted by another tool, rounding the end time differently.
+    let expected = std::fs::read_to_string("./fixtures/only_one.srt").unwrap();
+    let (times, expected) = (srt_times(&srt), srt_times(&expected));
+    assert_eq!(times.len(), expected.len());
+    assert!(times.iter().zip(&expected).all(|(time, expected)| {
+        time.start == expected.start && (time.end.msecs() - expected.end.msecs()).abs() <= 1
+    }));
+    let (_, text) = &parse_srt(&srt).unwrap()[0];
+    assert!(text.ends_with(" characters"), "unexpected text {text:?}");
+}
+
+#[test]
+fn idx_sub_to_vtt() {
+    let idx = Index::open("./fixtures/tiny.idx").unwrap();
+    let sub = Sub::open("./fixtures/tiny.sub").unwrap();
+    let opt = ToOcrImageOpt::new();
+
+    let mut writer = VttWriter::new(Vec::new());
+    for subtitle in sub.subtitles::<(TimeSpan, VobSubIndexedImage)>() {
+        let (time, image) = subtitle.unwrap();
+        let ocr = image
+            .to_indexed(idx.palette())
+            .to_ocr_image(ocr_color(&opt), &opt);
+        writer.write_cue(time, &stub_ocr(&ocr, &opt)).unwrap();
+    }
+    writer.finish().unwrap();
+    let vtt = String::from_utf8(writer.into_inner()).unwrap();
+
+    // The reference was converted by another tool, with another duration : only the start
+    // time is compared.
+    let expected = std::fs::read_to_string("./fixtures/tiny.srt").unwrap();
+    let start = srt_times(&expected)[0].start.msecs();
+    let (secs, msecs) = (start / 1000, start % 1000);
+    assert!(vtt.starts_with(&format!(
+        "WEBVTT\n\n{:02}:{:02}:{:02}.{msecs:03} --> ",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )));
+    assert!(vtt.contains("\n1 characters\n"), "unexpected vtt {vtt:?}");
+}
+
+#[test]
+fn sup_retime_to_srt() {
+    // Delay the subtitles by 2 seconds, and limit their duration to 400ms.
+    let delay = |time: TimePoint| TimePoint::from_msecs(time.msecs() + 2000);
+    let subtitles = sup_to_text("./fixtures/only_one.sup");
+    let mut writer = SplitLong::new(SrtWriter::new(Vec::new()), 400);
+    writer
+        .write_all(
+            subtitles
+                .iter()
+                .map(|(time, text)| (TimeSpan::new(delay(time.start), delay(time.end)), text)),
+        )
+        .unwrap();
+    let srt = String::from_utf8(writer.into_inner().into_inner()).unwrap();
+
+    let times = srt_times(&srt);
+    let (first, last) = (times.first().unwrap(), times.last().unwrap());
+    assert!(times.len() > 1);
+    assert_eq!(first.start, delay(subtitles[0].0.start));
+    assert_eq!(last.end, delay(subtitles[0].0.end));
+    assert!(times
+        .iter()
+        .all(|time| time.end.msecs() - time.start.msecs() <= 400));
+}
+
+#[test]
+fn sup_to_sup() {
+    let subtitles =
+        SupParser::<BufReader<File>, DecodeTimeImage>::from_file("./fixtures/only_one.sup")
+            .unwrap()
+            .map(|subtitle| {
+                let (time, image) = subtitle.unwrap();
+                (time, image.to_indexed().to_image())
+            })
+            .collect::<Vec<_>>();
+
+    // The positions are not decoded : the images are placed at the bottom of the screen.
+    let mut writer = SupWriter::new(Vec::new());
+    writer
+        .write_all(subtitles.iter().map(|(time, image)| {
+            let y = 1000 - u16::try_from(image.height()).unwrap();
+            (*time, PlacedBitmap::new(100, y, image.clone()))
+        }))
+        .unwrap();
+    let sup = writer.into_inner();
+
+    let decoded = SupParser::<_, DecodeTimeImage>::new(Cursor::new(sup))
+        .map(|subtitle| subtitle.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(decoded.len(), subtitles.len());
+    for ((time, image), (expected_time, expected)) in decoded.iter().zip(&subtitles) {
+        assert_eq!(time, expected_time);
+        let image = image.to_indexed().to_image();
+        assert_eq!(image.dimensions(), expected.dimensions());
+        // Colors are converted back and forth to `YCbCr`.
+        assert!(image
+            .pixels()
+            .zip(expected.pixels())
+            .all(|(pixel, expected)| {
+                pixel[3] == expected[3]
+                    && (pixel.0.iter().zip(expected.0)).all(|(a, b)| a.abs_diff(b) <= 3)
+            }));
+    }
+}